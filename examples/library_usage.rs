@@ -0,0 +1,41 @@
+//! Embedding the eq query engine in another program.
+//!
+//! Run with `cargo run --example library_usage`.
+
+use eq::{EdnValue, EqError, OutputConfig};
+
+const ORDERS: &str = r#"
+[{:id 1 :customer "alice" :total 30 :status :shipped}
+ {:id 2 :customer "bob"   :total 12 :status :pending}
+ {:id 3 :customer "alice" :total 55 :status :shipped}]
+"#;
+
+fn main() -> Result<(), EqError> {
+    let orders = eq::parse_edn(ORDERS)?;
+
+    // Filters are the same expressions the eq command line takes
+    let shipped = eq::eval_query("(select (fn [o] (= (:status o) :shipped)) .)", &orders)?;
+    let revenue = eq::eval_query("(sum (map :total .))", &shipped)?;
+    println!("shipped revenue: {}", eq::format_edn(&revenue, &OutputConfig::default()));
+
+    // Results are plain EdnValues, ready to inspect from Rust
+    let by_customer = eq::eval_query("(frequencies-by :customer .)", &orders)?;
+    if let EdnValue::Map(counts) = &by_customer {
+        for (customer, count) in counts {
+            println!("orders from {}: {}", customer, count);
+        }
+    }
+
+    // Compact output on one line, as with `eq -c`
+    let compact = OutputConfig { compact: true, ..OutputConfig::default() };
+    let ids = eq::eval_query("(map :id .)", &orders)?;
+    println!("ids: {}", eq::format_edn(&ids, &compact));
+
+    // Bad filters and failing queries are reported as EqError values
+    match eq::eval_query("(+ 1 :not-a-number)", &orders) {
+        Ok(value) => println!("unexpected result: {}", value),
+        Err(err) => println!("query failed as expected: {}", err),
+    }
+
+    Ok(())
+}
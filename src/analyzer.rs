@@ -55,7 +55,7 @@ fn analyze_once(expr: Expr) -> EqResult<Expr> {
                         if let FunctionType::Macro(macro_func) = func_type {
                             // Convert EDN args to Expr args for macro
                             let expr_args = args.iter()
-                                .map(|arg| edn_to_expr(arg))
+                                .map(edn_to_expr)
                                 .collect::<Result<Vec<_>, _>>()?;
                             // Expand the macro
                             macro_func(&expr_args)
@@ -69,7 +69,17 @@ fn analyze_once(expr: Expr) -> EqResult<Expr> {
                     }
                 }
                 EdnValue::Keyword(name) => analyze_keyword_call(name, args),
-                _ => Err(EqError::query_error("First element of list must be a symbol or keyword")),
+                EdnValue::List(_) | EdnValue::Lambda(_) => {
+                    // Head evaluates to a function, e.g. ((partial < 10) x), or
+                    // is one already, as in calls generated by destructure
+                    Ok(Expr::LambdaCall {
+                        func: Box::new(analyze(edn_to_expr(head)?)?),
+                        args: args.iter()
+                            .map(|arg| analyze(edn_to_expr(arg)?))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    })
+                }
+                _ => Err(EqError::query_error("First element of list must be a symbol, keyword or list")),
             }
         }
         
@@ -157,36 +167,173 @@ fn edn_to_expr(value: &EdnValue) -> EqResult<Expr> {
 
 // Helper functions for special cases
 
-/// Analyze lambda syntax: (fn [params] body)
+/// Analyze lambda syntax: (fn [params] body), where the params may end with
+/// `& rest` and any of them may be a destructuring pattern. Patterns are
+/// given generated names and the body is wrapped in a let that destructures them.
 fn analyze_lambda(args: &[EdnValue]) -> EqResult<Expr> {
     if args.len() != 2 {
         return Err(EqError::query_error("fn requires exactly 2 arguments: parameter vector and body"));
     }
     
     // First argument should be a parameter vector
-    let params = match &args[0] {
-        EdnValue::Vector(params) => {
-            let mut param_names = Vec::new();
-            for param in params {
-                if let EdnValue::Symbol(name) = param {
-                    param_names.push(name.clone());
-                } else {
-                    return Err(EqError::query_error("fn parameters must be symbols"));
-                }
+    let EdnValue::Vector(param_forms) = &args[0] else {
+        return Err(EqError::query_error("fn first argument must be a parameter vector"));
+    };
+    let mut params = Vec::new();
+    let mut rest_param = None;
+    let mut destructured = Vec::new();
+    let mut forms = param_forms.iter();
+    while let Some(form) = forms.next() {
+        if matches!(form, EdnValue::Symbol(s) if s == "&") {
+            match (forms.next(), forms.next()) {
+                (Some(rest), None) => rest_param = Some(param_name(rest, &mut destructured)?),
+                _ => return Err(EqError::query_error("fn & must be followed by exactly one parameter")),
             }
-            param_names
+        } else {
+            params.push(param_name(form, &mut destructured)?);
         }
-        _ => return Err(EqError::query_error("fn first argument must be a parameter vector")),
-    };
+    }
     
     // Second argument is the body
-    let body = &args[1];
+    let body = if destructured.is_empty() {
+        args[1].clone()
+    } else {
+        EdnValue::List(vec![
+            EdnValue::Symbol("let".to_string()),
+            EdnValue::Vector(destructured),
+            args[1].clone(),
+        ])
+    };
+    let body = &body;
     
     // Create lambda and return as literal expression
     let lambda = EdnLambda {
         params,
+        rest_param,
         body: Box::new(body.clone()),
+        native: None,
+        captured_env: std::collections::HashMap::new(),
     };
     
     Ok(Expr::Literal(EdnValue::Lambda(lambda)))
+}
+
+/// The name for a fn parameter. A destructuring pattern gets a generated
+/// name, and the pattern and that name are added to the `destructured` let bindings.
+fn param_name(form: &EdnValue, destructured: &mut Vec<EdnValue>) -> EqResult<String> {
+    match form {
+        EdnValue::Symbol(name) if name != "&" => Ok(name.clone()),
+        EdnValue::Vector(_) | EdnValue::Map(_) => {
+            let name = format!("__arg{}", destructured.len() / 2);
+            destructured.push(form.clone());
+            destructured.push(EdnValue::Symbol(name.clone()));
+            Ok(name)
+        }
+        _ => Err(EqError::query_error("fn parameters must be symbols or destructuring patterns")),
+    }
+}
+
+/// Expand a let binding pattern into plain `(name form)` bindings, in the
+/// order they must be evaluated. `form` is the expression being destructured.
+///
+/// - `[a b & more :as all]` binds elements with nth-safe, the remaining ones with drop
+/// - `{:keys [a b] n :name :or {a 1} :as all}` binds values with get; `:or`
+///   supplies defaults for missing keys
+///
+/// Patterns nest. The value is first bound to a generated name so it is only
+/// evaluated once.
+pub fn destructure(pattern: &EdnValue, form: EdnValue, bindings: &mut Vec<(String, EdnValue)>) -> EqResult<()> {
+    let call = |name: &str, args: Vec<EdnValue>| {
+        EdnValue::List(std::iter::once(builtin_value(name)).chain(args).collect())
+    };
+
+    match pattern {
+        EdnValue::Symbol(name) if name != "&" => bindings.push((name.clone(), form)),
+        EdnValue::Vector(elements) => {
+            let whole = bind_whole(form, bindings);
+            let mut index = 0;
+            let mut elements = elements.iter();
+            while let Some(element) = elements.next() {
+                match element {
+                    EdnValue::Symbol(s) if s == "&" => {
+                        let rest = elements.next()
+                            .ok_or_else(|| EqError::query_error("& in a binding pattern must be followed by a pattern"))?;
+                        destructure(rest, call("drop", vec![EdnValue::Integer(index), whole.clone()]), bindings)?;
+                    }
+                    EdnValue::Keyword(k) if k == "as" => bind_as(elements.next(), &whole, bindings)?,
+                    element => {
+                        destructure(element, call("nth-safe", vec![whole.clone(), EdnValue::Integer(index)]), bindings)?;
+                        index += 1;
+                    }
+                }
+            }
+        }
+        EdnValue::Map(entries) => {
+            let whole = bind_whole(form, bindings);
+            let defaults = match entries.get(&EdnValue::Keyword("or".to_string())) {
+                Some(EdnValue::Map(defaults)) => Some(defaults),
+                Some(_) => return Err(EqError::query_error(":or in a binding pattern must be a map")),
+                None => None,
+            };
+            let lookup = |name: &EdnValue, key: EdnValue| {
+                let get = call("get", vec![whole.clone(), key.clone()]);
+                match defaults.and_then(|defaults| defaults.get(name)) {
+                    Some(default) => call("if", vec![call("contains?", vec![whole.clone(), key]), get, default.clone()]),
+                    None => get,
+                }
+            };
+            for (key, value) in entries {
+                match key {
+                    EdnValue::Keyword(k) if k == "keys" => {
+                        let EdnValue::Vector(names) = value else {
+                            return Err(EqError::query_error(":keys in a binding pattern must be a vector of symbols"));
+                        };
+                        for name in names {
+                            let EdnValue::Symbol(symbol) = name else {
+                                return Err(EqError::query_error(":keys in a binding pattern must be a vector of symbols"));
+                            };
+                            // :keys [user/id] binds id to :user/id
+                            let local = symbol.rsplit('/').next().unwrap_or(symbol);
+                            let form = lookup(&EdnValue::Symbol(local.to_string()), EdnValue::Keyword(symbol.clone()));
+                            bindings.push((local.to_string(), form));
+                        }
+                    }
+                    EdnValue::Keyword(k) if k == "as" => bind_as(Some(value), &whole, bindings)?,
+                    EdnValue::Keyword(k) if k == "or" => {}
+                    pattern => destructure(pattern, lookup(pattern, value.clone()), bindings)?,
+                }
+            }
+        }
+        other => return Err(EqError::query_error(format!("Invalid binding pattern: {}", other))),
+    }
+    Ok(())
+}
+
+/// A builtin function as a value for the head of a generated call, so a local
+/// binding of the same name, which would shadow the symbol, can't capture it.
+/// Special forms such as if stay symbols; they can't be shadowed.
+fn builtin_value(name: &str) -> EdnValue {
+    match get_analyzer_registry().get(name) {
+        Some(FunctionType::Regular(func)) => {
+            let func = func.clone();
+            EdnValue::Lambda(EdnLambda::native(EdnValue::Symbol(name.to_string()), move |args| func(args)))
+        }
+        _ => EdnValue::Symbol(name.to_string()),
+    }
+}
+
+fn bind_whole(form: EdnValue, bindings: &mut Vec<(String, EdnValue)>) -> EdnValue {
+    let name = format!("__destructure{}", bindings.len());
+    bindings.push((name.clone(), form));
+    EdnValue::Symbol(name)
+}
+
+fn bind_as(name: Option<&EdnValue>, whole: &EdnValue, bindings: &mut Vec<(String, EdnValue)>) -> EqResult<()> {
+    match name {
+        Some(EdnValue::Symbol(name)) => {
+            bindings.push((name.clone(), whole.clone()));
+            Ok(())
+        }
+        _ => Err(EqError::query_error(":as in a binding pattern must be followed by a symbol")),
+    }
 }
\ No newline at end of file
@@ -1,7 +1,23 @@
-use crate::edn::{EdnValue, EdnSequential, EdnIterable, EdnAssociative, value::EdnLambda};
+use crate::edn::{EdnValue, EdnSequential, EdnIterable, EdnAssociative, value::{EdnLambda, LazySeq}};
+use crate::evaluator::apply_function;
 use crate::error::{EqError, EqResult};
 use crate::query::ast::{FunctionRegistry, Expr};
+use crate::formatter::{Formatter, PrettyFormatter};
+use crate::output::{format_edn, OutputConfig};
+use chrono::{DateTime, FixedOffset, Months, NaiveDateTime, SecondsFormat, TimeDelta};
 use indexmap::IndexMap;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive};
+use rand::seq::SliceRandom;
+use regex::Regex;
+use uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::num::IntErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 /// Initialize the builtin function registry with all standard functions
 /// Special forms are added separately in the evaluator module to avoid circular dependencies
@@ -13,40 +29,248 @@ pub fn create_builtin_registry() -> FunctionRegistry {
     registry.register("get-in".to_string(), builtin_get_in);
 
     // Collection operations
-    registry.register("first".to_string(), builtin_first);
+    registry.register_lazy("first".to_string(), builtin_first);
+    registry.register("second".to_string(), builtin_second);
+    registry.register("third".to_string(), builtin_third);
+    registry.register("fourth".to_string(), builtin_fourth);
     registry.register("last".to_string(), builtin_last);
-    registry.register("rest".to_string(), builtin_rest);
-    registry.register("take".to_string(), builtin_take);
+    registry.register("butlast".to_string(), builtin_butlast);
+    registry.register_lazy("rest".to_string(), builtin_rest);
+    registry.register_lazy("take".to_string(), builtin_take);
     registry.register("drop".to_string(), builtin_drop);
+    registry.register("take-last".to_string(), builtin_take_last);
+    registry.register("drop-last".to_string(), builtin_drop_last);
     registry.register("nth".to_string(), builtin_nth);
+    registry.register("nth-safe".to_string(), builtin_nth_safe);
     registry.register("count".to_string(), builtin_count);
     registry.register("keys".to_string(), builtin_keys);
     registry.register("vals".to_string(), builtin_vals);
+    registry.register("keys-of".to_string(), builtin_keys_of);
+    registry.register("keyword-keys".to_string(), builtin_keyword_keys);
+    registry.register("string-keys".to_string(), builtin_string_keys);
+    registry.register("symbol-keys".to_string(), builtin_symbol_keys);
+    registry.register("keys-where".to_string(), builtin_keys_where);
+    registry.register("vals-where".to_string(), builtin_vals_where);
+    registry.register("concat".to_string(), builtin_concat);
+    registry.register("vec".to_string(), builtin_vec);
+    registry.register("vector".to_string(), builtin_vector);
+    registry.register("list".to_string(), builtin_list);
+    registry.register("set".to_string(), builtin_set);
+    registry.register("hash-set".to_string(), builtin_hash_set);
+    registry.register("reverse".to_string(), builtin_reverse);
+    registry.register("sort".to_string(), builtin_sort);
+    registry.register("distinct".to_string(), builtin_distinct);
+    registry.register("shuffle".to_string(), builtin_shuffle);
+    registry.register("zip".to_string(), builtin_zip);
+    registry.register("zipmap".to_string(), builtin_zipmap);
+    registry.register("hash-map".to_string(), builtin_hash_map);
+    // Maps keep insertion order, so array-map is the same as hash-map
+    registry.register("array-map".to_string(), builtin_hash_map);
+    registry.register("sorted-map".to_string(), builtin_sorted_map);
+    registry.register("interleave".to_string(), builtin_interleave);
+    registry.register("interpose".to_string(), builtin_interpose);
+    registry.register("rename-keys".to_string(), builtin_rename_keys);
+    registry.register("flatten-keys".to_string(), builtin_flatten_keys);
+    registry.register("unflatten-keys".to_string(), builtin_unflatten_keys);
+    registry.register("diff".to_string(), builtin_diff);
+    registry.register("sliding-window".to_string(), builtin_sliding_window);
+    registry.register("strides".to_string(), builtin_strides);
 
     // Predicates
-    registry.register("nil?".to_string(), builtin_is_nil);
-    registry.register("empty?".to_string(), builtin_is_empty);
+    registry.register_lazy("nil?".to_string(), builtin_is_nil);
+    registry.register_lazy("empty?".to_string(), builtin_is_empty);
     registry.register("contains?".to_string(), builtin_contains);
     registry.register("number?".to_string(), builtin_is_number);
     registry.register("string?".to_string(), builtin_is_string);
+    registry.register("map?".to_string(), builtin_is_map);
+    registry.register("vector?".to_string(), builtin_is_vector);
+    registry.register("list?".to_string(), builtin_is_list);
+    registry.register("set?".to_string(), builtin_is_set);
+    registry.register_lazy("coll?".to_string(), builtin_is_coll);
     registry.register("keyword?".to_string(), builtin_is_keyword);
     registry.register("boolean?".to_string(), builtin_is_boolean);
+    registry.register("bigint?".to_string(), builtin_is_bigint);
+    registry.register("ratio?".to_string(), builtin_is_ratio);
+    registry.register("qualified?".to_string(), builtin_is_qualified);
+    registry.register("uuid?".to_string(), builtin_is_uuid);
+    registry.register("nan?".to_string(), builtin_is_nan);
+    registry.register("infinite?".to_string(), builtin_is_infinite);
+    registry.register("finite?".to_string(), builtin_is_finite);
+    registry.register("not".to_string(), builtin_not);
 
     // Comparison
-    registry.register("=".to_string(), builtin_equal);
+    registry.register_lazy("=".to_string(), builtin_equal);
     registry.register("<".to_string(), builtin_less_than);
     registry.register(">".to_string(), builtin_greater_than);
     registry.register("<=".to_string(), builtin_less_equal);
     registry.register(">=".to_string(), builtin_greater_equal);
 
+    // Arithmetic
+    registry.register("+".to_string(), builtin_add);
+    registry.register("-".to_string(), builtin_subtract);
+    registry.register("*".to_string(), builtin_multiply);
+    registry.register("/".to_string(), builtin_divide);
+    registry.register("numerator".to_string(), builtin_numerator);
+    registry.register("denominator".to_string(), builtin_denominator);
+    registry.register("abs".to_string(), builtin_abs);
+    registry.register("floor".to_string(), builtin_floor);
+    registry.register("ceil".to_string(), builtin_ceil);
+    registry.register("round".to_string(), builtin_round);
+    registry.register("sqrt".to_string(), builtin_sqrt);
+    registry.register("pow".to_string(), builtin_pow);
+    registry.register("log".to_string(), builtin_log);
+    registry.register("log10".to_string(), builtin_log10);
+    registry.register("exp".to_string(), builtin_exp);
+    registry.register("min-val".to_string(), builtin_min_val);
+    registry.register("max-val".to_string(), builtin_max_val);
+    registry.register("infinity".to_string(), builtin_infinity);
+    registry.register("negative-infinity".to_string(), builtin_negative_infinity);
+    registry.register("nan".to_string(), builtin_nan);
+    registry.register("clamp".to_string(), builtin_clamp);
+    registry.register("clamp-or-nil".to_string(), builtin_clamp_or_nil);
+    registry.register("in-range?".to_string(), builtin_in_range);
+
+    // Bitwise operations
+    registry.register("bit-and".to_string(), builtin_bit_and);
+    registry.register("bit-or".to_string(), builtin_bit_or);
+    registry.register("bit-xor".to_string(), builtin_bit_xor);
+    registry.register("bit-not".to_string(), builtin_bit_not);
+    registry.register("bit-shift-left".to_string(), builtin_bit_shift_left);
+    registry.register("bit-shift-right".to_string(), builtin_bit_shift_right);
+    registry.register("bit-test".to_string(), builtin_bit_test);
+
+    // Type conversion
+    registry.register("int".to_string(), builtin_int);
+    registry.register("float".to_string(), builtin_float);
+    registry.register("str".to_string(), builtin_str);
+    registry.register("keyword".to_string(), builtin_keyword);
+    registry.register("symbol".to_string(), builtin_symbol);
+    registry.register("parse-int".to_string(), builtin_parse_int);
+    registry.register("parse-float".to_string(), builtin_parse_float);
+    registry.register("parse".to_string(), builtin_parse);
+    registry.register("number->string".to_string(), builtin_number_to_string);
+    registry.register("string->number".to_string(), builtin_string_to_number);
+    registry.register("hex->int".to_string(), builtin_hex_to_int);
+    registry.register("int->hex".to_string(), builtin_int_to_hex);
+    registry.register("bin->int".to_string(), builtin_bin_to_int);
+    registry.register("int->bin".to_string(), builtin_int_to_bin);
+    registry.register("oct->int".to_string(), builtin_oct_to_int);
+    registry.register("int->oct".to_string(), builtin_int_to_oct);
+
+    // Strings
+    registry.register("format".to_string(), builtin_format);
+    registry.register("printf".to_string(), builtin_printf);
+    registry.register("starts-with?".to_string(), builtin_starts_with);
+    registry.register("str/starts-with?".to_string(), builtin_starts_with);
+    registry.register("ends-with?".to_string(), builtin_ends_with);
+    registry.register("str/ends-with?".to_string(), builtin_ends_with);
+    // contains? already tests collection membership, so substring search is only namespaced
+    registry.register("str/contains?".to_string(), builtin_str_contains);
+    registry.register("index-of".to_string(), builtin_index_of);
+    registry.register("str/index-of".to_string(), builtin_index_of);
+    registry.register("str/replace".to_string(), builtin_str_replace);
+    registry.register("str/replace-first".to_string(), builtin_str_replace_first);
+    registry.register("str/split-lines".to_string(), builtin_split_lines);
+    registry.register("str/trim-newline".to_string(), builtin_trim_newline);
+    registry.register("str/blank?".to_string(), builtin_blank);
+    registry.register("str/pad-left".to_string(), builtin_pad_left);
+    registry.register("str/pad-right".to_string(), builtin_pad_right);
+    registry.register("str/center".to_string(), builtin_center);
+    registry.register("str/repeat".to_string(), builtin_str_repeat);
+    registry.register("str/insert".to_string(), builtin_str_insert);
+    registry.register("str/remove-prefix".to_string(), builtin_remove_prefix);
+    registry.register("str/remove-suffix".to_string(), builtin_remove_suffix);
+    registry.register("str/take".to_string(), builtin_str_take);
+    registry.register("str/drop".to_string(), builtin_str_drop);
+
+    // Instants
+    registry.register("inst/before?".to_string(), builtin_inst_before);
+    registry.register("inst/after?".to_string(), builtin_inst_after);
+    registry.register("inst/parse".to_string(), builtin_inst_parse);
+    registry.register("inst/format".to_string(), builtin_inst_format);
+    registry.register("inst/epoch-ms".to_string(), builtin_inst_epoch_ms);
+    registry.register("range-of-dates".to_string(), builtin_range_of_dates);
+    registry.register("date-seq".to_string(), builtin_date_seq);
+
+    // UUIDs
+    registry.register("uuid/random".to_string(), builtin_uuid_random);
+    registry.register("uuid/parse".to_string(), builtin_uuid_parse);
+    registry.register("uuid/valid?".to_string(), builtin_uuid_valid);
+    registry.register("uuid/str".to_string(), builtin_uuid_str);
+    registry.register("uuid/from-bytes".to_string(), builtin_uuid_from_bytes);
+
+    // Imports
+    registry.register("import-edn".to_string(), builtin_import_edn);
+
+    // Schemas
+    registry.register("schema/validate".to_string(), builtin_schema_validate);
+
+    // Environment variables
+    registry.register("env".to_string(), builtin_env);
+    registry.register("env-all".to_string(), builtin_env_all);
+
+    // Keywords and symbols
+    registry.register("namespace".to_string(), builtin_namespace);
+    registry.register("name".to_string(), builtin_name);
+    registry.register("qualified-keyword".to_string(), builtin_qualified_keyword);
+
     // Higher-order operations
-    registry.register("map".to_string(), builtin_map);
+    registry.register_lazy("map".to_string(), builtin_map);
+    registry.register("map-indexed".to_string(), builtin_map_indexed);
+    registry.register("keep".to_string(), builtin_keep);
+    registry.register("keep-indexed".to_string(), builtin_keep_indexed);
+    registry.register("positions".to_string(), builtin_positions);
+    registry.register("find-index".to_string(), builtin_find_index);
+    registry.register("last-index-of-by".to_string(), builtin_last_index_of_by);
     registry.register("remove".to_string(), builtin_remove);
     registry.register("select-keys".to_string(), builtin_select_keys);
+    registry.register("select-keys-recursive".to_string(), builtin_select_keys_recursive);
     registry.register("select".to_string(), builtin_select);
+    registry.register("reduce-kv".to_string(), builtin_reduce_kv);
+    registry.register("update-vals".to_string(), builtin_update_vals);
+    registry.register("update-keys".to_string(), builtin_update_keys);
+    registry.register("merge-with".to_string(), builtin_merge_with);
+    registry.register("merge-with-default".to_string(), builtin_merge_with_default);
+    registry.register("distinct-by".to_string(), builtin_distinct_by);
+    registry.register("index-by".to_string(), builtin_index_by);
+    registry.register("index-by-group".to_string(), builtin_index_by_group);
+    registry.register("partial".to_string(), builtin_partial);
+    registry.register("juxt".to_string(), builtin_juxt);
+    registry.register("complement".to_string(), builtin_complement);
+    registry.register("fnil".to_string(), builtin_fnil);
+    registry.register("keyword->fn".to_string(), builtin_keyword_to_fn);
+    registry.register("fn->keyword".to_string(), builtin_fn_to_keyword);
+    registry.register("memoize".to_string(), builtin_memoize);
+    registry.register("walk".to_string(), builtin_walk);
+    registry.register("postwalk".to_string(), builtin_postwalk);
+    registry.register("prewalk".to_string(), builtin_prewalk);
+    registry.register("tree-seq".to_string(), builtin_tree_seq);
+
+    // Lazy sequences (lazy-seq itself is a special form)
+    registry.register_lazy("realize".to_string(), builtin_realize);
+    registry.register_lazy("iterate".to_string(), builtin_iterate);
 
     // Aggregation
     registry.register("frequencies".to_string(), builtin_frequencies);
+    registry.register("frequencies-by".to_string(), builtin_frequencies_by);
+    registry.register("min".to_string(), builtin_min);
+    registry.register("max".to_string(), builtin_max);
+    registry.register("min-by".to_string(), builtin_min_by);
+    registry.register("max-by".to_string(), builtin_max_by);
+    registry.register("sum".to_string(), builtin_sum);
+    registry.register("average".to_string(), builtin_average);
+    registry.register("count-by".to_string(), builtin_count_by);
+
+    // Debugging
+    registry.register("tap".to_string(), builtin_tap);
+    registry.register("spy".to_string(), builtin_spy);
+    registry.register("print".to_string(), builtin_print);
+    registry.register("println".to_string(), builtin_println);
+    registry.register("eprint".to_string(), builtin_print);
+    registry.register("eprintln".to_string(), builtin_println);
+    registry.register("pp".to_string(), builtin_pp);
+    registry.register("pp-str".to_string(), builtin_pp_str);
+    registry.register("with-format".to_string(), builtin_with_format);
 
     // Threading macros
     registry.register_macro("->".to_string(), macro_thread_first);
@@ -54,6 +278,8 @@ pub fn create_builtin_registry() -> FunctionRegistry {
     
     // Control flow macros
     registry.register_macro("when".to_string(), macro_when);
+    registry.register_macro("with-default".to_string(), macro_with_default);
+    registry.register_macro("??".to_string(), macro_default_op);
 
     registry
 }
@@ -72,18 +298,21 @@ fn builtin_get(args: &[EdnValue]) -> EqResult<EdnValue> {
 }
 
 fn builtin_get_in(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let path = match args.get(1) {
+        Some(EdnValue::Vector(path)) => path,
+        Some(other) => return Err(EqError::type_error("vector", other.type_name())),
+        None => return Err(EqError::query_error("get-in expects 2 or 3 arguments".to_string())),
+    };
     match args.len() {
         2 => {
             // (get-in map path) - get path from map
-            let map = &args[0];
-            match &args[1] {
-                EdnValue::Vector(path) => {
-                    Ok(map.get_in(path.clone()).cloned().unwrap_or(EdnValue::Nil))
-                }
-                _ => Err(EqError::type_error("vector", args[1].type_name())),
-            }
+            Ok(args[0].get_in(path.clone()).cloned().unwrap_or(EdnValue::Nil))
         }
-        _ => Err(EqError::query_error("get-in expects exactly 2 arguments".to_string())),
+        3 => {
+            // (get-in map path default) - default when the path is missing or nil
+            Ok(args[0].get_in_with_default(path.clone(), &args[2]).clone())
+        }
+        _ => Err(EqError::query_error("get-in expects 2 or 3 arguments".to_string())),
     }
 }
 
@@ -93,8 +322,31 @@ fn builtin_first(args: &[EdnValue]) -> EqResult<EdnValue> {
         return Err(EqError::query_error("first expects exactly 1 argument".to_string()));
     }
     
-    let target = &args[0];
-    Ok(target.first().cloned().unwrap_or(EdnValue::Nil))
+    match &args[0] {
+        EdnValue::LazySeq(seq) => Ok(seq.step()?.map(|(first, _)| first).unwrap_or(EdnValue::Nil)),
+        target => Ok(target.first().cloned().unwrap_or(EdnValue::Nil)),
+    }
+}
+
+fn builtin_second(args: &[EdnValue]) -> EqResult<EdnValue> {
+    element_at("second", 1, args)
+}
+
+fn builtin_third(args: &[EdnValue]) -> EqResult<EdnValue> {
+    element_at("third", 2, args)
+}
+
+fn builtin_fourth(args: &[EdnValue]) -> EqResult<EdnValue> {
+    element_at("fourth", 3, args)
+}
+
+/// Like first: nil when the collection is too short
+fn element_at(name: &str, index: usize, args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error(format!("{} expects exactly 1 argument", name)));
+    }
+
+    Ok(args[0].as_slice().get(index).cloned().unwrap_or(EdnValue::Nil))
 }
 
 fn builtin_last(args: &[EdnValue]) -> EqResult<EdnValue> {
@@ -106,13 +358,24 @@ fn builtin_last(args: &[EdnValue]) -> EqResult<EdnValue> {
     Ok(target.last().cloned().unwrap_or(EdnValue::Nil))
 }
 
+fn builtin_butlast(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("butlast expects exactly 1 argument".to_string()));
+    }
+
+    let items = args[0].as_slice();
+    Ok(EdnValue::Vector(items[..items.len().saturating_sub(1)].to_vec()))
+}
+
 fn builtin_rest(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 1 {
         return Err(EqError::query_error("rest expects exactly 1 argument".to_string()));
     }
     
-    let target = &args[0];
-    Ok(target.rest())
+    match &args[0] {
+        EdnValue::LazySeq(seq) => Ok(seq.step()?.map(|(_, rest)| rest).unwrap_or(EdnValue::Vector(Vec::new()))),
+        target => Ok(target.rest()),
+    }
 }
 
 fn builtin_take(args: &[EdnValue]) -> EqResult<EdnValue> {
@@ -127,13 +390,49 @@ fn builtin_take(args: &[EdnValue]) -> EqResult<EdnValue> {
         }
         
         let count = *count as usize;
-        let target = &args[1];
-        Ok(target.take(count))
+        match &args[1] {
+            EdnValue::LazySeq(seq) => Ok(EdnValue::Vector(seq.take(count)?)),
+            target => Ok(target.take(count)),
+        }
     } else {
         Err(EqError::type_error("integer", args[0].type_name()))
     }
 }
 
+fn builtin_sliding_window(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (sliding-window n coll) - every run of n consecutive elements
+    match args {
+        [size, coll] => windows("sliding-window", size, &EdnValue::Integer(1), coll),
+        _ => Err(EqError::query_error("sliding-window expects exactly 2 arguments: size and collection".to_string())),
+    }
+}
+
+fn builtin_strides(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (strides n step coll) - runs of n elements starting every step elements
+    match args {
+        [size, step, coll] => windows("strides", size, step, coll),
+        _ => Err(EqError::query_error("strides expects exactly 3 arguments: size, step and collection".to_string())),
+    }
+}
+
+/// Vectors of `size` elements starting every `step` elements. A trailing run
+/// shorter than `size` is left out, so a short collection gives no windows.
+fn windows(name: &str, size: &EdnValue, step: &EdnValue, coll: &EdnValue) -> EqResult<EdnValue> {
+    let positive = |value: &EdnValue, what: &str| match value {
+        EdnValue::Integer(n) if *n > 0 => Ok(*n as usize),
+        EdnValue::Integer(n) => Err(EqError::query_error(format!("{} {} must be positive, got {}", name, what, n))),
+        other => Err(EqError::type_error("integer", other.type_name())),
+    };
+    let (size, step) = (positive(size, "size")?, positive(step, "step")?);
+    let items: Vec<&EdnValue> = coll.iter_values().collect();
+    let windows = items
+        .windows(size)
+        .step_by(step)
+        .map(|window| EdnValue::Vector(window.iter().map(|item| (*item).clone()).collect()))
+        .collect();
+    Ok(EdnValue::Vector(windows))
+}
+
 fn builtin_drop(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 2 {
         return Err(EqError::query_error("drop expects exactly 2 arguments".to_string()));
@@ -153,20 +452,74 @@ fn builtin_drop(args: &[EdnValue]) -> EqResult<EdnValue> {
     }
 }
 
+fn builtin_take_last(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("take-last expects exactly 2 arguments".to_string()));
+    }
+
+    // (take-last n coll) - the last n elements
+    let count = suffix_count(&args[0])?;
+    let items = args[1].as_slice();
+    Ok(EdnValue::Vector(items[items.len().saturating_sub(count)..].to_vec()))
+}
+
+fn builtin_drop_last(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (drop-last coll) drops one element, (drop-last n coll) drops n
+    let (count, target) = match args {
+        [target] => (1, target),
+        [n, target] => (suffix_count(n)?, target),
+        _ => return Err(EqError::query_error("drop-last expects 1 or 2 arguments".to_string())),
+    };
+    let items = target.as_slice();
+    Ok(EdnValue::Vector(items[..items.len().saturating_sub(count)].to_vec()))
+}
+
+/// Element count for the suffix operations; negative counts mean none
+fn suffix_count(n: &EdnValue) -> EqResult<usize> {
+    match n {
+        EdnValue::Integer(count) => Ok((*count).max(0) as usize),
+        other => Err(EqError::type_error("integer", other.type_name())),
+    }
+}
+
 fn builtin_nth(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 2 {
         return Err(EqError::query_error("nth expects exactly 2 arguments".to_string()));
     }
 
-    // (nth coll index) - get element at index from collection
+    // (nth coll index) - get element at index from collection; an index
+    // outside a vector or list is an error (see nth-safe)
     let collection = &args[0];
     if let EdnValue::Integer(index) = &args[1] {
-        Ok(collection.get(&EdnValue::Integer(*index)).cloned().unwrap_or(EdnValue::Nil))
+        match collection.get(&EdnValue::Integer(*index)) {
+            Some(value) => Ok(value.clone()),
+            None if matches!(collection, EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::WithMetadata { .. }) => {
+                Err(EqError::query_error(format!(
+                    "nth index {} out of bounds for {} of length {}",
+                    index,
+                    collection.type_name(),
+                    collection.as_slice().len()
+                )))
+            }
+            None => Ok(EdnValue::Nil),
+        }
     } else {
         Err(EqError::type_error("integer", args[1].type_name()))
     }
 }
 
+fn builtin_nth_safe(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("nth-safe expects exactly 2 arguments".to_string()));
+    }
+
+    // (nth-safe coll index) - like nth, but nil when the index is out of bounds
+    match &args[1] {
+        index @ EdnValue::Integer(_) => Ok(args[0].get(index).cloned().unwrap_or(EdnValue::Nil)),
+        other => Err(EqError::type_error("integer", other.type_name())),
+    }
+}
+
 fn builtin_count(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 1 {
         return Err(EqError::query_error("count expects exactly 1 argument".to_string()));
@@ -210,6 +563,464 @@ fn builtin_vals(args: &[EdnValue]) -> EqResult<EdnValue> {
     }
 }
 
+/// The entries of a map whose keys satisfy keep, in map order. Like keys
+/// and vals, anything but a map has no entries.
+fn entries_where(
+    target: &EdnValue,
+    mut keep: impl FnMut(&EdnValue) -> EqResult<bool>,
+) -> EqResult<Vec<(&EdnValue, &EdnValue)>> {
+    let mut entries = Vec::new();
+    if let EdnValue::Map(m) = target {
+        for (k, v) in m {
+            if keep(k)? {
+                entries.push((k, v));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn keys_where(target: &EdnValue, keep: impl FnMut(&EdnValue) -> EqResult<bool>) -> EqResult<EdnValue> {
+    let entries = entries_where(target, keep)?;
+    Ok(EdnValue::Vector(entries.into_iter().map(|(k, _)| k.clone()).collect()))
+}
+
+/// (keys-of :string m) is the keys of m of that type. Types are named as in
+/// type errors: :keyword, :string, :symbol, :integer and so on.
+fn builtin_keys_of(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [EdnValue::Keyword(kind) | EdnValue::String(kind), target] => {
+            keys_where(target, |k| Ok(k.type_name() == kind))
+        }
+        [other, _] => Err(EqError::type_error("keyword", other.type_name())),
+        _ => Err(EqError::query_error("keys-of expects exactly 2 arguments: type and map".to_string())),
+    }
+}
+
+fn builtin_keyword_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [target] => keys_where(target, |k| Ok(matches!(k, EdnValue::Keyword(_)))),
+        _ => Err(EqError::query_error("keyword-keys expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_string_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [target] => keys_where(target, |k| Ok(matches!(k, EdnValue::String(_)))),
+        _ => Err(EqError::query_error("string-keys expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_symbol_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [target] => keys_where(target, |k| Ok(matches!(k, EdnValue::Symbol(_)))),
+        _ => Err(EqError::query_error("symbol-keys expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_keys_where(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [pred, target] => keys_where(target, |k| Ok(apply_function(pred, std::slice::from_ref(k))?.is_truthy())),
+        _ => Err(EqError::query_error("keys-where expects exactly 2 arguments: predicate and map".to_string())),
+    }
+}
+
+/// (vals-where pred m) is the values of m whose keys satisfy pred
+fn builtin_vals_where(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [pred, target] => {
+            let entries = entries_where(target, |k| Ok(apply_function(pred, std::slice::from_ref(k))?.is_truthy()))?;
+            Ok(EdnValue::Vector(entries.into_iter().map(|(_, v)| v.clone()).collect()))
+        }
+        _ => Err(EqError::query_error("vals-where expects exactly 2 arguments: predicate and map".to_string())),
+    }
+}
+
+fn builtin_concat(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let mut results = Vec::new();
+    for coll in args {
+        match coll {
+            EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::Nil => {
+                results.extend(coll.iter_values().cloned());
+            }
+            _ => return Err(EqError::type_error("sequential collection", coll.type_name())),
+        }
+    }
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_vec(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("vec expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::Nil => {
+            Ok(EdnValue::Vector(args[0].iter_values().cloned().collect()))
+        }
+        other => Err(EqError::type_error("sequential collection or set", other.type_name())),
+    }
+}
+
+fn builtin_vector(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Vector(args.to_vec()))
+}
+
+fn builtin_list(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::List(args.to_vec()))
+}
+
+fn builtin_set(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("set expects exactly 1 argument; use hash-set to list the elements".to_string()));
+    }
+
+    // Like vec, (set coll) converts a collection; duplicates are dropped
+    match &args[0] {
+        EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::Nil => {
+            Ok(EdnValue::Set(args[0].iter_values().cloned().collect()))
+        }
+        other => Err(EqError::type_error("sequential collection or set", other.type_name())),
+    }
+}
+
+fn builtin_hash_set(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Set(args.iter().cloned().collect()))
+}
+
+fn builtin_sort(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("sort expects exactly 1 argument".to_string()));
+    }
+
+    // Values of any mix of types can be sorted; see EdnValue's Ord
+    match &args[0] {
+        EdnValue::Nil => Ok(EdnValue::Vector(Vec::new())),
+        coll @ (EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::Map(_)) => {
+            let mut items: Vec<EdnValue> = coll.iter_values().cloned().collect();
+            items.sort();
+            Ok(EdnValue::Vector(items))
+        }
+        other => Err(EqError::type_error("collection", other.type_name())),
+    }
+}
+
+fn builtin_distinct(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("distinct expects exactly 1 argument".to_string()));
+    }
+
+    // Keeps the first occurrence of each value, in order
+    let mut seen = HashSet::new();
+    let items = args[0].iter_values()
+        .filter(|item| seen.insert(*item))
+        .cloned()
+        .collect();
+    Ok(EdnValue::Vector(items))
+}
+
+fn builtin_reverse(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("reverse expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::Vector(items) | EdnValue::List(items) => {
+            Ok(EdnValue::Vector(items.iter().rev().cloned().collect()))
+        }
+        other => Err(EqError::type_error("vector or list", other.type_name())),
+    }
+}
+
+fn builtin_shuffle(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("shuffle expects exactly 1 argument".to_string()));
+    }
+
+    let mut items: Vec<EdnValue> = match &args[0] {
+        EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::Nil => {
+            args[0].iter_values().cloned().collect()
+        }
+        other => return Err(EqError::type_error("sequential collection or set", other.type_name())),
+    };
+    items.shuffle(&mut rand::thread_rng());
+    Ok(EdnValue::Vector(items))
+}
+
+fn builtin_zip(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("zip expects exactly 2 arguments".to_string()));
+    }
+
+    let pairs = args[0].iter_values()
+        .zip(args[1].iter_values())
+        .map(|(a, b)| EdnValue::Vector(vec![a.clone(), b.clone()]))
+        .collect();
+    Ok(EdnValue::Vector(pairs))
+}
+
+fn builtin_zipmap(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("zipmap expects exactly 2 arguments: keys and values".to_string()));
+    }
+
+    let map = args[0].iter_values()
+        .zip(args[1].iter_values())
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Ok(EdnValue::Map(map))
+}
+
+fn builtin_hash_map(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (hash-map k1 v1 k2 v2 ...); a repeated key keeps its last value
+    Ok(EdnValue::Map(key_value_pairs("hash-map", args)?))
+}
+
+fn builtin_sorted_map(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let mut map = key_value_pairs("sorted-map", args)?;
+    map.sort_keys();
+    Ok(EdnValue::Map(map))
+}
+
+fn key_value_pairs(name: &str, args: &[EdnValue]) -> EqResult<IndexMap<EdnValue, EdnValue>> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EqError::query_error(format!(
+            "{} expects an even number of arguments (keys and values), got {}",
+            name,
+            args.len()
+        )));
+    }
+    Ok(args.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect())
+}
+
+fn builtin_interleave(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() < 2 {
+        return Err(EqError::query_error("interleave expects at least 2 arguments".to_string()));
+    }
+
+    // Stops at the end of the shortest collection
+    let colls: Vec<Vec<&EdnValue>> = args.iter().map(|c| c.iter_values().collect()).collect();
+    let shortest = colls.iter().map(Vec::len).min().unwrap_or(0);
+    let mut results = Vec::new();
+    for i in 0..shortest {
+        for coll in &colls {
+            results.push(coll[i].clone());
+        }
+    }
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_interpose(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("interpose expects exactly 2 arguments: separator and collection".to_string()));
+    }
+
+    let separator = &args[0];
+    let mut results = Vec::new();
+    for (i, item) in args[1].iter_values().enumerate() {
+        if i > 0 {
+            results.push(separator.clone());
+        }
+        results.push(item.clone());
+    }
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_rename_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("rename-keys expects exactly 2 arguments: map and key mapping".to_string()));
+    }
+
+    let (map, mapping) = match (&args[0], &args[1]) {
+        (EdnValue::Map(map), EdnValue::Map(mapping)) => (map, mapping),
+        (EdnValue::Map(_), other) | (other, _) => return Err(EqError::type_error("map", other.type_name())),
+    };
+
+    // Keys that a rename will write to; a renamed value replaces whatever was there
+    let targets: std::collections::HashSet<&EdnValue> = mapping.iter()
+        .filter(|(old, _)| map.contains_key(*old))
+        .map(|(_, new)| new)
+        .collect();
+
+    let mut result = IndexMap::new();
+    for (key, value) in map {
+        match mapping.get(key) {
+            Some(new_key) => {
+                result.insert(new_key.clone(), value.clone());
+            }
+            None if targets.contains(key) => {}
+            None => {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_flatten_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (map, separator) = flatten_args("flatten-keys", args)?;
+
+    let mut result = IndexMap::new();
+    flatten_into(&mut result, None, map, separator);
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_unflatten_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (map, separator) = flatten_args("unflatten-keys", args)?;
+
+    let mut result = IndexMap::new();
+    for (key, value) in map {
+        let path: Vec<EdnValue> = match key {
+            EdnValue::Keyword(name) => name.split(separator).map(|part| EdnValue::Keyword(part.to_string())).collect(),
+            EdnValue::String(name) => name.split(separator).map(|part| EdnValue::String(part.to_string())).collect(),
+            other => vec![other.clone()],
+        };
+
+        let (last, parents) = path.split_last().expect("split always yields at least one part");
+        let mut target = &mut result;
+        for part in parents {
+            let entry = target.entry(part.clone()).or_insert_with(|| EdnValue::Map(IndexMap::new()));
+            target = match entry {
+                EdnValue::Map(nested) => nested,
+                _ => return Err(EqError::query_error(format!("unflatten-keys: {} is both a value and a parent of {}", part, key))),
+            };
+        }
+        if target.contains_key(last) {
+            return Err(EqError::query_error(format!("unflatten-keys: {} is both a value and a parent of other keys", key)));
+        }
+        target.insert(last.clone(), value.clone());
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_diff(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [a, b] => Ok(diff_values(a, b)),
+        _ => Err(EqError::query_error("diff expects exactly 2 arguments".to_string())),
+    }
+}
+
+/// The structural difference between two values, as returned by (diff a b):
+/// a map of what is :only-in-a, :only-in-b and :in-both. Maps compare key by
+/// key and vectors and lists index by index, with the values that differ
+/// under :changed; sets compare by membership. Other values are either
+/// equal or entirely different.
+pub fn diff_values(a: &EdnValue, b: &EdnValue) -> EdnValue {
+    match (a, b) {
+        (EdnValue::Map(a), EdnValue::Map(b)) => {
+            let only_in = |x: &IndexMap<EdnValue, EdnValue>, y: &IndexMap<EdnValue, EdnValue>| {
+                EdnValue::Map(x.iter().filter(|(k, _)| !y.contains_key(*k)).map(|(k, v)| (k.clone(), v.clone())).collect())
+            };
+            let mut in_both = IndexMap::new();
+            let mut changed = IndexMap::new();
+            for (key, from) in a {
+                match b.get(key) {
+                    Some(to) if to == from => { in_both.insert(key.clone(), from.clone()); }
+                    Some(to) => { changed.insert(key.clone(), diff_change(vec![], from, to)); }
+                    None => {}
+                }
+            }
+            keyword_map([
+                ("only-in-a", only_in(a, b)),
+                ("only-in-b", only_in(b, a)),
+                ("in-both", EdnValue::Map(in_both)),
+                ("changed", EdnValue::Map(changed)),
+            ])
+        }
+        (EdnValue::Vector(a) | EdnValue::List(a), EdnValue::Vector(b) | EdnValue::List(b)) => {
+            let shared = a.len().min(b.len());
+            let mut in_both = Vec::new();
+            let mut changed = Vec::new();
+            for (i, (from, to)) in a.iter().zip(b).enumerate() {
+                if from == to {
+                    in_both.push(from.clone());
+                } else {
+                    changed.push(diff_change(vec![("index", EdnValue::Integer(i as i64))], from, to));
+                }
+            }
+            keyword_map([
+                ("only-in-a", EdnValue::Vector(a[shared..].to_vec())),
+                ("only-in-b", EdnValue::Vector(b[shared..].to_vec())),
+                ("in-both", EdnValue::Vector(in_both)),
+                ("changed", EdnValue::Vector(changed)),
+            ])
+        }
+        (EdnValue::Set(a), EdnValue::Set(b)) => keyword_map([
+            ("only-in-a", EdnValue::Set(a.difference(b).cloned().collect())),
+            ("only-in-b", EdnValue::Set(b.difference(a).cloned().collect())),
+            ("in-both", EdnValue::Set(a.intersection(b).cloned().collect())),
+        ]),
+        _ if a == b => keyword_map([
+            ("only-in-a", EdnValue::Nil),
+            ("only-in-b", EdnValue::Nil),
+            ("in-both", a.clone()),
+        ]),
+        _ => keyword_map([
+            ("only-in-a", a.clone()),
+            ("only-in-b", b.clone()),
+            ("in-both", EdnValue::Nil),
+        ]),
+    }
+}
+
+/// One entry of :changed: {:from old :to new}, after any identifying
+/// entries, plus the nested :diff when both sides are collections of a kind
+fn diff_change(mut entries: Vec<(&'static str, EdnValue)>, from: &EdnValue, to: &EdnValue) -> EdnValue {
+    entries.push(("from", from.clone()));
+    entries.push(("to", to.clone()));
+    let comparable = matches!(
+        (from, to),
+        (EdnValue::Map(_), EdnValue::Map(_))
+            | (EdnValue::Set(_), EdnValue::Set(_))
+            | (EdnValue::Vector(_) | EdnValue::List(_), EdnValue::Vector(_) | EdnValue::List(_))
+    );
+    if comparable {
+        entries.push(("diff", diff_values(from, to)));
+    }
+    keyword_map(entries)
+}
+
+fn keyword_map(entries: impl IntoIterator<Item = (&'static str, EdnValue)>) -> EdnValue {
+    EdnValue::Map(entries.into_iter().map(|(k, v)| (EdnValue::Keyword(k.to_string()), v)).collect())
+}
+
+/// (flatten-keys map) or (flatten-keys map separator); the separator defaults to "."
+fn flatten_args<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(&'a IndexMap<EdnValue, EdnValue>, &'a str)> {
+    let separator = match args {
+        [_] => ".",
+        [_, EdnValue::String(separator)] if !separator.is_empty() => separator,
+        [_, EdnValue::String(_)] => return Err(EqError::query_error(format!("{} separator can't be empty", name))),
+        [_, other] => return Err(EqError::type_error("string", other.type_name())),
+        _ => return Err(EqError::query_error(format!("{} expects 1 or 2 arguments: map and optional separator", name))),
+    };
+    match &args[0] {
+        EdnValue::Map(map) => Ok((map, separator)),
+        other => Err(EqError::type_error("map", other.type_name())),
+    }
+}
+
+fn flatten_into(result: &mut IndexMap<EdnValue, EdnValue>, prefix: Option<&str>, map: &IndexMap<EdnValue, EdnValue>, separator: &str) {
+    for (key, value) in map {
+        let name = match key {
+            EdnValue::Keyword(name) | EdnValue::String(name) => name.clone(),
+            other => other.to_string(),
+        };
+        let name = match prefix {
+            Some(prefix) => format!("{}{}{}", prefix, separator, name),
+            None => name,
+        };
+        match value {
+            // Empty maps stay as values so they survive a round trip
+            EdnValue::Map(nested) if !nested.is_empty() => flatten_into(result, Some(&name), nested, separator),
+            _ => {
+                result.insert(EdnValue::Keyword(name), value.clone());
+            }
+        }
+    }
+}
+
 // Predicates
 fn builtin_is_nil(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 1 {
@@ -228,7 +1039,10 @@ fn builtin_is_empty(args: &[EdnValue]) -> EqResult<EdnValue> {
     
     let target = &args[0];
 
-    let result = target.count().map(|c| c == 0).unwrap_or(false);
+    let result = match target {
+        EdnValue::LazySeq(seq) => seq.step()?.is_none(),
+        _ => target.count().map(|c| c == 0).unwrap_or(false),
+    };
     Ok(EdnValue::Bool(result))
 }
 
@@ -256,7 +1070,7 @@ fn builtin_is_number(args: &[EdnValue]) -> EqResult<EdnValue> {
     
     let target = &args[0];
 
-    Ok(EdnValue::Bool(matches!(target, EdnValue::Integer(_) | EdnValue::Float(_))))
+    Ok(EdnValue::Bool(matches!(target, EdnValue::Integer(_) | EdnValue::BigInt(_) | EdnValue::Ratio { .. } | EdnValue::Float(_))))
 }
 
 fn builtin_is_string(args: &[EdnValue]) -> EqResult<EdnValue> {
@@ -269,6 +1083,35 @@ fn builtin_is_string(args: &[EdnValue]) -> EqResult<EdnValue> {
     Ok(EdnValue::Bool(matches!(target, EdnValue::String(_))))
 }
 
+fn builtin_is_map(args: &[EdnValue]) -> EqResult<EdnValue> {
+    type_predicate("map?", args, |value| matches!(value, EdnValue::Map(_)))
+}
+
+fn builtin_is_vector(args: &[EdnValue]) -> EqResult<EdnValue> {
+    type_predicate("vector?", args, |value| matches!(value, EdnValue::Vector(_)))
+}
+
+fn builtin_is_list(args: &[EdnValue]) -> EqResult<EdnValue> {
+    type_predicate("list?", args, |value| matches!(value, EdnValue::List(_)))
+}
+
+fn builtin_is_set(args: &[EdnValue]) -> EqResult<EdnValue> {
+    type_predicate("set?", args, |value| matches!(value, EdnValue::Set(_)))
+}
+
+fn builtin_is_coll(args: &[EdnValue]) -> EqResult<EdnValue> {
+    type_predicate("coll?", args, |value| {
+        matches!(value, EdnValue::Map(_) | EdnValue::Vector(_) | EdnValue::List(_) | EdnValue::Set(_) | EdnValue::LazySeq(_))
+    })
+}
+
+fn type_predicate(name: &str, args: &[EdnValue], test: fn(&EdnValue) -> bool) -> EqResult<EdnValue> {
+    match args {
+        [value] => Ok(EdnValue::Bool(test(value))),
+        _ => Err(EqError::query_error(format!("{} expects exactly 1 argument", name))),
+    }
+}
+
 fn builtin_is_keyword(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 1 {
         return Err(EqError::query_error("keyword? expects exactly 1 argument".to_string()));
@@ -289,22 +1132,95 @@ fn builtin_is_boolean(args: &[EdnValue]) -> EqResult<EdnValue> {
     Ok(EdnValue::Bool(matches!(target, EdnValue::Bool(_))))
 }
 
-// Comparison
-fn builtin_equal(args: &[EdnValue]) -> EqResult<EdnValue> {
-    match args.len() {
-        0 | 1 => {
-            // (=) or (= a) - vacuously true  
+fn builtin_is_bigint(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("bigint? expects exactly 1 argument".to_string()));
+    }
+
+    Ok(EdnValue::Bool(matches!(args[0], EdnValue::BigInt(_))))
+}
+
+fn builtin_is_ratio(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("ratio? expects exactly 1 argument".to_string()));
+    }
+
+    Ok(EdnValue::Bool(matches!(args[0], EdnValue::Ratio { .. })))
+}
+
+fn builtin_is_qualified(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("qualified? expects exactly 1 argument".to_string()));
+    }
+
+    Ok(EdnValue::Bool(args[0].namespace().is_some()))
+}
+
+fn builtin_is_uuid(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("uuid? expects exactly 1 argument".to_string()));
+    }
+
+    Ok(EdnValue::Bool(matches!(args[0], EdnValue::Uuid(_))))
+}
+
+// nan?, infinite? and finite? take any number; only floats can be NaN or infinite
+fn builtin_is_nan(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let x = single_number("nan?", args)?;
+    Ok(EdnValue::Bool(matches!(x, EdnValue::Float(f) if f.is_nan())))
+}
+
+fn builtin_is_infinite(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let x = single_number("infinite?", args)?;
+    Ok(EdnValue::Bool(matches!(x, EdnValue::Float(f) if f.is_infinite())))
+}
+
+fn builtin_is_finite(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let x = single_number("finite?", args)?;
+    Ok(EdnValue::Bool(!matches!(x, EdnValue::Float(f) if !f.is_finite())))
+}
+
+fn builtin_not(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("not expects exactly 1 argument".to_string()));
+    }
+
+    Ok(EdnValue::Bool(!args[0].is_truthy()))
+}
+
+// Comparison
+fn builtin_equal(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args.len() {
+        0 | 1 => {
+            // (=) or (= a) - vacuously true  
             Ok(EdnValue::Bool(true))
         }
         _ => {
             // (= a b c ...) - all arguments must be equal
             let first = &args[0];
-            let all_equal = args.iter().skip(1).all(|arg| arg == first);
-            Ok(EdnValue::Bool(all_equal))
+            for arg in &args[1..] {
+                if !values_equal(first, arg)? {
+                    return Ok(EdnValue::Bool(false));
+                }
+            }
+            Ok(EdnValue::Bool(true))
         }
     }
 }
 
+/// A lazy sequence equals any sequence with the same elements. It is only
+/// realized as far as needed to tell, so infinite sequences compare unequal
+/// rather than failing.
+fn values_equal(a: &EdnValue, b: &EdnValue) -> EqResult<bool> {
+    match (a, b) {
+        (EdnValue::LazySeq(seq), EdnValue::Vector(items) | EdnValue::List(items))
+        | (EdnValue::Vector(items) | EdnValue::List(items), EdnValue::LazySeq(seq)) => {
+            Ok(seq.take(items.len() + 1)? == *items)
+        }
+        _ => Ok(a == b),
+    }
+}
+
 fn builtin_less_than(args: &[EdnValue]) -> EqResult<EdnValue> {
     match args.len() {
         0 | 1 => {
@@ -362,128 +1278,2030 @@ fn builtin_less_equal(args: &[EdnValue]) -> EqResult<EdnValue> {
     }
 }
 
-fn builtin_greater_equal(args: &[EdnValue]) -> EqResult<EdnValue> {
-    match args.len() {
-        0 | 1 => {
-            // (>= ) or (>= a) - vacuously true
-            Ok(EdnValue::Bool(true))
-        }
-        _ => {
-            // (>= a b c ...) - check that a >= b >= c >= ...
-            for i in 0..args.len()-1 {
-                let result = compare_values(&args[i], &args[i+1])?;
-                if result < 0 {
-                    return Ok(EdnValue::Bool(false));
-                }
+fn builtin_greater_equal(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args.len() {
+        0 | 1 => {
+            // (>= ) or (>= a) - vacuously true
+            Ok(EdnValue::Bool(true))
+        }
+        _ => {
+            // (>= a b c ...) - check that a >= b >= c >= ...
+            for i in 0..args.len()-1 {
+                let result = compare_values(&args[i], &args[i+1])?;
+                if result < 0 {
+                    return Ok(EdnValue::Bool(false));
+                }
+            }
+            Ok(EdnValue::Bool(true))
+        }
+    }
+}
+
+// Arithmetic
+fn builtin_add(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (+) is 0
+    args.iter().try_fold(EdnValue::Integer(0), |acc, x| arithmetic(ArithOp::Add, &acc, x))
+}
+
+fn builtin_subtract(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [] => Err(EqError::query_error("- expects at least 1 argument".to_string())),
+        // (- x) negates
+        [x] => arithmetic(ArithOp::Subtract, &EdnValue::Integer(0), x),
+        [first, rest @ ..] => rest.iter().try_fold(first.clone(), |acc, x| arithmetic(ArithOp::Subtract, &acc, x)),
+    }
+}
+
+fn builtin_multiply(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (*) is 1
+    args.iter().try_fold(EdnValue::Integer(1), |acc, x| arithmetic(ArithOp::Multiply, &acc, x))
+}
+
+fn builtin_divide(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [] => Err(EqError::query_error("/ expects at least 1 argument".to_string())),
+        // (/ x) is the reciprocal
+        [x] => divide(&EdnValue::Integer(1), x),
+        [first, rest @ ..] => rest.iter().try_fold(first.clone(), |acc, x| divide(&acc, x)),
+    }
+}
+
+fn builtin_numerator(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("numerator expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::Ratio { numerator, .. } => Ok(EdnValue::Integer(*numerator)),
+        integer @ (EdnValue::Integer(_) | EdnValue::BigInt(_)) => Ok(integer.clone()),
+        other => Err(EqError::type_error("ratio", other.type_name())),
+    }
+}
+
+fn builtin_denominator(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("denominator expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::Ratio { denominator, .. } => Ok(EdnValue::Integer(*denominator)),
+        EdnValue::Integer(_) | EdnValue::BigInt(_) => Ok(EdnValue::Integer(1)),
+        other => Err(EqError::type_error("ratio", other.type_name())),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+/// Apply a binary arithmetic operation. Any Float operand gives a Float;
+/// otherwise any Ratio operand gives an exact rational result, and any BigInt
+/// operand, or an overflowing Integer result, gives a BigInt.
+fn arithmetic(op: ArithOp, left: &EdnValue, right: &EdnValue) -> EqResult<EdnValue> {
+    match (left, right) {
+        (EdnValue::Integer(a), EdnValue::Integer(b)) => {
+            let result = match op {
+                ArithOp::Add => a.checked_add(*b),
+                ArithOp::Subtract => a.checked_sub(*b),
+                ArithOp::Multiply => a.checked_mul(*b),
+            };
+            Ok(match result {
+                Some(n) => EdnValue::Integer(n),
+                None => EdnValue::BigInt(bigint_op(op, BigInt::from(*a), BigInt::from(*b))),
+            })
+        }
+        (EdnValue::Float(_), _) | (_, EdnValue::Float(_)) => {
+            let (a, b) = (to_f64(left)?, to_f64(right)?);
+            Ok(EdnValue::Float(match op {
+                ArithOp::Add => a + b,
+                ArithOp::Subtract => a - b,
+                ArithOp::Multiply => a * b,
+            }))
+        }
+        (EdnValue::Ratio { .. }, _) | (_, EdnValue::Ratio { .. }) => {
+            let ((a, b), (c, d)) = (to_rational(left)?, to_rational(right)?);
+            let (numerator, denominator) = match op {
+                ArithOp::Add => (a * d + c * b, b * d),
+                ArithOp::Subtract => (a * d - c * b, b * d),
+                ArithOp::Multiply => (a * c, b * d),
+            };
+            make_ratio(numerator, denominator)
+        }
+        _ => Ok(EdnValue::BigInt(bigint_op(op, to_bigint(left)?, to_bigint(right)?))),
+    }
+}
+
+/// Divide with the same promotion rules as arithmetic. Integers that don't
+/// divide evenly give a Ratio; dividing an exact number by zero is an error.
+fn divide(left: &EdnValue, right: &EdnValue) -> EqResult<EdnValue> {
+    if matches!(left, EdnValue::Float(_)) || matches!(right, EdnValue::Float(_)) {
+        return Ok(EdnValue::Float(to_f64(left)? / to_f64(right)?));
+    }
+    let ((a, b), (c, d)) = match (left, right) {
+        (EdnValue::BigInt(_), _) | (_, EdnValue::BigInt(_)) => {
+            let (dividend, divisor) = (to_bigint(left)?, to_bigint(right)?);
+            if divisor == BigInt::from(0) {
+                return Err(EqError::query_error("Divide by zero"));
+            }
+            if (&dividend % &divisor) == BigInt::from(0) {
+                return Ok(EdnValue::BigInt(dividend / divisor));
+            }
+            (to_rational(left)?, to_rational(right)?)
+        }
+        _ => (to_rational(left)?, to_rational(right)?),
+    };
+    if c == 0 {
+        return Err(EqError::query_error("Divide by zero"));
+    }
+    make_ratio(a * d, b * c)
+}
+
+/// Exact number as a (numerator, denominator) pair, widened so cross-multiplying can't overflow
+fn to_rational(value: &EdnValue) -> EqResult<(i128, i128)> {
+    match value {
+        EdnValue::Integer(i) => Ok((*i as i128, 1)),
+        EdnValue::Ratio { numerator, denominator } => Ok((*numerator as i128, *denominator as i128)),
+        EdnValue::BigInt(n) => n.to_i64()
+            .map(|i| (i as i128, 1))
+            .ok_or_else(|| EqError::query_error(format!("{}N is too large for ratio arithmetic", n))),
+        other => Err(EqError::type_error("number", other.type_name())),
+    }
+}
+
+fn make_ratio(numerator: i128, denominator: i128) -> EqResult<EdnValue> {
+    EdnValue::ratio(numerator, denominator)
+        .ok_or_else(|| EqError::query_error("Ratio result is out of range"))
+}
+
+fn bigint_op(op: ArithOp, a: BigInt, b: BigInt) -> BigInt {
+    match op {
+        ArithOp::Add => a + b,
+        ArithOp::Subtract => a - b,
+        ArithOp::Multiply => a * b,
+    }
+}
+
+fn to_f64(value: &EdnValue) -> EqResult<f64> {
+    match value {
+        EdnValue::Integer(i) => Ok(*i as f64),
+        EdnValue::BigInt(n) => Ok(n.to_f64().unwrap_or(f64::NAN)),
+        EdnValue::Ratio { numerator, denominator } => Ok(*numerator as f64 / *denominator as f64),
+        EdnValue::Float(f) => Ok(*f),
+        other => Err(EqError::type_error("number", other.type_name())),
+    }
+}
+
+fn to_bigint(value: &EdnValue) -> EqResult<BigInt> {
+    match value {
+        EdnValue::Integer(i) => Ok(BigInt::from(*i)),
+        EdnValue::BigInt(n) => Ok(n.clone()),
+        other => Err(EqError::type_error("number", other.type_name())),
+    }
+}
+
+// Math functions
+fn builtin_abs(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match single_number("abs", args)? {
+        EdnValue::Integer(i) => Ok(i.checked_abs().map_or_else(|| EdnValue::BigInt(BigInt::from(*i).abs()), EdnValue::Integer)),
+        EdnValue::BigInt(n) => Ok(EdnValue::BigInt(n.abs())),
+        EdnValue::Ratio { numerator, denominator } => make_ratio((*numerator as i128).abs(), *denominator as i128),
+        other => Ok(EdnValue::Float(to_f64(other)?.abs())),
+    }
+}
+
+fn builtin_floor(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Float(to_f64(single_number("floor", args)?)?.floor()))
+}
+
+fn builtin_ceil(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Float(to_f64(single_number("ceil", args)?)?.ceil()))
+}
+
+/// Nearest integer, with halves rounded away from zero
+fn builtin_round(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let rounded = match single_number("round", args)? {
+        integer @ (EdnValue::Integer(_) | EdnValue::BigInt(_)) => return Ok(integer.clone()),
+        other => to_f64(other)?.round(),
+    };
+    if rounded >= i64::MIN as f64 && rounded < i64::MAX as f64 {
+        Ok(EdnValue::Integer(rounded as i64))
+    } else {
+        BigInt::from_f64(rounded)
+            .map(EdnValue::BigInt)
+            .ok_or_else(|| EqError::query_error(format!("Cannot round {}", rounded)))
+    }
+}
+
+fn builtin_sqrt(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let x = to_f64(single_number("sqrt", args)?)?;
+    if x < 0.0 {
+        return Err(EqError::query_error(format!("sqrt of negative number {}", x)));
+    }
+    Ok(EdnValue::Float(x.sqrt()))
+}
+
+/// Exact for an integer base and a non-negative integer exponent, promoting
+/// to BigInt on overflow like the other arithmetic; otherwise a Float
+fn builtin_pow(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let [base, exponent] = args else {
+        return Err(EqError::query_error("pow expects exactly 2 arguments: base and exponent".to_string()));
+    };
+    match (base, exponent) {
+        (EdnValue::Integer(_) | EdnValue::BigInt(_), EdnValue::Integer(e)) if *e >= 0 => {
+            let e = u32::try_from(*e).map_err(|_| EqError::query_error(format!("pow exponent {} is too large", e)))?;
+            if let EdnValue::Integer(b) = base {
+                if let Some(n) = b.checked_pow(e) {
+                    return Ok(EdnValue::Integer(n));
+                }
+            }
+            Ok(EdnValue::BigInt(num_traits::pow(to_bigint(base)?, e as usize)))
+        }
+        _ => Ok(EdnValue::Float(to_f64(base)?.powf(to_f64(exponent)?))),
+    }
+}
+
+fn builtin_log(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Float(positive_f64("log", args)?.ln()))
+}
+
+fn builtin_log10(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Float(positive_f64("log10", args)?.log10()))
+}
+
+fn builtin_exp(args: &[EdnValue]) -> EqResult<EdnValue> {
+    Ok(EdnValue::Float(to_f64(single_number("exp", args)?)?.exp()))
+}
+
+// Numeric constants, e.g. as starting values for comparisons
+fn builtin_min_val(args: &[EdnValue]) -> EqResult<EdnValue> {
+    no_arguments("min-val", args)?;
+    Ok(EdnValue::Integer(i64::MIN))
+}
+
+fn builtin_max_val(args: &[EdnValue]) -> EqResult<EdnValue> {
+    no_arguments("max-val", args)?;
+    Ok(EdnValue::Integer(i64::MAX))
+}
+
+fn builtin_infinity(args: &[EdnValue]) -> EqResult<EdnValue> {
+    no_arguments("infinity", args)?;
+    Ok(EdnValue::Float(f64::INFINITY))
+}
+
+fn builtin_negative_infinity(args: &[EdnValue]) -> EqResult<EdnValue> {
+    no_arguments("negative-infinity", args)?;
+    Ok(EdnValue::Float(f64::NEG_INFINITY))
+}
+
+fn builtin_nan(args: &[EdnValue]) -> EqResult<EdnValue> {
+    no_arguments("nan", args)?;
+    Ok(EdnValue::Float(f64::NAN))
+}
+
+fn builtin_clamp(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (clamp x min max) - a bound replaces x when x is outside it
+    let (x, min, max) = range_args("clamp", args)?;
+    Ok(if compare_values(x, min)? < 0 {
+        min.clone()
+    } else if compare_values(x, max)? > 0 {
+        max.clone()
+    } else {
+        x.clone()
+    })
+}
+
+fn builtin_clamp_or_nil(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (x, min, max) = range_args("clamp-or-nil", args)?;
+    Ok(if in_range(x, min, max)? { x.clone() } else { EdnValue::Nil })
+}
+
+fn builtin_in_range(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (x, min, max) = range_args("in-range?", args)?;
+    Ok(EdnValue::Bool(in_range(x, min, max)?))
+}
+
+/// Inclusive at both ends
+fn in_range(x: &EdnValue, min: &EdnValue, max: &EdnValue) -> EqResult<bool> {
+    Ok(compare_values(x, min)? >= 0 && compare_values(x, max)? <= 0)
+}
+
+/// The arguments of (f x min max): three numbers, with min no greater than max
+fn range_args<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(&'a EdnValue, &'a EdnValue, &'a EdnValue)> {
+    let [x, min, max] = args else {
+        return Err(EqError::query_error(format!("{} expects exactly 3 arguments: value, min and max", name)));
+    };
+    let is_number = |v: &&EdnValue| matches!(v, EdnValue::Integer(_) | EdnValue::BigInt(_) | EdnValue::Ratio { .. } | EdnValue::Float(_));
+    if let Some(other) = [x, min, max].into_iter().find(|v| !is_number(v)) {
+        return Err(EqError::type_error("number", other.type_name()));
+    }
+    if compare_values(min, max)? > 0 {
+        return Err(EqError::query_error(format!("{}: min {} is greater than max {}", name, min, max)));
+    }
+    Ok((x, min, max))
+}
+
+fn no_arguments(name: &str, args: &[EdnValue]) -> EqResult<()> {
+    if args.is_empty() {
+        Ok(())
+    } else {
+        Err(EqError::query_error(format!("{} expects no arguments", name)))
+    }
+}
+
+fn single_number<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<&'a EdnValue> {
+    match args {
+        [x @ (EdnValue::Integer(_) | EdnValue::BigInt(_) | EdnValue::Ratio { .. } | EdnValue::Float(_))] => Ok(x),
+        [other] => Err(EqError::type_error("number", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 1 argument", name))),
+    }
+}
+
+/// The argument of a logarithm, which must be greater than zero
+fn positive_f64(name: &str, args: &[EdnValue]) -> EqResult<f64> {
+    let x = to_f64(single_number(name, args)?)?;
+    if x <= 0.0 {
+        return Err(EqError::query_error(format!("{} of non-positive number {}", name, x)));
+    }
+    Ok(x)
+}
+
+// Bitwise operations, on 64-bit signed integers only
+fn builtin_bit_and(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, b) = integer_pair("bit-and", args)?;
+    Ok(EdnValue::Integer(a & b))
+}
+
+fn builtin_bit_or(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, b) = integer_pair("bit-or", args)?;
+    Ok(EdnValue::Integer(a | b))
+}
+
+fn builtin_bit_xor(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, b) = integer_pair("bit-xor", args)?;
+    Ok(EdnValue::Integer(a ^ b))
+}
+
+fn builtin_bit_not(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [EdnValue::Integer(a)] => Ok(EdnValue::Integer(!a)),
+        [other] => Err(EqError::type_error("integer", other.type_name())),
+        _ => Err(EqError::query_error("bit-not expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_bit_shift_left(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, n) = integer_pair("bit-shift-left", args)?;
+    Ok(EdnValue::Integer(a << bit_index("bit-shift-left", n)?))
+}
+
+/// Arithmetic shift: the sign bit is copied in from the left
+fn builtin_bit_shift_right(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, n) = integer_pair("bit-shift-right", args)?;
+    Ok(EdnValue::Integer(a >> bit_index("bit-shift-right", n)?))
+}
+
+fn builtin_bit_test(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (a, n) = integer_pair("bit-test", args)?;
+    Ok(EdnValue::Bool(a & (1 << bit_index("bit-test", n)?) != 0))
+}
+
+fn integer_pair(name: &str, args: &[EdnValue]) -> EqResult<(i64, i64)> {
+    match args {
+        [EdnValue::Integer(a), EdnValue::Integer(b)] => Ok((*a, *b)),
+        [EdnValue::Integer(_), other] | [other, _] => Err(EqError::type_error("integer", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 2 arguments", name))),
+    }
+}
+
+/// A shift amount or bit number, which must be in 0..=63
+fn bit_index(name: &str, n: i64) -> EqResult<u32> {
+    u32::try_from(n)
+        .ok()
+        .filter(|n| *n < i64::BITS)
+        .ok_or_else(|| EqError::query_error(format!("{} bit index must be between 0 and 63, got {}", name, n)))
+}
+
+// Type conversion
+fn builtin_int(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("int expects exactly 1 argument".to_string()));
+    }
+
+    let converted = match &args[0] {
+        EdnValue::Integer(i) => Some(*i),
+        EdnValue::BigInt(n) => n.to_i64(),
+        // Truncates toward zero
+        EdnValue::Ratio { numerator, denominator } => Some(numerator / denominator),
+        EdnValue::Float(f) if f.is_finite() && *f >= i64::MIN as f64 && *f < i64::MAX as f64 => Some(*f as i64),
+        EdnValue::String(s) => s.trim().parse::<i64>().ok(),
+        EdnValue::Bool(b) => Some(*b as i64),
+        EdnValue::Character(c) => Some(*c as i64),
+        _ => None,
+    };
+    converted
+        .map(EdnValue::Integer)
+        .ok_or_else(|| EqError::query_error(format!("Cannot convert {} to int", args[0])))
+}
+
+fn builtin_float(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("float expects exactly 1 argument".to_string()));
+    }
+
+    let converted = match &args[0] {
+        EdnValue::String(s) => s.trim().parse::<f64>().ok(),
+        number @ (EdnValue::Integer(_) | EdnValue::BigInt(_) | EdnValue::Ratio { .. } | EdnValue::Float(_)) => to_f64(number).ok(),
+        _ => None,
+    };
+    converted
+        .map(EdnValue::Float)
+        .ok_or_else(|| EqError::query_error(format!("Cannot convert {} to float", args[0])))
+}
+
+fn builtin_str(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // Concatenates its arguments like Clojure's str: strings and characters
+    // as-is, nil as nothing, everything else as compact EDN
+    let mut result = String::new();
+    for arg in args {
+        match arg {
+            EdnValue::Nil => {}
+            EdnValue::String(s) => result.push_str(s),
+            EdnValue::Character(c) => result.push(*c),
+            other => result.push_str(&other.to_string()),
+        }
+    }
+    Ok(EdnValue::String(result))
+}
+
+fn builtin_format(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args.split_first() {
+        Some((EdnValue::String(fmt), rest)) => Ok(EdnValue::String(format_string(fmt, rest)?)),
+        Some((other, _)) => Err(EqError::type_error("string", other.type_name())),
+        None => Err(EqError::query_error("format expects a format string".to_string())),
+    }
+}
+
+fn builtin_printf(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let EdnValue::String(text) = builtin_format(args)? else {
+        unreachable!("format returns a string")
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()?;
+    Ok(EdnValue::Nil)
+}
+
+/// printf-style formatting. Supports %s, %d, %f, %e, %b and %%; %f and %e
+/// take a precision such as %.2f and default to 6 decimals, as in C.
+fn format_string(fmt: &str, args: &[EdnValue]) -> EqResult<String> {
+    let mut result = String::new();
+    let mut remaining = args.iter();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut digits = String::new();
+            while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                digits.push(d);
+            }
+            precision = Some(digits.parse::<usize>().map_err(|_| {
+                EqError::query_error(format!("Invalid precision in format string: {:?}", fmt))
+            })?);
+        }
+
+        let spec = chars.next().ok_or_else(|| {
+            EqError::query_error(format!("Format string ends with an incomplete specifier: {:?}", fmt))
+        })?;
+        if spec == '%' && precision.is_none() {
+            result.push('%');
+            continue;
+        }
+        if precision.is_some() && !matches!(spec, 'f' | 'e') {
+            return Err(EqError::query_error(format!("Precision is only supported for %f and %e, not %{}", spec)));
+        }
+
+        let arg = remaining.next().ok_or_else(|| {
+            EqError::query_error(format!("format string has more specifiers than the {} argument(s) given", args.len()))
+        })?;
+        match spec {
+            's' => match arg {
+                EdnValue::String(s) => result.push_str(s),
+                EdnValue::Character(c) => result.push(*c),
+                other => result.push_str(&other.to_string()),
+            },
+            'd' => match arg {
+                EdnValue::Integer(_) | EdnValue::BigInt(_) => result.push_str(&arg.to_string()),
+                other => return Err(EqError::type_error("integer", other.type_name())),
+            },
+            'f' => result.push_str(&format!("{:.*}", precision.unwrap_or(6), to_f64(arg)?)),
+            'e' => result.push_str(&format_scientific(to_f64(arg)?, precision.unwrap_or(6))),
+            'b' => result.push_str(if arg.is_truthy() { "true" } else { "false" }),
+            other => return Err(EqError::query_error(format!("Unknown format specifier: %{}", other))),
+        }
+    }
+
+    if remaining.next().is_some() {
+        return Err(EqError::query_error(format!(
+            "format string has fewer specifiers than the {} argument(s) given",
+            args.len()
+        )));
+    }
+    Ok(result)
+}
+
+/// C-style %e: a signed exponent of at least two digits, e.g. 1.500000e+03
+fn format_scientific(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let (sign, digits) = match exponent.strip_prefix('-') {
+                Some(digits) => ('-', digits),
+                None => ('+', exponent),
+            };
+            format!("{}e{}{:0>2}", mantissa, sign, digits)
+        }
+        // inf and NaN have no exponent
+        None => formatted,
+    }
+}
+
+fn builtin_starts_with(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (prefix, s) = string_pair("starts-with?", args)?;
+    Ok(EdnValue::Bool(s.starts_with(prefix)))
+}
+
+fn builtin_ends_with(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (suffix, s) = string_pair("ends-with?", args)?;
+    Ok(EdnValue::Bool(s.ends_with(suffix)))
+}
+
+fn builtin_str_contains(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (sub, s) = string_pair("str/contains?", args)?;
+    Ok(EdnValue::Bool(s.contains(sub)))
+}
+
+fn builtin_index_of(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // The index counts characters, not bytes; -1 when sub doesn't occur
+    let (sub, s) = string_pair("index-of", args)?;
+    Ok(EdnValue::Integer(match s.find(sub) {
+        Some(byte_index) => s[..byte_index].chars().count() as i64,
+        None => -1,
+    }))
+}
+
+/// The arguments of the string searches, (f needle s), or of (f s other): both strings
+fn string_pair<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(&'a str, &'a str)> {
+    match args {
+        [EdnValue::String(needle), EdnValue::String(s)] => Ok((needle, s)),
+        [EdnValue::String(_), other] | [other, _] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 2 arguments", name))),
+    }
+}
+
+fn builtin_str_replace(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (regex, s, replacement) = replace_args("str/replace", args)?;
+    Ok(EdnValue::String(regex.replace_all(s, replacement).into_owned()))
+}
+
+fn builtin_str_replace_first(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (regex, s, replacement) = replace_args("str/replace-first", args)?;
+    Ok(EdnValue::String(regex.replace(s, replacement).into_owned()))
+}
+
+/// The arguments of (str/replace s pattern replacement): the pattern is a
+/// regex and the replacement may refer to groups as $1 or ${name}
+fn replace_args<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(Regex, &'a str, &'a str)> {
+    let [s, pattern, replacement] = args else {
+        return Err(EqError::query_error(format!("{} expects exactly 3 arguments: string, pattern and replacement", name)));
+    };
+    let string = |value: &'a EdnValue| match value {
+        EdnValue::String(s) => Ok(s.as_str()),
+        other => Err(EqError::type_error("string", other.type_name())),
+    };
+    let (s, pattern, replacement) = (string(s)?, string(pattern)?, string(replacement)?);
+    let regex = Regex::new(pattern)
+        .map_err(|e| EqError::query_error(format!("Invalid regex {:?} in {}: {}", pattern, name, e)))?;
+    Ok((regex, s, replacement))
+}
+
+fn builtin_split_lines(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // Splits on \n or \r\n; a trailing newline doesn't add an empty line
+    let s = single_string("str/split-lines", args)?;
+    Ok(EdnValue::Vector(s.lines().map(|line| EdnValue::String(line.to_string())).collect()))
+}
+
+fn builtin_trim_newline(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let s = single_string("str/trim-newline", args)?;
+    let trimmed = s.strip_suffix("\r\n").or_else(|| s.strip_suffix('\n')).unwrap_or(s);
+    Ok(EdnValue::String(trimmed.to_string()))
+}
+
+fn builtin_blank(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // nil counts as blank, as in Clojure
+    if let [EdnValue::Nil] = args {
+        return Ok(EdnValue::Bool(true));
+    }
+    let s = single_string("str/blank?", args)?;
+    Ok(EdnValue::Bool(s.trim().is_empty()))
+}
+
+fn builtin_pad_left(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, padding, c) = pad_args("str/pad-left", args)?;
+    Ok(EdnValue::String(padded(s, padding, 0, c)))
+}
+
+fn builtin_pad_right(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, padding, c) = pad_args("str/pad-right", args)?;
+    Ok(EdnValue::String(padded(s, 0, padding, c)))
+}
+
+fn builtin_center(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // An odd amount of padding puts the extra character on the right
+    let (s, padding, c) = pad_args("str/center", args)?;
+    Ok(EdnValue::String(padded(s, padding / 2, padding - padding / 2, c)))
+}
+
+/// The arguments of (str/pad-left s width char), returning the string, how
+/// many characters it is short of the width, and the padding character.
+/// Widths are counted in characters; longer strings are never truncated.
+fn pad_args<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(&'a str, usize, char)> {
+    match args {
+        [EdnValue::String(s), EdnValue::Integer(width), EdnValue::Character(c)] => {
+            let padding = usize::try_from(*width).unwrap_or(0).saturating_sub(s.chars().count());
+            Ok((s, padding, *c))
+        }
+        [EdnValue::String(_), EdnValue::Integer(_), other] => Err(EqError::type_error("character", other.type_name())),
+        [EdnValue::String(_), other, _] => Err(EqError::type_error("integer", other.type_name())),
+        [other, _, _] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 3 arguments: string, width and padding character", name))),
+    }
+}
+
+fn padded(s: &str, left: usize, right: usize, c: char) -> String {
+    let mut result: String = std::iter::repeat_n(c, left).collect();
+    result.push_str(s);
+    result.extend(std::iter::repeat_n(c, right));
+    result
+}
+
+fn builtin_str_repeat(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, n) = string_and_count("str/repeat", args)?;
+    if s.len().checked_mul(n).is_none_or(|len| len > isize::MAX as usize) {
+        return Err(EqError::query_error(format!("str/repeat: {} copies is too long", n)));
+    }
+    Ok(EdnValue::String(s.repeat(n)))
+}
+
+fn builtin_str_insert(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (str/insert s index sub) - the index counts characters and may be the end of s
+    let (s, index, sub) = match args {
+        [EdnValue::String(s), EdnValue::Integer(index), EdnValue::String(sub)] => (s, *index, sub),
+        [EdnValue::String(_), EdnValue::Integer(_), other] => return Err(EqError::type_error("string", other.type_name())),
+        [EdnValue::String(_), other, _] => return Err(EqError::type_error("integer", other.type_name())),
+        [other, _, _] => return Err(EqError::type_error("string", other.type_name())),
+        _ => return Err(EqError::query_error("str/insert expects exactly 3 arguments: string, index and string to insert".to_string())),
+    };
+    let byte_index = usize::try_from(index)
+        .ok()
+        .and_then(|index| s.char_indices().map(|(i, _)| i).chain([s.len()]).nth(index))
+        .ok_or_else(|| EqError::query_error(format!("str/insert: index {} out of bounds for a string of {} characters", index, s.chars().count())))?;
+    let mut result = s.clone();
+    result.insert_str(byte_index, sub);
+    Ok(EdnValue::String(result))
+}
+
+fn builtin_remove_prefix(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, prefix) = string_pair("str/remove-prefix", args)?;
+    Ok(EdnValue::String(s.strip_prefix(prefix).unwrap_or(s).to_string()))
+}
+
+fn builtin_remove_suffix(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, suffix) = string_pair("str/remove-suffix", args)?;
+    Ok(EdnValue::String(s.strip_suffix(suffix).unwrap_or(s).to_string()))
+}
+
+fn builtin_str_take(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, n) = string_and_count("str/take", args)?;
+    Ok(EdnValue::String(s.chars().take(n).collect()))
+}
+
+fn builtin_str_drop(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let (s, n) = string_and_count("str/drop", args)?;
+    Ok(EdnValue::String(s.chars().skip(n).collect()))
+}
+
+/// The arguments of (f s n), where a negative n counts as zero
+fn string_and_count<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<(&'a str, usize)> {
+    match args {
+        [EdnValue::String(s), EdnValue::Integer(n)] => Ok((s, usize::try_from(*n).unwrap_or(0))),
+        [EdnValue::String(_), other] => Err(EqError::type_error("integer", other.type_name())),
+        [other, _] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 2 arguments: string and count", name))),
+    }
+}
+
+fn single_string<'a>(name: &str, args: &'a [EdnValue]) -> EqResult<&'a str> {
+    match args {
+        [EdnValue::String(s)] => Ok(s),
+        [other] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error(format!("{} expects exactly 1 argument", name))),
+    }
+}
+
+fn builtin_inst_before(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let [a, b] = args else {
+        return Err(EqError::query_error("inst/before? expects exactly 2 arguments".to_string()));
+    };
+    Ok(EdnValue::Bool(instant_time(a)? < instant_time(b)?))
+}
+
+fn builtin_inst_after(args: &[EdnValue]) -> EqResult<EdnValue> {
+    let [a, b] = args else {
+        return Err(EqError::query_error("inst/after? expects exactly 2 arguments".to_string()));
+    };
+    Ok(EdnValue::Bool(instant_time(a)? > instant_time(b)?))
+}
+
+fn builtin_inst_parse(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // nil when the string isn't a timestamp, like parse-int
+    match args {
+        [EdnValue::String(s)] => Ok(parse_instant(s).map_or(EdnValue::Nil, |_| EdnValue::Instant(s.clone()))),
+        [instant @ EdnValue::Instant(_)] => Ok(instant.clone()),
+        [other] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error("inst/parse expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_inst_format(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (inst/format inst fmt) with strftime-style fmt, e.g. "%Y-%m-%d"
+    let [instant, fmt] = args else {
+        return Err(EqError::query_error("inst/format expects exactly 2 arguments: instant and format".to_string()));
+    };
+    let EdnValue::String(fmt) = fmt else {
+        return Err(EqError::type_error("string", fmt.type_name()));
+    };
+    let mut formatted = String::new();
+    write!(formatted, "{}", instant_time(instant)?.format(fmt))
+        .map_err(|_| EqError::query_error(format!("Invalid inst/format pattern: {:?}", fmt)))?;
+    Ok(EdnValue::String(formatted))
+}
+
+fn builtin_inst_epoch_ms(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [instant] => Ok(EdnValue::Integer(instant_time(instant)?.timestamp_millis())),
+        _ => Err(EqError::query_error("inst/epoch-ms expects exactly 1 argument".to_string())),
+    }
+}
+
+/// Most instants range-of-dates and date-seq will produce
+const MAX_DATE_SEQ: usize = 10_000;
+
+fn builtin_range_of_dates(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // One instant per day from start (inclusive) to end (exclusive)
+    let [start, end] = args else {
+        return Err(EqError::query_error("range-of-dates expects exactly 2 arguments: start and end instants".to_string()));
+    };
+    let (start, end) = (instant_time(start)?, instant_time(end)?);
+
+    let mut dates = Vec::new();
+    while let Some(time) = offset_time(start, dates.len() as i64, "days").filter(|time| *time < end) {
+        if dates.len() == MAX_DATE_SEQ {
+            return Err(EqError::query_error(format!("range-of-dates would return more than {} dates", MAX_DATE_SEQ)));
+        }
+        dates.push(instant_value(time));
+    }
+    Ok(EdnValue::Vector(dates))
+}
+
+fn builtin_date_seq(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (date-seq start step unit): the first MAX_DATE_SEQ instants, e.g. for take
+    let [start, step, unit] = args else {
+        return Err(EqError::query_error("date-seq expects exactly 3 arguments: start, step and unit".to_string()));
+    };
+    let start = instant_time(start)?;
+    let step = match step {
+        EdnValue::Integer(0) => return Err(EqError::query_error("date-seq step must not be 0".to_string())),
+        EdnValue::Integer(n) => *n,
+        other => return Err(EqError::type_error("integer", other.type_name())),
+    };
+    let unit = match unit {
+        EdnValue::Keyword(k) if matches!(k.as_str(), "hours" | "days" | "months" | "years") => k,
+        EdnValue::Keyword(k) => {
+            return Err(EqError::query_error(format!("date-seq unit must be :hours, :days, :months or :years, not :{}", k)));
+        }
+        other => return Err(EqError::type_error("keyword", other.type_name())),
+    };
+
+    // Each instant is computed from start, so month ends don't drift (Jan 31, Feb 29, Mar 31)
+    let dates = (0..MAX_DATE_SEQ as i64)
+        .map_while(|i| offset_time(start, step.checked_mul(i)?, unit))
+        .map(instant_value)
+        .collect();
+    Ok(EdnValue::Vector(dates))
+}
+
+/// `time` moved by `amount` units, or None when the result is out of range
+fn offset_time(time: DateTime<FixedOffset>, amount: i64, unit: &str) -> Option<DateTime<FixedOffset>> {
+    match unit {
+        "hours" => time.checked_add_signed(TimeDelta::try_hours(amount)?),
+        "days" => time.checked_add_signed(TimeDelta::try_days(amount)?),
+        "months" | "years" => {
+            let months = if unit == "years" { amount.checked_mul(12)? } else { amount };
+            let shift = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+            if months < 0 { time.checked_sub_months(shift) } else { time.checked_add_months(shift) }
+        }
+        _ => None,
+    }
+}
+
+fn instant_value(time: DateTime<FixedOffset>) -> EdnValue {
+    EdnValue::Instant(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+fn builtin_uuid_random(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if !args.is_empty() {
+        return Err(EqError::query_error("uuid/random expects no arguments".to_string()));
+    }
+
+    Ok(uuid_value(Uuid::new_v4()))
+}
+
+fn builtin_uuid_parse(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // nil when the string isn't a UUID; accepted forms are normalized to
+    // lowercase and hyphenated
+    match args {
+        [EdnValue::String(s)] => Ok(Uuid::parse_str(s).map_or(EdnValue::Nil, uuid_value)),
+        [uuid @ EdnValue::Uuid(_)] => Ok(uuid.clone()),
+        [other] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error("uuid/parse expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_uuid_valid(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [EdnValue::String(s)] => Ok(EdnValue::Bool(Uuid::parse_str(s).is_ok())),
+        [EdnValue::Uuid(_)] => Ok(EdnValue::Bool(true)),
+        [_] => Ok(EdnValue::Bool(false)),
+        _ => Err(EqError::query_error("uuid/valid? expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_uuid_str(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [EdnValue::Uuid(s)] => Ok(EdnValue::String(s.clone())),
+        [other] => Err(EqError::type_error("uuid", other.type_name())),
+        _ => Err(EqError::query_error("uuid/str expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_uuid_from_bytes(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("uuid/from-bytes expects exactly 1 argument".to_string()));
+    }
+
+    // (uuid/from-bytes [b0 ... b15]) with each byte an integer from 0 to 255
+    let items = sequential_items(&args[0])?;
+    let bytes: [u8; 16] = items
+        .iter()
+        .map(|item| match item {
+            EdnValue::Integer(b) => u8::try_from(*b)
+                .map_err(|_| EqError::query_error(format!("uuid/from-bytes: {} is not a byte", b))),
+            other => Err(EqError::type_error("integer", other.type_name())),
+        })
+        .collect::<EqResult<Vec<u8>>>()?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| {
+            EqError::query_error(format!("uuid/from-bytes expects 16 bytes, got {}", bytes.len()))
+        })?;
+    Ok(uuid_value(Uuid::from_bytes(bytes)))
+}
+
+fn uuid_value(uuid: Uuid) -> EdnValue {
+    EdnValue::Uuid(uuid.hyphenated().to_string())
+}
+
+/// The point in time of an Instant value
+fn instant_time(value: &EdnValue) -> EqResult<DateTime<FixedOffset>> {
+    match value {
+        EdnValue::Instant(s) => parse_instant(s).ok_or_else(|| EqError::query_error(format!("Invalid instant: {}", s))),
+        other => Err(EqError::type_error("instant", other.type_name())),
+    }
+}
+
+/// RFC 3339 timestamps; without an offset the time is taken to be UTC
+fn parse_instant(s: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(s).ok().or_else(|| {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|time| time.and_utc().fixed_offset())
+    })
+}
+
+fn builtin_keyword(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("keyword expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::String(name) | EdnValue::Symbol(name) | EdnValue::Keyword(name) => Ok(EdnValue::Keyword(name.clone())),
+        other => Err(EqError::query_error(format!("Cannot convert {} to keyword", other))),
+    }
+}
+
+fn builtin_symbol(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("symbol expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::String(name) | EdnValue::Keyword(name) | EdnValue::Symbol(name) => Ok(EdnValue::Symbol(name.clone())),
+        other => Err(EqError::query_error(format!("Cannot convert {} to symbol", other))),
+    }
+}
+
+fn builtin_parse_int(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("parse-int expects exactly 1 argument".to_string()));
+    }
+
+    // Unparseable strings (and nil) give nil rather than an error
+    match &args[0] {
+        EdnValue::String(s) => Ok(s.trim().parse::<i64>().map(EdnValue::Integer).unwrap_or(EdnValue::Nil)),
+        EdnValue::Nil => Ok(EdnValue::Nil),
+        other => Err(EqError::type_error("string", other.type_name())),
+    }
+}
+
+fn builtin_parse_float(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("parse-float expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::String(s) => Ok(s.trim().parse::<f64>().map(EdnValue::Float).unwrap_or(EdnValue::Nil)),
+        EdnValue::Nil => Ok(EdnValue::Nil),
+        other => Err(EqError::type_error("string", other.type_name())),
+    }
+}
+
+fn builtin_parse(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // Strings holding EDN become data; unlike parse-int, bad input is an error
+    match args {
+        [EdnValue::String(s)] => s.parse().map_err(|e| EqError::query_error(format!("parse: {}", e))),
+        [EdnValue::Nil] => Ok(EdnValue::Nil),
+        [other] => Err(EqError::type_error("string", other.type_name())),
+        _ => Err(EqError::query_error("parse expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_number_to_string(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (number->string n radix) - lowercase digits, radix 10 if not given
+    match args {
+        [n] => format_radix(n, 10),
+        [n, radix] => format_radix(n, radix_arg("number->string", radix)?),
+        _ => Err(EqError::query_error("number->string expects 1 or 2 arguments: integer and radix".to_string())),
+    }
+}
+
+fn builtin_string_to_number(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (string->number s radix) - nil when s isn't a number in that radix, like parse-int
+    match args {
+        [s] => parse_radix(s, 10),
+        [s, radix] => parse_radix(s, radix_arg("string->number", radix)?),
+        _ => Err(EqError::query_error("string->number expects 1 or 2 arguments: string and radix".to_string())),
+    }
+}
+
+fn builtin_hex_to_int(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_parse("hex->int", args, 16)
+}
+
+fn builtin_int_to_hex(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_format("int->hex", args, 16)
+}
+
+fn builtin_bin_to_int(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_parse("bin->int", args, 2)
+}
+
+fn builtin_int_to_bin(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_format("int->bin", args, 2)
+}
+
+fn builtin_oct_to_int(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_parse("oct->int", args, 8)
+}
+
+fn builtin_int_to_oct(args: &[EdnValue]) -> EqResult<EdnValue> {
+    radix_shorthand_format("int->oct", args, 8)
+}
+
+fn radix_shorthand_format(name: &str, args: &[EdnValue], radix: u32) -> EqResult<EdnValue> {
+    match args {
+        [n] => format_radix(n, radix),
+        _ => Err(EqError::query_error(format!("{} expects exactly 1 argument", name))),
+    }
+}
+
+fn radix_shorthand_parse(name: &str, args: &[EdnValue], radix: u32) -> EqResult<EdnValue> {
+    match args {
+        [s] => parse_radix(s, radix),
+        _ => Err(EqError::query_error(format!("{} expects exactly 1 argument", name))),
+    }
+}
+
+fn radix_arg(name: &str, radix: &EdnValue) -> EqResult<u32> {
+    match radix {
+        EdnValue::Integer(r) if (2..=36).contains(r) => Ok(*r as u32),
+        other => Err(EqError::query_error(format!("{}: radix must be an integer from 2 to 36, got {}", name, other))),
+    }
+}
+
+fn format_radix(n: &EdnValue, radix: u32) -> EqResult<EdnValue> {
+    let digits = match n {
+        EdnValue::Integer(i) => BigInt::from(*i).to_str_radix(radix),
+        EdnValue::BigInt(n) => n.to_str_radix(radix),
+        other => return Err(EqError::type_error("integer", other.type_name())),
+    };
+    Ok(EdnValue::String(digits))
+}
+
+fn parse_radix(s: &EdnValue, radix: u32) -> EqResult<EdnValue> {
+    let s = match s {
+        EdnValue::String(s) => s.trim(),
+        EdnValue::Nil => return Ok(EdnValue::Nil),
+        other => return Err(EqError::type_error("string", other.type_name())),
+    };
+    Ok(match i64::from_str_radix(s, radix) {
+        Ok(i) => EdnValue::Integer(i),
+        // Too big for i64 but otherwise valid
+        Err(e) if matches!(e.kind(), IntErrorKind::PosOverflow | IntErrorKind::NegOverflow) => {
+            BigInt::parse_bytes(s.as_bytes(), radix).map_or(EdnValue::Nil, EdnValue::BigInt)
+        }
+        Err(_) => EdnValue::Nil,
+    })
+}
+
+// Schemas
+fn builtin_schema_validate(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (schema/validate schema data) - true, or a vector of {:path [...] :error "..."} maps
+    match args {
+        [schema, data] => {
+            let errors = crate::schema::validate(schema, data)?;
+            Ok(if errors.is_empty() { EdnValue::Bool(true) } else { EdnValue::Vector(errors) })
+        }
+        _ => Err(EqError::query_error("schema/validate expects exactly 2 arguments: schema and data".to_string())),
+    }
+}
+
+// Imports
+
+/// Directory that import-edn resolves relative filenames against; unset
+/// (and import-edn disabled) unless --allow-imports was given
+static IMPORT_BASE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Files already imported this run, shared by all --jobs workers
+static IMPORT_CACHE: OnceLock<Mutex<HashMap<String, EdnValue>>> = OnceLock::new();
+
+/// Enable import-edn for the rest of the run, reading files relative to `base_dir`
+pub fn allow_imports(base_dir: PathBuf) {
+    let _ = IMPORT_BASE_DIR.set(base_dir);
+}
+
+fn builtin_import_edn(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("import-edn expects exactly 1 argument".to_string()));
+    }
+    let filename = match &args[0] {
+        EdnValue::String(s) => s,
+        other => return Err(EqError::type_error("string", other.type_name())),
+    };
+    let base_dir = IMPORT_BASE_DIR.get()
+        .ok_or_else(|| EqError::query_error("import-edn is disabled; pass --allow-imports to enable it"))?;
+
+    let cache = IMPORT_CACHE.get_or_init(Mutex::default);
+    if let Some(value) = cache.lock().unwrap_or_else(PoisonError::into_inner).get(filename).cloned() {
+        return Ok(value);
+    }
+
+    // Only files under the base directory: no absolute paths or .., and no
+    // symbolic links that lead out of it
+    let outside = || EqError::query_error(format!("import-edn can only read files under {}: {}", base_dir.display(), filename));
+    if !Path::new(filename).components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(outside());
+    }
+    let path = base_dir.join(filename);
+    if let (Ok(base), Ok(resolved)) = (base_dir.canonicalize(), path.canonicalize()) {
+        if !resolved.starts_with(base) {
+            return Err(outside());
+        }
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| EqError::query_error(format!("import-edn could not read {}: {}", path.display(), e)))?;
+    let value = crate::edn::Parser::new(&text).parse()
+        .map_err(|e| EqError::query_error(format!("import-edn could not parse {}: {}", path.display(), e)))?;
+
+    cache.lock().unwrap_or_else(PoisonError::into_inner).insert(filename.clone(), value.clone());
+    Ok(value)
+}
+
+// Environment variables
+
+/// Set by --no-env; env and env-all then behave as if no variables were set
+static ENV_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Hide the process environment from queries for the rest of the run
+pub fn disable_env() {
+    ENV_DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn builtin_env(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EqError::query_error("env expects 1 or 2 arguments: name and optional default".to_string()));
+    }
+    let name = match &args[0] {
+        EdnValue::String(s) => s,
+        other => return Err(EqError::type_error("string", other.type_name())),
+    };
+    let default = args.get(1).cloned().unwrap_or(EdnValue::Nil);
+
+    if ENV_DISABLED.load(Ordering::Relaxed) {
+        return Ok(default);
+    }
+    // Variables that aren't valid Unicode count as unset
+    Ok(std::env::var(name).map(EdnValue::String).unwrap_or(default))
+}
+
+/// All variables as a map, with names like BASE_URL turned into :base-url
+fn builtin_env_all(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if !args.is_empty() {
+        return Err(EqError::query_error("env-all expects no arguments".to_string()));
+    }
+    if ENV_DISABLED.load(Ordering::Relaxed) {
+        return Ok(EdnValue::Map(IndexMap::new()));
+    }
+
+    let mut vars: Vec<(String, String)> = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect();
+    vars.sort();
+    let map = vars.into_iter()
+        .map(|(name, value)| (EdnValue::Keyword(name.to_lowercase().replace('_', "-")), EdnValue::String(value)))
+        .collect();
+    Ok(EdnValue::Map(map))
+}
+
+// Keywords and symbols
+fn builtin_namespace(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("namespace expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        value @ (EdnValue::Keyword(_) | EdnValue::Symbol(_)) => {
+            Ok(value.namespace().map_or(EdnValue::Nil, |ns| EdnValue::String(ns.to_string())))
+        }
+        other => Err(EqError::type_error("keyword or symbol", other.type_name())),
+    }
+}
+
+fn builtin_name(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("name expects exactly 1 argument".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::String(s) => Ok(EdnValue::String(s.clone())),
+        value => value
+            .name()
+            .map(|name| EdnValue::String(name.to_string()))
+            .ok_or_else(|| EqError::type_error("keyword or symbol", value.type_name())),
+    }
+}
+
+fn builtin_qualified_keyword(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("qualified-keyword expects exactly 2 arguments".to_string()));
+    }
+
+    match (&args[0], &args[1]) {
+        (EdnValue::String(ns), EdnValue::String(name)) if !ns.is_empty() && !name.is_empty() => {
+            Ok(EdnValue::Keyword(format!("{}/{}", ns, name)))
+        }
+        (EdnValue::String(_), EdnValue::String(_)) => {
+            Err(EqError::query_error("qualified-keyword expects a non-empty namespace and name".to_string()))
+        }
+        (EdnValue::String(_), other) | (other, _) => Err(EqError::type_error("string", other.type_name())),
+    }
+}
+
+// Higher-order operations
+fn builtin_map(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("map expects exactly 2 arguments: function and collection".to_string()));
+    }
+    
+    let func = &args[0];
+    let collection = &args[1];
+    
+    // Lazy in, lazy out: f is only called for elements that get realized
+    if let EdnValue::LazySeq(_) = collection {
+        return Ok(map_lazy(func.clone(), collection.clone()));
+    }
+    
+    // Apply function to each element
+    let mut results = Vec::new();
+    for item in collection.iter_values() {
+        let result = apply_function(func, std::slice::from_ref(item))?;
+        results.push(result);
+    }
+    
+    Ok(EdnValue::Vector(results))
+}
+
+// Indexed variants call f with two arguments: the index (Integer) and the element
+fn builtin_map_indexed(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("map-indexed expects exactly 2 arguments: function and collection".to_string()));
+    }
+
+    let func = &args[0];
+    let collection = &args[1];
+
+    let mut results = Vec::new();
+    for (i, item) in collection.iter_values().enumerate() {
+        results.push(apply_function(func, &[EdnValue::Integer(i as i64), item.clone()])?);
+    }
+
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_keep(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("keep expects exactly 2 arguments: function and collection".to_string()));
+    }
+
+    let func = &args[0];
+    let collection = &args[1];
+
+    // Like map, but nil results are dropped (false is kept)
+    let mut results = Vec::new();
+    for item in collection.iter_values() {
+        let result = apply_function(func, std::slice::from_ref(item))?;
+        if result != EdnValue::Nil {
+            results.push(result);
+        }
+    }
+
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_keep_indexed(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("keep-indexed expects exactly 2 arguments: function and collection".to_string()));
+    }
+
+    let func = &args[0];
+    let collection = &args[1];
+
+    let mut results = Vec::new();
+    for (i, item) in collection.iter_values().enumerate() {
+        let result = apply_function(func, &[EdnValue::Integer(i as i64), item.clone()])?;
+        if result != EdnValue::Nil {
+            results.push(result);
+        }
+    }
+
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_positions(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("positions expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+
+    let predicate = &args[0];
+    let mut indexes = Vec::new();
+    for (i, item) in args[1].iter_values().enumerate() {
+        if apply_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+            indexes.push(EdnValue::Integer(i as i64));
+        }
+    }
+
+    Ok(EdnValue::Vector(indexes))
+}
+
+fn builtin_find_index(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("find-index expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+
+    // Stops at the first match, so pred isn't called on the rest
+    let predicate = &args[0];
+    for (i, item) in args[1].iter_values().enumerate() {
+        if apply_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+            return Ok(EdnValue::Integer(i as i64));
+        }
+    }
+
+    Ok(EdnValue::Nil)
+}
+
+fn builtin_last_index_of_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("last-index-of-by expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+
+    // Searches from the end, so pred isn't called before the last match
+    let predicate = &args[0];
+    let items: Vec<&EdnValue> = args[1].iter_values().collect();
+    for (i, item) in items.into_iter().enumerate().rev() {
+        if apply_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+            return Ok(EdnValue::Integer(i as i64));
+        }
+    }
+
+    Ok(EdnValue::Nil)
+}
+
+fn builtin_remove(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("remove expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+    
+    let predicate = &args[0];
+    let collection = &args[1];
+    
+    
+    // Keep elements that don't satisfy the predicate
+    let mut results = Vec::new();
+    for item in collection.iter_values() {
+        let result = apply_function(predicate, std::slice::from_ref(item))?;
+        // Keep if predicate returns false/nil
+        if !result.is_truthy() {
+            results.push(item.clone());
+        }
+    }
+    
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_select_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("select-keys expects exactly 2 arguments".to_string()));
+    }
+
+    // (select-keys map keys) - select specified keys from map
+    let map = &args[0];
+    let keys = match &args[1] {
+        EdnValue::Vector(keys) => keys,
+        EdnValue::List(keys) => keys,
+        _ => return Err(EqError::type_error("vector or list", args[1].type_name())),
+    };
+
+    if let EdnValue::Map(m) = map {
+        let mut result = IndexMap::new();
+        for key in keys {
+            if let Some(value) = m.get(key) {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+        Ok(EdnValue::Map(result))
+    } else {
+        Ok(EdnValue::Map(IndexMap::new()))
+    }
+}
+
+fn builtin_select_keys_recursive(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("select-keys-recursive expects exactly 2 arguments: schema and value".to_string()));
+    }
+
+    match &args[0] {
+        EdnValue::Map(schema) => project(schema, &args[1]),
+        other => Err(EqError::type_error("map", other.type_name())),
+    }
+}
+
+/// Keeps the keys of `value` named in `schema`. A truthy schema entry keeps the
+/// value whole and a nested map projects it further; vectors and lists are
+/// projected element by element.
+fn project(schema: &IndexMap<EdnValue, EdnValue>, value: &EdnValue) -> EqResult<EdnValue> {
+    match value {
+        EdnValue::Map(m) => {
+            let mut result = IndexMap::new();
+            for (key, selection) in schema {
+                let Some(field) = m.get(key) else { continue };
+                let projected = match selection {
+                    EdnValue::Map(nested) => project(nested, field)?,
+                    selection if selection.is_truthy() => field.clone(),
+                    _ => continue,
+                };
+                result.insert(key.clone(), projected);
+            }
+            Ok(EdnValue::Map(result))
+        }
+        EdnValue::Vector(items) => Ok(EdnValue::Vector(
+            items.iter().map(|item| project(schema, item)).collect::<EqResult<_>>()?,
+        )),
+        EdnValue::List(items) => Ok(EdnValue::List(
+            items.iter().map(|item| project(schema, item)).collect::<EqResult<_>>()?,
+        )),
+        EdnValue::WithMetadata { value, .. } => project(schema, value),
+        // Nothing to select from a scalar
+        other => Ok(other.clone()),
+    }
+}
+
+fn builtin_select(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("select expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+    
+    let predicate = &args[0];
+    let collection = &args[1];
+    
+    
+    // Keep elements that satisfy the predicate
+    let mut results = Vec::new();
+    for item in collection.iter_values() {
+        let result = apply_function(predicate, std::slice::from_ref(item))?;
+        // Keep if predicate returns true
+        if result.is_truthy() {
+            results.push(item.clone());
+        }
+    }
+    
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_reduce_kv(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 3 {
+        return Err(EqError::query_error("reduce-kv expects exactly 3 arguments: function, initial value and map".to_string()));
+    }
+
+    let func = &args[0];
+    let map = match &args[2] {
+        EdnValue::Map(map) => map,
+        other => return Err(EqError::type_error("map", other.type_name())),
+    };
+
+    // Calls (f acc key val) for each entry in insertion order
+    let mut acc = args[1].clone();
+    for (key, value) in map {
+        acc = apply_function(func, &[acc, key.clone(), value.clone()])?;
+    }
+
+    Ok(acc)
+}
+
+fn builtin_update_vals(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("update-vals expects exactly 2 arguments: function and map".to_string()));
+    }
+
+    let func = &args[0];
+    let map = match &args[1] {
+        EdnValue::Map(map) => map,
+        other => return Err(EqError::type_error("map", other.type_name())),
+    };
+
+    let mut result = IndexMap::with_capacity(map.len());
+    for (key, value) in map {
+        result.insert(key.clone(), apply_function(func, std::slice::from_ref(value))?);
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_update_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("update-keys expects exactly 2 arguments: function and map".to_string()));
+    }
+
+    let func = &args[0];
+    let map = match &args[1] {
+        EdnValue::Map(map) => map,
+        other => return Err(EqError::type_error("map", other.type_name())),
+    };
+
+    // When two keys map to the same new key the later value wins, at the
+    // position of the first
+    let mut result = IndexMap::with_capacity(map.len());
+    for (key, value) in map {
+        let new_key = apply_function(func, std::slice::from_ref(key))?;
+        if result.contains_key(&new_key) {
+            eprintln!("Warning: update-keys produced duplicate key {}; keeping the last value", new_key);
+        }
+        result.insert(new_key, value.clone());
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_merge_with(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() < 2 {
+        return Err(EqError::query_error("merge-with expects a function and at least 1 map".to_string()));
+    }
+
+    // (merge-with f m1 m2 ...) calls (f existing new) for keys in more than one map
+    let func = &args[0];
+    let mut result: IndexMap<EdnValue, EdnValue> = IndexMap::new();
+    for map in merge_maps(&args[1..])? {
+        for (key, value) in map {
+            let merged = match result.get(key) {
+                Some(existing) => apply_function(func, &[existing.clone(), value.clone()])?,
+                None => value.clone(),
+            };
+            result.insert(key.clone(), merged);
+        }
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+fn builtin_merge_with_default(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() < 3 {
+        return Err(EqError::query_error("merge-with-default expects a function, a default and at least 1 map".to_string()));
+    }
+
+    // (merge-with-default f default m1 m2 ...) folds f over every map for
+    // every key, using default where a map doesn't have the key
+    let (func, default) = (&args[0], &args[1]);
+    let maps = merge_maps(&args[2..])?;
+    let mut keys: Vec<&EdnValue> = Vec::new();
+    let mut seen = HashSet::new();
+    for map in &maps {
+        keys.extend(map.keys().filter(|key| seen.insert(*key)));
+    }
+
+    let mut result = IndexMap::with_capacity(keys.len());
+    for key in keys {
+        let mut values = maps.iter().map(|map| map.get(key).unwrap_or(default));
+        let first = values.next().cloned().unwrap_or(EdnValue::Nil);
+        let merged = values.try_fold(first, |acc, value| apply_function(func, &[acc, value.clone()]))?;
+        result.insert(key.clone(), merged);
+    }
+
+    Ok(EdnValue::Map(result))
+}
+
+/// The maps to merge; nil arguments are skipped, as in Clojure
+fn merge_maps(args: &[EdnValue]) -> EqResult<Vec<&IndexMap<EdnValue, EdnValue>>> {
+    args.iter()
+        .filter(|arg| !matches!(arg, EdnValue::Nil))
+        .map(|arg| match arg {
+            EdnValue::Map(map) => Ok(map),
+            other => Err(EqError::type_error("map", other.type_name())),
+        })
+        .collect()
+}
+
+fn builtin_distinct_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("distinct-by expects exactly 2 arguments: key function and collection".to_string()));
+    }
+
+    // Keeps the first element for each distinct key
+    let keyfn = &args[0];
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+    for item in args[1].iter_values() {
+        if seen.insert(apply_function(keyfn, std::slice::from_ref(item))?) {
+            results.push(item.clone());
+        }
+    }
+
+    Ok(EdnValue::Vector(results))
+}
+
+fn builtin_index_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("index-by expects exactly 2 arguments: key function and collection".to_string()));
+    }
+
+    // A repeated key keeps its first position but takes the last element
+    let mut index = IndexMap::new();
+    for item in sequential_items(&args[1])? {
+        index.insert(apply_function(&args[0], std::slice::from_ref(item))?, item.clone());
+    }
+
+    Ok(EdnValue::Map(index))
+}
+
+fn builtin_index_by_group(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("index-by-group expects exactly 2 arguments: key function and collection".to_string()));
+    }
+
+    let mut groups: IndexMap<EdnValue, Vec<EdnValue>> = IndexMap::new();
+    for item in sequential_items(&args[1])? {
+        let key = apply_function(&args[0], std::slice::from_ref(item))?;
+        groups.entry(key).or_default().push(item.clone());
+    }
+
+    Ok(EdnValue::Map(groups.into_iter().map(|(k, items)| (k, EdnValue::Vector(items))).collect()))
+}
+
+fn sequential_items(coll: &EdnValue) -> EqResult<&[EdnValue]> {
+    match coll {
+        EdnValue::Vector(items) | EdnValue::List(items) => Ok(items),
+        EdnValue::WithMetadata { value, .. } => sequential_items(value),
+        other => Err(EqError::type_error("vector or list", other.type_name())),
+    }
+}
+
+fn builtin_partial(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.is_empty() {
+        return Err(EqError::query_error("partial expects at least 1 argument: function".to_string()));
+    }
+
+    let func = args[0].clone();
+    if !matches!(func, EdnValue::Lambda(_) | EdnValue::Keyword(_)) {
+        return Err(EqError::type_error("function", func.type_name()));
+    }
+    let bound_args = args[1..].to_vec();
+
+    // Printed form mirrors the call that created it, e.g. (partial < 10)
+    let mut description = vec![EdnValue::Symbol("partial".to_string())];
+    description.extend(args.iter().cloned());
+
+    Ok(EdnValue::Lambda(EdnLambda::native(EdnValue::List(description), move |call_args| {
+        let mut all_args = bound_args.clone();
+        all_args.extend_from_slice(call_args);
+        apply_function(&func, &all_args)
+    })))
+}
+
+fn builtin_juxt(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.is_empty() {
+        return Err(EqError::query_error("juxt expects at least 1 argument: function".to_string()));
+    }
+
+    for func in args {
+        if !matches!(func, EdnValue::Lambda(_) | EdnValue::Keyword(_)) {
+            return Err(EqError::type_error("function", func.type_name()));
+        }
+    }
+    let funcs = args.to_vec();
+
+    let mut description = vec![EdnValue::Symbol("juxt".to_string())];
+    description.extend(args.iter().cloned());
+
+    Ok(EdnValue::Lambda(EdnLambda::native(EdnValue::List(description), move |call_args| {
+        let results = funcs.iter()
+            .map(|func| apply_function(func, call_args))
+            .collect::<EqResult<Vec<_>>>()?;
+        Ok(EdnValue::Vector(results))
+    })))
+}
+
+fn builtin_complement(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("complement expects exactly 1 argument: function".to_string()));
+    }
+
+    let func = args[0].clone();
+    if !matches!(func, EdnValue::Lambda(_) | EdnValue::Keyword(_)) {
+        return Err(EqError::type_error("function", func.type_name()));
+    }
+
+    let description = EdnValue::List(vec![EdnValue::Symbol("complement".to_string()), func.clone()]);
+    Ok(EdnValue::Lambda(EdnLambda::native(description, move |call_args| {
+        Ok(EdnValue::Bool(!apply_function(&func, call_args)?.is_truthy()))
+    })))
+}
+
+fn builtin_fnil(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() < 2 {
+        return Err(EqError::query_error("fnil expects at least 2 arguments: function and default".to_string()));
+    }
+
+    let func = args[0].clone();
+    if !matches!(func, EdnValue::Lambda(_) | EdnValue::Keyword(_)) {
+        return Err(EqError::type_error("function", func.type_name()));
+    }
+    // (fnil f x y) replaces a nil first argument with x and a nil second with y
+    let defaults = args[1..].to_vec();
+
+    let mut description = vec![EdnValue::Symbol("fnil".to_string())];
+    description.extend(args.iter().cloned());
+
+    Ok(EdnValue::Lambda(EdnLambda::native(EdnValue::List(description), move |call_args| {
+        let patched: Vec<EdnValue> = call_args.iter().enumerate()
+            .map(|(i, arg)| match (arg, defaults.get(i)) {
+                (EdnValue::Nil, Some(default)) => default.clone(),
+                _ => arg.clone(),
+            })
+            .collect();
+        apply_function(&func, &patched)
+    })))
+}
+
+/// (keyword->fn :k) is a function that looks :k up, as :k itself does
+/// wherever a function is expected
+fn builtin_keyword_to_fn(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [key @ EdnValue::Keyword(_)] => {
+            let key = key.clone();
+            let description = EdnValue::List(vec![EdnValue::Symbol("keyword->fn".to_string()), key.clone()]);
+            Ok(EdnValue::Lambda(EdnLambda::native(description, move |call_args| apply_function(&key, call_args))))
+        }
+        [other] => Err(EqError::type_error("keyword", other.type_name())),
+        _ => Err(EqError::query_error("keyword->fn expects exactly 1 argument: keyword".to_string())),
+    }
+}
+
+/// (fn->keyword f) is the keyword f looks up in its argument, for lambdas
+/// like (fn [m] (:k m)), #(get % :k) or (keyword->fn :k)
+fn builtin_fn_to_keyword(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [key @ EdnValue::Keyword(_)] => Ok(key.clone()),
+        [func @ EdnValue::Lambda(lambda)] => lambda_keyword(lambda).ok_or_else(|| {
+            EqError::query_error(format!("fn->keyword: {} does more than look up a keyword", func))
+        }),
+        [other] => Err(EqError::type_error("function", other.type_name())),
+        _ => Err(EqError::query_error("fn->keyword expects exactly 1 argument: function".to_string())),
+    }
+}
+
+fn lambda_keyword(lambda: &EdnLambda) -> Option<EdnValue> {
+    let items = match &*lambda.body {
+        EdnValue::List(items) => items.as_slice(),
+        _ => return None,
+    };
+    if lambda.native.is_some() {
+        return match items {
+            [EdnValue::Symbol(head), key @ EdnValue::Keyword(_)] if head == "keyword->fn" => Some(key.clone()),
+            _ => None,
+        };
+    }
+    let [param] = lambda.params.as_slice() else {
+        return None;
+    };
+    if lambda.rest_param.is_some() {
+        return None;
+    }
+    match items {
+        [key @ EdnValue::Keyword(_), EdnValue::Symbol(arg)] if arg == param => Some(key.clone()),
+        [EdnValue::Symbol(get), EdnValue::Symbol(arg), key @ EdnValue::Keyword(_)] if get == "get" && arg == param => {
+            Some(key.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Results kept by each memoized function before the least recently used is evicted
+const MEMOIZE_CACHE_SIZE: usize = 1024;
+
+fn builtin_memoize(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("memoize expects exactly 1 argument: function".to_string()));
+    }
+
+    memoize_with_capacity(args[0].clone(), MEMOIZE_CACHE_SIZE)
+}
+
+/// Wrap func in a lambda that caches results by argument list. Entries are
+/// kept in least to most recently used order. Errors are not cached.
+pub(crate) fn memoize_with_capacity(func: EdnValue, capacity: usize) -> EqResult<EdnValue> {
+    if !matches!(func, EdnValue::Lambda(_) | EdnValue::Keyword(_)) {
+        return Err(EqError::type_error("function", func.type_name()));
+    }
+
+    let cache: Arc<Mutex<IndexMap<Vec<EdnValue>, EdnValue>>> = Arc::new(Mutex::new(IndexMap::new()));
+    let description = EdnValue::List(vec![EdnValue::Symbol("memoize".to_string()), func.clone()]);
+    Ok(EdnValue::Lambda(EdnLambda::native(description, move |call_args| {
+        {
+            let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(result) = cache.shift_remove(call_args) {
+                cache.insert(call_args.to_vec(), result.clone());
+                return Ok(result);
             }
-            Ok(EdnValue::Bool(true))
         }
-    }
+
+        // The lock is released while calling func, which may itself be slow
+        let result = apply_function(&func, call_args)?;
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if cache.len() >= capacity {
+            cache.shift_remove_index(0);
+        }
+        cache.insert(call_args.to_vec(), result.clone());
+        Ok(result)
+    })))
 }
 
-// Higher-order operations
-fn builtin_map(args: &[EdnValue]) -> EqResult<EdnValue> {
-    if args.len() != 2 {
-        return Err(EqError::query_error("map expects exactly 2 arguments: function and collection".to_string()));
+fn builtin_walk(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 3 {
+        return Err(EqError::query_error("walk expects exactly 3 arguments: inner function, outer function and form".to_string()));
     }
-    
-    let func = &args[0];
-    let collection = &args[1];
-    
-    // Extract the lambda
-    let lambda = match func {
-        EdnValue::Lambda(lambda) => lambda,
-        _ => return Err(EqError::type_error("lambda", func.type_name())),
+
+    let (inner, outer, form) = (&args[0], &args[1], &args[2]);
+    let walked = match walk_children(form.clone()) {
+        Ok((shape, children)) => {
+            let children = children.iter()
+                .map(|child| apply_function(inner, std::slice::from_ref(child)))
+                .collect::<EqResult<Vec<_>>>()?;
+            rebuild_walked(shape, children)?
+        }
+        Err(leaf) => leaf,
     };
-    
-    // Apply function to each element
-    let mut results = Vec::new();
-    for item in collection.iter_values() {
-        let result = call_lambda_simple(lambda, &[item.clone()])?;
-        results.push(result);
+    apply_function(outer, &[walked])
+}
+
+fn builtin_postwalk(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("postwalk expects exactly 2 arguments: function and form".to_string()));
     }
-    
-    Ok(EdnValue::Vector(results))
+
+    walk_tree(&args[0], &args[1], WalkOrder::Post)
 }
 
-fn builtin_remove(args: &[EdnValue]) -> EqResult<EdnValue> {
+fn builtin_prewalk(args: &[EdnValue]) -> EqResult<EdnValue> {
     if args.len() != 2 {
-        return Err(EqError::query_error("remove expects exactly 2 arguments: predicate and collection".to_string()));
+        return Err(EqError::query_error("prewalk expects exactly 2 arguments: function and form".to_string()));
     }
-    
-    let predicate = &args[0];
-    let collection = &args[1];
-    
-    // Extract the lambda
-    let lambda = match predicate {
-        EdnValue::Lambda(lambda) => lambda,
-        _ => return Err(EqError::type_error("lambda", predicate.type_name())),
+
+    walk_tree(&args[0], &args[1], WalkOrder::Pre)
+}
+
+/// Most nodes tree-seq collects before assuming `children` never bottoms out
+const MAX_TREE_NODES: usize = 100_000;
+
+fn builtin_tree_seq(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (tree-seq branch? children root) - every node, depth first, root first
+    let [is_branch, children, root] = args else {
+        return Err(EqError::query_error("tree-seq expects exactly 3 arguments: branch? function, children function and root".to_string()));
     };
-    
-    // Keep elements that don't satisfy the predicate
-    let mut results = Vec::new();
-    for item in collection.iter_values() {
-        let result = call_lambda_simple(lambda, &[item.clone()])?;
-        // Keep if predicate returns false/nil
-        if !result.is_truthy() {
-            results.push(item.clone());
+
+    // An explicit stack of (node, depth) rather than recursion. `path` holds
+    // the ancestors of the node being visited, and `ancestors` the same for
+    // quick lookup, to catch a node that is its own descendant
+    let mut nodes = Vec::new();
+    let mut path: Vec<EdnValue> = Vec::new();
+    let mut ancestors: HashSet<EdnValue> = HashSet::new();
+    let mut stack = vec![(root.clone(), 0)];
+    while let Some((node, depth)) = stack.pop() {
+        if nodes.len() == MAX_TREE_NODES {
+            return Err(EqError::query_error(format!("tree-seq: more than {} nodes; does children always reach a leaf?", MAX_TREE_NODES)));
+        }
+        for left in path.drain(depth..) {
+            ancestors.remove(&left);
         }
+        if apply_function(is_branch, std::slice::from_ref(&node))?.is_truthy() {
+            if ancestors.contains(&node) {
+                return Err(EqError::query_error(format!("tree-seq: {} is its own descendant", node)));
+            }
+            let kids = match apply_function(children, std::slice::from_ref(&node))? {
+                EdnValue::LazySeq(seq) => seq.realize()?,
+                kids => kids.iter_values().cloned().collect(),
+            };
+            stack.extend(kids.into_iter().rev().map(|kid| (kid, depth + 1)));
+            path.push(node.clone());
+            ancestors.insert(node.clone());
+        }
+        nodes.push(node);
     }
-    
-    Ok(EdnValue::Vector(results))
+
+    Ok(EdnValue::Vector(nodes))
 }
 
-fn builtin_select_keys(args: &[EdnValue]) -> EqResult<EdnValue> {
-    if args.len() != 2 {
-        return Err(EqError::query_error("select-keys expects exactly 2 arguments".to_string()));
+#[derive(Clone, Copy, PartialEq)]
+enum WalkOrder {
+    Pre,
+    Post,
+}
+
+/// The collection a walked node's children are put back into
+enum WalkShape {
+    Vector,
+    List,
+    Map,
+    Set,
+    Metadata(Box<EdnValue>),
+}
+
+enum WalkTask {
+    Visit(EdnValue),
+    Rebuild(WalkShape, usize),
+}
+
+/// Takes a collection apart into its children, with map entries as [key value]
+/// vectors as in Clojure. Anything else is a leaf and is handed back as is.
+fn walk_children(form: EdnValue) -> Result<(WalkShape, Vec<EdnValue>), EdnValue> {
+    match form {
+        EdnValue::Vector(items) => Ok((WalkShape::Vector, items)),
+        EdnValue::List(items) => Ok((WalkShape::List, items)),
+        EdnValue::Set(items) => Ok((WalkShape::Set, items.into_iter().collect())),
+        EdnValue::Map(map) => Ok((
+            WalkShape::Map,
+            map.into_iter().map(|(k, v)| EdnValue::Vector(vec![k, v])).collect(),
+        )),
+        EdnValue::WithMetadata { metadata, value } => Ok((WalkShape::Metadata(metadata), vec![*value])),
+        leaf => Err(leaf),
     }
+}
 
-    // (select-keys map keys) - select specified keys from map
-    let map = &args[0];
-    let keys = match &args[1] {
-        EdnValue::Vector(keys) => keys,
-        EdnValue::List(keys) => keys,
-        _ => return Err(EqError::type_error("vector or list", args[1].type_name())),
-    };
+fn rebuild_walked(shape: WalkShape, children: Vec<EdnValue>) -> EqResult<EdnValue> {
+    Ok(match shape {
+        WalkShape::Vector => EdnValue::Vector(children),
+        WalkShape::List => EdnValue::List(children),
+        WalkShape::Set => EdnValue::Set(children.into_iter().collect()),
+        WalkShape::Map => {
+            let mut map = IndexMap::new();
+            for entry in children {
+                match entry.as_slice() {
+                    [key, value] => {
+                        map.insert(key.clone(), value.clone());
+                    }
+                    _ => return Err(EqError::query_error(format!("walk expects map entries to stay [key value] pairs, got {}", entry))),
+                }
+            }
+            EdnValue::Map(map)
+        }
+        WalkShape::Metadata(metadata) => EdnValue::WithMetadata {
+            metadata,
+            value: Box::new(children.into_iter().next().unwrap_or(EdnValue::Nil)),
+        },
+    })
+}
 
-    if let EdnValue::Map(m) = map {
-        let mut result = IndexMap::new();
-        for key in keys {
-            if let Some(value) = m.get(key) {
-                result.insert(key.clone(), value.clone());
+/// Applies `func` to every node of `form`. Uses an explicit stack instead of
+/// recursion, so the walk itself handles any nesting the parser can read.
+fn walk_tree(func: &EdnValue, form: &EdnValue, order: WalkOrder) -> EqResult<EdnValue> {
+    let mut tasks = vec![WalkTask::Visit(form.clone())];
+    let mut results: Vec<EdnValue> = Vec::new();
+
+    while let Some(task) = tasks.pop() {
+        match task {
+            WalkTask::Visit(node) => {
+                let node = if order == WalkOrder::Pre { apply_function(func, &[node])? } else { node };
+                match walk_children(node) {
+                    Ok((shape, children)) => {
+                        tasks.push(WalkTask::Rebuild(shape, children.len()));
+                        tasks.extend(children.into_iter().rev().map(WalkTask::Visit));
+                    }
+                    Err(leaf) if order == WalkOrder::Post => results.push(apply_function(func, &[leaf])?),
+                    Err(leaf) => results.push(leaf),
+                }
+            }
+            WalkTask::Rebuild(shape, count) => {
+                let children = results.split_off(results.len() - count);
+                let node = rebuild_walked(shape, children)?;
+                let node = if order == WalkOrder::Post { apply_function(func, &[node])? } else { node };
+                results.push(node);
             }
         }
-        Ok(EdnValue::Map(result))
-    } else {
-        Ok(EdnValue::Map(IndexMap::new()))
     }
+
+    Ok(results.pop().unwrap_or(EdnValue::Nil))
 }
 
-fn builtin_select(args: &[EdnValue]) -> EqResult<EdnValue> {
-    if args.len() != 2 {
-        return Err(EqError::query_error("select expects exactly 2 arguments: predicate and collection".to_string()));
-    }
-    
-    let predicate = &args[0];
-    let collection = &args[1];
-    
-    // Extract the lambda
-    let lambda = match predicate {
-        EdnValue::Lambda(lambda) => lambda,
-        _ => return Err(EqError::type_error("lambda", predicate.type_name())),
-    };
-    
-    // Keep elements that satisfy the predicate
-    let mut results = Vec::new();
-    for item in collection.iter_values() {
-        let result = call_lambda_simple(lambda, &[item.clone()])?;
-        // Keep if predicate returns true
-        if result.is_truthy() {
-            results.push(item.clone());
+// Lazy sequences
+fn map_lazy(func: EdnValue, seq: EdnValue) -> EdnValue {
+    EdnValue::LazySeq(LazySeq::new(move || match crate::edn::value::seq_step(&seq)? {
+        Some((first, rest)) => {
+            let mapped = apply_function(&func, &[first])?;
+            Ok(EdnValue::LazySeq(LazySeq::cons(mapped, map_lazy(func.clone(), rest))))
         }
+        None => Ok(EdnValue::Nil),
+    }))
+}
+
+fn builtin_realize(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (realize n s) - force the first n elements of s and return them as a vector
+    match args {
+        [EdnValue::Integer(n), EdnValue::LazySeq(seq)] => Ok(EdnValue::Vector(seq.take((*n).max(0) as usize)?)),
+        [EdnValue::Integer(n), coll] => {
+            crate::edn::value::seq_step(coll)?;
+            Ok(coll.take((*n).max(0) as usize))
+        }
+        [other, _] => Err(EqError::type_error("integer", other.type_name())),
+        _ => Err(EqError::query_error("realize expects exactly 2 arguments: count and sequence")),
+    }
+}
+
+fn builtin_iterate(args: &[EdnValue]) -> EqResult<EdnValue> {
+    // (iterate f x) - the infinite lazy sequence x, (f x), (f (f x)), ...
+    match args {
+        [func, start] => Ok(iterate_from(func.clone(), start.clone())),
+        _ => Err(EqError::query_error("iterate expects exactly 2 arguments: function and start value")),
     }
-    
-    Ok(EdnValue::Vector(results))
+}
+
+fn iterate_from(func: EdnValue, value: EdnValue) -> EdnValue {
+    let next = value.clone();
+    let rest = LazySeq::new(move || Ok(iterate_from(func.clone(), apply_function(&func, std::slice::from_ref(&next))?)));
+    EdnValue::LazySeq(LazySeq::cons(value, EdnValue::LazySeq(rest)))
 }
 
 // Aggregation
@@ -505,7 +3323,111 @@ fn builtin_frequencies(args: &[EdnValue]) -> EqResult<EdnValue> {
     Ok(EdnValue::Map(freq_map))
 }
 
+fn builtin_frequencies_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("frequencies-by expects exactly 2 arguments: key function and collection".to_string()));
+    }
+
+    // Keys appear in order of first occurrence; a nil key collects every nil result
+    let keyfn = &args[0];
+    let mut freq_map: IndexMap<EdnValue, i64> = IndexMap::new();
+    for item in args[1].iter_values() {
+        let key = apply_function(keyfn, std::slice::from_ref(item))?;
+        *freq_map.entry(key).or_insert(0) += 1;
+    }
+
+    Ok(EdnValue::Map(freq_map.into_iter().map(|(k, n)| (k, EdnValue::Integer(n))).collect()))
+}
+
 /// Compare two values for ordering
+fn builtin_min(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("min expects exactly 1 argument".to_string()));
+    }
+    extreme_by(&args[0], None, -1)
+}
+
+fn builtin_max(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("max expects exactly 1 argument".to_string()));
+    }
+    extreme_by(&args[0], None, 1)
+}
+
+fn builtin_min_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("min-by expects exactly 2 arguments: key function and collection".to_string()));
+    }
+    extreme_by(&args[1], Some(&args[0]), -1)
+}
+
+fn builtin_max_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("max-by expects exactly 2 arguments: key function and collection".to_string()));
+    }
+    extreme_by(&args[1], Some(&args[0]), 1)
+}
+
+fn builtin_sum(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("sum expects exactly 1 argument".to_string()));
+    }
+    sum_values(&args[0])
+}
+
+fn builtin_average(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("average expects exactly 1 argument".to_string()));
+    }
+
+    let count = args[0].iter_values().count();
+    if count == 0 {
+        return Ok(EdnValue::Nil);
+    }
+    Ok(EdnValue::Float(to_f64(&sum_values(&args[0])?)? / count as f64))
+}
+
+fn builtin_count_by(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("count-by expects exactly 2 arguments: predicate and collection".to_string()));
+    }
+
+    let predicate = &args[0];
+    let mut count = 0;
+    for item in args[1].iter_values() {
+        if apply_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+            count += 1;
+        }
+    }
+
+    Ok(EdnValue::Integer(count))
+}
+
+/// Sum numbers with the same promotion rules as +
+fn sum_values(collection: &EdnValue) -> EqResult<EdnValue> {
+    collection.iter_values().try_fold(EdnValue::Integer(0), |total, item| arithmetic(ArithOp::Add, &total, item))
+}
+
+/// Find the element whose key compares furthest in `direction` (-1 for min, 1 for max).
+/// Ties keep the first element seen; an empty collection yields nil.
+fn extreme_by(collection: &EdnValue, keyfn: Option<&EdnValue>, direction: i32) -> EqResult<EdnValue> {
+    let mut best: Option<(EdnValue, EdnValue)> = None;
+    for item in collection.iter_values() {
+        let key = match keyfn {
+            Some(func) => apply_function(func, std::slice::from_ref(item))?,
+            None => item.clone(),
+        };
+        let replace = match &best {
+            Some((best_key, _)) => compare_values(&key, best_key)? * direction > 0,
+            None => true,
+        };
+        if replace {
+            best = Some((key, item.clone()));
+        }
+    }
+    Ok(best.map(|(_, item)| item).unwrap_or(EdnValue::Nil))
+}
+
 fn compare_values(left: &EdnValue, right: &EdnValue) -> EqResult<i32> {
     match (left, right) {
         (EdnValue::Integer(a), EdnValue::Integer(b)) => Ok(a.cmp(b) as i32),
@@ -526,10 +3448,134 @@ fn compare_values(left: &EdnValue, right: &EdnValue) -> EqResult<i32> {
             else if *a > b_float { Ok(1) }
             else { Ok(0) }
         }
+        (EdnValue::BigInt(_), EdnValue::BigInt(_) | EdnValue::Integer(_))
+        | (EdnValue::Integer(_), EdnValue::BigInt(_)) => Ok(to_bigint(left)?.cmp(&to_bigint(right)?) as i32),
+        // Exact comparison by cross-multiplying
+        (EdnValue::Ratio { .. }, EdnValue::Ratio { .. } | EdnValue::Integer(_) | EdnValue::BigInt(_))
+        | (EdnValue::Integer(_) | EdnValue::BigInt(_), EdnValue::Ratio { .. }) => {
+            let ((a, b), (c, d)) = (to_rational(left)?, to_rational(right)?);
+            Ok((a * d).cmp(&(c * b)) as i32)
+        }
+        (EdnValue::BigInt(_) | EdnValue::Ratio { .. }, EdnValue::Float(_))
+        | (EdnValue::Float(_), EdnValue::BigInt(_) | EdnValue::Ratio { .. }) => {
+            let (a, b) = (to_f64(left)?, to_f64(right)?);
+            if a < b { Ok(-1) }
+            else if a > b { Ok(1) }
+            else { Ok(0) }
+        }
         (EdnValue::String(a), EdnValue::String(b)) => Ok(a.cmp(b) as i32),
+        (EdnValue::Instant(_), EdnValue::Instant(_)) => Ok(instant_time(left)?.cmp(&instant_time(right)?) as i32),
         _ => Err(EqError::type_error("comparable types", 
-            &format!("{} and {}", left.type_name(), right.type_name()))),
+            format!("{} and {}", left.type_name(), right.type_name()))),
+    }
+}
+
+// Debugging
+fn builtin_tap(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("tap expects exactly 2 arguments: function and value".to_string()));
+    }
+
+    // f is called only for its side effects; its result is discarded
+    apply_function(&args[0], std::slice::from_ref(&args[1]))?;
+    Ok(args[1].clone())
+}
+
+fn builtin_spy(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("spy expects exactly 1 argument".to_string()));
+    }
+
+    eprintln!("{}", args[0]);
+    Ok(args[0].clone())
+}
+
+/// print and println always write to stderr so results on stdout stay clean
+fn builtin_print(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("print expects exactly 1 argument".to_string()));
+    }
+
+    eprint!("{}", args[0]);
+    Ok(args[0].clone())
+}
+
+fn builtin_println(args: &[EdnValue]) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("println expects exactly 1 argument".to_string()));
+    }
+
+    eprintln!("{}", args[0]);
+    Ok(args[0].clone())
+}
+
+/// Pretty-printed EDN for a value with the default output options, whatever
+/// the command line asked for
+fn pretty_string(value: &EdnValue) -> EqResult<String> {
+    Ok(PrettyFormatter.format(&*value.realize_all()?, &OutputConfig::default(), 0))
+}
+
+fn builtin_pp(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [value] => {
+            eprintln!("{}", pretty_string(value)?);
+            Ok(value.clone())
+        }
+        _ => Err(EqError::query_error("pp expects exactly 1 argument".to_string())),
+    }
+}
+
+fn builtin_pp_str(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [value] => Ok(EdnValue::String(pretty_string(value)?)),
+        _ => Err(EqError::query_error("pp-str expects exactly 1 argument".to_string())),
+    }
+}
+
+/// (with-format {:compact true} x) is x formatted as EDN with those options
+/// in place of the defaults. It is a string, so --raw-output prints it as is.
+fn builtin_with_format(args: &[EdnValue]) -> EqResult<EdnValue> {
+    match args {
+        [EdnValue::Map(options), value] => {
+            let config = format_options(options)?;
+            Ok(EdnValue::String(format_edn(&*value.realize_all()?, &config)))
+        }
+        [other, _] => Err(EqError::type_error("map", other.type_name())),
+        _ => Err(EqError::query_error("with-format expects exactly 2 arguments: options and value".to_string())),
+    }
+}
+
+/// Output options named like the command line flags that set them
+fn format_options(options: &IndexMap<EdnValue, EdnValue>) -> EqResult<OutputConfig> {
+    let mut config = OutputConfig::default();
+    for (key, value) in options {
+        let name = match key {
+            EdnValue::Keyword(name) => name.as_str(),
+            other => return Err(EqError::type_error("keyword", other.type_name())),
+        };
+        let limit = |value: &EdnValue| match value {
+            EdnValue::Nil => Ok(None),
+            EdnValue::Integer(n) if *n >= 0 => Ok(Some(*n as usize)),
+            other => Err(EqError::query_error(format!("with-format :{} must be a non-negative integer or nil, got {}", name, other))),
+        };
+        match (name, value) {
+            ("compact", EdnValue::Bool(b)) => config.compact = *b,
+            ("tab", EdnValue::Bool(b)) => config.use_tabs = *b,
+            ("align-map-values", EdnValue::Bool(b)) => config.align_map_values = *b,
+            ("indent", EdnValue::Integer(n)) if *n >= 0 => config.indent_size = *n as usize,
+            ("indent", other) => {
+                return Err(EqError::query_error(format!("with-format :indent must be a non-negative integer, got {}", other)));
+            }
+            ("max-string-length", value) => config.max_string_length = limit(value)?,
+            ("max-collection-size", value) => config.max_collection_size = limit(value)?,
+            ("max-output-depth", value) => config.max_depth = limit(value)?,
+            ("compact" | "tab" | "align-map-values", other) => {
+                return Err(EqError::type_error("boolean", other.type_name()));
+            }
+            _ => return Err(EqError::query_error(format!("with-format: unknown option :{}", name))),
+        }
     }
+    Ok(config)
 }
 
 // Macro implementations
@@ -561,6 +3607,42 @@ fn macro_when(args: &[Expr]) -> EqResult<Expr> {
     })
 }
 
+/// With-default macro: (with-default default expr) =>
+/// (let [v expr] (if (nil? v) default v)), so expr is only evaluated once
+fn macro_with_default(args: &[Expr]) -> EqResult<Expr> {
+    match args {
+        [default, expr] => default_expansion("with-default", expr, default),
+        _ => Err(EqError::query_error("with-default expects exactly 2 arguments")),
+    }
+}
+
+/// Default operator macro: (?? expr default) is (with-default default expr),
+/// with expr first so it threads: (-> . :name (?? "Unknown"))
+fn macro_default_op(args: &[Expr]) -> EqResult<Expr> {
+    match args {
+        [expr, default] => default_expansion("??", expr, default),
+        _ => Err(EqError::query_error("?? expects exactly 2 arguments")),
+    }
+}
+
+fn default_expansion(name: &str, expr: &Expr, default: &Expr) -> EqResult<Expr> {
+    let form = |expr: &Expr| {
+        expr.to_form()
+            .ok_or_else(|| EqError::query_error(format!("{} can't expand {:?}", name, expr)))
+    };
+    let value = EdnValue::Symbol("__default".to_string());
+    Ok(Expr::List(vec![
+        EdnValue::Symbol("let".to_string()),
+        EdnValue::Vector(vec![value.clone(), form(expr)?]),
+        EdnValue::List(vec![
+            EdnValue::Symbol("if".to_string()),
+            EdnValue::List(vec![EdnValue::Symbol("nil?".to_string()), value.clone()]),
+            form(default)?,
+            value,
+        ]),
+    ]))
+}
+
 /// Threading first macro: (-> x f g h) becomes (h (g (f x)))
 fn macro_thread_first(args: &[Expr]) -> EqResult<Expr> {
     if args.is_empty() {
@@ -594,7 +3676,7 @@ fn macro_thread_last(args: &[Expr]) -> EqResult<Expr> {
 }
 
 /// Thread first: insert threaded value as first argument
-fn thread_first_expr(threaded_value: Expr, form: &Expr) -> EqResult<Expr> {
+pub(crate) fn thread_first_expr(threaded_value: Expr, form: &Expr) -> EqResult<Expr> {
     match form {
         // If it's a symbol like 'first', convert to (first threaded_value)
         Expr::Symbol(name) => {
@@ -715,87 +3797,3 @@ fn thread_last_expr(threaded_value: Expr, form: &Expr) -> EqResult<Expr> {
         _ => Err(EqError::query_error("Invalid form in ->> macro")),
     }
 }
-
-/// Simple lambda call implementation for builtin functions
-/// This is a simplified version that doesn't have access to full evaluation context
-fn call_lambda_simple(lambda: &EdnLambda, args: &[EdnValue]) -> EqResult<EdnValue> {
-    // Check argument count
-    if args.len() != lambda.params.len() {
-        return Err(EqError::query_error(format!(
-            "Lambda expects {} arguments, got {}",
-            lambda.params.len(),
-            args.len()
-        )));
-    }
-    
-    // For now, we'll implement a very basic evaluation that only handles simple expressions
-    // This is a limitation but allows us to test the basic functionality
-    match &*lambda.body {
-        // Handle simple function calls like (< 10 %)
-        EdnValue::List(elements) if !elements.is_empty() => {
-            if let EdnValue::Symbol(func_name) = &elements[0] {
-                // Create a simple environment for parameter substitution
-                let mut substituted_args = Vec::new();
-                for arg_edn in &elements[1..] {
-                    let substituted = substitute_params(arg_edn, &lambda.params, args)?;
-                    substituted_args.push(substituted);
-                }
-                
-                // Call the function with substituted arguments
-                match func_name.as_str() {
-                    "=" => builtin_equal(&substituted_args),
-                    "<" => builtin_less_than(&substituted_args),
-                    ">" => builtin_greater_than(&substituted_args),
-                    "<=" => builtin_less_equal(&substituted_args),
-                    ">=" => builtin_greater_equal(&substituted_args),
-                    "nil?" => builtin_is_nil(&substituted_args),
-                    "empty?" => builtin_is_empty(&substituted_args),
-                    "number?" => builtin_is_number(&substituted_args),
-                    "string?" => builtin_is_string(&substituted_args),
-                    "keyword?" => builtin_is_keyword(&substituted_args),
-                    "boolean?" => builtin_is_boolean(&substituted_args),
-                    _ => Err(EqError::query_error(format!("Unsupported function in lambda: {}", func_name))),
-                }
-            } else {
-                Err(EqError::query_error("Lambda body must start with a function symbol".to_string()))
-            }
-        }
-        // Handle direct parameter reference like %
-        EdnValue::Symbol(param) => {
-            if let Some(pos) = lambda.params.iter().position(|p| p == param) {
-                Ok(args[pos].clone())
-            } else {
-                Err(EqError::query_error(format!("Unknown parameter: {}", param)))
-            }
-        }
-        // Handle literals
-        _ => Ok(lambda.body.as_ref().clone()),
-    }
-}
-
-/// Substitute parameters in an EDN value
-fn substitute_params(value: &EdnValue, params: &[String], args: &[EdnValue]) -> EqResult<EdnValue> {
-    match value {
-        EdnValue::Symbol(name) => {
-            if let Some(pos) = params.iter().position(|p| p == name) {
-                Ok(args[pos].clone())
-            } else {
-                Ok(value.clone())
-            }
-        }
-        EdnValue::List(elements) => {
-            let substituted: Result<Vec<_>, _> = elements.iter()
-                .map(|elem| substitute_params(elem, params, args))
-                .collect();
-            Ok(EdnValue::List(substituted?))
-        }
-        EdnValue::Vector(elements) => {
-            let substituted: Result<Vec<_>, _> = elements.iter()
-                .map(|elem| substitute_params(elem, params, args))
-                .collect();
-            Ok(EdnValue::Vector(substituted?))
-        }
-        _ => Ok(value.clone()),
-    }
-}
-
@@ -1,5 +1,31 @@
-use clap::Parser;
+use clap::{Parser, ValueHint};
+use clap_complete::Shell;
 use std::path::PathBuf;
+use eq::output::{ColorMode, OutputFormat, TableFormat};
+
+/// How errors are printed to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// A message, plus the offending line for parse errors
+    #[default]
+    Text,
+    /// One EDN map per error, e.g. {:type :parse-error :message "..." :line 5 :column 12}
+    Edn,
+    /// The same map as a JSON object
+    Json,
+}
+
+/// Input parsing format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    Edn,
+    Json,
+    /// One JSON document per line
+    Ndjson,
+    /// Detect JSON from the file extension or the first character of input
+    Auto,
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "eq")]
@@ -7,9 +33,11 @@ use std::path::PathBuf;
 #[command(version)]
 pub struct Args {
     /// Filter expression to apply
+    #[arg(required_unless_present_any = ["repl", "generate_completions", "list_builtins", "from_file", "diff"], default_value = "")]
     pub filter: String,
     
     /// Input files (reads from stdin if none provided)
+    #[arg(value_hint = ValueHint::FilePath)]
     pub files: Vec<PathBuf>,
     
     /// Compact instead of pretty-printed output
@@ -20,10 +48,51 @@ pub struct Args {
     #[arg(long)]
     pub raw_output: bool,
     
+    /// With --raw-output, don't print a newline after each result
+    #[arg(long, requires = "raw_output")]
+    pub join_output: bool,
+    
+    /// End each result with a NUL byte instead of a newline, for xargs -0
+    #[arg(short = '0', long, conflicts_with = "join_output")]
+    pub null_separator: bool,
+    
+    /// Output format
+    #[arg(short = 'F', long, value_name = "FORMAT", value_enum, default_value_t = OutputFormat::Edn)]
+    pub output_format: OutputFormat,
+    
+    /// Output JSON (shorthand for --output-format json)
+    #[arg(short = 'j', long)]
+    pub json_output: bool,
+    
+    /// Field separator for CSV output
+    #[arg(long, value_name = "CHAR", default_value = ",")]
+    pub csv_separator: char,
+    
+    /// Table style for table output
+    #[arg(long, value_name = "STYLE", value_enum, default_value_t = TableFormat::Ascii)]
+    pub table_format: TableFormat,
+    
+    /// Write output to FILE instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub output_file: Option<PathBuf>,
+    
+    /// Append to the --output-file instead of truncating it
+    #[arg(long, requires = "output_file")]
+    pub append: bool,
+    
+    /// Replace each input file with its output, keeping a backup with SUFFIX if given
+    #[arg(short = 'i', long, value_name = "SUFFIX", num_args = 0..=1, require_equals = true,
+          default_missing_value = "", conflicts_with_all = ["output_file", "null_input"])]
+    pub in_place: Option<String>,
+    
     /// Each line of input is a string, not parsed as EDN
     #[arg(short = 'R', long)]
     pub raw_input: bool,
     
+    /// Input format
+    #[arg(short = 'I', long, value_name = "FORMAT", value_enum, default_value_t = InputFormat::Edn)]
+    pub input_format: InputFormat,
+    
     /// Read entire input stream into array
     #[arg(short = 's', long)]
     pub slurp: bool,
@@ -32,14 +101,50 @@ pub struct Args {
     #[arg(short = 'n', long)]
     pub null_input: bool,
     
-    /// Set exit status based on output
+    /// Bind NAME to the string VALUE in the filter (repeatable)
+    #[arg(long = "arg", num_args = 2, value_names = ["NAME", "VALUE"])]
+    pub arg: Vec<String>,
+    
+    /// Bind NAME to VALUE parsed as JSON or EDN (repeatable)
+    #[arg(long, num_args = 2, value_names = ["NAME", "VALUE"])]
+    pub argjson: Vec<String>,
+    
+    /// Let import-edn read files, relative to the --from-file directory or the working directory
+    #[arg(long)]
+    pub allow_imports: bool,
+    
+    /// Hide environment variables from env and env-all
+    #[arg(long)]
+    pub no_env: bool,
+    
+    /// Exit with status 1 unless some output is truthy (errors exit with 2)
     #[arg(short = 'e', long)]
     pub exit_status: bool,
     
-    /// Read filter from file
-    #[arg(short = 'f', long, value_name = "FILE")]
+    /// Read filter from file; the first positional argument is then an input file
+    #[arg(short = 'f', long, value_name = "FILE", value_hint = ValueHint::FilePath)]
     pub from_file: Option<PathBuf>,
     
+    /// Process up to N input files in parallel (0 uses all CPU cores); output stays in file order
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+    
+    /// Show a progress bar on stderr while processing multiple files
+    #[arg(long)]
+    pub progress: bool,
+    
+    /// Show at most N characters of each string in EDN output, ending cut strings with ...
+    #[arg(long, value_name = "N")]
+    pub max_string_length: Option<usize>,
+    
+    /// Show at most N elements of each collection in EDN output, ending cut collections with ...
+    #[arg(long, value_name = "N")]
+    pub max_collection_size: Option<usize>,
+    
+    /// Show collections nested more than N levels deep in EDN output as [...], {...} and so on
+    #[arg(long, value_name = "N")]
+    pub max_output_depth: Option<usize>,
+    
     /// Use tabs for indentation
     #[arg(long)]
     pub tab: bool,
@@ -48,10 +153,35 @@ pub struct Args {
     #[arg(long, value_name = "N", default_value = "2")]
     pub indent: usize,
     
-    /// Show debug information
+    /// Line up the values of multi-line maps in pretty-printed EDN output
+    #[arg(long)]
+    pub align_map_values: bool,
+    
+    /// Colorize EDN output; auto colors only when stdout is a terminal
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = ColorMode::Auto, num_args = 0..=1,
+          require_equals = true, default_missing_value = "always", overrides_with = "no_color")]
+    pub color: ColorMode,
+    
+    /// Never colorize output (same as --color=never)
+    #[arg(long, overrides_with = "color")]
+    pub no_color: bool,
+    
+    /// How to print errors to stderr
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = ErrorFormat::Text)]
+    pub error_format: ErrorFormat,
+    
+    /// Print the parsed and analyzed filter to stderr
     #[arg(long)]
     pub debug: bool,
     
+    /// Print how long each input took to process to stderr when done
+    #[arg(long)]
+    pub profile: bool,
+    
+    /// Print each input value to stderr before running the filter on it
+    #[arg(long)]
+    pub debug_input: bool,
+    
     /// Verbose output
     #[arg(short = 'v', long)]
     pub verbose: bool,
@@ -60,17 +190,74 @@ pub struct Args {
     #[arg(short = 'H', long)]
     pub with_filename: bool,
     
+    /// Print the line each input value started on before its output
+    #[arg(long)]
+    pub with_line_numbers: bool,
+    
     /// Recursively search directories for files
     #[arg(short = 'r', long = "recursive")]
     pub recursive: bool,
     
-    /// Glob pattern for file matching (default: "*.edn")
-    #[arg(short = 'p', long = "pattern", default_value = "*.edn")]
-    pub glob_pattern: String,
+    /// With --recursive, only read files at most N levels below each directory (1 is direct children)
+    #[arg(long, value_name = "N", requires = "recursive")]
+    pub max_depth: Option<usize>,
+    
+    /// With --recursive, only read files at least N levels below each directory
+    #[arg(long, value_name = "N", requires = "recursive")]
+    pub min_depth: Option<usize>,
+    
+    /// With --recursive, follow symbolic links to directories and files
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    
+    /// Read input files in order of path
+    #[arg(long, conflicts_with = "sort_by_mtime")]
+    pub sort_by_name: bool,
+    
+    /// Read input files oldest first, by modification time
+    #[arg(long)]
+    pub sort_by_mtime: bool,
+    
+    /// Glob pattern for the names of files to read from directories; repeat for more patterns
+    // -p and --pattern are the deprecated spellings from before --exclude
+    #[arg(short = 'p', long = "include", alias = "pattern", value_name = "PATTERN", default_value = "*.edn")]
+    pub include_patterns: Vec<String>,
+    
+    /// Glob pattern for file or directory names to skip, even if they match --include; repeatable
+    #[arg(long = "exclude", value_name = "PATTERN")]
+    pub exclude_patterns: Vec<String>,
     
     /// Suppress output when query result is nil
     #[arg(long = "suppress-nil")]
     pub suppress_nil: bool,
+    
+    /// Print how many results were not nil instead of the results (per file with --with-filename)
+    #[arg(long, conflicts_with = "in_place")]
+    pub count: bool,
+    
+    /// Collect all results and print them as one vector (a JSON array with --json-output)
+    #[arg(long, conflicts_with_all = ["count", "with_filename", "with_line_numbers"])]
+    pub stream: bool,
+    
+    /// Start an interactive REPL; all positional arguments are input files
+    #[arg(long)]
+    pub repl: bool,
+    
+    /// Print a shell completion script and exit
+    #[arg(long, value_name = "SHELL", hide = true)]
+    pub generate_completions: Option<Shell>,
+    
+    /// Print what differs between two EDN files, as (diff a b) would, and exit
+    #[arg(long, num_args = 2, value_names = ["A", "B"], value_hint = ValueHint::FilePath)]
+    pub diff: Vec<PathBuf>,
+    
+    /// Print the names of all builtin functions and exit
+    #[arg(long)]
+    pub list_builtins: bool,
+    
+    /// Check that the filter parses and analyzes, without reading input
+    #[arg(short = 'C', long)]
+    pub check: bool,
 }
 
 #[cfg(test)]
@@ -85,7 +272,7 @@ mod tests {
     
     #[test]
     fn test_basic_args() {
-        let args = Args::try_parse_from(&["eq", "."]).unwrap();
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
         assert_eq!(args.filter, ".");
         assert!(args.files.is_empty());
         assert!(!args.compact);
@@ -93,7 +280,7 @@ mod tests {
     
     #[test]
     fn test_file_args() {
-        let args = Args::try_parse_from(&["eq", "(first .)", "test.edn"]).unwrap();
+        let args = Args::try_parse_from(["eq", "(first .)", "test.edn"]).unwrap();
         assert_eq!(args.filter, "(first .)");
         assert_eq!(args.files.len(), 1);
         assert_eq!(args.files[0], PathBuf::from("test.edn"));
@@ -101,42 +288,268 @@ mod tests {
     
     #[test]
     fn test_flags() {
-        let args = Args::try_parse_from(&["eq", "-c", "--raw-output", "--tab", "."]).unwrap();
+        let args = Args::try_parse_from(["eq", "-c", "--raw-output", "--tab", "."]).unwrap();
         assert!(args.compact);
         assert!(args.raw_output);
         assert!(args.tab);
     }
     
+    #[test]
+    fn test_join_output_requires_raw_output() {
+        let args = Args::try_parse_from(["eq", "--raw-output", "--join-output", "."]).unwrap();
+        assert!(args.join_output);
+
+        assert!(Args::try_parse_from(["eq", "--join-output", "."]).is_err());
+    }
+    
+    #[test]
+    fn test_null_separator_flag() {
+        let args = Args::try_parse_from(["eq", "-0", "."]).unwrap();
+        assert!(args.null_separator);
+
+        let args = Args::try_parse_from(["eq", "--null-separator", "."]).unwrap();
+        assert!(args.null_separator);
+
+        assert!(Args::try_parse_from(["eq", "-0", "--raw-output", "--join-output", "."]).is_err());
+    }
+    
+    #[test]
+    fn test_in_place_flag() {
+        let args = Args::try_parse_from(["eq", "-i", ".", "a.edn"]).unwrap();
+        assert_eq!(args.in_place.as_deref(), Some(""));
+        assert_eq!(args.filter, ".");
+
+        let args = Args::try_parse_from(["eq", "--in-place=.bak", ".", "a.edn"]).unwrap();
+        assert_eq!(args.in_place.as_deref(), Some(".bak"));
+
+        let args = Args::try_parse_from(["eq", ".", "a.edn"]).unwrap();
+        assert_eq!(args.in_place, None);
+
+        assert!(Args::try_parse_from(["eq", "-i", "-o", "out.edn", ".", "a.edn"]).is_err());
+    }
+    
     #[test]
     fn test_with_filename_flag() {
-        let args = Args::try_parse_from(&["eq", "-H", ".", "file1.edn"]).unwrap();
+        let args = Args::try_parse_from(["eq", "-H", ".", "file1.edn"]).unwrap();
         assert!(args.with_filename);
         
-        let args = Args::try_parse_from(&["eq", "--with-filename", ".", "file1.edn"]).unwrap();
+        let args = Args::try_parse_from(["eq", "--with-filename", ".", "file1.edn"]).unwrap();
         assert!(args.with_filename);
     }
     
     #[test]
     fn test_recursive_flag() {
-        let args = Args::try_parse_from(&["eq", "-r", ".", "dir/"]).unwrap();
+        let args = Args::try_parse_from(["eq", "-r", ".", "dir/"]).unwrap();
         assert!(args.recursive);
         
-        let args = Args::try_parse_from(&["eq", "--recursive", ".", "dir/"]).unwrap();
+        let args = Args::try_parse_from(["eq", "--recursive", ".", "dir/"]).unwrap();
         assert!(args.recursive);
+        assert_eq!((args.min_depth, args.max_depth), (None, None));
+        
+        let args = Args::try_parse_from(["eq", "-r", "--min-depth", "1", "--max-depth", "2", ".", "dir/"]).unwrap();
+        assert_eq!((args.min_depth, args.max_depth), (Some(1), Some(2)));
+        
+        // Depths only apply to a recursive search
+        assert!(Args::try_parse_from(["eq", "--max-depth", "2", ".", "dir/"]).is_err());
+    }
+    
+    #[test]
+    fn test_error_format_flag() {
+        assert_eq!(Args::try_parse_from(["eq", "."]).unwrap().error_format, ErrorFormat::Text);
+        let args = Args::try_parse_from(["eq", "--error-format", "json", "."]).unwrap();
+        assert_eq!(args.error_format, ErrorFormat::Json);
+        assert!(Args::try_parse_from(["eq", "--error-format", "xml", "."]).is_err());
+    }
+    
+    #[test]
+    fn test_output_limit_flags() {
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!((args.max_string_length, args.max_collection_size, args.max_output_depth), (None, None, None));
+        
+        let args = Args::try_parse_from([
+            "eq", "--max-string-length", "10", "--max-collection-size", "5", "--max-output-depth", "2", ".",
+        ]).unwrap();
+        assert_eq!(args.max_string_length, Some(10));
+        assert_eq!(args.max_collection_size, Some(5));
+        assert_eq!(args.max_output_depth, Some(2));
+        
+        assert!(!args.align_map_values);
+        assert!(Args::try_parse_from(["eq", "--align-map-values", "."]).unwrap().align_map_values);
+    }
+    
+    #[test]
+    fn test_symlinks_and_sorting() {
+        let args = Args::try_parse_from(["eq", "-r", ".", "dir/"]).unwrap();
+        assert!(!args.follow_symlinks && !args.sort_by_name && !args.sort_by_mtime);
+        
+        let args = Args::try_parse_from(["eq", "-r", "--follow-symlinks", "--sort-by-name", ".", "dir/"]).unwrap();
+        assert!(args.follow_symlinks && args.sort_by_name);
+        
+        assert!(Args::try_parse_from(["eq", "--sort-by-name", "--sort-by-mtime", ".", "dir/"]).is_err());
     }
     
     #[test]
     fn test_glob_pattern() {
         // Test default value
-        let args = Args::try_parse_from(&["eq", "."]).unwrap();
-        assert_eq!(args.glob_pattern, "*.edn");
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!(args.include_patterns, ["*.edn"]);
+        assert!(args.exclude_patterns.is_empty());
         
-        // Test custom value with short form
-        let args = Args::try_parse_from(&["eq", "-p", "*.json", "."]).unwrap();
-        assert_eq!(args.glob_pattern, "*.json");
+        // Test the deprecated short and long forms
+        let args = Args::try_parse_from(["eq", "-p", "*.json", "."]).unwrap();
+        assert_eq!(args.include_patterns, ["*.json"]);
         
-        // Test custom value with long form
-        let args = Args::try_parse_from(&["eq", "--pattern", "*.json", "."]).unwrap();
-        assert_eq!(args.glob_pattern, "*.json");
+        let args = Args::try_parse_from(["eq", "--pattern", "*.json", "."]).unwrap();
+        assert_eq!(args.include_patterns, ["*.json"]);
+        
+        // Several patterns replace the default
+        let args = Args::try_parse_from(["eq", "--include", "*.edn", "--include", "*.clj", "--exclude", "*.bak.edn", "."]).unwrap();
+        assert_eq!(args.include_patterns, ["*.edn", "*.clj"]);
+        assert_eq!(args.exclude_patterns, ["*.bak.edn"]);
+    }
+
+    #[test]
+    fn test_output_format() {
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Edn);
+
+        let args = Args::try_parse_from(["eq", "-F", "json", "."]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Json);
+
+        let args = Args::try_parse_from(["eq", "--output-format", "edn", "."]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Edn);
+
+        let args = Args::try_parse_from(["eq", "-j", "."]).unwrap();
+        assert!(args.json_output);
+
+        let args = Args::try_parse_from(["eq", "-F", "csv", "--csv-separator", ";", "."]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Csv);
+        assert_eq!(args.csv_separator, ';');
+
+        let args = Args::try_parse_from(["eq", "-F", "table", "--table-format", "markdown", "."]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Table);
+        assert_eq!(args.table_format, TableFormat::Markdown);
+
+        assert!(Args::try_parse_from(["eq", "-F", "xml", "."]).is_err());
+    }
+
+    #[test]
+    fn test_input_format() {
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!(args.input_format, InputFormat::Edn);
+
+        let args = Args::try_parse_from(["eq", "-I", "json", "."]).unwrap();
+        assert_eq!(args.input_format, InputFormat::Json);
+
+        let args = Args::try_parse_from(["eq", "--input-format", "auto", "."]).unwrap();
+        assert_eq!(args.input_format, InputFormat::Auto);
+
+        let args = Args::try_parse_from(["eq", "-I", "ndjson", "."]).unwrap();
+        assert_eq!(args.input_format, InputFormat::Ndjson);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_repl_flag() {
+        let args = Args::try_parse_from(["eq", "--repl"]).unwrap();
+        assert!(args.repl);
+        assert_eq!(args.filter, "");
+
+        let args = Args::try_parse_from(["eq", "--repl", "a.edn", "b.edn"]).unwrap();
+        assert_eq!(args.filter, "a.edn");
+        assert_eq!(args.files, vec![PathBuf::from("b.edn")]);
+
+        // A filter is still required without --repl
+        assert!(Args::try_parse_from(["eq"]).is_err());
+    }
+
+    #[test]
+    fn test_completion_flags() {
+        let args = Args::try_parse_from(["eq", "--generate-completions", "zsh"]).unwrap();
+        assert_eq!(args.generate_completions, Some(Shell::Zsh));
+
+        let args = Args::try_parse_from(["eq", "--list-builtins"]).unwrap();
+        assert!(args.list_builtins);
+
+        assert!(Args::try_parse_from(["eq", "--generate-completions", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_flag() {
+        // No filter is needed
+        let args = Args::try_parse_from(["eq", "--diff", "a.edn", "b.edn"]).unwrap();
+        assert_eq!(args.diff, vec![PathBuf::from("a.edn"), PathBuf::from("b.edn")]);
+
+        assert!(Args::try_parse_from(["eq", "--diff", "a.edn"]).is_err());
+    }
+
+    #[test]
+    fn test_from_file_and_check() {
+        // With --from-file no filter argument is needed
+        let args = Args::try_parse_from(["eq", "-f", "query.eq"]).unwrap();
+        assert_eq!(args.from_file, Some(PathBuf::from("query.eq")));
+        assert_eq!(args.filter, "");
+
+        let args = Args::try_parse_from(["eq", "-C", "(first .)"]).unwrap();
+        assert!(args.check);
+
+        let args = Args::try_parse_from(["eq", "--check", "-f", "query.eq"]).unwrap();
+        assert!(args.check);
+    }
+
+    #[test]
+    fn test_arg_flags() {
+        let args = Args::try_parse_from([
+            "eq", "-n", "--arg", "greeting", "hello", "--arg", "name", "Bob", "--argjson", "n", "[1, 2]", "(str greeting)",
+        ]).unwrap();
+        assert_eq!(args.arg, ["greeting", "hello", "name", "Bob"]);
+        assert_eq!(args.argjson, ["n", "[1, 2]"]);
+        assert_eq!(args.filter, "(str greeting)");
+
+        assert!(Args::try_parse_from(["eq", "--arg", "greeting"]).is_err());
+    }
+
+    #[test]
+    fn test_output_file_flags() {
+        let args = Args::try_parse_from(["eq", "-o", "out.edn", "."]).unwrap();
+        assert_eq!(args.output_file, Some(PathBuf::from("out.edn")));
+        assert!(!args.append);
+
+        let args = Args::try_parse_from(["eq", "--output-file", "out.edn", "--append", "."]).unwrap();
+        assert!(args.append);
+
+        // --append only makes sense with an output file
+        assert!(Args::try_parse_from(["eq", "--append", "."]).is_err());
+    }
+
+    #[test]
+    fn test_color_flags() {
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!(args.color, ColorMode::Auto);
+        assert!(!args.no_color);
+
+        let args = Args::try_parse_from(["eq", "--color", "."]).unwrap();
+        assert_eq!(args.color, ColorMode::Always);
+        assert_eq!(args.filter, ".");
+
+        let args = Args::try_parse_from(["eq", "--color=never", "."]).unwrap();
+        assert_eq!(args.color, ColorMode::Never);
+
+        // The last of --color and --no-color wins
+        let args = Args::try_parse_from(["eq", "--color", "--no-color", "."]).unwrap();
+        assert!(args.no_color);
+        let args = Args::try_parse_from(["eq", "--no-color", "--color=always", "."]).unwrap();
+        assert!(!args.no_color);
+        assert_eq!(args.color, ColorMode::Always);
+    }
+
+    #[test]
+    fn test_jobs_flag() {
+        let args = Args::try_parse_from(["eq", "."]).unwrap();
+        assert_eq!(args.jobs, 1);
+
+        let args = Args::try_parse_from(["eq", "--jobs", "0", ".", "a.edn"]).unwrap();
+        assert_eq!(args.jobs, 0);
+
+        assert!(Args::try_parse_from(["eq", "--jobs", "-1", "."]).is_err());
+    }
+}
@@ -34,16 +34,18 @@ impl<'a> CollectionFormatter<'a> {
         }
     }
 
-    /// Format collection with key-value pairs (for maps)
+    /// Format collection with key-value pairs (for maps), ending with `more`
+    /// when some pairs were left out
     pub fn format_pairs(
         &self,
         prefix: &str,
         suffix: &str,
         pairs: impl Iterator<Item = (String, String)>,
+        more: Option<String>,
         depth: usize,
         should_inline: bool,
     ) -> String {
-        let items: Vec<String> = pairs.map(|(k, v)| format!("{} {}", k, v)).collect();
+        let items: Vec<String> = pairs.map(|(k, v)| format!("{} {}", k, v)).chain(more).collect();
         
         if items.is_empty() {
             return format!("{}{}", prefix, suffix);
@@ -68,9 +70,7 @@ impl<'a> CollectionFormatter<'a> {
         result.push_str(prefix);
         
         for (i, item) in items.iter().enumerate() {
-            if i == 0 && prefix.chars().last() != Some('{') {
-                result.push(' ');
-            } else if i == 0 {
+            if i == 0 {
                 result.push(' ');
             } else {
                 result.push('\n');
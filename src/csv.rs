@@ -0,0 +1,126 @@
+//! CSV output for collections of maps
+
+use crate::edn::EdnValue;
+use crate::error::{EqError, EqResult};
+use crate::formatter::{CompactFormatter, Formatter};
+use crate::output::OutputConfig;
+
+/// Format a vector of maps as CSV.
+///
+/// The header row comes from the keys of the first map, in insertion order.
+/// Missing keys and nil values produce empty cells; values other than strings
+/// and numbers are written as compact EDN in a quoted cell.
+pub fn format_csv(value: &EdnValue, config: &OutputConfig) -> EqResult<String> {
+    let rows = match value {
+        EdnValue::Vector(rows) | EdnValue::List(rows) => rows,
+        other => return Err(EqError::query_error(format!(
+            "CSV output requires a vector of maps, got {}",
+            other.type_name()
+        ))),
+    };
+
+    let mut maps = Vec::with_capacity(rows.len());
+    for row in rows {
+        match row {
+            EdnValue::Map(map) => maps.push(map),
+            other => return Err(EqError::query_error(format!(
+                "CSV output requires a vector of maps, found {} element",
+                other.type_name()
+            ))),
+        }
+    }
+
+    let Some(first) = maps.first() else {
+        return Ok(String::new());
+    };
+    let columns: Vec<&EdnValue> = first.keys().collect();
+    let separator = config.csv_separator;
+
+    let mut lines = Vec::with_capacity(maps.len() + 1);
+    let header: Vec<String> = columns.iter()
+        .map(|key| quote_if_needed(&header_name(key), separator))
+        .collect();
+    lines.push(header.join(&separator.to_string()));
+
+    for map in maps {
+        let cells: Vec<String> = columns.iter()
+            .map(|key| format_cell(map.get(*key), separator))
+            .collect();
+        lines.push(cells.join(&separator.to_string()));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Column name for a map key: keyword and symbol names without prefix, strings as-is
+pub fn header_name(key: &EdnValue) -> String {
+    match key {
+        EdnValue::Keyword(name) | EdnValue::String(name) | EdnValue::Symbol(name) => name.clone(),
+        other => compact_edn(other),
+    }
+}
+
+fn format_cell(value: Option<&EdnValue>, separator: char) -> String {
+    match value {
+        None | Some(EdnValue::Nil) => String::new(),
+        Some(EdnValue::String(s)) => quote_if_needed(s, separator),
+        Some(EdnValue::Integer(i)) => i.to_string(),
+        Some(EdnValue::BigInt(n)) => n.to_string(),
+        Some(EdnValue::Float(f)) => f.to_string(),
+        Some(other) => quote(&compact_edn(other)),
+    }
+}
+
+fn compact_edn(value: &EdnValue) -> String {
+    let config = OutputConfig { compact: true, ..OutputConfig::default() };
+    CompactFormatter.format(value, &config, 0)
+}
+
+fn quote_if_needed(s: &str, separator: char) -> String {
+    if s.contains(separator) || s.contains('"') || s.contains('\n') || s.contains('\r') {
+        quote(s)
+    } else {
+        s.to_string()
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edn::Parser;
+
+    fn to_csv(edn: &str) -> EqResult<String> {
+        let value = Parser::new(edn).parse().unwrap();
+        format_csv(&value, &OutputConfig::default())
+    }
+
+    #[test]
+    fn test_csv_basic() {
+        let result = to_csv(r#"[{:name "Alice" :age 30} {:name "Bob, Jr." :age 25.5}]"#).unwrap();
+        assert_eq!(result, "name,age\nAlice,30\n\"Bob, Jr.\",25.5");
+    }
+
+    #[test]
+    fn test_csv_cells() {
+        let result = to_csv(r#"[{:a 1 :b :x :c "say \"hi\""} {:a nil :b [1 2]}]"#).unwrap();
+        assert_eq!(result, "a,b,c\n1,\":x\",\"say \"\"hi\"\"\"\n,\"[1 2]\",");
+    }
+
+    #[test]
+    fn test_csv_separator() {
+        let value = Parser::new(r#"[{:a 1 :b "x;y"}]"#).parse().unwrap();
+        let config = OutputConfig { csv_separator: ';', ..OutputConfig::default() };
+        assert_eq!(format_csv(&value, &config).unwrap(), "a;b\n1;\"x;y\"");
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        assert!(to_csv("{:a 1}").is_err());
+        assert!(to_csv("[{:a 1} 2]").is_err());
+        assert_eq!(to_csv("[]").unwrap(), "");
+    }
+}
@@ -1,5 +1,9 @@
 pub mod value;
 pub mod parser;
+pub mod streaming;
+#[cfg(feature = "serde")]
+pub mod serde_impl;
 
 pub use value::{EdnValue, EdnSequential, EdnIterable, EdnAssociative};
-pub use parser::Parser;
\ No newline at end of file
+pub use parser::Parser;
+pub use streaming::StreamingParser;
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::edn::EdnValue;
 use crate::error::{EqError, EqResult};
 use indexmap::IndexMap;
+use num_bigint::BigInt;
 use std::collections::HashSet;
 
 #[derive(Debug)]
@@ -9,6 +10,10 @@ pub struct Parser {
     position: usize,
     line: usize,
     column: usize,
+    /// Line where the most recent top-level value started
+    start_line: usize,
+    /// Source text before the input on its first line, shown in error context
+    line_prefix: Option<String>,
     filename: Option<String>,
 }
 
@@ -19,21 +24,37 @@ impl Parser {
             position: 0,
             line: 1,
             column: 1,
+            start_line: 1,
+            line_prefix: Some(String::new()),
             filename: None,
         }
     }
     
     pub fn new_with_filename(input: &str, filename: Option<String>) -> Self {
+        Self::new_at(input, filename, 1, 1, Some(""))
+    }
+
+    /// Create a parser for input that starts at the given line and column of a larger source.
+    /// `line_prefix` is the source text before that column, if known, for error context.
+    pub fn new_at(input: &str, filename: Option<String>, line: usize, column: usize, line_prefix: Option<&str>) -> Self {
         Self {
             input: input.chars().collect(),
             position: 0,
-            line: 1,
-            column: 1,
+            line,
+            column,
+            start_line: line,
+            line_prefix: line_prefix.map(str::to_string),
             filename,
         }
     }
 
+    /// Parse the next top-level value, returning nil at end of input
     pub fn parse(&mut self) -> EqResult<EdnValue> {
+        Ok(self.parse_next()?.unwrap_or(EdnValue::Nil))
+    }
+
+    /// Parse the next top-level value, or None if only whitespace, comments and discards remain
+    pub fn parse_next(&mut self) -> EqResult<Option<EdnValue>> {
         self.skip_whitespace_and_comments();
         
         // Handle top-level discards
@@ -44,14 +65,16 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Ok(EdnValue::Nil);
+            return Ok(None);
         }
         
-        self.parse_value()
+        self.start_line = self.line;
+        self.parse_value().map(Some)
     }
-    
-    pub fn remaining_input(&self) -> String {
-        self.input[self.position..].iter().collect()
+
+    /// The line where the value last returned by parse or parse_next started
+    pub fn start_line(&self) -> usize {
+        self.start_line
     }
 
     fn parse_value(&mut self) -> EqResult<EdnValue> {
@@ -65,7 +88,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unexpected end of input"));
+            return Err(self.error("Unexpected end of input"));
         }
 
         let ch = self.peek();
@@ -90,11 +113,7 @@ impl Parser {
                 }
             }
             _ if ch.is_alphabetic() || "+-*/_!?$%&=<>.-".contains(ch) => self.parse_symbol(),
-            _ => Err(EqError::parse_error_with_file(self.filename.clone(),
-                self.line,
-                self.column,
-                format!("Unexpected character: '{}'", ch),
-            )),
+            _ => Err(self.error(format!("Unexpected character: '{}'", ch))),
         }
     }
 
@@ -124,7 +143,7 @@ impl Parser {
             if self.peek() == '\\' {
                 self.advance(); // consume backslash
                 if self.is_at_end() {
-                    return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated string escape"));
+                    return Err(self.error("Unterminated string escape"));
                 }
                 match self.peek() {
                     '"' => value.push('"'),
@@ -140,11 +159,7 @@ impl Parser {
                         continue; // Skip the advance() at the end of the loop
                     }
                     c => {
-                        return Err(EqError::parse_error_with_file(self.filename.clone(),
-                            self.line,
-                            self.column,
-                            format!("Invalid escape sequence: \\{}", c),
-                        ))
+                        return Err(self.error(format!("Invalid escape sequence: \\{}", c)))
                     }
                 }
                 self.advance();
@@ -155,7 +170,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated string"));
+            return Err(self.error("Unterminated string"));
         }
         
         self.advance(); // consume closing quote
@@ -166,7 +181,7 @@ impl Parser {
         self.advance(); // consume ':'
         let name = self.read_symbol_name();
         if name.is_empty() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Empty keyword"));
+            return Err(self.error("Empty keyword"));
         }
         Ok(EdnValue::Keyword(name))
     }
@@ -174,11 +189,11 @@ impl Parser {
     fn parse_character(&mut self) -> EqResult<EdnValue> {
         self.advance(); // consume '\'
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Incomplete character literal"));
+            return Err(self.error("Incomplete character literal"));
         }
         
-        // Check for unicode escape
-        if self.peek() == 'u' {
+        // Check for unicode escape; a lone \u is just the letter
+        if self.peek() == 'u' && self.peek_ahead(1).is_some_and(|c| c.is_ascii_hexdigit()) {
             return self.parse_unicode_character();
         }
         
@@ -187,6 +202,10 @@ impl Parser {
         while !self.is_at_end() && self.is_symbol_char(self.peek()) {
             self.advance();
         }
+        // Punctuation and the like stand for themselves: \( \; \😀
+        if self.position == start_pos {
+            self.advance();
+        }
         
         let char_name: String = self.input[start_pos..self.position].iter().collect();
         
@@ -197,12 +216,8 @@ impl Parser {
             "space" => ' ',
             "formfeed" => '\x0C',
             "backspace" => '\x08',
-            single_char if single_char.len() == 1 => single_char.chars().next().unwrap(),
-            _ => return Err(EqError::parse_error_with_file(self.filename.clone(),
-                self.line,
-                self.column,
-                format!("Invalid character literal: \\{}", char_name)
-            )),
+            single_char if single_char.chars().count() == 1 => single_char.chars().next().unwrap(),
+            _ => return Err(self.error(format!("Invalid character literal: \\{}", char_name))),
         };
         
         Ok(EdnValue::Character(character))
@@ -225,7 +240,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated vector"));
+            return Err(self.error("Unterminated vector"));
         }
         
         self.advance(); // consume ']'
@@ -249,7 +264,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated list"));
+            return Err(self.error("Unterminated list"));
         }
         
         self.advance(); // consume ')'
@@ -277,11 +292,7 @@ impl Parser {
             self.skip_whitespace_and_comments();
             
             if self.is_at_end() || self.peek() == '}' {
-                return Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    "Map literal must contain an even number of forms"
-                ));
+                return Err(self.error("Map literal must contain an even number of forms"));
             }
             
             // Parse the value (discards are handled by parse_value)
@@ -292,7 +303,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated map"));
+            return Err(self.error("Unterminated map"));
         }
         
         self.advance(); // consume '}'
@@ -302,7 +313,7 @@ impl Parser {
     fn parse_dispatch(&mut self) -> EqResult<EdnValue> {
         self.advance(); // consume '#'
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Incomplete dispatch"));
+            return Err(self.error("Incomplete dispatch"));
         }
         
         match self.peek() {
@@ -310,11 +321,7 @@ impl Parser {
             '(' => self.parse_anonymous_function(),
             '_' => {
                 // This should not happen as #_ is handled in parse_value
-                Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    "Unexpected discard macro in dispatch"
-                ))
+                Err(self.error("Unexpected discard macro in dispatch"))
             },
             _ => self.parse_tagged_literal(),
         }
@@ -333,18 +340,14 @@ impl Parser {
             } else {
                 let element = self.parse_value()?;
                 if !set.insert(element.clone()) {
-                    return Err(EqError::parse_error_with_file(self.filename.clone(),
-                        self.line,
-                        self.column,
-                        "Duplicate element in set"
-                    ));
+                    return Err(self.error("Duplicate element in set"));
                 }
             }
             self.skip_whitespace_and_comments();
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated set"));
+            return Err(self.error("Unterminated set"));
         }
         
         self.advance(); // consume '}'
@@ -368,7 +371,7 @@ impl Parser {
         }
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unterminated anonymous function"));
+            return Err(self.error("Unterminated anonymous function"));
         }
         
         self.advance(); // consume ')'
@@ -396,7 +399,7 @@ impl Parser {
     fn parse_tagged_literal(&mut self) -> EqResult<EdnValue> {
         let tag = self.read_symbol_name();
         if tag.is_empty() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Empty tag"));
+            return Err(self.error("Empty tag"));
         }
         
         self.skip_whitespace_and_comments();
@@ -410,18 +413,10 @@ impl Parser {
                     if self.is_valid_instant_string(&s) {
                         Ok(EdnValue::Instant(s))
                     } else {
-                        Err(EqError::parse_error_with_file(self.filename.clone(),
-                            self.line,
-                            self.column,
-                            format!("Invalid instant format: {}", s)
-                        ))
+                        Err(self.error(format!("Invalid instant format: {}", s)))
                     }
                 } else {
-                    Err(EqError::parse_error_with_file(self.filename.clone(),
-                        self.line,
-                        self.column,
-                        "#inst requires a string value"
-                    ))
+                    Err(self.error("#inst requires a string value"))
                 }
             }
             "uuid" => {
@@ -430,18 +425,10 @@ impl Parser {
                     if self.is_valid_uuid_string(&s) {
                         Ok(EdnValue::Uuid(s))
                     } else {
-                        Err(EqError::parse_error_with_file(self.filename.clone(),
-                            self.line,
-                            self.column,
-                            format!("Invalid UUID format: {}", s)
-                        ))
+                        Err(self.error(format!("Invalid UUID format: {}", s)))
                     }
                 } else {
-                    Err(EqError::parse_error_with_file(self.filename.clone(),
-                        self.line,
-                        self.column,
-                        "#uuid requires a string value"
-                    ))
+                    Err(self.error("#uuid requires a string value"))
                 }
             }
             _ => {
@@ -498,7 +485,7 @@ impl Parser {
         self.skip_whitespace_and_comments();
         
         if self.is_at_end() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Unexpected end of input"));
+            return Err(self.error("Unexpected end of input"));
         }
 
         let ch = self.peek();
@@ -528,7 +515,7 @@ impl Parser {
             '0'..='9' => self.parse_number(),
             '-' => {
                 // Look ahead to see if this is a negative number or a symbol
-                if self.peek_ahead(1).map_or(false, |c| c.is_ascii_digit()) {
+                if self.peek_ahead(1).is_some_and(|c| c.is_ascii_digit()) {
                     self.parse_number()
                 } else {
                     self.parse_symbol()
@@ -536,7 +523,7 @@ impl Parser {
             }
             '+' => {
                 // Look ahead to see if this is a positive number or a symbol
-                if self.peek_ahead(1).map_or(false, |c| c.is_ascii_digit()) {
+                if self.peek_ahead(1).is_some_and(|c| c.is_ascii_digit()) {
                     self.parse_number()
                 } else {
                     self.parse_symbol()
@@ -584,7 +571,7 @@ impl Parser {
                 }
             } else {
                 // Invalid exponent format - backtrack to before 'e'/'E'
-                self.position = start_pos;
+                self.backtrack(start_pos);
                 while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '.') {
                     if self.peek() == '.' {
                         if has_dot {
@@ -600,29 +587,48 @@ impl Parser {
         
         let number_str: String = self.input[start_pos..self.position].iter().collect();
         
-        if has_dot || has_exponent {
+        // Arbitrary precision integers: explicit N suffix, or too large for i64
+        let big_suffix = !has_dot && !has_exponent && !self.is_at_end() && self.peek() == 'N';
+        if big_suffix {
+            self.advance(); // consume 'N'
+        }
+        
+        // Ratios: digits/digits, reduced to lowest terms
+        let is_ratio = !has_dot && !has_exponent && !big_suffix && self.peek() == '/'
+            && self.peek_ahead(1).is_some_and(|c| c.is_ascii_digit());
+        if is_ratio {
+            self.advance(); // consume '/'
+            let denominator_start = self.position;
+            while !self.is_at_end() && self.peek().is_ascii_digit() {
+                self.advance();
+            }
+            let denominator_str: String = self.input[denominator_start..self.position].iter().collect();
+            let invalid = || self.error(format!("Invalid ratio: {}/{}", number_str, denominator_str));
+            let numerator = number_str.parse::<i64>().map_err(|_| invalid())?;
+            let denominator = denominator_str.parse::<i64>().map_err(|_| invalid())?;
+            return EdnValue::ratio(numerator.into(), denominator.into()).ok_or_else(invalid);
+        }
+        
+        if big_suffix {
+            number_str.parse::<BigInt>()
+                .map(EdnValue::BigInt)
+                .map_err(|_| self.error(format!("Invalid integer: {}N", number_str)))
+        } else if has_dot || has_exponent {
             number_str.parse::<f64>()
                 .map(EdnValue::Float)
-                .map_err(|_| EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    format!("Invalid float: {}", number_str)
-                ))
+                .map_err(|_| self.error(format!("Invalid float: {}", number_str)))
         } else {
             number_str.parse::<i64>()
                 .map(EdnValue::Integer)
-                .map_err(|_| EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    format!("Invalid integer: {}", number_str)
-                ))
+                .or_else(|_| number_str.parse::<BigInt>().map(EdnValue::BigInt))
+                .map_err(|_| self.error(format!("Invalid integer: {}", number_str)))
         }
     }
 
     fn parse_symbol(&mut self) -> EqResult<EdnValue> {
         let name = self.read_symbol_name();
         if name.is_empty() {
-            return Err(EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, "Empty symbol"));
+            return Err(self.error("Empty symbol"));
         }
         Ok(EdnValue::Symbol(name))
     }
@@ -646,7 +652,7 @@ impl Parser {
         
         for expected_char in literal.chars() {
             if self.is_at_end() || self.peek() != expected_char {
-                self.position = start_pos; // backtrack
+                self.backtrack(start_pos);
                 return false;
             }
             self.advance();
@@ -654,7 +660,7 @@ impl Parser {
         
         // Make sure we're at a word boundary
         if !self.is_at_end() && self.is_symbol_char(self.peek()) {
-            self.position = start_pos; // backtrack
+            self.backtrack(start_pos);
             return false;
         }
         
@@ -665,17 +671,8 @@ impl Parser {
     fn skip_whitespace_and_comments(&mut self) {
         while !self.is_at_end() {
             let ch = self.peek();
-            if ch.is_whitespace() {
-                if ch == '\n' {
-                    self.line += 1;
-                    self.column = 1;
-                } else {
-                    self.column += 1;
-                }
-                self.advance();
-            } else if ch == ',' {
-                // Treat comma as whitespace (EDN/Clojure behavior)
-                self.column += 1;
+            if ch.is_whitespace() || ch == ',' {
+                // Commas are whitespace (EDN/Clojure behavior)
                 self.advance();
             } else if ch == ';' {
                 // Skip comment until end of line
@@ -707,8 +704,41 @@ impl Parser {
 
     fn advance(&mut self) {
         if !self.is_at_end() {
+            if self.input[self.position] == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
             self.position += 1;
-            self.column += 1;
+        }
+    }
+
+    /// Return to an earlier position on the current line
+    fn backtrack(&mut self, position: usize) {
+        self.column -= self.position - position;
+        self.position = position;
+    }
+
+    /// Build a parse error at the current position, keeping the text of the current line
+    fn error(&self, message: impl Into<String>) -> EqError {
+        let position = self.position.min(self.input.len());
+        let line_start = self.input[..position].iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+        let line_end = self.input[position..].iter().position(|&c| c == '\n').map_or(self.input.len(), |i| position + i);
+        let mut text: String = self.input[line_start..line_end].iter().collect();
+        let error = EqError::parse_error_with_file(self.filename.clone(), self.line, self.column, message);
+        if line_start == 0 {
+            match &self.line_prefix {
+                Some(prefix) => text.insert_str(0, prefix),
+                // Without the start of the line the caret can't be placed
+                None => return error,
+            }
+        }
+
+        if text.trim().is_empty() {
+            error
+        } else {
+            error.with_source_context(text)
         }
     }
 
@@ -786,11 +816,7 @@ impl Parser {
         let mut hex_digits = String::new();
         for _ in 0..4 {
             if self.is_at_end() || !self.peek().is_ascii_hexdigit() {
-                return Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    "Unicode escape requires exactly 4 hex digits"
-                ));
+                return Err(self.error("Unicode escape requires exactly 4 hex digits"));
             }
             hex_digits.push(self.peek());
             self.advance();
@@ -801,18 +827,10 @@ impl Parser {
             if let Some(character) = char::from_u32(code_point) {
                 Ok(EdnValue::Character(character))
             } else {
-                Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    format!("Invalid Unicode code point: U+{}", hex_digits)
-                ))
+                Err(self.error(format!("Invalid Unicode code point: U+{}", hex_digits)))
             }
         } else {
-            Err(EqError::parse_error_with_file(self.filename.clone(),
-                self.line,
-                self.column,
-                format!("Invalid hex digits in Unicode escape: {}", hex_digits)
-            ))
+            Err(self.error(format!("Invalid hex digits in Unicode escape: {}", hex_digits)))
         }
     }
 
@@ -821,11 +839,7 @@ impl Parser {
         let mut hex_digits = String::new();
         for _ in 0..4 {
             if self.is_at_end() || !self.peek().is_ascii_hexdigit() {
-                return Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    "Unicode escape in string requires exactly 4 hex digits"
-                ));
+                return Err(self.error("Unicode escape in string requires exactly 4 hex digits"));
             }
             hex_digits.push(self.peek());
             self.advance();
@@ -836,18 +850,10 @@ impl Parser {
             if let Some(character) = char::from_u32(code_point) {
                 Ok(character)
             } else {
-                Err(EqError::parse_error_with_file(self.filename.clone(),
-                    self.line,
-                    self.column,
-                    format!("Invalid Unicode code point in string: U+{}", hex_digits)
-                ))
+                Err(self.error(format!("Invalid Unicode code point in string: U+{}", hex_digits)))
             }
         } else {
-            Err(EqError::parse_error_with_file(self.filename.clone(),
-                self.line,
-                self.column,
-                format!("Invalid hex digits in Unicode escape: {}", hex_digits)
-            ))
+            Err(self.error(format!("Invalid hex digits in Unicode escape: {}", hex_digits)))
         }
     }
 }
@@ -957,8 +963,8 @@ mod tests {
         let mut parser = Parser::new("-17");
         assert_eq!(parser.parse().unwrap(), EdnValue::Integer(-17));
         
-        let mut parser = Parser::new("3.14");
-        assert_eq!(parser.parse().unwrap(), EdnValue::Float(3.14));
+        let mut parser = Parser::new("3.25");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Float(3.25));
         
         let mut parser = Parser::new("-2.5");
         assert_eq!(parser.parse().unwrap(), EdnValue::Float(-2.5));
@@ -976,13 +982,53 @@ mod tests {
         let mut parser = Parser::new("1E5");
         assert_eq!(parser.parse().unwrap(), EdnValue::Float(1E5));
         
-        let mut parser = Parser::new("-3.14E+2");
-        assert_eq!(parser.parse().unwrap(), EdnValue::Float(-3.14E+2));
+        let mut parser = Parser::new("-3.25E+2");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Float(-3.25E+2));
         
         let mut parser = Parser::new("2e3");
         assert_eq!(parser.parse().unwrap(), EdnValue::Float(2e3));
     }
 
+    #[test]
+    fn test_parse_bigint() {
+        let mut parser = Parser::new("42N");
+        assert_eq!(parser.parse().unwrap(), EdnValue::BigInt(BigInt::from(42)));
+        
+        let mut parser = Parser::new("-123456789012345678901234567890N");
+        let expected: BigInt = "-123456789012345678901234567890".parse().unwrap();
+        assert_eq!(parser.parse().unwrap(), EdnValue::BigInt(expected));
+        
+        // Integers beyond i64 range are promoted without the suffix
+        let mut parser = Parser::new("9223372036854775808");
+        let expected: BigInt = "9223372036854775808".parse().unwrap();
+        assert_eq!(parser.parse().unwrap(), EdnValue::BigInt(expected));
+        
+        let mut parser = Parser::new("9223372036854775807");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Integer(i64::MAX));
+        
+        let mut parser = Parser::new("[1N 2]");
+        assert_eq!(parser.parse().unwrap().to_string(), "[1N 2]");
+    }
+
+    #[test]
+    fn test_parse_ratio() {
+        let mut parser = Parser::new("22/7");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Ratio { numerator: 22, denominator: 7 });
+
+        // Normalized to lowest terms
+        let mut parser = Parser::new("-6/4");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Ratio { numerator: -3, denominator: 2 });
+
+        let mut parser = Parser::new("4/2");
+        assert_eq!(parser.parse().unwrap(), EdnValue::Integer(2));
+
+        let mut parser = Parser::new("[1/2 3]");
+        assert_eq!(parser.parse().unwrap().to_string(), "[1/2 3]");
+
+        let mut parser = Parser::new("1/0");
+        assert!(parser.parse().is_err());
+    }
+
     #[test]
     fn test_parse_symbol() {
         let mut parser = Parser::new("symbol");
@@ -1243,6 +1289,39 @@ mod tests {
         assert!(parser.parse().is_err());
     }
 
+    #[test]
+    fn test_error_source_context() {
+        let mut parser = Parser::new("{:a 1,\n \"multi\nline\" :b @}");
+        let err = parser.parse().unwrap_err();
+        match &err {
+            EqError::ParseError { line, column, .. } => assert_eq!((*line, *column), (3, 10)),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+        assert_eq!(err.source_snippet().unwrap(), "line\" :b @}\n         ^");
+
+        // Input that starts partway through a line includes the text before it
+        let mut parser = Parser::new_at("{:a}", None, 1, 5, Some("1 2 "));
+        assert_eq!(parser.parse().unwrap_err().source_snippet().unwrap(), "1 2 {:a}\n       ^");
+
+        let mut parser = Parser::new_at("{:a}", None, 1, 5000, None);
+        assert!(parser.parse().unwrap_err().source_snippet().is_none());
+    }
+
+    #[test]
+    fn test_start_line() {
+        let mut parser = Parser::new("1\n; comment\n\n{:a\n 2} #_ :skip\n\n[3]");
+        parser.parse_next().unwrap();
+        assert_eq!(parser.start_line(), 1);
+        parser.parse_next().unwrap();
+        assert_eq!(parser.start_line(), 4);
+        parser.parse_next().unwrap();
+        assert_eq!(parser.start_line(), 7);
+
+        let mut parser = Parser::new_at("\n:x", None, 10, 1, None);
+        parser.parse_next().unwrap();
+        assert_eq!(parser.start_line(), 11);
+    }
+
     #[test]
     fn test_whitespace_handling() {
         let inputs = vec![
@@ -0,0 +1,263 @@
+//! serde support for EDN values, behind the `serde` feature.
+//!
+//! Serialization produces the same JSON-compatible shape as `--json-output`:
+//! keywords, symbols, characters, instants and uuids become strings, lists
+//! and sets become sequences and map keys become strings. Deserialization is
+//! the reverse of `--json-input`: objects become maps with keyword keys and
+//! arrays become vectors.
+
+use crate::edn::value::EdnValue;
+use indexmap::IndexMap;
+use num_traits::ToPrimitive;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::fmt;
+
+/// How to serialize values that have no JSON-compatible form: tagged
+/// values, values with metadata and functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Opaque {
+    /// Fail, as `--json-output` does
+    #[default]
+    Error,
+    /// Leave them out of collections and maps; on their own they become null
+    Skip,
+    /// A single-entry map: `{"#tag": value}` for a tagged value,
+    /// `{"#with-meta": [metadata, value]}` and `{"#fn": "(fn [x] ...)"}`
+    Tagged,
+}
+
+/// An EDN value serialized with a chosen treatment of opaque values.
+/// Serializing an `EdnValue` directly is the same as `Opaque::Error`.
+pub struct WithOpaque<'a> {
+    value: &'a EdnValue,
+    opaque: Opaque,
+}
+
+impl EdnValue {
+    /// Serialize this value with `opaque` deciding what happens to tagged
+    /// values, metadata and functions
+    pub fn with_opaque(&self, opaque: Opaque) -> WithOpaque<'_> {
+        WithOpaque { value: self, opaque }
+    }
+}
+
+impl Serialize for EdnValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.with_opaque(Opaque::Error).serialize(serializer)
+    }
+}
+
+impl WithOpaque<'_> {
+    fn nested<'b>(&self, value: &'b EdnValue) -> WithOpaque<'b> {
+        WithOpaque { value, opaque: self.opaque }
+    }
+
+    /// Whether this value is dropped from the collection it is in
+    fn skipped(&self) -> bool {
+        self.opaque == Opaque::Skip && is_opaque(self.value)
+    }
+
+    fn serialize_items<'b, S: Serializer>(
+        &self,
+        items: impl Iterator<Item = &'b EdnValue>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let items: Vec<WithOpaque> = items.map(|item| self.nested(item)).filter(|item| !item.skipped()).collect();
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in &items {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_opaque<S: Serializer, T: Serialize + ?Sized>(&self, name: &str, value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.opaque {
+            Opaque::Error => Err(ser::Error::custom(format!(
+                "{} has no JSON-compatible representation",
+                self.value.type_name()
+            ))),
+            Opaque::Skip => serializer.serialize_unit(),
+            Opaque::Tagged => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(&format!("#{}", name), value)?;
+                map.end()
+            }
+        }
+    }
+}
+
+fn is_opaque(value: &EdnValue) -> bool {
+    matches!(value, EdnValue::Tagged { .. } | EdnValue::WithMetadata { .. } | EdnValue::Lambda(_))
+}
+
+impl Serialize for WithOpaque<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            EdnValue::Nil => serializer.serialize_unit(),
+            EdnValue::Bool(b) => serializer.serialize_bool(*b),
+            EdnValue::String(s) | EdnValue::Keyword(s) | EdnValue::Symbol(s)
+            | EdnValue::Instant(s) | EdnValue::Uuid(s) => serializer.serialize_str(s),
+            EdnValue::Character(c) => serializer.serialize_char(*c),
+            EdnValue::Integer(i) => serializer.serialize_i64(*i),
+            // Too big even for i128: keep every digit as a string
+            EdnValue::BigInt(n) => match n.to_i128() {
+                Some(i) => serializer.serialize_i128(i),
+                None => serializer.serialize_str(&n.to_string()),
+            },
+            EdnValue::Ratio { numerator, denominator } => serializer.serialize_f64(*numerator as f64 / *denominator as f64),
+            EdnValue::Float(f) => serializer.serialize_f64(*f),
+            EdnValue::Vector(items) | EdnValue::List(items) => self.serialize_items(items.iter(), serializer),
+            EdnValue::Set(items) => {
+                // Sorted for deterministic output, as --json-output does
+                let mut items: Vec<&EdnValue> = items.iter().collect();
+                items.sort_by_key(|item| item.to_string());
+                self.serialize_items(items.into_iter(), serializer)
+            }
+            EdnValue::Map(entries) => {
+                let entries: Vec<(String, WithOpaque)> = entries
+                    .iter()
+                    .map(|(k, v)| (k, self.nested(v)))
+                    .filter(|(k, v)| !v.skipped() && !self.nested(k).skipped())
+                    .map(|(k, v)| Ok((crate::json::json_key(k).map_err(ser::Error::custom)?, v)))
+                    .collect::<Result<_, S::Error>>()?;
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in &entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            EdnValue::LazySeq(seq) => {
+                let items = seq.realize().map_err(ser::Error::custom)?;
+                self.serialize_items(items.iter(), serializer)
+            }
+            EdnValue::Tagged { tag, value } => self.serialize_opaque(tag, &self.nested(value), serializer),
+            EdnValue::WithMetadata { metadata, value } => {
+                let pair = [self.nested(metadata), self.nested(value)];
+                self.serialize_opaque("with-meta", &pair, serializer)
+            }
+            EdnValue::Lambda(_) => self.serialize_opaque("fn", &self.value.to_string(), serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EdnValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EdnValueVisitor)
+    }
+}
+
+struct EdnValueVisitor;
+
+impl<'de> Visitor<'de> for EdnValueVisitor {
+    type Value = EdnValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON-compatible value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<EdnValue, E> {
+        Ok(EdnValue::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<EdnValue, E> {
+        Ok(EdnValue::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<EdnValue, D::Error> {
+        EdnValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<EdnValue, E> {
+        Ok(EdnValue::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<EdnValue, E> {
+        Ok(EdnValue::Integer(i))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<EdnValue, E> {
+        Ok(i64::try_from(u).map(EdnValue::Integer).unwrap_or_else(|_| EdnValue::BigInt(u.into())))
+    }
+
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<EdnValue, E> {
+        Ok(i64::try_from(i).map(EdnValue::Integer).unwrap_or_else(|_| EdnValue::BigInt(i.into())))
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<EdnValue, E> {
+        Ok(EdnValue::Float(f))
+    }
+
+    fn visit_char<E: de::Error>(self, c: char) -> Result<EdnValue, E> {
+        Ok(EdnValue::String(c.to_string()))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<EdnValue, E> {
+        Ok(EdnValue::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<EdnValue, E> {
+        Ok(EdnValue::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<EdnValue, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(EdnValue::Vector(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<EdnValue, A::Error> {
+        let mut map = IndexMap::new();
+        while let Some((key, value)) = access.next_entry::<String, EdnValue>()? {
+            map.insert(EdnValue::Keyword(key), value);
+        }
+        Ok(EdnValue::Map(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edn::Parser;
+
+    fn parse(s: &str) -> EdnValue {
+        Parser::new(s).parse().unwrap()
+    }
+
+    #[test]
+    fn test_serialize_like_json_output() {
+        let value = parse(r#"{:name "eq" :tags #{:b :a} :id 12345678901234567890N :ratio 1/2 :db/id (1 \x nil)}"#);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"name":"eq","tags":["a","b"],"id":12345678901234567890,"ratio":0.5,"db/id":[1,"x",null]}"#
+        );
+        assert!(serde_json::to_string(&parse("{[1] 2}")).is_err());
+    }
+
+    #[test]
+    fn test_opaque_values() {
+        let value = parse(r#"[1 #point [2 3] ^{:doc "x"} [4]]"#);
+        assert!(serde_json::to_string(&value).unwrap_err().to_string().contains("tagged has no JSON-compatible"));
+        assert_eq!(serde_json::to_string(&value.with_opaque(Opaque::Skip)).unwrap(), "[1]");
+        assert_eq!(
+            serde_json::to_string(&value.with_opaque(Opaque::Tagged)).unwrap(),
+            r##"[1,{"#point":[2,3]},{"#with-meta":[{"doc":"x"},[4]]}]"##
+        );
+
+        let map = parse("{:a #point [1] :b 2}");
+        assert_eq!(serde_json::to_string(&map.with_opaque(Opaque::Skip)).unwrap(), r#"{"b":2}"#);
+        assert_eq!(serde_json::to_string(&parse("#point [1]").with_opaque(Opaque::Skip)).unwrap(), "null");
+    }
+
+    #[test]
+    fn test_deserialize_like_json_input() {
+        let value: EdnValue = serde_json::from_str(r#"{"name": "eq", "n": [1, 2.5, null, true], "big": 18446744073709551615}"#).unwrap();
+        assert_eq!(value, parse(r#"{:name "eq" :n [1 2.5 nil true] :big 18446744073709551615N}"#));
+
+        // A round trip keeps JSON-compatible values
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<EdnValue>(&json).unwrap(), value);
+    }
+}
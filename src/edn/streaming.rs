@@ -0,0 +1,395 @@
+//! Streaming EDN parsing for large inputs
+//!
+//! Input is read incrementally and split into top-level forms by a lightweight
+//! scanner that tracks nesting, strings, comments and character literals. Only
+//! the form currently being read is buffered; each complete form is handed to
+//! the regular `Parser`.
+
+use crate::edn::{EdnValue, Parser};
+use crate::error::EqResult;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+
+/// Iterator over the top-level values of an EDN stream
+pub struct StreamingParser<R: Read> {
+    reader: BufReader<R>,
+    filename: Option<String>,
+    scanner: FormScanner,
+    /// Parsed values with the line each one starts on
+    ready: VecDeque<EqResult<(usize, EdnValue)>>,
+    start_line: usize,
+    done: bool,
+}
+
+impl<R: Read> StreamingParser<R> {
+    pub fn new(reader: R, filename: Option<String>) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            filename,
+            scanner: FormScanner::new(),
+            ready: VecDeque::new(),
+            start_line: 1,
+            done: false,
+        }
+    }
+
+    /// The line where the value last returned by the iterator started
+    pub fn start_line(&self) -> usize {
+        self.start_line
+    }
+
+    fn parse_forms(&mut self) {
+        for form in self.scanner.take_forms() {
+            let mut parser = Parser::new_at(&form.text, self.filename.clone(), form.line, form.column, form.line_prefix.as_deref());
+            loop {
+                match parser.parse_next() {
+                    Ok(Some(value)) => self.ready.push_back(Ok((parser.start_line(), value))),
+                    Ok(None) => break,
+                    Err(e) => {
+                        self.ready.push_back(Err(e));
+                        self.done = true;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for StreamingParser<R> {
+    type Item = EqResult<EdnValue>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return Some(item.map(|(line, value)| {
+                    self.start_line = line;
+                    value
+                }));
+            }
+            if self.done {
+                return None;
+            }
+
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+                Ok(0) => {
+                    self.done = true;
+                    self.scanner.finish();
+                }
+                Ok(_) => line.chars().for_each(|c| self.scanner.feed(c)),
+            }
+            self.parse_forms();
+        }
+    }
+}
+
+/// Source text of one top-level form and where it starts
+struct Form {
+    text: String,
+    line: usize,
+    column: usize,
+    /// Text before the form on its first line, unless the line is too long to keep
+    line_prefix: Option<String>,
+}
+
+/// Longest line prefix kept for error context; copying more per form would
+/// make huge single-line inputs quadratic
+const MAX_LINE_PREFIX: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScanState {
+    Normal,
+    String { escape: bool },
+    Comment,
+    /// The character right after a backslash
+    CharLiteral,
+    /// A top-level scalar; prefix atoms (tags like #inst) need a following form
+    Atom { prefix: bool },
+    /// A top-level '#' waiting for the dispatch character
+    Hash,
+}
+
+struct FormScanner {
+    text: String,
+    start_line: usize,
+    start_column: usize,
+    start_prefix: Option<String>,
+    line: usize,
+    column: usize,
+    /// Everything read so far on the current line, dropped once it exceeds MAX_LINE_PREFIX
+    line_text: Option<String>,
+    depth: usize,
+    /// Forms still required before the current top-level form is complete (metadata, discards)
+    pending: usize,
+    /// The form so far is only discards, so it ends as soon as they are complete
+    discarding: bool,
+    state: ScanState,
+    forms: Vec<Form>,
+}
+
+impl FormScanner {
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+            start_line: 1,
+            start_column: 1,
+            start_prefix: Some(String::new()),
+            line: 1,
+            column: 1,
+            line_text: Some(String::new()),
+            depth: 0,
+            pending: 0,
+            discarding: false,
+            state: ScanState::Normal,
+            forms: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, c: char) {
+        match self.state {
+            ScanState::String { escape } => {
+                self.push(c);
+                if escape {
+                    self.state = ScanState::String { escape: false };
+                } else if c == '\\' {
+                    self.state = ScanState::String { escape: true };
+                } else if c == '"' {
+                    self.state = ScanState::Normal;
+                    if self.depth == 0 {
+                        self.complete_form(false);
+                    }
+                }
+                return;
+            }
+            ScanState::Comment => {
+                self.push(c);
+                if c == '\n' {
+                    self.state = ScanState::Normal;
+                }
+                return;
+            }
+            ScanState::CharLiteral => {
+                self.push(c);
+                self.state = if self.depth == 0 { ScanState::Atom { prefix: false } } else { ScanState::Normal };
+                return;
+            }
+            ScanState::Atom { prefix } => {
+                if !is_delimiter(c) {
+                    self.push(c);
+                    return;
+                }
+                // The delimiter belongs to whatever comes next
+                self.state = ScanState::Normal;
+                self.complete_form(prefix);
+            }
+            ScanState::Hash => {
+                self.state = ScanState::Normal;
+                match c {
+                    '{' | '(' | '"' => {}
+                    '_' => {
+                        if self.pending == 0 {
+                            self.discarding = self.text.trim() == "#";
+                        }
+                        self.push(c);
+                        self.pending += 1;
+                        return;
+                    }
+                    _ => {
+                        self.push(c);
+                        self.state = ScanState::Atom { prefix: true };
+                        return;
+                    }
+                }
+            }
+            ScanState::Normal => {}
+        }
+
+        self.push(c);
+        match c {
+            '"' => self.state = ScanState::String { escape: false },
+            ';' => self.state = ScanState::Comment,
+            '\\' => self.state = ScanState::CharLiteral,
+            '(' | '[' | '{' => self.depth += 1,
+            ')' | ']' | '}' => {
+                // An unmatched closer is passed along so the parser can report it
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 0 {
+                    self.complete_form(false);
+                }
+            }
+            _ if self.depth > 0 => {}
+            '#' => self.state = ScanState::Hash,
+            '^' => self.pending += 1,
+            c if c.is_whitespace() || c == ',' => {}
+            _ => self.state = ScanState::Atom { prefix: false },
+        }
+    }
+
+    /// Emit whatever is buffered at end of input
+    fn finish(&mut self) {
+        if !self.text.trim().is_empty() {
+            self.emit();
+        }
+    }
+
+    fn take_forms(&mut self) -> Vec<Form> {
+        std::mem::take(&mut self.forms)
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+            self.line_text = Some(String::new());
+        } else {
+            self.column += 1;
+            if let Some(text) = &mut self.line_text {
+                text.push(c);
+                if text.len() > MAX_LINE_PREFIX {
+                    self.line_text = None;
+                }
+            }
+        }
+    }
+
+    fn complete_form(&mut self, prefix: bool) {
+        if prefix {
+            // A tag is part of the form it tags, which completes this one
+            return;
+        }
+        if self.pending > 0 {
+            self.pending -= 1;
+            if self.pending == 0 && self.discarding {
+                self.emit();
+            }
+        } else {
+            self.emit();
+        }
+    }
+
+    fn emit(&mut self) {
+        self.forms.push(Form {
+            text: std::mem::take(&mut self.text),
+            line: self.start_line,
+            column: self.start_column,
+            line_prefix: std::mem::replace(&mut self.start_prefix, self.line_text.clone()),
+        });
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.discarding = false;
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ',' | ';' | '"' | '(' | ')' | '[' | ']' | '{' | '}' | '^')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EqError;
+    use std::io;
+
+    fn parse_all(input: &str) -> EqResult<Vec<EdnValue>> {
+        StreamingParser::new(input.as_bytes(), None).collect()
+    }
+
+    #[test]
+    fn test_stream_multiple_values() {
+        let values = parse_all("1 :a \"two\"\n[3 4]\n{:b\n 5}nil").unwrap();
+        let rendered: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(rendered, vec!["1", ":a", "\"two\"", "[3 4]", "{:b 5}", "nil"]);
+    }
+
+    #[test]
+    fn test_stream_start_lines() {
+        let mut parser = StreamingParser::new("1 2\n\n{:a\n 1}\n  [3]".as_bytes(), None);
+        let mut lines = Vec::new();
+        while let Some(value) = parser.next() {
+            value.unwrap();
+            lines.push(parser.start_line());
+        }
+        assert_eq!(lines, vec![1, 1, 3, 5]);
+    }
+
+    #[test]
+    fn test_stream_tricky_forms() {
+        let input = r#"
+            ["a ] string" \a "\" ]" ] ; comment with [ bracket
+            #inst "2023-01-01T00:00:00Z"
+            #myapp/tag
+              {:x 1}
+            ^{:meta true} [1]
+            #_ [:discarded] :kept
+            #{1 2}
+        "#;
+        let values = parse_all(input).unwrap();
+        assert_eq!(values.len(), 6);
+        assert_eq!(values[0].to_string(), r#"["a ] string" \a "\" ]"]"#);
+        assert!(matches!(values[1], EdnValue::Instant(_)));
+        assert!(matches!(values[2], EdnValue::Tagged { .. }));
+        assert!(matches!(values[3], EdnValue::WithMetadata { .. }));
+        assert_eq!(values[4], EdnValue::Keyword("kept".to_string()));
+        assert!(matches!(values[5], EdnValue::Set(_)));
+    }
+
+    #[test]
+    fn test_stream_empty_and_discard_only() {
+        assert!(parse_all("").unwrap().is_empty());
+        assert!(parse_all("  ; just a comment\n#_ 1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stream_error_line() {
+        let err = parse_all("1\n2\n[3 4").unwrap_err();
+        match err {
+            EqError::ParseError { line, .. } => assert_eq!(line, 3),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+
+        // Context includes earlier forms on the same line
+        let err = parse_all("1 2 [3 4").unwrap_err();
+        assert_eq!(err.source_snippet().unwrap(), "1 2 [3 4\n        ^");
+    }
+
+    /// Yields its data, then fails instead of reporting end of input
+    struct FailAfter<'a> {
+        data: &'a [u8],
+    }
+
+    impl Read for FailAfter<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return Err(io::Error::other("no more input"));
+            }
+            let n = buf.len().min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_stream_yields_before_end_of_input() {
+        let mut parser = StreamingParser::new(FailAfter { data: b"{:a 1}\n" }, None);
+        assert_eq!(parser.next().unwrap().unwrap().to_string(), "{:a 1}");
+        assert!(parser.next().unwrap().is_err());
+
+        // A tagged value is complete without waiting for the next form
+        let mut parser = StreamingParser::new(FailAfter { data: b"#inst \"2023-01-01T00:00:00Z\"\n" }, None);
+        assert_eq!(parser.next().unwrap().unwrap().to_string(), "#inst \"2023-01-01T00:00:00Z\"");
+        assert!(parser.next().unwrap().is_err());
+
+        // So is a discard, and metadata still waits for the value it applies to
+        let mut parser = StreamingParser::new(FailAfter { data: b"#_ :gone ^:m [1] ^:n #_ 2 [3]\n" }, None);
+        assert_eq!(parser.next().unwrap().unwrap().to_string(), "^:m [1]");
+        assert_eq!(parser.next().unwrap().unwrap().to_string(), "^:n [3]");
+        assert!(parser.next().unwrap().is_err());
+    }
+}
@@ -1,7 +1,16 @@
+use crate::edn::parser::Parser;
+use crate::error::{EqError, EqResult};
+use crate::primitives::{escape_string, format_character, format_float};
 use indexmap::IndexMap;
-use std::collections::HashSet;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
 
 /// Trait for sequential collection operations like first, last, rest, take, drop
 pub trait EdnSequential {
@@ -41,11 +50,235 @@ pub trait EdnAssociative {
     }
 }
 
+/// Rust closure backing lambdas that are built by builtins (e.g. `partial`)
+/// rather than written as `(fn [params] body)` in a query
+#[derive(Clone)]
+pub struct NativeFn(NativeFnImpl);
+
+type NativeFnImpl = Arc<dyn Fn(&[EdnValue]) -> EqResult<EdnValue> + Send + Sync>;
+
+impl NativeFn {
+    pub fn new<F>(func: F) -> Self
+    where
+        F: Fn(&[EdnValue]) -> EqResult<EdnValue> + Send + Sync + 'static,
+    {
+        Self(Arc::new(func))
+    }
+
+    pub fn call(&self, args: &[EdnValue]) -> EqResult<EdnValue> {
+        (self.0)(args)
+    }
+
+    /// Address of the shared closure; equal exactly when the two are `==`
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.0).cast::<()>() as usize
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFn")
+    }
+}
+
+impl PartialEq for NativeFn {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Most elements a lazy sequence is realized to when it is printed; beyond
+/// this it is assumed to be infinite
+pub const MAX_REALIZED: usize = 100_000;
+
+/// A sequence whose elements are computed when something first asks for them.
+/// Forcing a cell runs its thunk at most once, so walking the first few
+/// elements of an infinite sequence is cheap.
+#[derive(Clone)]
+pub struct LazySeq(Arc<LazyCell>);
+
+type LazyThunk = Box<dyn Fn() -> EqResult<EdnValue> + Send + Sync>;
+
+struct LazyCell {
+    thunk: LazyThunk, // Yields nil, a list or vector, or another lazy sequence
+    step: OnceLock<Option<(EdnValue, EdnValue)>>, // First element and the rest, once forced
+}
+
+impl LazySeq {
+    pub fn new<F>(thunk: F) -> Self
+    where
+        F: Fn() -> EqResult<EdnValue> + Send + Sync + 'static,
+    {
+        Self(Arc::new(LazyCell { thunk: Box::new(thunk), step: OnceLock::new() }))
+    }
+
+    /// An already realized cell: `first` followed by the sequence `rest`
+    pub fn cons(first: EdnValue, rest: EdnValue) -> Self {
+        Self(Arc::new(LazyCell {
+            thunk: Box::new(|| Ok(EdnValue::Nil)),
+            step: OnceLock::from(Some((first, rest))),
+        }))
+    }
+
+    /// Force the first cell: its element and the rest of the sequence, or None if empty
+    pub fn step(&self) -> EqResult<Option<(EdnValue, EdnValue)>> {
+        if let Some(step) = self.0.step.get() {
+            return Ok(step.clone());
+        }
+        let step = seq_step(&(self.0.thunk)()?)?;
+        Ok(self.0.step.get_or_init(|| step).clone())
+    }
+
+    /// Realize up to `n` elements from the front of the sequence
+    pub fn take(&self, n: usize) -> EqResult<Vec<EdnValue>> {
+        let mut items = Vec::new();
+        let mut current = EdnValue::LazySeq(self.clone());
+        while items.len() < n {
+            match current {
+                EdnValue::LazySeq(seq) => match seq.step()? {
+                    Some((first, rest)) => {
+                        items.push(first);
+                        current = rest;
+                    }
+                    None => break,
+                },
+                // Already realized; no need to step through it cell by cell
+                EdnValue::Vector(rest) | EdnValue::List(rest) => {
+                    let remaining = n - items.len();
+                    items.extend(rest.into_iter().take(remaining));
+                    break;
+                }
+                other => {
+                    seq_step(&other)?;
+                    break;
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    /// Realize the whole sequence, failing if it is longer than MAX_REALIZED
+    pub fn realize(&self) -> EqResult<Vec<EdnValue>> {
+        let items = self.take(MAX_REALIZED + 1)?;
+        if items.len() > MAX_REALIZED {
+            return Err(crate::error::EqError::query_error(format!(
+                "Lazy sequence has more than {} elements; use take or realize to limit it",
+                MAX_REALIZED
+            )));
+        }
+        Ok(items)
+    }
+}
+
+/// Split a sequence into its first element and the rest, forcing it if lazy.
+/// None means the sequence is empty.
+pub fn seq_step(value: &EdnValue) -> EqResult<Option<(EdnValue, EdnValue)>> {
+    match value {
+        EdnValue::Nil => Ok(None),
+        EdnValue::Vector(items) | EdnValue::List(items) => Ok(items
+            .split_first()
+            .map(|(first, rest)| (first.clone(), EdnValue::List(rest.to_vec())))),
+        EdnValue::LazySeq(seq) => seq.step(),
+        EdnValue::WithMetadata { value, .. } => seq_step(value),
+        other => Err(crate::error::EqError::type_error("sequence", other.type_name())),
+    }
+}
+
+// A realized sequence is a chain of cells, each owning the next. Unlink it
+// iteratively so dropping a long one doesn't overflow the stack.
+impl Drop for LazyCell {
+    fn drop(&mut self) {
+        let mut next = self.step.take().flatten().map(|(_, rest)| rest);
+        while let Some(EdnValue::LazySeq(seq)) = next {
+            next = match Arc::try_unwrap(seq.0) {
+                Ok(mut cell) => cell.step.take().flatten().map(|(_, rest)| rest),
+                Err(_) => None, // Still shared; its other owner drops it
+            };
+        }
+    }
+}
+
+impl fmt::Debug for LazySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LazySeq")
+    }
+}
+
+/// Lazy sequences are equal when they have equal elements. They are realized
+/// only as far as the first difference; two that agree on their first
+/// MAX_REALIZED elements, or that fail to realize, are only equal to themselves.
+impl PartialEq for LazySeq {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+            || compare_lazy(self, other, |a, b| if a == b { Ordering::Equal } else { Ordering::Less }) == Some(Ordering::Equal)
+    }
+}
+
+/// Elements hashed from the front of a lazy sequence. Equal sequences share
+/// every prefix, and hashing a key shouldn't realize more than a few elements.
+const HASHED_PREFIX: usize = 8;
+
+/// Compare two lazy sequences element by element, realizing them in growing
+/// chunks only as far as the first difference. None if they agree on the first
+/// MAX_REALIZED elements or either fails to realize.
+fn compare_lazy(a: &LazySeq, b: &LazySeq, compare: impl Fn(&EdnValue, &EdnValue) -> Ordering) -> Option<Ordering> {
+    let (mut checked, mut n) = (0, 64);
+    loop {
+        let (xs, ys) = (a.take(n).ok()?, b.take(n).ok()?);
+        let difference = xs.iter().zip(&ys).skip(checked)
+            .map(|(x, y)| compare(x, y))
+            .find(|ordering| ordering.is_ne());
+        if difference.is_some() {
+            return difference;
+        }
+        if xs.len() < n || ys.len() < n {
+            return Some(xs.len().cmp(&ys.len()));
+        }
+        if n > MAX_REALIZED {
+            return None;
+        }
+        checked = n;
+        n = (n * 2).min(MAX_REALIZED + 1);
+    }
+}
+
 /// Simple representation of a lambda function
 #[derive(Debug, Clone, PartialEq)]
 pub struct EdnLambda {
     pub params: Vec<String>,
+    pub rest_param: Option<String>, // For (fn [a & rest] ...): bound to a Vector of the arguments after params
     pub body: Box<EdnValue>, // The body as an EdnValue (will be parsed to Expr later)
+    pub native: Option<NativeFn>, // When set, called instead of evaluating body; body is only a description
+    pub captured_env: HashMap<String, EdnValue>, // Bindings in scope where the fn was created
+}
+
+impl EdnLambda {
+    /// Create a lambda backed by a Rust closure. The description is what gets printed.
+    pub fn native<F>(description: EdnValue, func: F) -> Self
+    where
+        F: Fn(&[EdnValue]) -> EqResult<EdnValue> + Send + Sync + 'static,
+    {
+        Self {
+            params: Vec::new(),
+            rest_param: None,
+            body: Box::new(description),
+            native: Some(NativeFn::new(func)),
+            captured_env: HashMap::new(),
+        }
+    }
+
+    /// The parameter vector's contents as written, e.g. "a b & rest"
+    pub fn param_list(&self) -> String {
+        let mut list = self.params.join(" ");
+        if let Some(rest) = &self.rest_param {
+            if !list.is_empty() {
+                list.push(' ');
+            }
+            list.push_str("& ");
+            list.push_str(rest);
+        }
+        list
+    }
 }
 
 /// EDN value types with zero-copy string optimization
@@ -58,6 +291,11 @@ pub enum EdnValue {
     Symbol(String),
     Character(char),
     Integer(i64),
+    BigInt(BigInt), // Arbitrary precision integer (1234N)
+    Ratio {
+        numerator: i64,
+        denominator: i64, // Always positive; construct with EdnValue::ratio
+    },
     Float(f64),
     Vector(Vec<EdnValue>),
     List(Vec<EdnValue>),
@@ -74,9 +312,27 @@ pub enum EdnValue {
     Lambda(EdnLambda), // Lambda function (fn [params] body)
     Instant(String), // ISO 8601 timestamp string
     Uuid(String),    // UUID string
+    LazySeq(LazySeq), // Sequence realized on demand, e.g. from (lazy-seq ...) or iterate
 }
 
 impl EdnValue {
+    /// Build a ratio in lowest terms with a positive denominator.
+    /// Whole results collapse to an Integer. Returns None if the denominator
+    /// is zero or the reduced ratio doesn't fit in i64.
+    pub fn ratio(numerator: i128, denominator: i128) -> Option<EdnValue> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let numerator = i64::try_from(numerator / divisor).ok()?;
+        let denominator = i64::try_from(denominator / divisor).ok()?;
+        if denominator == 1 {
+            Some(EdnValue::Integer(numerator))
+        } else {
+            Some(EdnValue::Ratio { numerator, denominator })
+        }
+    }
+
     /// Returns the type name of this value
     pub fn type_name(&self) -> &'static str {
         match self {
@@ -87,6 +343,8 @@ impl EdnValue {
             EdnValue::Symbol(_) => "symbol",
             EdnValue::Character(_) => "character",
             EdnValue::Integer(_) => "integer",
+            EdnValue::BigInt(_) => "bigint",
+            EdnValue::Ratio { .. } => "ratio",
             EdnValue::Float(_) => "float",
             EdnValue::Vector(_) => "vector",
             EdnValue::List(_) => "list",
@@ -97,6 +355,7 @@ impl EdnValue {
             EdnValue::Lambda(_) => "lambda",
             EdnValue::Instant(_) => "instant",
             EdnValue::Uuid(_) => "uuid",
+            EdnValue::LazySeq(_) => "lazy-seq",
         }
     }
     
@@ -105,6 +364,49 @@ impl EdnValue {
         !matches!(self, EdnValue::Nil | EdnValue::Bool(false))
     }
     
+    /// This value with every lazy sequence in it realized into a list, for
+    /// output. Borrows when there is nothing to realize.
+    pub fn realize_all(&self) -> EqResult<Cow<'_, EdnValue>> {
+        if self.contains_lazy() {
+            self.realized().map(Cow::Owned)
+        } else {
+            Ok(Cow::Borrowed(self))
+        }
+    }
+
+    fn contains_lazy(&self) -> bool {
+        match self {
+            EdnValue::LazySeq(_) => true,
+            EdnValue::Vector(items) | EdnValue::List(items) => items.iter().any(EdnValue::contains_lazy),
+            EdnValue::Map(m) => m.iter().any(|(k, v)| k.contains_lazy() || v.contains_lazy()),
+            EdnValue::Set(s) => s.iter().any(EdnValue::contains_lazy),
+            EdnValue::Tagged { value, .. } => value.contains_lazy(),
+            EdnValue::WithMetadata { metadata, value } => metadata.contains_lazy() || value.contains_lazy(),
+            _ => false,
+        }
+    }
+
+    fn realized(&self) -> EqResult<EdnValue> {
+        let all = |items: &[EdnValue]| items.iter().map(EdnValue::realized).collect::<EqResult<Vec<_>>>();
+        Ok(match self {
+            EdnValue::LazySeq(seq) => EdnValue::List(all(&seq.realize()?)?),
+            EdnValue::Vector(items) => EdnValue::Vector(all(items)?),
+            EdnValue::List(items) => EdnValue::List(all(items)?),
+            EdnValue::Map(m) => EdnValue::Map(
+                m.iter()
+                    .map(|(k, v)| Ok((k.realized()?, v.realized()?)))
+                    .collect::<EqResult<_>>()?,
+            ),
+            EdnValue::Set(s) => EdnValue::Set(s.iter().map(EdnValue::realized).collect::<EqResult<_>>()?),
+            EdnValue::Tagged { tag, value } => EdnValue::Tagged { tag: tag.clone(), value: Box::new(value.realized()?) },
+            EdnValue::WithMetadata { metadata, value } => EdnValue::WithMetadata {
+                metadata: Box::new(metadata.realized()?),
+                value: Box::new(value.realized()?),
+            },
+            other => other.clone(),
+        })
+    }
+
     /// Get the count of elements in a collection
     pub fn count(&self) -> Option<usize> {
         match self {
@@ -129,6 +431,71 @@ impl EdnValue {
         }
         current
     }
+
+    /// Namespace of a qualified keyword or symbol, e.g. `db` for `:db/id`
+    pub fn namespace(&self) -> Option<&str> {
+        self.split_qualified().and_then(|(ns, _)| ns)
+    }
+
+    /// Local name of a keyword or symbol, e.g. `id` for `:db/id`
+    pub fn name(&self) -> Option<&str> {
+        self.split_qualified().map(|(_, name)| name)
+    }
+
+    fn split_qualified(&self) -> Option<(Option<&str>, &str)> {
+        let full = match self {
+            EdnValue::Keyword(s) | EdnValue::Symbol(s) => s.as_str(),
+            _ => return None,
+        };
+        // A lone `/` is the division symbol, not a separator
+        match full.split_once('/') {
+            Some((ns, name)) if !ns.is_empty() && !name.is_empty() => Some((Some(ns), name)),
+            _ => Some((None, full)),
+        }
+    }
+
+    /// Print as EDN that reads back as an equal value. Fails for values with
+    /// no such form: functions, infinite and NaN floats, and keywords or
+    /// symbols whose names wouldn't read back. Lazy sequences read back as lists.
+    pub fn to_edn_string(&self) -> EqResult<String> {
+        self.check_readable()?;
+        Ok(self.to_string())
+    }
+
+    fn check_readable(&self) -> EqResult<()> {
+        let unreadable = |what: &str| Err(EqError::query_error(format!("{} {} has no EDN representation", what, self)));
+        match self {
+            EdnValue::Float(f) if !f.is_finite() => unreadable("float"),
+            EdnValue::Keyword(name) if !is_readable_name(name) => unreadable("keyword"),
+            EdnValue::Symbol(name) if !is_readable_symbol(name) => unreadable("symbol"),
+            EdnValue::Lambda(_) => unreadable("function"),
+            EdnValue::Tagged { tag, .. } if !is_readable_symbol(tag) => unreadable("tagged value"),
+            EdnValue::Tagged { value, .. } => value.check_readable(),
+            EdnValue::WithMetadata { metadata, value } => {
+                metadata.check_readable()?;
+                value.check_readable()
+            }
+            EdnValue::Vector(items) | EdnValue::List(items) => items.iter().try_for_each(EdnValue::check_readable),
+            EdnValue::Set(items) => items.iter().try_for_each(EdnValue::check_readable),
+            EdnValue::Map(entries) => entries.iter().try_for_each(|(k, v)| {
+                k.check_readable()?;
+                v.check_readable()
+            }),
+            EdnValue::LazySeq(seq) => seq.realize()?.iter().try_for_each(EdnValue::check_readable),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like get_in, but returns `default` if any step is missing or the value is nil
+    pub fn get_in_with_default<'a, I>(&'a self, path: I, default: &'a EdnValue) -> &'a EdnValue
+    where
+        I: IntoIterator<Item = EdnValue>,
+    {
+        match self.get_in(path) {
+            None | Some(EdnValue::Nil) => default,
+            Some(value) => value,
+        }
+    }
 }
 
 impl EdnSequential for EdnValue {
@@ -219,6 +586,14 @@ impl EdnAssociative for EdnValue {
     }
 }
 
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
 // Implement Eq for EdnValue (required for HashMap keys)
 impl Eq for EdnValue {}
 
@@ -233,6 +608,11 @@ impl Hash for EdnValue {
             EdnValue::Symbol(s) => s.hash(state),
             EdnValue::Character(c) => c.hash(state),
             EdnValue::Integer(i) => i.hash(state),
+            EdnValue::BigInt(n) => n.hash(state),
+            EdnValue::Ratio { numerator, denominator } => {
+                numerator.hash(state);
+                denominator.hash(state);
+            }
             EdnValue::Float(f) => {
                 // Handle NaN and convert to bits for consistent hashing
                 if f.is_nan() {
@@ -264,14 +644,170 @@ impl Hash for EdnValue {
             }
             EdnValue::Lambda(lambda) => {
                 lambda.params.hash(state);
+                lambda.rest_param.hash(state);
                 lambda.body.hash(state);
             }
             EdnValue::Instant(s) => s.hash(state),
             EdnValue::Uuid(s) => s.hash(state),
+            // Hashed by its first few elements, which sequences equal by == share
+            EdnValue::LazySeq(seq) => seq.take(HASHED_PREFIX).unwrap_or_default().hash(state),
+        }
+    }
+}
+
+/// Total ordering over all EDN values, following Clojure's comparator where it
+/// has one: nil < booleans < numbers < characters < strings < keywords <
+/// symbols < vectors < lists < maps < sets, then the remaining types.
+///
+/// Numbers compare by value across integer, bigint, ratio and float; values
+/// that are numerically equal but of different types (`1` and `1.0`) order by
+/// type so that only equal values compare equal. Sequences compare element by
+/// element, maps and sets by their sorted contents.
+pub fn total_compare(left: &EdnValue, right: &EdnValue) -> Ordering {
+    use EdnValue::*;
+    match (left, right) {
+        (Bool(a), Bool(b)) => a.cmp(b),
+        (Character(a), Character(b)) => a.cmp(b),
+        (String(a), String(b)) | (Keyword(a), Keyword(b)) | (Symbol(a), Symbol(b))
+        | (Instant(a), Instant(b)) | (Uuid(a), Uuid(b)) => a.cmp(b),
+        (Integer(_) | BigInt(_) | Ratio { .. } | Float(_), Integer(_) | BigInt(_) | Ratio { .. } | Float(_)) => {
+            compare_numbers(left, right).then_with(|| type_rank(left).cmp(&type_rank(right)))
+        }
+        (Vector(a), Vector(b)) | (List(a), List(b)) => compare_sequences(a.iter(), b.iter()),
+        (Map(a), Map(b)) => {
+            let (a, b) = (sorted_entries(a), sorted_entries(b));
+            a.iter().zip(&b)
+                .map(|((ka, va), (kb, vb))| total_compare(ka, kb).then_with(|| total_compare(va, vb)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        }
+        (Set(a), Set(b)) => {
+            let (mut a, mut b): (Vec<_>, Vec<_>) = (a.iter().collect(), b.iter().collect());
+            a.sort();
+            b.sort();
+            compare_sequences(a.into_iter(), b.into_iter())
+        }
+        (Tagged { tag: ta, value: va }, Tagged { tag: tb, value: vb }) => ta.cmp(tb).then_with(|| total_compare(va, vb)),
+        (WithMetadata { metadata: ma, value: va }, WithMetadata { metadata: mb, value: vb }) => {
+            total_compare(va, vb).then_with(|| total_compare(ma, mb))
+        }
+        (Lambda(a), Lambda(b)) => a.params.cmp(&b.params)
+            .then_with(|| a.rest_param.cmp(&b.rest_param))
+            .then_with(|| total_compare(&a.body, &b.body))
+            // Lambdas equal in source still differ by closure and captures, as with ==
+            .then_with(|| a.native.as_ref().map(NativeFn::addr).cmp(&b.native.as_ref().map(NativeFn::addr)))
+            .then_with(|| compare_envs(&a.captured_env, &b.captured_env)),
+        // Sequences that are equal as far as they can be compared fall back to
+        // identity, as == does
+        (LazySeq(a), LazySeq(b)) if Arc::ptr_eq(&a.0, &b.0) => Ordering::Equal,
+        (LazySeq(a), LazySeq(b)) => compare_lazy(a, b, total_compare)
+            .unwrap_or_else(|| Arc::as_ptr(&a.0).cmp(&Arc::as_ptr(&b.0))),
+        _ => type_rank(left).cmp(&type_rank(right)),
+    }
+}
+
+fn sorted_entries(map: &IndexMap<EdnValue, EdnValue>) -> Vec<(&EdnValue, &EdnValue)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|x, y| total_compare(x.0, y.0));
+    entries
+}
+
+fn compare_envs(a: &HashMap<String, EdnValue>, b: &HashMap<String, EdnValue>) -> Ordering {
+    let (mut a, mut b): (Vec<_>, Vec<_>) = (a.iter().collect(), b.iter().collect());
+    a.sort_by(|x, y| x.0.cmp(y.0));
+    b.sort_by(|x, y| x.0.cmp(y.0));
+    a.iter().zip(&b)
+        .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| total_compare(va, vb)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+fn type_rank(value: &EdnValue) -> u8 {
+    match value {
+        EdnValue::Nil => 0,
+        EdnValue::Bool(_) => 1,
+        EdnValue::Integer(_) => 2,
+        EdnValue::BigInt(_) => 3,
+        EdnValue::Ratio { .. } => 4,
+        EdnValue::Float(_) => 5,
+        EdnValue::Character(_) => 6,
+        EdnValue::String(_) => 7,
+        EdnValue::Keyword(_) => 8,
+        EdnValue::Symbol(_) => 9,
+        EdnValue::Vector(_) => 10,
+        EdnValue::List(_) => 11,
+        EdnValue::Map(_) => 12,
+        EdnValue::Set(_) => 13,
+        EdnValue::Instant(_) => 14,
+        EdnValue::Uuid(_) => 15,
+        EdnValue::Tagged { .. } => 16,
+        EdnValue::WithMetadata { .. } => 17,
+        EdnValue::Lambda(_) => 18,
+        EdnValue::LazySeq(_) => 19,
+    }
+}
+
+fn compare_sequences<'a>(
+    mut a: impl Iterator<Item = &'a EdnValue>,
+    mut b: impl Iterator<Item = &'a EdnValue>,
+) -> Ordering {
+    loop {
+        match (a.next(), b.next()) {
+            (Some(x), Some(y)) => match total_compare(x, y) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            },
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (None, None) => return Ordering::Equal,
+        }
+    }
+}
+
+/// Compares two numbers by value. Integers, bigints and ratios compare exactly;
+/// anything involving a float compares as f64, with NaN above everything else.
+fn compare_numbers(left: &EdnValue, right: &EdnValue) -> Ordering {
+    fn exact(value: &EdnValue) -> Option<(BigInt, BigInt)> {
+        match value {
+            EdnValue::Integer(i) => Some((BigInt::from(*i), BigInt::from(1))),
+            EdnValue::BigInt(n) => Some((n.clone(), BigInt::from(1))),
+            EdnValue::Ratio { numerator, denominator } => Some((BigInt::from(*numerator), BigInt::from(*denominator))),
+            _ => None,
+        }
+    }
+    fn float(value: &EdnValue) -> f64 {
+        match value {
+            EdnValue::Float(f) => *f,
+            EdnValue::Integer(i) => *i as f64,
+            EdnValue::BigInt(n) => n.to_f64().unwrap_or(f64::NAN),
+            EdnValue::Ratio { numerator, denominator } => *numerator as f64 / *denominator as f64,
+            _ => f64::NAN,
+        }
+    }
+
+    match (exact(left), exact(right)) {
+        // Denominators are always positive, so cross-multiplying keeps the order
+        (Some((a, b)), Some((c, d))) => (a * d).cmp(&(c * b)),
+        _ => {
+            let (a, b) = (float(left), float(right));
+            // total_cmp alone would order -0.0 before 0.0, which are equal
+            if a == b { Ordering::Equal } else { a.total_cmp(&b) }
         }
     }
 }
 
+impl PartialOrd for EdnValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EdnValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        total_compare(self, other)
+    }
+}
+
 impl fmt::Display for EdnValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -280,9 +816,11 @@ impl fmt::Display for EdnValue {
             EdnValue::String(s) => write!(f, "\"{}\"", escape_string(s)),
             EdnValue::Keyword(k) => write!(f, ":{}", k),
             EdnValue::Symbol(s) => write!(f, "{}", s),
-            EdnValue::Character(c) => write!(f, "\\{}", c),
+            EdnValue::Character(c) => write!(f, "{}", format_character(*c)),
             EdnValue::Integer(i) => write!(f, "{}", i),
-            EdnValue::Float(fl) => write!(f, "{}", fl),
+            EdnValue::BigInt(n) => write!(f, "{}N", n),
+            EdnValue::Ratio { numerator, denominator } => write!(f, "{}/{}", numerator, denominator),
+            EdnValue::Float(fl) => write!(f, "{}", format_float(*fl)),
             EdnValue::Vector(v) => {
                 write!(f, "[")?;
                 for (i, item) in v.iter().enumerate() {
@@ -327,33 +865,58 @@ impl fmt::Display for EdnValue {
             }
             EdnValue::Tagged { tag, value } => write!(f, "#{} {}", tag, value),
             EdnValue::WithMetadata { metadata, value } => write!(f, "^{} {}", metadata, value),
-            EdnValue::Lambda(lambda) => {
-                write!(f, "(fn [")?;
-                for (i, param) in lambda.params.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, "{}", param)?;
-                }
-                write!(f, "] {})", lambda.body)
-            }
+            EdnValue::Lambda(lambda) if lambda.native.is_some() => write!(f, "{}", lambda.body),
+            EdnValue::Lambda(lambda) => write!(f, "(fn [{}] {})", lambda.param_list(), lambda.body),
             EdnValue::Instant(s) => write!(f, "#inst \"{}\"", s),
             EdnValue::Uuid(s) => write!(f, "#uuid \"{}\"", s),
+            EdnValue::LazySeq(seq) => match seq.realize() {
+                Ok(items) => write!(f, "{}", EdnValue::List(items)),
+                Err(_) => write!(f, "(lazy-seq ...)"),
+            },
         }
     }
 }
 
-fn escape_string(s: &str) -> String {
-    s.chars()
-        .map(|c| match c {
-            '"' => "\\\"".to_string(),
-            '\\' => "\\\\".to_string(),
-            '\n' => "\\n".to_string(),
-            '\r' => "\\r".to_string(),
-            '\t' => "\\t".to_string(),
-            c => c.to_string(),
-        })
-        .collect()
+/// Whether the parser reads `name` back whole as a keyword name
+fn is_readable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || "+-*/_!?$%&=<>.".contains(c))
+}
+
+/// Whether the parser reads `name` back as a symbol, rather than as a
+/// number, nil, a boolean or an error
+fn is_readable_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_like_symbol = match (chars.next(), chars.next()) {
+        (Some('+' | '-'), Some(c)) => !c.is_ascii_digit(),
+        (Some(c), _) => c.is_alphabetic() || "+-*/_!?$%&=<>.".contains(c),
+        (None, _) => false,
+    };
+    starts_like_symbol && is_readable_name(name) && !matches!(name, "nil" | "true" | "false")
+}
+
+/// Parse the first value in a string of EDN; an empty string is nil
+impl FromStr for EdnValue {
+    type Err = EqError;
+
+    fn from_str(s: &str) -> EqResult<Self> {
+        Parser::new(s).parse()
+    }
+}
+
+impl TryFrom<&str> for EdnValue {
+    type Error = EqError;
+
+    fn try_from(s: &str) -> EqResult<Self> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for EdnValue {
+    type Error = EqError;
+
+    fn try_from(s: String) -> EqResult<Self> {
+        s.parse()
+    }
 }
 
 #[cfg(test)]
@@ -440,6 +1003,17 @@ mod tests {
         assert_eq!(root.get_in(path), Some(&EdnValue::String("Bob".to_string())));
     }
 
+    #[test]
+    fn test_get_in_with_default() {
+        let root = crate::edn::Parser::new("{:user {:name \"Bob\" :email nil}}").parse().unwrap();
+        let default = EdnValue::String("missing".to_string());
+        let path = |keys: &[&str]| keys.iter().map(|k| EdnValue::Keyword(k.to_string())).collect::<Vec<_>>();
+
+        assert_eq!(root.get_in_with_default(path(&["user", "name"]), &default), &EdnValue::String("Bob".to_string()));
+        assert_eq!(root.get_in_with_default(path(&["user", "email"]), &default), &default);
+        assert_eq!(root.get_in_with_default(path(&["account", "id", "x"]), &default), &default);
+    }
+
     #[test]
     fn test_get_in_mixed_keys() {
         // Create a structure with mixed keyword and integer access:
@@ -532,8 +1106,189 @@ mod tests {
         let _ = map.get(&nan_key2);
         
         // Test normal float values work
-        let float_key = EdnValue::Float(3.14);
+        let float_key = EdnValue::Float(3.25);
         map.insert(float_key.clone(), "pi");
         assert_eq!(map.get(&float_key), Some(&"pi"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_namespace_and_name() {
+        let kw = EdnValue::Keyword("db/id".to_string());
+        assert_eq!(kw.namespace(), Some("db"));
+        assert_eq!(kw.name(), Some("id"));
+
+        let sym = EdnValue::Symbol("clojure.core//".to_string());
+        assert_eq!(sym.namespace(), Some("clojure.core"));
+        assert_eq!(sym.name(), Some("/"));
+
+        let plain = EdnValue::Symbol("/".to_string());
+        assert_eq!(plain.namespace(), None);
+        assert_eq!(plain.name(), Some("/"));
+
+        assert_eq!(EdnValue::Keyword("name".to_string()).namespace(), None);
+        assert_eq!(EdnValue::String("db/id".to_string()).name(), None);
+    }
+
+    #[test]
+    fn test_total_ordering() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+
+        // Type order
+        let mut values = match parse(r#"[#{1} {:a 1} (1) [1] foo :foo "foo" \a 1.5 1 true nil]"#) {
+            EdnValue::Vector(v) => v,
+            _ => unreachable!(),
+        };
+        values.sort();
+        assert_eq!(EdnValue::Vector(values).to_string(), r#"[nil true 1 1.5 \a "foo" :foo foo [1] (1) {:a 1} #{1}]"#);
+
+        // Numbers compare by value across types, with ties broken by type
+        assert!(parse("1") < parse("3/2"));
+        assert!(parse("3/2") < parse("1.6"));
+        assert!(parse("1.6") < parse("2N"));
+        assert!(parse("99999999999999999999") > parse("1e3"));
+        assert!(parse("1") < parse("1.0"));
+        assert_eq!(parse("0.0").cmp(&parse("-0.0")), Ordering::Equal);
+
+        // Sequences are lexicographic
+        assert!(parse("[1 2]") < parse("[1 3]"));
+        assert!(parse("[1 2]") < parse("[1 2 0]"));
+        assert!(parse("[:b]") > parse("[:a :z]"));
+
+        // Equal maps and sets compare equal regardless of order
+        assert_eq!(parse("{:a 1 :b 2}").cmp(&parse("{:b 2 :a 1}")), Ordering::Equal);
+        assert!(parse("{:a 1}") < parse("{:a 2}"));
+        assert_eq!(parse("#{3 1 2}").cmp(&parse("#{1 2 3}")), Ordering::Equal);
+
+        let set: std::collections::BTreeSet<EdnValue> = [parse(":b"), parse("2"), parse(":a"), parse("nil")].into_iter().collect();
+        assert_eq!(set.into_iter().map(|v| v.to_string()).collect::<Vec<_>>(), ["nil", "2", ":a", ":b"]);
+
+        // Lambdas order consistently with ==, which also looks at native code and captures
+        let lambda = |native: Option<NativeFn>, captured: &[(&str, i64)]| EdnValue::Lambda(EdnLambda {
+            params: vec!["x".to_string()],
+            rest_param: None,
+            body: Box::new(parse("x")),
+            native,
+            captured_env: captured.iter().map(|(k, v)| (k.to_string(), EdnValue::Integer(*v))).collect(),
+        });
+        let native = NativeFn::new(|_| Ok(EdnValue::Nil));
+        let pairs = [
+            (lambda(None, &[("y", 1)]), lambda(None, &[("y", 2)])),
+            (lambda(None, &[]), lambda(None, &[("y", 1)])),
+            (lambda(None, &[]), lambda(Some(native.clone()), &[])),
+            (lambda(Some(native.clone()), &[]), lambda(Some(NativeFn::new(|_| Ok(EdnValue::Nil))), &[])),
+        ];
+        for (a, b) in &pairs {
+            assert_ne!(a, b);
+            assert_ne!(a.cmp(b), Ordering::Equal);
+            assert_eq!(a.cmp(b), b.cmp(a).reverse());
+        }
+        let same = lambda(Some(native.clone()), &[("y", 1)]);
+        assert_eq!(same, lambda(Some(native), &[("y", 1)]));
+        assert_eq!(same.cmp(&same.clone()), Ordering::Equal);
+    }
+    #[test]
+    fn test_from_str() {
+        let value: EdnValue = "{:a 1}".parse().unwrap();
+        assert_eq!(value, EdnValue::Map(IndexMap::from([(EdnValue::Keyword("a".to_string()), EdnValue::Integer(1))])));
+        assert_eq!(EdnValue::try_from("[1 2]").unwrap(), EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(2)]));
+        assert_eq!(EdnValue::try_from(":k".to_string()).unwrap(), EdnValue::Keyword("k".to_string()));
+        assert_eq!("".parse::<EdnValue>().unwrap(), EdnValue::Nil);
+        assert!(matches!("{:a".parse::<EdnValue>(), Err(EqError::ParseError { .. })));
+    }
+
+    #[test]
+    fn test_edn_string_round_trip() {
+        let sources = [
+            "nil", "true", "false",
+            r#""plain""#, r#""quote \" backslash \\ newline \n tab \t return \r""#, "\"bell \u{7} formfeed \u{c}\"",
+            ":k", ":db/id", ":a-b?", "sym", "my.ns/sym", "+", "-", "/", "nil?", "-x",
+            r"\a", r"\u", r"\newline", r"\tab", r"\return", r"\space", r"\formfeed", r"\backspace",
+            r"\(", r"\;", r"\é", r"\😀", r"\u0001", r"\ ",
+            "0", "-42", "9223372036854775807", "12345678901234567890N", "7N", "22/7", "-3/2",
+            "2.5", "-0.5", "3.0", "1e20", "1.5e-10", "-0.0",
+            "[]", "[1 [2 3] \"x\"]", "()", "(1 (2) :x)", "{}", "{:a 1 \"b\" [2] [3] {4 5}}", "#{}", "#{1 :a \"s\" #{2}}",
+            "#point [1 2]", "#my.ns/tag {:a 1}", "^{:doc \"d\"} [1]", "^:private sym",
+            "#inst \"2024-01-15T10:30:00Z\"", "#uuid \"f81d4fae-7dec-11d0-a765-00a0c91e6bf6\"",
+        ];
+        for source in sources {
+            let value: EdnValue = source.parse().unwrap();
+            let printed = value.to_edn_string().unwrap();
+            assert_eq!(printed.parse::<EdnValue>().unwrap(), value, "{} printed as {}", source, printed);
+            assert_eq!(printed, value.to_string());
+        }
+
+        // Control characters and whole floats print in a form that reads back
+        assert_eq!(EdnValue::Character('\u{1}').to_edn_string().unwrap(), r"\u0001");
+        assert_eq!(EdnValue::Float(3.0).to_edn_string().unwrap(), "3.0");
+
+        // Lazy sequences are realized and read back as lists
+        let lazy = EdnValue::LazySeq(LazySeq::cons(EdnValue::Integer(1), EdnValue::Nil));
+        assert_eq!(lazy.to_edn_string().unwrap(), "(1)");
+    }
+
+    #[test]
+    fn test_lazy_seq_equality() {
+        let lazy = |items: Vec<i64>| {
+            EdnValue::LazySeq(LazySeq::new(move || Ok(EdnValue::List(items.iter().copied().map(EdnValue::Integer).collect()))))
+        };
+        fn ones() -> LazySeq {
+            LazySeq::new(|| Ok(EdnValue::LazySeq(LazySeq::cons(EdnValue::Integer(1), EdnValue::LazySeq(ones())))))
+        }
+        // Counts the cells it forces
+        fn count_from(n: i64, forced: Arc<std::sync::atomic::AtomicUsize>) -> LazySeq {
+            LazySeq::new(move || {
+                forced.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(EdnValue::LazySeq(LazySeq::cons(EdnValue::Integer(n), EdnValue::LazySeq(count_from(n + 1, forced.clone())))))
+            })
+        }
+
+        // Finite sequences compare, order and hash by their elements
+        assert_eq!(lazy(vec![1, 2]), lazy(vec![1, 2]));
+        assert_ne!(lazy(vec![1, 2]), lazy(vec![1, 3]));
+        assert!(lazy(vec![1, 2]) < lazy(vec![1, 3]));
+        assert_eq!(lazy(vec![1, 2]).cmp(&lazy(vec![1, 2])), Ordering::Equal);
+        let set: HashSet<EdnValue> = [lazy(vec![1]), lazy(vec![1]), lazy(vec![2])].into_iter().collect();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&lazy(vec![2])));
+
+        // Still a different type from the list it realizes to; = in queries bridges them
+        assert_ne!(lazy(vec![1]), EdnValue::List(vec![EdnValue::Integer(1)]));
+
+        // One that can't be realized is only equal to itself
+        let infinite = EdnValue::LazySeq(ones());
+        assert_eq!(infinite, infinite.clone());
+        assert_ne!(infinite, EdnValue::LazySeq(ones()));
+        assert!(lazy(vec![1]) < infinite);
+        assert_ne!(infinite.cmp(&EdnValue::LazySeq(ones())), Ordering::Equal);
+
+        // Infinite sequences are compared only up to their first difference,
+        // and hashed by a short prefix
+        let forced = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let naturals = EdnValue::LazySeq(count_from(0, forced.clone()));
+        assert_ne!(naturals, infinite);
+        assert!(naturals < infinite);
+        let set: HashSet<EdnValue> = [naturals, infinite].into_iter().collect();
+        assert_eq!(set.len(), 2);
+        assert!(forced.load(std::sync::atomic::Ordering::SeqCst) < 100);
+    }
+
+    #[test]
+    fn test_edn_string_unreadable_values() {
+        let lambda = EdnValue::Lambda(EdnLambda::native(EdnValue::Symbol("inc".to_string()), |_| Ok(EdnValue::Nil)));
+        let unreadable = [
+            lambda.clone(),
+            EdnValue::Vector(vec![EdnValue::Integer(1), lambda]),
+            EdnValue::Float(f64::NAN),
+            EdnValue::Float(f64::INFINITY),
+            EdnValue::Keyword("has space".to_string()),
+            EdnValue::Keyword(String::new()),
+            EdnValue::Symbol("nil".to_string()),
+            EdnValue::Symbol("1x".to_string()),
+            EdnValue::Symbol("-1".to_string()),
+            EdnValue::Tagged { tag: "{".to_string(), value: Box::new(EdnValue::Nil) },
+        ];
+        for value in unreadable {
+            assert!(value.to_edn_string().is_err(), "{:?} should not print as EDN", value);
+        }
+    }
+}
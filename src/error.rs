@@ -1,8 +1,11 @@
+use crate::edn::EdnValue;
+use indexmap::IndexMap;
 use thiserror::Error;
 
 pub type EqResult<T> = Result<T, EqError>;
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum EqError {
     #[error("Parse error{} at line {line}, column {column}: {message}", if let Some(file) = filename { format!(" in file '{}'", file) } else { String::new() })]
     ParseError {
@@ -10,6 +13,8 @@ pub enum EqError {
         line: usize,
         column: usize,
         message: String,
+        /// Text of the offending line, for pointing at the error
+        source_context: Option<String>,
     },
     
     #[error("Query error: {message}")]
@@ -26,6 +31,16 @@ pub enum EqError {
     
     #[error("WalkDir error: {0}")]
     WalkDirError(#[from] walkdir::Error),
+    
+    #[error("Readline error: {0}")]
+    ReadlineError(#[from] rustyline::error::ReadlineError),
+    
+    /// Not a real error: (recur ...) unwinding to the nearest loop or fn
+    /// with the values to rebind. Internal to the evaluator, whose public
+    /// functions report a misplaced recur as a QueryError instead.
+    #[doc(hidden)]
+    #[error("Query error: recur can only be used in tail position of a loop or fn")]
+    RecurSignal(Vec<EdnValue>),
 }
 
 impl EqError {
@@ -35,6 +50,74 @@ impl EqError {
             line,
             column,
             message: message.into(),
+            source_context: None,
+        }
+    }
+
+    /// Keyword name for this kind of error, as matched by catch clauses in queries
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ParseError { .. } => "parse-error",
+            Self::QueryError { .. } | Self::RecurSignal(_) => "query-error",
+            Self::TypeError { .. } => "type-error",
+            Self::IoError(_) | Self::GlobError(_) | Self::WalkDirError(_) | Self::ReadlineError(_) => "io-error",
+        }
+    }
+
+    /// The error as EDN data for --error-format, e.g.
+    /// {:type :parse-error :message "Unterminated string" :line 5 :column 12 :file "data.edn"}
+    pub fn to_edn_map(&self) -> IndexMap<EdnValue, EdnValue> {
+        let key = |name: &str| EdnValue::Keyword(name.to_string());
+        let mut map = IndexMap::new();
+        map.insert(key("type"), key(self.kind()));
+        let message = match self {
+            Self::ParseError { message, .. } | Self::QueryError { message } => message.clone(),
+            Self::TypeError { expected, actual } => format!("expected {}, got {}", expected, actual),
+            Self::IoError(e) => e.to_string(),
+            Self::GlobError(e) => e.to_string(),
+            Self::WalkDirError(e) => e.to_string(),
+            Self::ReadlineError(e) => e.to_string(),
+            Self::RecurSignal(_) => "recur can only be used in tail position of a loop or fn".to_string(),
+        };
+        map.insert(key("message"), EdnValue::String(message));
+        match self {
+            Self::ParseError { filename, line, column, .. } => {
+                map.insert(key("line"), EdnValue::Integer(*line as i64));
+                map.insert(key("column"), EdnValue::Integer(*column as i64));
+                if let Some(file) = filename {
+                    map.insert(key("file"), EdnValue::String(file.clone()));
+                }
+            }
+            Self::TypeError { expected, actual } => {
+                map.insert(key("expected"), EdnValue::String(expected.clone()));
+                map.insert(key("actual"), EdnValue::String(actual.clone()));
+            }
+            _ => {}
+        }
+        map
+    }
+
+    /// Attach the text of the line a parse error occurred on
+    pub fn with_source_context(mut self, text: impl Into<String>) -> Self {
+        if let Self::ParseError { source_context, .. } = &mut self {
+            *source_context = Some(text.into());
+        }
+        self
+    }
+
+    /// The offending source line with a caret under the error column
+    pub fn source_snippet(&self) -> Option<String> {
+        match self {
+            Self::ParseError { column, source_context: Some(text), .. } => {
+                // Copy tabs so the caret lines up however the terminal expands them
+                let padding: String = text.chars()
+                    .chain(std::iter::repeat(' '))
+                    .take(column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                Some(format!("{}\n{}^", text, padding))
+            }
+            _ => None,
         }
     }
     
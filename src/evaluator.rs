@@ -1,9 +1,11 @@
-use crate::edn::{EdnValue, EdnAssociative};
+use crate::edn::{EdnValue, EdnAssociative, EdnIterable, value::{EdnLambda, LazySeq}};
 use crate::error::{EqError, EqResult};
 use crate::query::ast::{Expr, FunctionRegistry, Environment, FunctionType};
 use crate::builtins::create_builtin_registry;
+use crate::primitives::format_duration;
 
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 /// Global function registry - initialized once
 static FUNCTION_REGISTRY: OnceLock<FunctionRegistry> = OnceLock::new();
@@ -16,11 +18,27 @@ fn get_function_registry() -> &'static FunctionRegistry {
         // Add special forms here to avoid circular dependencies
         registry.register_special_form("if".to_string(), special_form_if);
         registry.register_special_form("do".to_string(), special_form_do);
+        registry.register_special_form("when-let".to_string(), special_form_when_let);
+        registry.register_special_form("if-let".to_string(), special_form_if_let);
+        registry.register_special_form("let".to_string(), special_form_let);
+        registry.register_special_form("for".to_string(), special_form_for);
+        registry.register_special_form("try".to_string(), special_form_try);
+        registry.register_special_form("doto".to_string(), special_form_doto);
+        registry.register_special_form("loop".to_string(), special_form_loop);
+        registry.register_special_form("recur".to_string(), special_form_recur);
+        registry.register_special_form("lazy-seq".to_string(), special_form_lazy_seq);
+        registry.register_special_form("time".to_string(), special_form_time);
+        registry.register_special_form("bench".to_string(), special_form_bench);
         
         registry
     })
 }
 
+/// Names of every function, macro and special form available to queries
+pub fn function_names() -> Vec<&'static str> {
+    get_function_registry().names()
+}
+
 /// Special form implementation for 'if'
 fn special_form_if(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
     match args.len() {
@@ -28,7 +46,7 @@ fn special_form_if(args: &[Expr], context: &EdnValue, env: &Environment) -> EqRe
             // (if test then)
             let test_result = evaluate_with_env(&args[0], context, env)?;
             if test_result.is_truthy() {
-                evaluate_with_env(&args[1], context, env)
+                evaluate_tail(&args[1], context, env)
             } else {
                 Ok(EdnValue::Nil)
             }
@@ -37,9 +55,9 @@ fn special_form_if(args: &[Expr], context: &EdnValue, env: &Environment) -> EqRe
             // (if test then else)
             let test_result = evaluate_with_env(&args[0], context, env)?;
             if test_result.is_truthy() {
-                evaluate_with_env(&args[1], context, env)
+                evaluate_tail(&args[1], context, env)
             } else {
-                evaluate_with_env(&args[2], context, env)
+                evaluate_tail(&args[2], context, env)
             }
         }
         _ => Err(EqError::query_error("if takes 2 or 3 arguments".to_string())),
@@ -48,18 +66,378 @@ fn special_form_if(args: &[Expr], context: &EdnValue, env: &Environment) -> EqRe
 
 /// Special form implementation for 'do'
 fn special_form_do(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
-    if args.is_empty() {
+    let Some((last, init)) = args.split_last() else {
         return Ok(EdnValue::Nil);
+    };
+
+    // Evaluate all expressions in sequence, returning the last result, which
+    // is in tail position
+    for expr in init {
+        evaluate_with_env(expr, context, env)?;
+    }
+    evaluate_tail(last, context, env)
+}
+
+/// Special form implementation for 'when-let': (when-let [sym expr] body...)
+fn special_form_when_let(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.is_empty() {
+        return Err(EqError::query_error("when-let requires a binding vector"));
+    }
+
+    match eval_let_binding("when-let", &args[0], context, env)? {
+        Some(body_env) => special_form_do(&args[1..], context, &body_env),
+        None => Ok(EdnValue::Nil),
+    }
+}
+
+/// Special form implementation for 'if-let': (if-let [sym expr] then else?)
+fn special_form_if_let(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(EqError::query_error("if-let takes a binding vector, a then branch and an optional else branch"));
+    }
+
+    match eval_let_binding("if-let", &args[0], context, env)? {
+        Some(then_env) => evaluate_tail(&args[1], context, &then_env),
+        None => match args.get(2) {
+            Some(else_expr) => evaluate_tail(else_expr, context, env),
+            None => Ok(EdnValue::Nil),
+        },
+    }
+}
+
+/// Special form implementation for 'let': (let [sym expr ...] body...)
+/// Bindings are evaluated in order, so later ones can refer to earlier ones.
+fn special_form_let(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.is_empty() {
+        return Err(EqError::query_error("let requires a binding vector"));
+    }
+
+    let elements = binding_vector("let", &args[0])?;
+    if elements.len() % 2 != 0 {
+        return Err(EqError::query_error("let binding vector must contain pairs"));
+    }
+    let mut body_env = env.clone();
+    for pair in elements.chunks(2) {
+        // Destructuring patterns expand to a binding per name
+        let mut bindings = Vec::new();
+        crate::analyzer::destructure(&pair[0], pair[1].clone(), &mut bindings)?;
+        for (name, form) in bindings {
+            let value = evaluate_with_env(&binding_expr(&form)?, context, &body_env)?;
+            body_env.bind(name, value);
+        }
+    }
+    special_form_do(&args[1..], context, &body_env)
+}
+
+/// Special form implementation for 'loop': (loop [x init ...] body...)
+/// Binds like let. When the body evaluates (recur v ...), the bindings are
+/// redone with the new values and the body runs again, without growing the stack.
+fn special_form_loop(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.is_empty() {
+        return Err(EqError::query_error("loop requires a binding vector"));
+    }
+
+    let elements = binding_vector("loop", &args[0])?;
+    if elements.len() % 2 != 0 {
+        return Err(EqError::query_error("loop binding vector must contain pairs"));
+    }
+    let patterns: Vec<&EdnValue> = elements.iter().step_by(2).collect();
+    let mut body_env = env.clone();
+    for pair in elements.chunks(2) {
+        let value = evaluate_with_env(&binding_expr(&pair[1])?, context, &body_env)?;
+        bind_pattern(pair[0].clone(), value, context, &mut body_env)?;
+    }
+
+    loop {
+        match special_form_do(&args[1..], context, &body_env) {
+            Err(EqError::RecurSignal(values)) => {
+                if values.len() != patterns.len() {
+                    return Err(EqError::query_error(format!(
+                        "recur expects {} arguments for this loop, got {}",
+                        patterns.len(),
+                        values.len()
+                    )));
+                }
+                body_env = env.clone();
+                for (pattern, value) in patterns.iter().zip(values) {
+                    bind_pattern((*pattern).clone(), value, context, &mut body_env)?;
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Special form implementation for 'recur': (recur v ...)
+/// Evaluates its arguments and unwinds to the nearest loop or fn, which
+/// starts over with them. It must be in tail position: only evaluate_tail
+/// lets the signal through, and evaluate_with_env turns it into an error.
+fn special_form_recur(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    let values = args.iter()
+        .map(|arg| evaluate_with_env(arg, context, env))
+        .collect::<EqResult<Vec<_>>>()?;
+    Err(EqError::RecurSignal(values))
+}
+
+/// Special form implementation for 'lazy-seq': (lazy-seq body...)
+/// Returns a sequence without evaluating the body. The body runs, in the
+/// scope where lazy-seq appeared, the first time an element is needed and
+/// must produce nil, a list or vector, or another lazy sequence.
+fn special_form_lazy_seq(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    let (body, context, env) = (args.to_vec(), context.clone(), env.clone());
+    // The body runs later, outside any loop or fn around the lazy-seq
+    Ok(EdnValue::LazySeq(LazySeq::new(move || reject_recur(special_form_do(&body, &context, &env)))))
+}
+
+/// Special form implementation for 'time': (time expr)
+/// Prints how long expr took to evaluate to stderr and returns its value
+fn special_form_time(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.len() != 1 {
+        return Err(EqError::query_error("time expects exactly 1 expression"));
+    }
+    let started = Instant::now();
+    let result = evaluate_with_env(&args[0], context, env)?;
+    eprintln!("Elapsed: {}", format_duration(started.elapsed()));
+    Ok(result)
+}
+
+/// Special form implementation for 'bench': (bench n expr)
+/// Evaluates expr n times, prints the fastest, slowest and average time to
+/// stderr and returns the last value
+fn special_form_bench(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("bench expects a run count and an expression"));
     }
-    
-    // Evaluate all expressions in sequence, returning the last result
+    let runs = match evaluate_with_env(&args[0], context, env)? {
+        EdnValue::Integer(n) if n > 0 => n as u32,
+        EdnValue::Integer(_) => return Err(EqError::query_error("bench needs a positive run count")),
+        other => return Err(EqError::type_error("integer", other.type_name())),
+    };
+
     let mut result = EdnValue::Nil;
-    for expr in args {
-        result = evaluate_with_env(expr, context, env)?;
+    let mut timings = Vec::new();
+    for _ in 0..runs {
+        let started = Instant::now();
+        result = evaluate_with_env(&args[1], context, env)?;
+        timings.push(started.elapsed());
     }
+    let min = timings.iter().min().copied().unwrap_or_default();
+    let max = timings.iter().max().copied().unwrap_or_default();
+    let total: Duration = timings.iter().sum();
+    eprintln!(
+        "Bench: {} runs, min {}, max {}, avg {}",
+        runs,
+        format_duration(min),
+        format_duration(max),
+        format_duration(total / runs)
+    );
     Ok(result)
 }
 
+/// Bind an already evaluated value to a let-style binding pattern
+fn bind_pattern(pattern: EdnValue, value: EdnValue, context: &EdnValue, env: &mut Environment) -> EqResult<()> {
+    if let EdnValue::Symbol(name) = &pattern {
+        if name != "&" {
+            env.bind(name.clone(), value);
+            return Ok(());
+        }
+    }
+    // Destructuring works on forms, so go through a name holding the value
+    let hidden = "__loop_value".to_string();
+    env.bind(hidden.clone(), value);
+    let mut bindings = Vec::new();
+    crate::analyzer::destructure(&pattern, EdnValue::Symbol(hidden), &mut bindings)?;
+    for (name, form) in bindings {
+        let value = evaluate_with_env(&binding_expr(&form)?, context, env)?;
+        env.bind(name, value);
+    }
+    Ok(())
+}
+
+/// Special form implementation for 'for': (for [x coll y coll2 :when test] body)
+/// Multiple bindings nest, the rightmost varying fastest. Results are collected into a vector.
+fn special_form_for(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    if args.len() != 2 {
+        return Err(EqError::query_error("for takes a binding vector and a body"));
+    }
+
+    let elements = binding_vector("for", &args[0])?;
+    if elements.len() % 2 != 0 {
+        return Err(EqError::query_error("for binding vector must contain pairs"));
+    }
+    let mut clauses = Vec::new();
+    for pair in elements.chunks(2) {
+        let expr = binding_expr(&pair[1])?;
+        clauses.push(match &pair[0] {
+            EdnValue::Symbol(name) => ForClause::Bind(name.clone(), expr),
+            EdnValue::Keyword(k) if k == "when" && !clauses.is_empty() => ForClause::When(expr),
+            other => return Err(EqError::query_error(format!("Invalid for binding: {}", other))),
+        });
+    }
+    if clauses.is_empty() {
+        return Err(EqError::query_error("for requires at least one binding"));
+    }
+
+    let mut results = Vec::new();
+    for_each_binding(&clauses, &args[1], context, env, &mut results)?;
+    Ok(EdnValue::Vector(results))
+}
+
+enum ForClause {
+    Bind(String, Expr),
+    When(Expr),
+}
+
+fn for_each_binding(
+    clauses: &[ForClause],
+    body: &Expr,
+    context: &EdnValue,
+    env: &Environment,
+    results: &mut Vec<EdnValue>,
+) -> EqResult<()> {
+    match clauses.split_first() {
+        None => results.push(evaluate_for_body(body, context, env)?),
+        Some((ForClause::When(test), rest)) => {
+            if evaluate_with_env(test, context, env)?.is_truthy() {
+                for_each_binding(rest, body, context, env, results)?;
+            }
+        }
+        Some((ForClause::Bind(name, expr), rest)) => {
+            let collection = match evaluate_with_env(expr, context, env)? {
+                EdnValue::LazySeq(seq) => EdnValue::List(seq.realize()?),
+                collection => collection,
+            };
+            for item in collection.iter_values() {
+                let mut item_env = env.clone();
+                item_env.bind(name.clone(), item.clone());
+                for_each_binding(rest, body, context, &item_env, results)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Vector literals are not evaluated in queries, but a vector body such as
+/// [(:name x) y] is the usual way to build tuples, so evaluate its elements
+fn evaluate_for_body(body: &Expr, context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    match body {
+        Expr::Literal(EdnValue::Vector(elements)) => elements.iter()
+            .map(|element| evaluate_with_env(&binding_expr(element)?, context, env))
+            .collect::<EqResult<Vec<_>>>()
+            .map(EdnValue::Vector),
+        _ => evaluate_with_env(body, context, env),
+    }
+}
+
+/// Special form implementation for 'try': (try body... (catch :kind? e handler...)...)
+/// The first catch clause whose kind matches the error runs with e bound to the
+/// error message. A clause without a kind catches everything.
+fn special_form_try(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    let body_len = args.iter().position(is_catch_clause).unwrap_or(args.len());
+    let (body, catches) = args.split_at(body_len);
+    let clauses = catches.iter()
+        .map(|clause| match clause {
+            Expr::Function { name, args } if name == "catch" => parse_catch_clause(args),
+            _ => Err(EqError::query_error("catch clauses must come last in try")),
+        })
+        .collect::<EqResult<Vec<_>>>()?;
+
+    let error = match special_form_do(body, context, env) {
+        Ok(value) => return Ok(value),
+        // recur passes through to its loop or fn
+        Err(recur @ EqError::RecurSignal(_)) => return Err(recur),
+        Err(error) => error,
+    };
+    match clauses.iter().find(|clause| clause.kind.is_none_or(|kind| kind == error.kind())) {
+        Some(clause) => {
+            let mut handler_env = env.clone();
+            handler_env.bind(clause.name.to_string(), EdnValue::String(error.to_string()));
+            special_form_do(clause.handler, context, &handler_env)
+        }
+        None => Err(error),
+    }
+}
+
+struct CatchClause<'a> {
+    kind: Option<&'a str>,
+    name: &'a str,
+    handler: &'a [Expr],
+}
+
+const ERROR_KINDS: [&str; 4] = ["parse-error", "query-error", "type-error", "io-error"];
+
+fn is_catch_clause(expr: &Expr) -> bool {
+    matches!(expr, Expr::Function { name, .. } if name == "catch")
+}
+
+fn parse_catch_clause(args: &[Expr]) -> EqResult<CatchClause<'_>> {
+    let (kind, rest) = match args.split_first() {
+        Some((Expr::Literal(EdnValue::Keyword(kind)), rest)) => {
+            if !ERROR_KINDS.contains(&kind.as_str()) {
+                return Err(EqError::query_error(format!(
+                    "Unknown error type :{} in catch, expected one of :{}",
+                    kind,
+                    ERROR_KINDS.join(", :")
+                )));
+            }
+            (Some(kind.as_str()), rest)
+        }
+        _ => (None, args),
+    };
+    match rest.split_first() {
+        Some((Expr::Symbol(name), handler)) => Ok(CatchClause { kind, name, handler }),
+        _ => Err(EqError::query_error("catch requires a symbol to bind the error message to")),
+    }
+}
+
+/// Evaluate a single [sym expr] binding, returning an environment with sym
+/// bound if the value is truthy
+fn eval_let_binding(form: &str, binding: &Expr, context: &EdnValue, env: &Environment) -> EqResult<Option<Environment>> {
+    let (name, expr) = match binding_vector(form, binding)? {
+        [EdnValue::Symbol(name), expr] => (name, expr),
+        [_, _] => return Err(EqError::query_error(format!("{} binding name must be a symbol", form))),
+        _ => return Err(EqError::query_error(format!("{} binding vector must have exactly 2 elements", form))),
+    };
+
+    let value = evaluate_with_env(&binding_expr(expr)?, context, env)?;
+    if !value.is_truthy() {
+        return Ok(None);
+    }
+    let mut bound_env = env.clone();
+    bound_env.bind(name.clone(), value);
+    Ok(Some(bound_env))
+}
+
+/// The elements of a binding vector. Vector literals are not analyzed, so
+/// their elements are still raw EDN.
+fn binding_vector<'a>(form: &str, binding: &'a Expr) -> EqResult<&'a [EdnValue]> {
+    match binding {
+        Expr::Literal(EdnValue::Vector(elements)) => Ok(elements),
+        _ => Err(EqError::query_error(format!("{} requires a binding vector", form))),
+    }
+}
+
+/// Analyze an expression taken from a binding vector
+fn binding_expr(value: &EdnValue) -> EqResult<Expr> {
+    crate::analyzer::analyze(edn_to_expr(value)?)
+}
+
+/// Special form implementation for 'doto': (doto x forms...)
+/// Evaluates x once and calls each form with it as the first argument, as ->
+/// does, for side effects only. Returns x.
+fn special_form_doto(args: &[Expr], context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    let Some((target, forms)) = args.split_first() else {
+        return Err(EqError::query_error("doto requires a value"));
+    };
+
+    let value = evaluate_with_env(target, context, env)?;
+    for form in forms {
+        let call = crate::builtins::thread_first_expr(Expr::Literal(value.clone()), form)?;
+        evaluate_with_env(&call, context, env)?;
+    }
+    Ok(value)
+}
+
 /// Direct AST evaluator that treats expressions as functions
 /// Each expression takes a context (current data) and returns a value
 pub fn evaluate(expr: &Expr, context: &EdnValue) -> EqResult<EdnValue> {
@@ -67,13 +445,48 @@ pub fn evaluate(expr: &Expr, context: &EdnValue) -> EqResult<EdnValue> {
     evaluate_with_env(expr, context, &env)
 }
 
+/// Evaluate an expression with `.` bound to the context on top of `globals`,
+/// e.g. the bindings from --arg
+pub fn evaluate_with_globals(expr: &Expr, context: &EdnValue, globals: &Environment) -> EqResult<EdnValue> {
+    let mut env = globals.clone();
+    env.bind(".".to_string(), context.clone());
+    evaluate_with_env(expr, context, &env)
+}
+
 /// Evaluate an expression with a given environment
 pub fn evaluate_with_env(expr: &Expr, context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
+    reject_recur(evaluate_tail(expr, context, env))
+}
+
+/// A recur that unwinds to a form not in tail position, or to the top
+/// level, has no loop or fn to go back to
+fn reject_recur(result: EqResult<EdnValue>) -> EqResult<EdnValue> {
+    match result {
+        Err(EqError::RecurSignal(_)) => Err(EqError::query_error("recur can only be used in tail position of a loop or fn")),
+        result => result,
+    }
+}
+
+/// Evaluate an expression in tail position, where the result is returned
+/// as is, so a (recur ...) may unwind through it to its loop or fn
+fn evaluate_tail(expr: &Expr, context: &EdnValue, env: &Environment) -> EqResult<EdnValue> {
     match expr {
         Expr::Symbol(name) => {
-            env.lookup(name)
-                .cloned()
-                .ok_or_else(|| EqError::query_error(format!("Undefined symbol: {}", name)))
+            if let Some(value) = env.lookup(name) {
+                return Ok(value.clone());
+            }
+            // A symbol naming a regular builtin evaluates to that function,
+            // so builtins can be passed to higher-order functions
+            match get_function_registry().get(name) {
+                Some(FunctionType::Regular(func)) => {
+                    let func = func.clone();
+                    Ok(EdnValue::Lambda(EdnLambda::native(
+                        EdnValue::Symbol(name.clone()),
+                        move |args| func(args),
+                    )))
+                }
+                _ => Err(EqError::query_error(format!("Undefined symbol: {}", name))),
+            }
         }
         
         
@@ -100,33 +513,40 @@ pub fn evaluate_with_env(expr: &Expr, context: &EdnValue, env: &Environment) ->
         // Function calls (regular functions and special forms)
         Expr::Function { name, args } => {
             let registry = get_function_registry();
-            if let Some(func_type) = registry.get(name) {
-                match func_type {
-                    FunctionType::Regular(func) => {
-                        // Evaluate all arguments for regular functions
-                        let mut eval_args = Vec::new();
-                        for arg in args {
-                            eval_args.push(evaluate_with_env(arg, context, env)?);
-                        }
-                        
-                        // Call the regular function
-                        func(&eval_args)
-                    }
-                    FunctionType::SpecialForm(special_func) => {
-                        // Pass unevaluated arguments to special forms
-                        special_func(args, context, env)
+            match (registry.get(name), env.lookup(name)) {
+                // A locally bound fn, e.g. from let or --argjson. It shadows a
+                // builtin function of the same name, but not a special form or macro.
+                (None | Some(FunctionType::Regular(_)), Some(func)) => {
+                    let func = func.clone();
+                    let mut eval_args = Vec::new();
+                    for arg in args {
+                        eval_args.push(evaluate_with_env(arg, context, env)?);
                     }
-                    FunctionType::Macro(macro_func) => {
-                        // Macros return new expressions that need to be analyzed and evaluated
-                        let expanded_expr = macro_func(args)?;
-                        // Re-analyze the expanded expression (may contain more macros)
-                        let analyzed_expr = crate::analyzer::analyze(expanded_expr)?;
-                        // Then evaluate the fully analyzed expression
-                        evaluate_with_env(&analyzed_expr, context, env)
+                    call_lambda(&func, &eval_args, context, env)
+                }
+                (Some(FunctionType::Regular(func)), None) => {
+                    // Evaluate all arguments for regular functions
+                    let mut eval_args = Vec::new();
+                    for arg in args {
+                        eval_args.push(evaluate_with_env(arg, context, env)?);
                     }
+                    
+                    // Call the regular function
+                    func(&eval_args)
                 }
-            } else {
-                Err(EqError::query_error(format!("Unknown function: {}", name)))
+                (Some(FunctionType::SpecialForm(special_func)), _) => {
+                    // Pass unevaluated arguments to special forms
+                    special_func(args, context, env)
+                }
+                (Some(FunctionType::Macro(macro_func)), _) => {
+                    // Macros return new expressions that need to be analyzed and evaluated
+                    let expanded_expr = macro_func(args)?;
+                    // Re-analyze the expanded expression (may contain more macros)
+                    let analyzed_expr = crate::analyzer::analyze(expanded_expr)?;
+                    // Then evaluate the fully analyzed expression, which takes the macro's place
+                    evaluate_tail(&analyzed_expr, context, env)
+                }
+                (None, None) => Err(EqError::query_error(format!("Unknown function: {}", name))),
             }
         }
 
@@ -156,6 +576,11 @@ pub fn evaluate_with_env(expr: &Expr, context: &EdnValue, env: &Environment) ->
         }
         
         
+        // A fn literal closes over the bindings in scope where it is evaluated
+        Expr::Literal(EdnValue::Lambda(lambda)) if lambda.native.is_none() => {
+            Ok(EdnValue::Lambda(capture_env(lambda, env)))
+        }
+
         // Literals
         Expr::Literal(value) => Ok(value.clone()),
         
@@ -166,34 +591,90 @@ pub fn evaluate_with_env(expr: &Expr, context: &EdnValue, env: &Environment) ->
     }
 }
 
+/// Snapshot the bindings of `env` into a lambda. Bindings it already captured
+/// win, since they come from an enclosing scope. `.` is left out: inside the
+/// body the context is the first argument.
+fn capture_env(lambda: &EdnLambda, env: &Environment) -> EdnLambda {
+    let mut captured: std::collections::HashMap<String, EdnValue> = env
+        .bindings()
+        .filter(|(name, _)| name.as_str() != ".")
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    captured.extend(lambda.captured_env.iter().map(|(name, value)| (name.clone(), value.clone())));
+    EdnLambda {
+        captured_env: captured,
+        ..lambda.clone()
+    }
+}
+
+/// Apply a function value to already evaluated arguments
+/// Used by builtins that take functions as arguments
+pub fn apply_function(func: &EdnValue, args: &[EdnValue]) -> EqResult<EdnValue> {
+    call_lambda(func, args, &EdnValue::Nil, &Environment::new())
+}
+
 /// Call a lambda function with the given arguments
 fn call_lambda(lambda_value: &EdnValue, args: &[EdnValue], _context: &EdnValue, _env: &Environment) -> EqResult<EdnValue> {
     match lambda_value {
         EdnValue::Lambda(lambda) => {
-            // Check argument count
-            if args.len() != lambda.params.len() {
-                return Err(EqError::query_error(format!(
-                    "Lambda expects {} arguments, got {}",
-                    lambda.params.len(),
-                    args.len()
-                )));
-            }
-            
-            // Create new environment with parameter bindings
-            let mut new_env = Environment::new();
-            for (param, arg) in lambda.params.iter().zip(args) {
-                new_env.bind(param.clone(), arg.clone());
+            if let Some(native) = &lambda.native {
+                return native.call(args);
             }
-            
+
             // Parse and analyze the lambda body into an expression
             let body_expr = edn_to_expr(&lambda.body)?;
             let analyzed_body = crate::analyzer::analyze(body_expr)?;
             
-            // Evaluate the body with the new environment
-            // Use the first argument as context, or nil if no arguments
-            let body_context = args.first().cloned().unwrap_or(EdnValue::Nil);
-            evaluate_with_env(&analyzed_body, &body_context, &new_env)
+            // A (recur ...) in the body calls it again with new arguments,
+            // in this loop rather than on the stack
+            let mut args = args.to_vec();
+            loop {
+                // Check argument count
+                if lambda.rest_param.is_some() && args.len() < lambda.params.len() {
+                    return Err(EqError::query_error(format!(
+                        "Lambda expects at least {} arguments, got {}",
+                        lambda.params.len(),
+                        args.len()
+                    )));
+                }
+                if lambda.rest_param.is_none() && args.len() != lambda.params.len() {
+                    return Err(EqError::query_error(format!(
+                        "Lambda expects {} arguments, got {}",
+                        lambda.params.len(),
+                        args.len()
+                    )));
+                }
+                
+                // Start from the captured bindings; parameters shadow them
+                let mut new_env = Environment::new();
+                for (name, value) in &lambda.captured_env {
+                    new_env.bind(name.clone(), value.clone());
+                }
+                for (param, arg) in lambda.params.iter().zip(&args) {
+                    new_env.bind(param.clone(), arg.clone());
+                }
+                if let Some(rest) = &lambda.rest_param {
+                    new_env.bind(rest.clone(), EdnValue::Vector(args[lambda.params.len()..].to_vec()));
+                }
+                
+                // Evaluate the body with the new environment
+                // Use the first argument as context, or nil if no arguments
+                let body_context = args.first().cloned().unwrap_or(EdnValue::Nil);
+                match evaluate_tail(&analyzed_body, &body_context, &new_env) {
+                    Err(EqError::RecurSignal(new_args)) => args = new_args,
+                    result => return result,
+                }
+            }
         }
+        // Keywords act as functions that look themselves up, like (:key map default)
+        EdnValue::Keyword(_) => match args {
+            [target] => Ok(target.get(lambda_value).cloned().unwrap_or(EdnValue::Nil)),
+            [target, default] => Ok(target.get(lambda_value).cloned().unwrap_or_else(|| default.clone())),
+            _ => Err(EqError::query_error(format!(
+                "Keyword expects 1 or 2 arguments, got {}",
+                args.len()
+            ))),
+        },
         _ => Err(EqError::type_error("lambda", lambda_value.type_name())),
     }
 }
@@ -543,11 +1024,14 @@ mod tests {
         // Create (map (fn [x] (< 3 x)) .)
         let lambda = EdnValue::Lambda(crate::edn::value::EdnLambda {
             params: vec!["x".to_string()],
+            rest_param: None,
             body: Box::new(EdnValue::List(vec![
                 EdnValue::Symbol("<".to_string()),
                 EdnValue::Integer(3),
                 EdnValue::Symbol("x".to_string()),
             ])),
+            native: None,
+            captured_env: std::collections::HashMap::new(),
         });
         
         let expr = Expr::Function {
@@ -584,11 +1068,14 @@ mod tests {
         // Create (select (fn [x] (< 3 x)) .)
         let lambda = EdnValue::Lambda(crate::edn::value::EdnLambda {
             params: vec!["x".to_string()],
+            rest_param: None,
             body: Box::new(EdnValue::List(vec![
                 EdnValue::Symbol("<".to_string()),
                 EdnValue::Integer(3),
                 EdnValue::Symbol("x".to_string()),
             ])),
+            native: None,
+            captured_env: std::collections::HashMap::new(),
         });
         
         let expr = Expr::Function {
@@ -622,11 +1109,14 @@ mod tests {
         // Create (remove (fn [x] (< 3 x)) .)
         let lambda = EdnValue::Lambda(crate::edn::value::EdnLambda {
             params: vec!["x".to_string()],
+            rest_param: None,
             body: Box::new(EdnValue::List(vec![
                 EdnValue::Symbol("<".to_string()),
                 EdnValue::Integer(3),
                 EdnValue::Symbol("x".to_string()),
             ])),
+            native: None,
+            captured_env: std::collections::HashMap::new(),
         });
         
         let expr = Expr::Function {
@@ -647,4 +1137,1671 @@ mod tests {
         
         assert_eq!(result, expected);
     }
-}
\ No newline at end of file
+
+    fn eval_query(query: &str, input: &EdnValue) -> EqResult<EdnValue> {
+        let expr = crate::query::QueryParser::parse(query)?;
+        let analyzed = crate::analyzer::analyze(expr)?;
+        evaluate(&analyzed, input)
+    }
+
+    #[test]
+    fn test_partial() {
+        let input = EdnValue::Vector(vec![
+            EdnValue::Integer(5),
+            EdnValue::Integer(15),
+            EdnValue::Integer(20),
+        ]);
+
+        // (partial < 10) behaves like (fn [x] (< 10 x))
+        let result = eval_query("(select (partial < 10) .)", &input).unwrap();
+        assert_eq!(result, EdnValue::Vector(vec![EdnValue::Integer(15), EdnValue::Integer(20)]));
+
+        // Partial application of a lambda
+        let result = eval_query("((partial (fn [a b] (= a b)) 15) 15)", &input).unwrap();
+        assert_eq!(result, EdnValue::Bool(true));
+
+        // Nested partials accumulate arguments
+        let result = eval_query("((partial (partial (fn [a b c] (< a b c)) 1) 2) 3)", &input).unwrap();
+        assert_eq!(result, EdnValue::Bool(true));
+
+        // Printed form mirrors the call
+        let result = eval_query("(partial < 10)", &input).unwrap();
+        assert_eq!(result.to_string(), "(partial < 10)");
+
+        assert!(eval_query("(partial 1 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_builtin_symbol_as_function() {
+        let input = EdnValue::Vector(vec![
+            EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(2)]),
+            EdnValue::Vector(vec![EdnValue::Integer(3)]),
+        ]);
+
+        let result = eval_query("(map first .)", &input).unwrap();
+        assert_eq!(result, EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(3)]));
+
+        assert!(eval_query("undefined-thing", &input).is_err());
+    }
+
+    #[test]
+    fn test_juxt() {
+        let input = crate::edn::Parser::new(r#"[{:name "Alice" :age 30} {:name "Bob"}]"#).parse().unwrap();
+
+        let result = eval_query("(map (juxt :name :age) .)", &input).unwrap();
+        assert_eq!(result.to_string(), r#"[["Alice" 30] ["Bob" nil]]"#);
+
+        let result = eval_query("((juxt first count (fn [x] (empty? x))) [1 2 3])", &input).unwrap();
+        assert_eq!(result.to_string(), "[1 3 false]");
+
+        assert!(eval_query("(juxt)", &input).is_err());
+        assert!(eval_query("(juxt 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_map_indexed_and_keep() {
+        let input = crate::edn::Parser::new("[:a :b :c :d]").parse().unwrap();
+
+        let result = eval_query("(map-indexed (fn [i x] i) .)", &input).unwrap();
+        assert_eq!(result.to_string(), "[0 1 2 3]");
+
+        let result = eval_query("(map-indexed (fn [i x] (if (< i 2) x i)) .)", &input).unwrap();
+        assert_eq!(result.to_string(), "[:a :b 2 3]");
+
+        let result = eval_query("(keep-indexed (fn [i x] (when (< 1 i) x)) .)", &input).unwrap();
+        assert_eq!(result.to_string(), "[:c :d]");
+
+        let records = crate::edn::Parser::new("[{:a 1} {:b 2} {:a false}]").parse().unwrap();
+        let result = eval_query("(keep :a .)", &records).unwrap();
+        assert_eq!(result.to_string(), "[1 false]");
+    }
+
+    #[test]
+    fn test_reduce_kv() {
+        let input = crate::edn::Parser::new("{:a 3 :b 7 :c 5}").parse().unwrap();
+
+        let result = eval_query("(reduce-kv (fn [acc k v] (if (< acc v) v acc)) 0 .)", &input).unwrap();
+        assert_eq!(result, EdnValue::Integer(7));
+
+        let result = eval_query("(reduce-kv (fn [acc k v] k) nil .)", &input).unwrap();
+        assert_eq!(result, EdnValue::Keyword("c".to_string()));
+
+        let err = eval_query("(reduce-kv (fn [acc k v] acc) 0 [1 2])", &input).unwrap_err();
+        assert!(matches!(err, EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let input = crate::edn::Parser::new("[3 1.5 7 2]").parse().unwrap();
+        assert_eq!(eval_query("(min .)", &input).unwrap(), EdnValue::Float(1.5));
+        assert_eq!(eval_query("(max .)", &input).unwrap(), EdnValue::Integer(7));
+        assert_eq!(eval_query("(max [])", &input).unwrap(), EdnValue::Nil);
+
+        let err = eval_query("(min [1 \"a\"])", &input).unwrap_err();
+        assert!(matches!(err, EqError::TypeError { .. }));
+
+        let records = crate::edn::Parser::new(r#"[{:name "a" :score 5} {:name "b" :score 9} {:name "c" :score 1}]"#).parse().unwrap();
+        let result = eval_query("(max-by :score .)", &records).unwrap();
+        assert_eq!(result.get(&EdnValue::Keyword("name".to_string())), Some(&EdnValue::String("b".to_string())));
+        let result = eval_query("(min-by (fn [r] (:score r)) .)", &records).unwrap();
+        assert_eq!(result.get(&EdnValue::Keyword("name".to_string())), Some(&EdnValue::String("c".to_string())));
+    }
+
+    #[test]
+    fn test_sum_average_count_by() {
+        let input = crate::edn::Parser::new("[1 2 3 4]").parse().unwrap();
+        assert_eq!(eval_query("(sum .)", &input).unwrap(), EdnValue::Integer(10));
+        assert_eq!(eval_query("(sum [1 2.5])", &input).unwrap(), EdnValue::Float(3.5));
+        assert_eq!(eval_query("(sum [])", &input).unwrap(), EdnValue::Integer(0));
+        assert_eq!(eval_query("(average .)", &input).unwrap(), EdnValue::Float(2.5));
+        assert_eq!(eval_query("(average [])", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(count-by (partial < 2) .)", &input).unwrap(), EdnValue::Integer(2));
+
+        let err = eval_query("(sum [1 :a])", &input).unwrap_err();
+        assert!(matches!(err, EqError::TypeError { .. }));
+
+        let people = crate::edn::Parser::new("[{:age 20} {:age 30}]").parse().unwrap();
+        assert_eq!(eval_query("(average (map :age .))", &people).unwrap(), EdnValue::Float(25.0));
+    }
+
+    #[test]
+    fn test_zip_and_interleave() {
+        let input = crate::edn::Parser::new("{:a {:value 1} :b {:value 2}}").parse().unwrap();
+
+        let result = eval_query("(zipmap (keys .) (map :value (vals .)))", &input).unwrap();
+        assert_eq!(result.to_string(), "{:a 1 :b 2}");
+
+        let result = eval_query("(zip [1 2 3] [:a :b])", &input).unwrap();
+        assert_eq!(result.to_string(), "[[1 :a] [2 :b]]");
+
+        let result = eval_query("(interleave [1 2 3] [:a :b])", &input).unwrap();
+        assert_eq!(result.to_string(), "[1 :a 2 :b]");
+
+        let result = eval_query("(interpose :sep [1 2 3])", &input).unwrap();
+        assert_eq!(result.to_string(), "[1 :sep 2 :sep 3]");
+    }
+
+    #[test]
+    fn test_concat_vec_list() {
+        let input = crate::edn::Parser::new("{:users [:a :b] :admins (:c)}").parse().unwrap();
+
+        let result = eval_query("(concat (:users .) (:admins .) [:d])", &input).unwrap();
+        assert_eq!(result.to_string(), "[:a :b :c :d]");
+        assert_eq!(eval_query("(concat)", &input).unwrap(), EdnValue::Vector(vec![]));
+
+        let err = eval_query("(concat [1] .)", &input).unwrap_err();
+        assert!(matches!(err, EqError::TypeError { .. }));
+
+        assert_eq!(eval_query("(vec (:admins .))", &input).unwrap().to_string(), "[:c]");
+        assert_eq!(eval_query("(list 1 2 3)", &input).unwrap().to_string(), "(1 2 3)");
+    }
+
+    #[test]
+    fn test_reverse_and_shuffle() {
+        let input = crate::edn::Parser::new("[1 2 3 4 5]").parse().unwrap();
+
+        assert_eq!(eval_query("(reverse .)", &input).unwrap().to_string(), "[5 4 3 2 1]");
+        assert_eq!(eval_query("(reverse (list 1 2))", &input).unwrap().to_string(), "[2 1]");
+        assert_eq!(eval_query("(reverse [])", &input).unwrap(), EdnValue::Vector(vec![]));
+        assert!(eval_query("(reverse \"abc\")", &input).is_err());
+
+        // Shuffling keeps the same elements
+        match eval_query("(shuffle .)", &input).unwrap() {
+            EdnValue::Vector(mut values) => {
+                values.sort_by_key(|v| v.to_string());
+                assert_eq!(EdnValue::Vector(values), input);
+            }
+            other => panic!("Expected vector, got {:?}", other),
+        }
+
+        assert_eq!(eval_query("(count (shuffle #{1 2 3}))", &input).unwrap(), EdnValue::Integer(3));
+    }
+
+    #[test]
+    fn test_arithmetic_and_bigint() {
+        let input = crate::edn::Parser::new("[1 2 3]").parse().unwrap();
+
+        assert_eq!(eval_query("(+ 1 2 3)", &input).unwrap(), EdnValue::Integer(6));
+        assert_eq!(eval_query("(- 10 4 1)", &input).unwrap(), EdnValue::Integer(5));
+        assert_eq!(eval_query("(- 3)", &input).unwrap(), EdnValue::Integer(-3));
+        assert_eq!(eval_query("(* 2 2.5)", &input).unwrap(), EdnValue::Float(5.0));
+        assert_eq!(eval_query("(+)", &input).unwrap(), EdnValue::Integer(0));
+        assert_eq!(eval_query("(*)", &input).unwrap(), EdnValue::Integer(1));
+        assert!(eval_query("(-)", &input).is_err());
+        assert!(eval_query("(+ 1 :a)", &input).is_err());
+
+        // Overflow and BigInt operands promote
+        assert_eq!(eval_query("(+ 9223372036854775807 1)", &input).unwrap().to_string(), "9223372036854775808N");
+        assert_eq!(eval_query("(* 2N 3)", &input).unwrap().to_string(), "6N");
+        assert_eq!(eval_query("(- 1N 0.5)", &input).unwrap(), EdnValue::Float(0.5));
+        assert_eq!(eval_query("(sum [9223372036854775807 1])", &input).unwrap().to_string(), "9223372036854775808N");
+
+        assert_eq!(eval_query("(bigint? 1N)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(bigint? 1)", &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query("(number? 1N)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(< 1 99999999999999999999)", &input).unwrap(), EdnValue::Bool(true));
+    }
+
+    #[test]
+    fn test_ratios() {
+        let input = crate::edn::Parser::new("[1/2 1/3]").parse().unwrap();
+
+        assert_eq!(eval_query("(/ 10 4)", &input).unwrap().to_string(), "5/2");
+        assert_eq!(eval_query("(/ 10 5)", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(/ 4)", &input).unwrap().to_string(), "1/4");
+        assert_eq!(eval_query("(/ 1 2.0)", &input).unwrap(), EdnValue::Float(0.5));
+        assert_eq!(eval_query("(/ 10N 5)", &input).unwrap().to_string(), "2N");
+        assert!(eval_query("(/ 1 0)", &input).is_err());
+        assert!(eval_query("(/)", &input).is_err());
+
+        assert_eq!(eval_query("(+ (first .) (last .))", &input).unwrap().to_string(), "5/6");
+        assert_eq!(eval_query("(* 1/2 4)", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(- 1/2 0.25)", &input).unwrap(), EdnValue::Float(0.25));
+        assert_eq!(eval_query("(sum .)", &input).unwrap().to_string(), "5/6");
+
+        assert_eq!(eval_query("(ratio? (first .))", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(ratio? 2)", &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query("(numerator 6/4)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(denominator 6/4)", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(denominator 5)", &input).unwrap(), EdnValue::Integer(1));
+        assert!(eval_query("(numerator 0.5)", &input).is_err());
+
+        // Ratios compare exactly against integers and by value against floats
+        assert_eq!(eval_query("(< 1/3 1/2 1)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(> 1/2 0.4)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(min .)", &input).unwrap().to_string(), "1/3");
+    }
+
+    #[test]
+    fn test_when_let_and_if_let() {
+        let input = crate::edn::Parser::new(r#"{:user {:name "Alice"} :active false}"#).parse().unwrap();
+
+        assert_eq!(eval_query("(when-let [u (:user .)] (:name u))", &input).unwrap(), EdnValue::String("Alice".to_string()));
+        assert_eq!(eval_query("(when-let [u (:missing .)] (:name u))", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(when-let [a (:active .)] :yes)", &input).unwrap(), EdnValue::Nil);
+        // The body is an implicit do, and . is still the input
+        assert_eq!(eval_query("(when-let [u (:user .)] (:name u) (count .))", &input).unwrap(), EdnValue::Integer(2));
+
+        assert_eq!(eval_query("(if-let [u (:user .)] (:name u) \"anonymous\")", &input).unwrap(), EdnValue::String("Alice".to_string()));
+        assert_eq!(eval_query("(if-let [u (:nobody .)] (:name u) \"anonymous\")", &input).unwrap(), EdnValue::String("anonymous".to_string()));
+        assert_eq!(eval_query("(if-let [u (:nobody .)] (:name u))", &input).unwrap(), EdnValue::Nil);
+
+        assert!(eval_query("(when-let [u] u)", &input).is_err());
+        assert!(eval_query("(when-let [u (:user .) v 1] u)", &input).is_err());
+        assert!(eval_query("(if-let [:u (:user .)] 1 2)", &input).is_err());
+        assert!(eval_query("(if-let (:user .) 1 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_for() {
+        let input = crate::edn::Parser::new("[1 2 3 4 5 6 7]").parse().unwrap();
+
+        assert_eq!(eval_query("(for [x .] (* x x))", &input).unwrap().to_string(), "[1 4 9 16 25 36 49]");
+        assert_eq!(eval_query("(for [x . :when (> x 5)] x)", &input).unwrap().to_string(), "[6 7]");
+        assert_eq!(eval_query("(for [x []] x)", &input).unwrap().to_string(), "[]");
+
+        let users = crate::edn::Parser::new(r#"{:users [{:name "a" :tags [:x :y]} {:name "b" :tags [:z]}]}"#).parse().unwrap();
+        assert_eq!(
+            eval_query("(for [u (:users .) t (:tags u)] [(:name u) t])", &users).unwrap().to_string(),
+            r#"[["a" :x] ["a" :y] ["b" :z]]"#
+        );
+        // Guards can refer to any earlier binding
+        assert_eq!(
+            eval_query("(for [u (:users .) t (:tags u) :when (= t :y)] (:name u))", &users).unwrap().to_string(),
+            r#"["a"]"#
+        );
+        // Cross product, rightmost binding varies fastest
+        assert_eq!(
+            eval_query("(for [x [1 2] y [10 20]] (+ x y))", &input).unwrap().to_string(),
+            "[11 21 12 22]"
+        );
+
+        assert!(eval_query("(for [x] x)", &input).is_err());
+        assert!(eval_query("(for [:when true x .] x)", &input).is_err());
+        assert!(eval_query("(for [x .])", &input).is_err());
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let input = crate::edn::Parser::new("{:a 1 :b :x}").parse().unwrap();
+
+        assert_eq!(eval_query("(try (:a .) (catch e :failed))", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(try (+ (:a .) (:b .)) (catch e :failed))", &input).unwrap(), EdnValue::Keyword("failed".to_string()));
+        assert_eq!(
+            eval_query("(try (+ 1 :x) (catch e e))", &input).unwrap(),
+            EdnValue::String("Type error: expected number, got keyword".to_string())
+        );
+
+        // The first clause matching the error kind wins
+        assert_eq!(eval_query("(try (+ 1 :x) (catch :query-error e 1) (catch :type-error e 2) (catch e 3))", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(try (nth [] 5 6 7) (catch :type-error e 1) (catch e 3))", &input).unwrap(), EdnValue::Integer(3));
+
+        // Unmatched errors propagate
+        let err = eval_query("(try (+ 1 :x) (catch :query-error e 1))", &input).unwrap_err();
+        assert!(matches!(err, EqError::TypeError { .. }));
+
+        // No catch clauses at all: errors still propagate
+        assert!(eval_query("(try (+ 1 :x))", &input).is_err());
+
+        assert!(eval_query("(try 1 (catch :oops e 1))", &input).is_err());
+        assert!(eval_query("(try 1 (catch :type-error 1))", &input).is_err());
+        assert!(eval_query("(try (catch e 1) 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_get_in_default() {
+        let input = crate::edn::Parser::new("{:a {:b {:c 1 :d nil}}}").parse().unwrap();
+
+        assert_eq!(eval_query("(get-in . [:a :b :c] \"missing\")", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(get-in . [:a :b :d] \"missing\")", &input).unwrap(), EdnValue::String("missing".to_string()));
+        assert_eq!(eval_query("(get-in . [:x :b :c] \"missing\")", &input).unwrap(), EdnValue::String("missing".to_string()));
+        assert_eq!(eval_query("(get-in . [:x :b :c])", &input).unwrap(), EdnValue::Nil);
+
+        assert!(eval_query("(get-in . :a 1)", &input).is_err());
+        assert!(eval_query("(get-in . [:a] 1 2)", &input).is_err());
+        assert!(eval_query("(get-in .)", &input).is_err());
+    }
+
+    #[test]
+    fn test_type_conversion() {
+        let input = EdnValue::Nil;
+
+        assert_eq!(eval_query("(int 3.9)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(int -3.9)", &input).unwrap(), EdnValue::Integer(-3));
+        assert_eq!(eval_query("(int \"42\")", &input).unwrap(), EdnValue::Integer(42));
+        assert_eq!(eval_query("(int true)", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(int 7/2)", &input).unwrap(), EdnValue::Integer(3));
+        assert!(matches!(eval_query("(int \"hello\")", &input).unwrap_err(), EqError::QueryError { .. }));
+        assert!(eval_query("(int 1e30)", &input).is_err());
+
+        assert_eq!(eval_query("(float 2)", &input).unwrap(), EdnValue::Float(2.0));
+        assert_eq!(eval_query("(float \"2.5\")", &input).unwrap(), EdnValue::Float(2.5));
+        assert_eq!(eval_query("(float 1/4)", &input).unwrap(), EdnValue::Float(0.25));
+        assert!(eval_query("(float :a)", &input).is_err());
+
+        assert_eq!(eval_query("(str \"abc\")", &input).unwrap(), EdnValue::String("abc".to_string()));
+        assert_eq!(eval_query("(str {:a [1 2]})", &input).unwrap(), EdnValue::String("{:a [1 2]}".to_string()));
+        assert_eq!(eval_query("(str \"n=\" 1 nil :k)", &input).unwrap(), EdnValue::String("n=1:k".to_string()));
+        assert_eq!(eval_query("(str)", &input).unwrap(), EdnValue::String(String::new()));
+
+        assert_eq!(eval_query("(keyword \"name\")", &input).unwrap(), EdnValue::Keyword("name".to_string()));
+        assert_eq!(eval_query("(keyword (symbol \"db/id\"))", &input).unwrap(), EdnValue::Keyword("db/id".to_string()));
+        assert_eq!(eval_query("(symbol :name)", &input).unwrap(), EdnValue::Symbol("name".to_string()));
+        assert!(eval_query("(keyword 1)", &input).is_err());
+        assert!(eval_query("(symbol nil)", &input).is_err());
+
+        assert_eq!(eval_query("(parse-int \" 12 \")", &input).unwrap(), EdnValue::Integer(12));
+        assert_eq!(eval_query("(parse-int \"12.5\")", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(parse-float \"12.5\")", &input).unwrap(), EdnValue::Float(12.5));
+        assert_eq!(eval_query("(parse-float \"abc\")", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(parse-int nil)", &input).unwrap(), EdnValue::Nil);
+        assert!(eval_query("(parse-int 12)", &input).is_err());
+    }
+
+    #[test]
+    fn test_namespaced_keywords() {
+        let input = crate::edn::Parser::new("{:db/id 7 :name \"x\" :fn clojure.core/map}").parse().unwrap();
+
+        assert_eq!(eval_query("(namespace :db/id)", &input).unwrap(), EdnValue::String("db".to_string()));
+        assert_eq!(eval_query("(name :db/id)", &input).unwrap(), EdnValue::String("id".to_string()));
+        assert_eq!(eval_query("(namespace :name)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(name :name)", &input).unwrap(), EdnValue::String("name".to_string()));
+        assert_eq!(eval_query("(namespace (:fn .))", &input).unwrap(), EdnValue::String("clojure.core".to_string()));
+        assert_eq!(eval_query("(name (:fn .))", &input).unwrap(), EdnValue::String("map".to_string()));
+        assert!(eval_query("(namespace 1)", &input).is_err());
+
+        assert_eq!(eval_query("(map namespace (keys .))", &input).unwrap(), EdnValue::Vector(vec![
+            EdnValue::String("db".to_string()),
+            EdnValue::Nil,
+            EdnValue::Nil,
+        ]));
+
+        assert_eq!(eval_query("(qualified-keyword \"user\" \"email\")", &input).unwrap(), EdnValue::Keyword("user/email".to_string()));
+        assert!(eval_query("(qualified-keyword \"\" \"email\")", &input).is_err());
+        assert!(eval_query("(qualified-keyword :user \"email\")", &input).is_err());
+
+        assert_eq!(eval_query("(qualified? :db/id)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(qualified? :name)", &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query("(qualified? \"db/id\")", &input).unwrap(), EdnValue::Bool(false));
+    }
+
+    #[test]
+    fn test_walk() {
+        let input = crate::edn::Parser::new("{:a 1 :b [2 {:c 3}] :d #{4} :e (5 \"x\")}").parse().unwrap();
+        let expected = crate::edn::Parser::new("{:a 2 :b [4 {:c 6}] :d #{8} :e (10 \"x\")}").parse().unwrap();
+
+        let result = eval_query("(postwalk (fn [x] (if (number? x) (* x 2) x)) .)", &input).unwrap();
+        assert_eq!(result, expected);
+        let result = eval_query("(prewalk (fn [x] (if (number? x) (* x 2) x)) .)", &input).unwrap();
+        assert_eq!(result, expected);
+
+        // postwalk sees children before their parents, prewalk the reverse
+        let input = crate::edn::Parser::new("[[1 2] [3]]").parse().unwrap();
+        let shrink = "(fn [x] (if (number? x) x (if (= (count x) 2) (first x) x)))";
+        assert_eq!(eval_query(&format!("(postwalk {} .)", shrink), &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(
+            eval_query(&format!("(prewalk {} .)", shrink), &input).unwrap(),
+            EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(2)]),
+        );
+
+        // walk applies inner to each element and outer once to the result
+        assert_eq!(eval_query("(walk (fn [x] (* x 10)) sum [1 2 3])", &EdnValue::Nil).unwrap(), EdnValue::Integer(60));
+        assert_eq!(eval_query("(walk (fn [x] x) (fn [x] x) 7)", &EdnValue::Nil).unwrap(), EdnValue::Integer(7));
+
+        // Map entries are visited as [key value] pairs
+        let input = crate::edn::Parser::new("{:a 1}").parse().unwrap();
+        assert!(eval_query("(postwalk (fn [x] (if (number? x) x (if (keyword? x) x 1))) .)", &input).is_err());
+    }
+
+    #[test]
+    fn test_postwalk_deep_nesting() {
+        // Deep enough that a recursive walk through lambda calls would overflow
+        let mut value = EdnValue::Integer(1);
+        for _ in 0..1_000 {
+            value = EdnValue::Vector(vec![value]);
+        }
+        let result = eval_query("(postwalk (fn [x] (if (number? x) (+ x 1) x)) .)", &value).unwrap();
+        assert_eq!(result.count(), Some(1));
+        assert_ne!(result, value);
+    }
+
+    #[test]
+    fn test_select_keys_recursive() {
+        let input = crate::edn::Parser::new(r#"
+            [{:name "Alice" :age 30
+              :address {:city "Paris" :zip "75001" :street "Rue X"}
+              :orders [{:id 1 :total 10 :items [:a]} {:id 2 :total 20}]}
+             {:name "Bob" :address nil}]
+        "#).parse().unwrap();
+        let expected = crate::edn::Parser::new(r#"
+            [{:name "Alice" :address {:city "Paris" :zip "75001"} :orders [{:id 1} {:id 2}]}
+             {:name "Bob" :address nil}]
+        "#).parse().unwrap();
+
+        let result = eval_query(
+            "(select-keys-recursive {:name true :address {:city true :zip true} :orders {:id true} :email true :age false} .)",
+            &input,
+        ).unwrap();
+        assert_eq!(result, expected);
+
+        assert!(eval_query("(select-keys-recursive [:name] .)", &input).is_err());
+    }
+
+    #[test]
+    fn test_frequencies_by() {
+        let input = crate::edn::Parser::new(r#"
+            [{:name "A" :dept :engineering} {:name "B" :dept :sales} {:name "C" :dept :engineering}
+             {:name "D"} {:name "E" :dept :engineering} {:name "F" :dept nil}]
+        "#).parse().unwrap();
+
+        let mut expected = IndexMap::new();
+        expected.insert(EdnValue::Keyword("engineering".to_string()), EdnValue::Integer(3));
+        expected.insert(EdnValue::Keyword("sales".to_string()), EdnValue::Integer(1));
+        expected.insert(EdnValue::Nil, EdnValue::Integer(2));
+        assert_eq!(eval_query("(frequencies-by :dept .)", &input).unwrap(), EdnValue::Map(expected));
+
+        let result = eval_query("(frequencies-by (fn [x] (> x 2)) [1 2 3 4 5])", &EdnValue::Nil).unwrap();
+        let mut expected = IndexMap::new();
+        expected.insert(EdnValue::Bool(false), EdnValue::Integer(2));
+        expected.insert(EdnValue::Bool(true), EdnValue::Integer(3));
+        assert_eq!(result, EdnValue::Map(expected));
+
+        assert!(eval_query("(frequencies-by :dept)", &input).is_err());
+    }
+
+    #[test]
+    fn test_rename_keys() {
+        let input = crate::edn::Parser::new("{:user-name \"alice\" :user-id 7 :role :admin}").parse().unwrap();
+        let expected = crate::edn::Parser::new("{:username \"alice\" :id 7 :role :admin}").parse().unwrap();
+        assert_eq!(eval_query("(rename-keys . {:user-name :username :user-id :id :missing :x})", &input).unwrap(), expected);
+
+        // The renamed value wins over an existing key, and keys can be swapped
+        let input = crate::edn::Parser::new("{:a 1 :b 2 :c 3}").parse().unwrap();
+        assert_eq!(
+            eval_query("(rename-keys . {:a :c})", &input).unwrap(),
+            crate::edn::Parser::new("{:c 1 :b 2}").parse().unwrap(),
+        );
+        assert_eq!(
+            eval_query("(rename-keys . {:a :b :b :a})", &input).unwrap(),
+            crate::edn::Parser::new("{:b 1 :a 2 :c 3}").parse().unwrap(),
+        );
+
+        let input = crate::edn::Parser::new("[{:n 1} {:n 2}]").parse().unwrap();
+        assert_eq!(
+            eval_query("(map (fn [m] (rename-keys m {:n :count})) .)", &input).unwrap(),
+            crate::edn::Parser::new("[{:count 1} {:count 2}]").parse().unwrap(),
+        );
+
+        assert!(matches!(eval_query("(rename-keys [1 2] {:a :b})", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(matches!(eval_query("(rename-keys {:a 1} [:a :b])", &input).unwrap_err(), EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_update_vals_and_keys() {
+        let input = crate::edn::Parser::new("{:a 1 :b 2 :c 3}").parse().unwrap();
+
+        assert_eq!(
+            eval_query("(update-vals (fn [v] (* v 2)) .)", &input).unwrap(),
+            crate::edn::Parser::new("{:a 2 :b 4 :c 6}").parse().unwrap(),
+        );
+        assert_eq!(
+            eval_query("(update-keys (fn [k] (name k)) .)", &input).unwrap(),
+            crate::edn::Parser::new("{\"a\" 1 \"b\" 2 \"c\" 3}").parse().unwrap(),
+        );
+
+        // Colliding keys keep the first position and the last value
+        let result = eval_query("(update-keys (fn [k] (= k :b)) .)", &input).unwrap();
+        let mut expected = IndexMap::new();
+        expected.insert(EdnValue::Bool(false), EdnValue::Integer(3));
+        expected.insert(EdnValue::Bool(true), EdnValue::Integer(2));
+        assert_eq!(result, EdnValue::Map(expected));
+
+        assert!(matches!(eval_query("(update-vals (fn [v] v) [1 2])", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(matches!(eval_query("(update-keys (fn [k] k) nil)", &input).unwrap_err(), EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_sort() {
+        let input = crate::edn::Parser::new(r#"[3 "b" :k nil 1.5 [2] "a" 1 true]"#).parse().unwrap();
+        assert_eq!(eval_query("(sort .)", &input).unwrap().to_string(), r#"[nil true 1 1.5 3 "a" "b" :k [2]]"#);
+
+        assert_eq!(eval_query("(sort #{3 1 2})", &input).unwrap().to_string(), "[1 2 3]");
+        assert_eq!(eval_query("(sort [[1 2] [1] [0 5]])", &input).unwrap().to_string(), "[[0 5] [1] [1 2]]");
+        assert_eq!(eval_query("(sort nil)", &input).unwrap(), EdnValue::Vector(vec![]));
+        assert!(eval_query("(sort 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_distinct() {
+        let input = crate::edn::Parser::new(r#"
+            [{:tx 1 :step "start"} {:tx 2 :step "start"} {:tx 1 :step "end"} {:step "orphan"} {:tx nil :step "none"}]
+        "#).parse().unwrap();
+
+        let result = eval_query("(map :step (distinct-by :tx .))", &input).unwrap();
+        assert_eq!(result.to_string(), r#"["start" "start" "orphan"]"#);
+
+        let result = eval_query("(distinct-by (fn [x] (> x 2)) [1 5 2 6 3])", &input).unwrap();
+        assert_eq!(result.to_string(), "[1 5]");
+
+        assert_eq!(eval_query("(distinct [3 1 3 2 1])", &input).unwrap().to_string(), "[3 1 2]");
+        assert_eq!(eval_query("(distinct [])", &input).unwrap(), EdnValue::Vector(vec![]));
+        assert!(eval_query("(distinct-by :tx)", &input).is_err());
+    }
+
+    #[test]
+    fn test_index_by() {
+        let input = crate::edn::Parser::new(r#"
+            [{:id 1 :name "Alice" :team :a} {:id 2 :name "Bob" :team :b} {:id 1 :name "Alicia" :team :a}]
+        "#).parse().unwrap();
+
+        let result = eval_query("(index-by :id .)", &input).unwrap();
+        assert_eq!(result.to_string(), r#"{1 {:id 1 :name "Alicia" :team :a} 2 {:id 2 :name "Bob" :team :b}}"#);
+
+        let result = eval_query("(index-by-group :team .)", &input).unwrap();
+        let expected = crate::edn::Parser::new(r#"
+            {:a [{:id 1 :name "Alice" :team :a} {:id 1 :name "Alicia" :team :a}] :b [{:id 2 :name "Bob" :team :b}]}
+        "#).parse().unwrap();
+        assert_eq!(result, expected);
+
+        assert_eq!(eval_query("(index-by (fn [x] (* x x)) [1 2 3])", &input).unwrap().to_string(), "{1 1 4 2 9 3}");
+
+        assert!(matches!(eval_query("(index-by :id {:id 1})", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(matches!(eval_query("(index-by-group :id 1)", &input).unwrap_err(), EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_flatten_keys() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        let nested = parse("{:a {:b 1 :c {:d 2}} :e [{:f 3}] :g {}}");
+        let flat = parse("{:a.b 1 :a.c.d 2 :e [{:f 3}] :g {}}");
+
+        assert_eq!(eval_query("(flatten-keys .)", &nested).unwrap(), flat);
+        assert_eq!(eval_query("(unflatten-keys .)", &flat).unwrap(), nested);
+        assert_eq!(eval_query("(unflatten-keys (flatten-keys .))", &nested).unwrap(), nested);
+
+        assert_eq!(
+            eval_query("(flatten-keys . \"_\")", &nested).unwrap(),
+            parse("{:a_b 1 :a_c_d 2 :e [{:f 3}] :g {}}"),
+        );
+        assert_eq!(
+            eval_query("(unflatten-keys {\"x/y\" 1 :p/q 2} \"/\")", &EdnValue::Nil).unwrap(),
+            parse("{\"x\" {\"y\" 1} :p {:q 2}}"),
+        );
+
+        assert!(eval_query("(unflatten-keys {:a 1 :a.b 2})", &EdnValue::Nil).is_err());
+        assert!(eval_query("(unflatten-keys {:a.b 2 :a 1})", &EdnValue::Nil).is_err());
+        assert!(eval_query("(flatten-keys . \"\")", &nested).is_err());
+        assert!(matches!(eval_query("(flatten-keys [1])", &nested).unwrap_err(), EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_tap_and_doto() {
+        let input = crate::edn::Parser::new("[1 2 3]").parse().unwrap();
+
+        assert_eq!(eval_query("(tap count .)", &input).unwrap(), input);
+        assert_eq!(eval_query("(sum (tap (fn [x] (first x)) .))", &input).unwrap(), EdnValue::Integer(6));
+        assert!(eval_query("(tap (fn [x] (no-such-function x)) .)", &input).is_err());
+
+        assert_eq!(eval_query("(spy (count .))", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(print (first .))", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(eprintln (last .))", &input).unwrap(), EdnValue::Integer(3));
+        assert!(eval_query("(println 1 2)", &input).is_err());
+
+        // Each form gets the value as its first argument; results are discarded
+        assert_eq!(eval_query("(doto (rest .) (count) first (get 0))", &input).unwrap().to_string(), "[2 3]");
+        assert_eq!(eval_query("(doto 5)", &input).unwrap(), EdnValue::Integer(5));
+        // Errors from the forms still propagate
+        assert!(eval_query("(doto . (/ 0))", &input).is_err());
+        assert!(eval_query("(doto)", &input).is_err());
+    }
+
+    #[test]
+    fn test_not_complement_fnil() {
+        let input = crate::edn::Parser::new("[1 :a nil 2 \"b\" false]").parse().unwrap();
+
+        assert_eq!(eval_query("(not nil)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(not false)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(not 0)", &input).unwrap(), EdnValue::Bool(false));
+        assert!(eval_query("(not)", &input).is_err());
+
+        assert_eq!(eval_query("(remove (complement number?) .)", &input).unwrap().to_string(), "[1 2]");
+        assert_eq!(eval_query("(select (complement nil?) .)", &input).unwrap().to_string(), "[1 :a 2 \"b\" false]");
+        assert_eq!(eval_query("(complement number?)", &input).unwrap().to_string(), "(complement number?)");
+        assert!(eval_query("(complement 1)", &input).is_err());
+
+        let input = crate::edn::Parser::new("[{:n 1} {:n nil} {}]").parse().unwrap();
+        assert_eq!(eval_query("(map (fnil (fn [n] (* n 10)) 0) (map :n .))", &input).unwrap().to_string(), "[10 0 0]");
+        assert_eq!(eval_query("((fnil + 1 2) nil nil)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("((fnil + 1 2) 5 nil)", &input).unwrap(), EdnValue::Integer(7));
+        assert_eq!(
+            eval_query("(reduce-kv (fnil (fn [acc k v] (+ acc v)) 100) nil {:a 1 :b 2})", &input).unwrap(),
+            EdnValue::Integer(103),
+        );
+        assert!(eval_query("(fnil +)", &input).is_err());
+    }
+
+    #[test]
+    fn test_sequence_accessors() {
+        let input = crate::edn::Parser::new("[1 2 3 4 5]").parse().unwrap();
+
+        assert_eq!(eval_query("(second .)", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(third .)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(fourth .)", &input).unwrap(), EdnValue::Integer(4));
+        assert_eq!(eval_query("(fourth [1 2])", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(second (list :a :b))", &input).unwrap(), EdnValue::Keyword("b".to_string()));
+
+        assert_eq!(eval_query("(nth . 1)", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(nth . -1)", &input).unwrap(), EdnValue::Integer(5));
+        assert!(matches!(eval_query("(nth . 5)", &input).unwrap_err(), EqError::QueryError { .. }));
+        assert_eq!(eval_query("(nth nil 5)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(nth-safe . 5)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(nth-safe . 0)", &input).unwrap(), EdnValue::Integer(1));
+        assert!(eval_query("(nth-safe . :a)", &input).is_err());
+
+        assert_eq!(eval_query("(butlast .)", &input).unwrap().to_string(), "[1 2 3 4]");
+        assert_eq!(eval_query("(butlast [])", &input).unwrap().to_string(), "[]");
+        assert_eq!(eval_query("(take-last 2 .)", &input).unwrap().to_string(), "[4 5]");
+        assert_eq!(eval_query("(take-last 10 .)", &input).unwrap().to_string(), "[1 2 3 4 5]");
+        assert_eq!(eval_query("(drop-last 2 .)", &input).unwrap().to_string(), "[1 2 3]");
+        assert_eq!(eval_query("(drop-last .)", &input).unwrap().to_string(), "[1 2 3 4]");
+        assert_eq!(eval_query("(drop-last -1 .)", &input).unwrap().to_string(), "[1 2 3 4 5]");
+        assert!(eval_query("(take-last :a .)", &input).is_err());
+    }
+
+    #[test]
+    fn test_let_and_closures() {
+        let input = crate::edn::Parser::new("[1 2 3]").parse().unwrap();
+
+        assert_eq!(eval_query("(let [x 10 y (+ x 1)] (* x y))", &input).unwrap(), EdnValue::Integer(110));
+        assert_eq!(eval_query("(let [n (count .)] n)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(let [x 1] (let [x 2] x))", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(let [] 5)", &input).unwrap(), EdnValue::Integer(5));
+        assert!(eval_query("(let [x] x)", &input).is_err());
+        assert!(eval_query("(let [:x 1] 1)", &input).is_err());
+
+        // fn bodies see the bindings in scope where the fn was created
+        assert_eq!(eval_query("((let [x 10] (fn [y] (+ x y))) 5)", &input).unwrap(), EdnValue::Integer(15));
+        assert_eq!(eval_query("(let [x 10] (map (fn [y] (+ x y)) .))", &input).unwrap().to_string(), "[11 12 13]");
+        assert_eq!(eval_query("(let [x 10] (map #(+ x %) .))", &input).unwrap().to_string(), "[11 12 13]");
+        assert_eq!(eval_query("(let [x 10] ((fn [x] x) 1))", &input).unwrap(), EdnValue::Integer(1));
+        // The captured binding is the one from where the fn was created
+        assert_eq!(
+            eval_query("(let [f (let [x 1] (fn [] x)) x 2] (f))", &input).unwrap(),
+            EdnValue::Integer(1)
+        );
+        // Nested fns close over each other's parameters
+        assert_eq!(eval_query("(((fn [a] (fn [b] (- a b))) 10) 3)", &input).unwrap(), EdnValue::Integer(7));
+    }
+
+    #[test]
+    fn test_locals_shadow_builtins() {
+        let input = crate::edn::Parser::new("[1 2 3]").parse().unwrap();
+        let eval = |query: &str| eval_query(query, &input).unwrap().to_string();
+
+        assert_eq!(eval("(let [first (fn [x] 42)] (first .))"), "42");
+        assert_eq!(eval("((fn [count] (count 1)) (fn [x] :mine))"), ":mine");
+        assert_eq!(eval("(let [{:keys [count]} (hash-map :count (fn [x] :mine))] (count .))"), ":mine");
+        // Only inside the binding's scope
+        assert_eq!(eval("(do (let [first (fn [x] 42)] 0) (first .))"), "1");
+
+        // Special forms and macros can't be shadowed
+        assert_eq!(eval("(let [if (fn [a b c] :mine)] (if true 1 2))"), "1");
+        assert_eq!(eval("(let [when (fn [a b] :mine)] (when true 1))"), "1");
+
+        // Destructuring still works when its helpers' names are bound
+        assert_eq!(eval("(let [{:keys [get a]} (hash-map :get 1 :a 2)] a)"), "2");
+        assert_eq!(eval("(let [[nth-safe b] .] b)"), "2");
+        assert_eq!(eval("(let [[drop & more] .] more)"), "[2 3]");
+        assert_eq!(eval("(let [{:keys [contains? a] :or {a 5}} (hash-map :contains? 1)] a)"), "5");
+    }
+
+    #[test]
+    fn test_globals_visible_in_fn_bodies() {
+        let mut globals = Environment::new();
+        globals.bind("limit".to_string(), EdnValue::Integer(2));
+        let input = crate::edn::Parser::new("[1 2 3]").parse().unwrap();
+        let expr = crate::analyzer::analyze(crate::query::QueryParser::parse("(select #(> % limit) .)").unwrap()).unwrap();
+
+        assert_eq!(evaluate_with_globals(&expr, &input, &globals).unwrap().to_string(), "[3]");
+    }
+
+    #[test]
+    fn test_memoize() {
+        let input = crate::edn::Parser::new("[1 2 1 2 3]").parse().unwrap();
+
+        assert_eq!(
+            eval_query("(let [square (memoize (fn [x] (* x x)))] (map square .))", &input).unwrap().to_string(),
+            "[1 4 1 4 9]"
+        );
+        assert_eq!(eval_query("((memoize +) 1 2)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("((memoize :a) {:a 1})", &input).unwrap(), EdnValue::Integer(1));
+        assert!(eval_query("(memoize 1)", &input).is_err());
+        assert!(eval_query("((memoize (fn [x] (+ x :a))) 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_memoize_evicts_least_recently_used() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let func = EdnValue::Lambda(EdnLambda::native(EdnValue::Symbol("count-calls".to_string()), move |args| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(args[0].clone())
+        }));
+        let memoized = crate::builtins::memoize_with_capacity(func, 2).unwrap();
+        let call = |n| apply_function(&memoized, &[EdnValue::Integer(n)]).unwrap();
+
+        call(1);
+        call(2);
+        call(1); // cached; 2 is now least recently used
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        call(3); // evicts 2
+        call(1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        call(2);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_format() {
+        let input = crate::edn::Parser::new(r#"{:name "Alice" :age 30 :score 91.256}"#).parse().unwrap();
+        let format = |q| eval_query(q, &input).unwrap().to_string();
+
+        assert_eq!(
+            format(r#"(format "Hello %s, you are %d years old" (:name .) (:age .))"#),
+            r#""Hello Alice, you are 30 years old""#
+        );
+        assert_eq!(format(r#"(format "%.1f%% %f" (:score .) 1)"#), r#""91.3% 1.000000""#);
+        assert_eq!(format(r#"(format "%e|%.2e|%e" 1500 0.00012 -2.5)"#), r#""1.500000e+03|1.20e-04|-2.500000e+00""#);
+        assert_eq!(format(r#"(format "%b %b %b" nil 0 false)"#), r#""false true false""#);
+        assert_eq!(format(r#"(format "%s %s %s" :k [1 "a"] nil)"#), r#"":k [1 \"a\"] nil""#);
+        assert_eq!(format(r#"(format "plain")"#), r#""plain""#);
+
+        assert!(eval_query(r#"(format "%s %s" 1)"#, &input).is_err());
+        assert!(eval_query(r#"(format "%s" 1 2)"#, &input).is_err());
+        assert!(eval_query(r#"(format "%d" 1.5)"#, &input).is_err());
+        assert!(eval_query(r#"(format "%f" "x")"#, &input).is_err());
+        assert!(eval_query(r#"(format "%q" 1)"#, &input).is_err());
+        assert!(eval_query(r#"(format "%.2s" "x")"#, &input).is_err());
+        assert!(eval_query(r#"(format "100%")"#, &input).is_err());
+        assert!(eval_query("(format 1)", &input).is_err());
+
+        assert_eq!(eval_query(r#"(printf "")"#, &input).unwrap(), EdnValue::Nil);
+    }
+
+    #[test]
+    fn test_string_predicates() {
+        let input = crate::edn::Parser::new(r#"[{:url "https://a.example"} {:url "ftp://b.example"}]"#).parse().unwrap();
+
+        assert_eq!(
+            eval_query(r#"(select (fn [x] (starts-with? "http" (:url x))) .)"#, &input).unwrap().to_string(),
+            r#"[{:url "https://a.example"}]"#
+        );
+        assert_eq!(eval_query(r#"(str/starts-with? "ftp" "ftp://x")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(ends-with? ".edn" "data.edn")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(str/ends-with? ".edn" "data.json")"#, &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query(r#"(str/contains? "amp" "example")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(str/contains? "" "x")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(index-of "b" "abc")"#, &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query(r#"(index-of "c" "äbc")"#, &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query(r#"(str/index-of "z" "abc")"#, &input).unwrap(), EdnValue::Integer(-1));
+        // contains? still checks collections
+        assert_eq!(eval_query("(contains? {:a 1} :a)", &input).unwrap(), EdnValue::Bool(true));
+
+        assert!(matches!(eval_query(r#"(starts-with? "a" :abc)"#, &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(matches!(eval_query(r#"(ends-with? 1 "abc")"#, &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query(r#"(index-of "a")"#, &input).is_err());
+    }
+
+    #[test]
+    fn test_str_replace() {
+        let input = crate::edn::Parser::new(r#"["a  b\t c" "2024-01-31"]"#).parse().unwrap();
+
+        assert_eq!(
+            eval_query(r#"(map (fn [x] (str/replace x "\\s+" " ")) .)"#, &input).unwrap().to_string(),
+            r#"["a b c" "2024-01-31"]"#
+        );
+        assert_eq!(
+            eval_query(r#"(str/replace "2024-01-31" "(\\d+)-(\\d+)-(\\d+)" "$3/$2/$1")"#, &input).unwrap(),
+            EdnValue::String("31/01/2024".to_string())
+        );
+        assert_eq!(
+            eval_query(r#"(str/replace-first "a.b.c" "\\." "-")"#, &input).unwrap(),
+            EdnValue::String("a-b.c".to_string())
+        );
+        assert_eq!(
+            eval_query(r#"(str/replace "abc" "x" "y")"#, &input).unwrap(),
+            EdnValue::String("abc".to_string())
+        );
+
+        let err = eval_query(r#"(str/replace "abc" "(" "y")"#, &input).unwrap_err();
+        assert!(matches!(err, EqError::QueryError { .. }));
+        assert!(err.to_string().contains("Invalid regex"));
+        assert!(matches!(eval_query(r#"(str/replace "abc" :a "y")"#, &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query(r#"(str/replace "abc" "a")"#, &input).is_err());
+    }
+
+    #[test]
+    fn test_line_utilities() {
+        let input = EdnValue::String("one\r\ntwo\n\nthree\n".to_string());
+
+        assert_eq!(eval_query("(str/split-lines .)", &input).unwrap().to_string(), r#"["one" "two" "" "three"]"#);
+        assert_eq!(eval_query(r#"(str/split-lines "")"#, &input).unwrap().to_string(), "[]");
+        assert_eq!(eval_query("(str/trim-newline .)", &input).unwrap(), EdnValue::String("one\r\ntwo\n\nthree".to_string()));
+        assert_eq!(eval_query(r#"(str/trim-newline "a\r\n\r\n")"#, &input).unwrap(), EdnValue::String("a\r\n".to_string()));
+        assert_eq!(eval_query(r#"(str/trim-newline "a")"#, &input).unwrap(), EdnValue::String("a".to_string()));
+
+        assert_eq!(eval_query(r#"(str/blank? " \t\n")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(str/blank? "")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(str/blank? nil)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(str/blank? " x ")"#, &input).unwrap(), EdnValue::Bool(false));
+
+        assert!(matches!(eval_query("(str/split-lines 1)", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query("(str/blank?)", &input).is_err());
+    }
+
+    #[test]
+    fn test_instants() {
+        let input = crate::edn::Parser::new(
+            r#"[#inst "2024-03-01T10:00:00Z" #inst "2024-03-01T11:30:00+02:00" #inst "2023-12-31T23:59:59.500"]"#,
+        ).parse().unwrap();
+
+        // 11:30+02:00 is 09:30 UTC, so it comes before 10:00Z
+        assert_eq!(eval_query("(inst/before? (second .) (first .))", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(inst/after? (second .) (first .))", &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query("(< (third .) (second .) (first .))", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(
+            eval_query("(max-by (fn [t] t) .)", &input).unwrap(),
+            EdnValue::Instant("2024-03-01T10:00:00Z".to_string())
+        );
+
+        assert_eq!(eval_query("(inst/epoch-ms (first .))", &input).unwrap(), EdnValue::Integer(1_709_287_200_000));
+        assert_eq!(eval_query("(inst/epoch-ms (third .))", &input).unwrap(), EdnValue::Integer(1_704_067_199_500));
+        assert_eq!(
+            eval_query(r#"(inst/format (second .) "%Y-%m-%d %H:%M %z")"#, &input).unwrap(),
+            EdnValue::String("2024-03-01 11:30 +0200".to_string())
+        );
+        assert!(eval_query(r#"(inst/format (first .) "%Q")"#, &input).is_err());
+
+        assert_eq!(
+            eval_query(r#"(inst/parse "2024-01-02T03:04:05Z")"#, &input).unwrap(),
+            EdnValue::Instant("2024-01-02T03:04:05Z".to_string())
+        );
+        assert_eq!(eval_query(r#"(inst/parse "yesterday")"#, &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query(r#"(inst/parse "2024-02-30T00:00:00Z")"#, &input).unwrap(), EdnValue::Nil);
+
+        assert!(matches!(eval_query(r#"(inst/before? (first .) "2024")"#, &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query("(inst/epoch-ms)", &input).is_err());
+    }
+
+    #[test]
+    fn test_uuids() {
+        let input = crate::edn::Parser::new(r#"#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6""#).parse().unwrap();
+
+        let random = eval_query("(uuid/random)", &input).unwrap();
+        let EdnValue::Uuid(s) = &random else { panic!("expected a uuid, got {}", random) };
+        assert_eq!(s.len(), 36);
+        assert_eq!(&s[14..15], "4");
+        assert_ne!(eval_query("(uuid/random)", &input).unwrap(), random);
+
+        assert_eq!(eval_query("(uuid? .)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(uuid? "f81d4fae-7dec-11d0-a765-00a0c91e6bf6")"#, &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(
+            eval_query("(uuid/str .)", &input).unwrap(),
+            EdnValue::String("f81d4fae-7dec-11d0-a765-00a0c91e6bf6".to_string())
+        );
+        assert_eq!(eval_query(r#"(uuid/parse "F81D4FAE7DEC11D0A76500A0C91E6BF6")"#, &input).unwrap(), input);
+        assert_eq!(eval_query(r#"(uuid/parse "not-a-uuid")"#, &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query(r#"(uuid/valid? "f81d4fae-7dec-11d0-a765-00a0c91e6bf6")"#, &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query(r#"(uuid/valid? "f81d4fae")"#, &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(eval_query("(uuid/valid? 42)", &input).unwrap(), EdnValue::Bool(false));
+        assert_eq!(
+            eval_query("(uuid/from-bytes [248 29 79 174 125 236 17 208 167 101 0 160 201 30 107 246])", &input).unwrap(),
+            input
+        );
+
+        assert!(eval_query("(uuid/from-bytes [1 2 3])", &input).is_err());
+        assert!(eval_query("(uuid/from-bytes [256 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0])", &input).is_err());
+        assert!(matches!(eval_query(r#"(uuid/str "x")"#, &input).unwrap_err(), EqError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_map_constructors() {
+        let input = crate::edn::Parser::new(r#"{:name "Alice" :items [1 2 3]}"#).parse().unwrap();
+
+        assert_eq!(
+            eval_query("(hash-map :name (:name .) :count (count (:items .)))", &input).unwrap().to_string(),
+            r#"{:name "Alice" :count 3}"#
+        );
+        assert_eq!(eval_query("(hash-map)", &input).unwrap().to_string(), "{}");
+        assert_eq!(eval_query("(array-map :b 1 :a 2 :b 3)", &input).unwrap().to_string(), "{:b 3 :a 2}");
+        assert_eq!(eval_query(r#"(sorted-map :c 1 :a 2 :b 3)"#, &input).unwrap().to_string(), "{:a 2 :b 3 :c 1}");
+        assert_eq!(eval_query(r#"(sorted-map 10 :x 2 :y 1.5 :z)"#, &input).unwrap().to_string(), "{1.5 :z 2 :y 10 :x}");
+
+        let err = eval_query("(hash-map :a 1 :b)", &input).unwrap_err();
+        assert!(matches!(err, EqError::QueryError { .. }));
+        assert!(eval_query("(sorted-map :a)", &input).is_err());
+    }
+
+    #[test]
+    fn test_collection_constructors() {
+        let input = crate::edn::Parser::new(r#"{:first-name "Ada" :last-name "Lovelace" :a 1 :b 2}"#).parse().unwrap();
+
+        assert_eq!(
+            eval_query("(vector (:first-name .) (:last-name .))", &input).unwrap().to_string(),
+            r#"["Ada" "Lovelace"]"#
+        );
+        assert_eq!(eval_query("(vector)", &input).unwrap(), EdnValue::Vector(vec![]));
+        assert_eq!(eval_query("(list (:a .) (:b .))", &input).unwrap().to_string(), "(1 2)");
+
+        let set = |items: Vec<EdnValue>| EdnValue::Set(items.into_iter().collect());
+        assert_eq!(
+            eval_query("(set (keys .))", &input).unwrap(),
+            set(vec![
+                EdnValue::Keyword("first-name".to_string()),
+                EdnValue::Keyword("last-name".to_string()),
+                EdnValue::Keyword("a".to_string()),
+                EdnValue::Keyword("b".to_string()),
+            ])
+        );
+        assert_eq!(eval_query("(set (list 1 2 1))", &input).unwrap(), set(vec![EdnValue::Integer(1), EdnValue::Integer(2)]));
+        assert_eq!(eval_query("(set nil)", &input).unwrap(), set(vec![]));
+        assert_eq!(
+            eval_query("(hash-set (:a .) (:b .) (:a .))", &input).unwrap(),
+            set(vec![EdnValue::Integer(1), EdnValue::Integer(2)])
+        );
+        assert_eq!(eval_query("(hash-set)", &input).unwrap(), set(vec![]));
+
+        assert!(matches!(eval_query("(set .)", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query("(set 1 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let input = crate::edn::Parser::new(r#"[{:a 1 :b 2} {:b 3 :c 4} {:c 10}]"#).parse().unwrap();
+
+        assert_eq!(
+            eval_query("(merge-with + {:a 1 :b 2} {:b 3 :c 4})", &input).unwrap().to_string(),
+            "{:a 1 :b 5 :c 4}"
+        );
+        assert_eq!(
+            eval_query("(merge-with (fn [old new] (list old new)) (first .) (second .) nil (third .))", &input).unwrap().to_string(),
+            "{:a 1 :b (2 3) :c (4 10)}"
+        );
+        assert_eq!(eval_query("(merge-with + {:a 1})", &input).unwrap().to_string(), "{:a 1}");
+
+        // Every map contributes to every key, with the default standing in
+        assert_eq!(
+            eval_query("(merge-with-default - 0 {:a 5 :b 1} {:b 2 :c 3})", &input).unwrap().to_string(),
+            "{:a 5 :b -1 :c -3}"
+        );
+        assert_eq!(
+            eval_query("(merge-with-default + 100 (first .) (second .) (third .))", &input).unwrap().to_string(),
+            "{:a 201 :b 105 :c 114}"
+        );
+
+        assert!(matches!(eval_query("(merge-with + {:a 1} [1])", &input).unwrap_err(), EqError::TypeError { .. }));
+        assert!(eval_query("(merge-with +)", &input).is_err());
+        assert!(eval_query("(merge-with-default + 0)", &input).is_err());
+        assert!(eval_query("(merge-with + {:a 1} {:a :x})", &input).is_err());
+    }
+
+    #[test]
+    fn test_env() {
+        let input = EdnValue::Nil;
+
+        assert_eq!(eval_query(r#"(env "EQ_SURELY_UNSET_VARIABLE")"#, &input).unwrap(), EdnValue::Nil);
+        assert_eq!(
+            eval_query(r#"(env "EQ_SURELY_UNSET_VARIABLE" "8080")"#, &input).unwrap(),
+            EdnValue::String("8080".to_string())
+        );
+        assert!(matches!(eval_query("(env-all)", &input).unwrap(), EdnValue::Map(_)));
+
+        assert!(eval_query("(env :home)", &input).is_err());
+        assert!(eval_query("(env)", &input).is_err());
+        assert!(eval_query(r#"(env-all "HOME")"#, &input).is_err());
+    }
+
+    #[test]
+    fn test_date_sequences() {
+        let input = EdnValue::Nil;
+
+        assert_eq!(
+            eval_query(r#"(range-of-dates #inst "2024-02-28T12:00:00Z" #inst "2024-03-01T12:00:00Z")"#, &input).unwrap().to_string(),
+            r#"[#inst "2024-02-28T12:00:00Z" #inst "2024-02-29T12:00:00Z"]"#
+        );
+        assert_eq!(
+            eval_query(r#"(range-of-dates #inst "2024-03-01T00:00:00Z" #inst "2024-03-01T00:00:00Z")"#, &input).unwrap(),
+            EdnValue::Vector(vec![])
+        );
+        assert!(eval_query(r#"(range-of-dates #inst "1990-01-01T00:00:00Z" #inst "2024-01-01T00:00:00Z")"#, &input).is_err());
+        assert!(eval_query(r#"(range-of-dates "2024-01-01" "2024-01-02")"#, &input).is_err());
+
+        // Offsets are kept and month ends clamp without drifting
+        assert_eq!(
+            eval_query(r#"(take 3 (date-seq #inst "2024-01-31T10:00:00+02:00" 1 :months))"#, &input).unwrap().to_string(),
+            r#"[#inst "2024-01-31T10:00:00+02:00" #inst "2024-02-29T10:00:00+02:00" #inst "2024-03-31T10:00:00+02:00"]"#
+        );
+        assert_eq!(
+            eval_query(r#"(take 2 (date-seq #inst "2024-03-01T00:00:00Z" -6 :hours))"#, &input).unwrap().to_string(),
+            r#"[#inst "2024-03-01T00:00:00Z" #inst "2024-02-29T18:00:00Z"]"#
+        );
+        assert_eq!(
+            eval_query(r#"(last (take 3 (date-seq #inst "2024-02-29T00:00:00Z" 1 :years)))"#, &input).unwrap().to_string(),
+            r#"#inst "2026-02-28T00:00:00Z""#
+        );
+        assert_eq!(eval_query(r#"(count (date-seq #inst "2024-01-01T00:00:00Z" 1 :days))"#, &input).unwrap(), EdnValue::Integer(10_000));
+
+        assert!(eval_query(r#"(date-seq #inst "2024-01-01T00:00:00Z" 0 :days)"#, &input).is_err());
+        assert!(eval_query(r#"(date-seq #inst "2024-01-01T00:00:00Z" 1 :weeks)"#, &input).is_err());
+        assert!(eval_query(r#"(date-seq #inst "2024-01-01T00:00:00Z" 1.5 :days)"#, &input).is_err());
+    }
+
+    #[test]
+    fn test_bitwise_operations() {
+        let input = EdnValue::Nil;
+        let eval = |q: &str| eval_query(q, &input).unwrap();
+
+        assert_eq!(eval("(bit-and 12 10)"), EdnValue::Integer(8));
+        assert_eq!(eval("(bit-or 12 10)"), EdnValue::Integer(14));
+        assert_eq!(eval("(bit-xor 12 10)"), EdnValue::Integer(6));
+        assert_eq!(eval("(bit-not 0)"), EdnValue::Integer(-1));
+        assert_eq!(eval("(bit-not -8)"), EdnValue::Integer(7));
+        assert_eq!(eval("(bit-shift-left 1 62)"), EdnValue::Integer(1 << 62));
+        assert_eq!(eval("(bit-shift-left 1 63)"), EdnValue::Integer(i64::MIN));
+        assert_eq!(eval("(bit-shift-right -16 2)"), EdnValue::Integer(-4));
+        assert_eq!(eval("(bit-test 8 3)"), EdnValue::Bool(true));
+        assert_eq!(eval("(bit-test 8 2)"), EdnValue::Bool(false));
+        assert_eq!(eval("(bit-test -1 63)"), EdnValue::Bool(true));
+
+        assert!(matches!(eval_query("(bit-and 1.0 1)", &input), Err(EqError::TypeError { .. })));
+        assert!(matches!(eval_query("(bit-or 1 2.0)", &input), Err(EqError::TypeError { .. })));
+        assert!(matches!(eval_query("(bit-not 1.5)", &input), Err(EqError::TypeError { .. })));
+        assert!(matches!(eval_query("(bit-shift-left 1 64)", &input), Err(EqError::QueryError { .. })));
+        assert!(matches!(eval_query("(bit-shift-right 1 -1)", &input), Err(EqError::QueryError { .. })));
+        assert!(matches!(eval_query("(bit-test 1 64)", &input), Err(EqError::QueryError { .. })));
+        assert!(eval_query("(bit-and 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let input = EdnValue::Nil;
+        let eval = |q: &str| eval_query(q, &input).unwrap();
+
+        assert_eq!(eval("(abs -5)"), EdnValue::Integer(5));
+        assert_eq!(eval("(abs -2.5)"), EdnValue::Float(2.5));
+        assert_eq!(eval("(abs -1/2)").to_string(), "1/2");
+        assert_eq!(eval("(abs -9223372036854775808)").to_string(), "9223372036854775808N");
+
+        assert_eq!(eval("(floor 2.7)"), EdnValue::Float(2.0));
+        assert_eq!(eval("(floor -2.5)"), EdnValue::Float(-3.0));
+        assert_eq!(eval("(ceil 2.1)"), EdnValue::Float(3.0));
+        assert_eq!(eval("(ceil 2)"), EdnValue::Float(2.0));
+
+        assert_eq!(eval("(round 2.5)"), EdnValue::Integer(3));
+        assert_eq!(eval("(round -2.5)"), EdnValue::Integer(-3));
+        assert_eq!(eval("(round 2.4)"), EdnValue::Integer(2));
+        assert_eq!(eval("(round 7/2)"), EdnValue::Integer(4));
+        assert_eq!(eval("(round 7)"), EdnValue::Integer(7));
+        assert!(matches!(eval("(round 1e30)"), EdnValue::BigInt(_)));
+
+        assert_eq!(eval("(sqrt 16)"), EdnValue::Float(4.0));
+        assert!(matches!(eval_query("(sqrt -1)", &input), Err(EqError::QueryError { .. })));
+
+        assert_eq!(eval("(pow 2 10)"), EdnValue::Integer(1024));
+        assert_eq!(eval("(pow 2 0)"), EdnValue::Integer(1));
+        assert_eq!(eval("(pow 2 64)").to_string(), "18446744073709551616N");
+        assert_eq!(eval("(pow 2 -1)"), EdnValue::Float(0.5));
+        assert_eq!(eval("(pow 2.0 3)"), EdnValue::Float(8.0));
+        assert_eq!(eval("(pow 4 0.5)"), EdnValue::Float(2.0));
+
+        assert_eq!(eval("(log 1)"), EdnValue::Float(0.0));
+        assert_eq!(eval("(log10 1000)"), EdnValue::Float(3.0));
+        assert_eq!(eval("(exp 0)"), EdnValue::Float(1.0));
+        assert!(matches!(eval_query("(log 0)", &input), Err(EqError::QueryError { .. })));
+        assert!(matches!(eval_query("(log10 -1)", &input), Err(EqError::QueryError { .. })));
+
+        assert!(matches!(eval_query(r#"(abs "x")"#, &input), Err(EqError::TypeError { .. })));
+        assert!(eval_query("(pow 2)", &input).is_err());
+        assert!(eval_query("(floor 1 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_numeric_constants() {
+        let input = EdnValue::Nil;
+        let eval = |q: &str| eval_query(q, &input).unwrap();
+
+        assert_eq!(eval("(min-val)"), EdnValue::Integer(i64::MIN));
+        assert_eq!(eval("(max-val)"), EdnValue::Integer(i64::MAX));
+        assert_eq!(eval("(infinity)"), EdnValue::Float(f64::INFINITY));
+        assert_eq!(eval("(negative-infinity)"), EdnValue::Float(f64::NEG_INFINITY));
+        assert!(matches!(eval("(nan)"), EdnValue::Float(f) if f.is_nan()));
+        assert!(eval_query("(infinity 1)", &input).is_err());
+
+        assert_eq!(eval("(< (max-val) (infinity))"), EdnValue::Bool(true));
+        assert_eq!(eval("(> (min-val) (negative-infinity))"), EdnValue::Bool(true));
+
+        assert_eq!(eval("(nan? (nan))"), EdnValue::Bool(true));
+        assert_eq!(eval("(nan? 1.5)"), EdnValue::Bool(false));
+        assert_eq!(eval("(infinite? (negative-infinity))"), EdnValue::Bool(true));
+        assert_eq!(eval("(infinite? (max-val))"), EdnValue::Bool(false));
+        assert_eq!(eval("(finite? 2.5)"), EdnValue::Bool(true));
+        assert_eq!(eval("(finite? 1/3)"), EdnValue::Bool(true));
+        assert_eq!(eval("(finite? (infinity))"), EdnValue::Bool(false));
+        assert_eq!(eval("(finite? (nan))"), EdnValue::Bool(false));
+        assert!(matches!(eval_query(r#"(nan? "x")"#, &input), Err(EqError::TypeError { .. })));
+
+        let scores = crate::edn::Parser::new("{:a 7 :b 3 :c 9}").parse().unwrap();
+        assert_eq!(
+            eval_query("(reduce-kv (fn [acc k v] (if (< v acc) v acc)) (infinity) .)", &scores).unwrap(),
+            EdnValue::Integer(3)
+        );
+    }
+
+    #[test]
+    fn test_variadic_lambdas() {
+        let input = EdnValue::Nil;
+
+        assert_eq!(
+            eval_query("((fn [a b & rest] (list a b rest)) 1 2 3 4)", &input).unwrap().to_string(),
+            "(1 2 [3 4])"
+        );
+        assert_eq!(eval_query("((fn [a & rest] rest) 1)", &input).unwrap(), EdnValue::Vector(vec![]));
+        assert_eq!(eval_query("(let [total (fn [& xs] (sum xs))] (total 1 2 3))", &input).unwrap(), EdnValue::Integer(6));
+        assert_eq!(eval_query("((fn [& xs] (count xs)))", &input).unwrap(), EdnValue::Integer(0));
+
+        // Too few arguments for the fixed parameters
+        let err = eval_query("((fn [a b & rest] a) 1)", &input).unwrap_err();
+        assert!(err.to_string().contains("at least 2 arguments"));
+
+        // & takes exactly one parameter after it
+        assert!(eval_query("(fn [a & b c] a)", &input).is_err());
+        assert!(eval_query("(fn [a &] a)", &input).is_err());
+        assert!(eval_query("(fn [& & a] a)", &input).is_err());
+
+        assert_eq!(eval_query("(fn [a & rest] a)", &input).unwrap().to_string(), "(fn [a & rest] a)");
+        assert_eq!(eval_query("(fn [& rest] rest)", &input).unwrap().to_string(), "(fn [& rest] rest)");
+    }
+
+    #[test]
+    fn test_destructuring() {
+        let input = crate::edn::Parser::new(r#"{:name "Alice" :age 30 :pos [3 4] :user/id 7}"#).parse().unwrap();
+        let eval = |q: &str| eval_query(q, &input).unwrap().to_string();
+
+        // Sequential destructuring, with & rest and :as
+        assert_eq!(eval("(let [[a b c] (list 1 2 3 4)] (list a b c))"), "(1 2 3)");
+        assert_eq!(eval("(let [[a b c] (list 1)] (list a b c))"), "(1 nil nil)");
+        assert_eq!(eval("(let [[a & more :as all] (list 1 2 3)] (list a more all))"), "(1 [2 3] (1 2 3))");
+        assert_eq!(eval("(let [[a [b c]] (list 1 (list 2 3))] (list a b c))"), "(1 2 3)");
+
+        // Map destructuring with :keys, :or, :as and key bindings
+        assert_eq!(eval(r#"(let [{:keys [name age]} .] (str name age))"#), r#""Alice30""#);
+        assert_eq!(eval("(let [{:keys [missing] :or {missing 0}} .] missing)"), "0");
+        assert_eq!(eval("(let [{:keys [age] :or {age 0}} .] age)"), "30");
+        assert_eq!(eval("(let [{:keys [id]} .] id)"), "nil");
+        assert_eq!(eval("(let [{:keys [user/id]} .] id)"), "7");
+        assert_eq!(eval("(let [{n :name [x y] :pos :as m} .] (list n x y (count m)))"), r#"("Alice" 3 4 4)"#);
+
+        // The value is evaluated once, before names in the pattern shadow anything
+        assert_eq!(eval("(let [x (list 1 2) [x y] x] (list x y))"), "(1 2)");
+
+        // fn parameters
+        assert_eq!(eval("((fn [{:keys [name]} [a b]] (list name a b)) . (list 1 2))"), r#"("Alice" 1 2)"#);
+        assert_eq!(eval("((fn [a & [b c]] (list a b c)) 1 2 3)"), "(1 2 3)");
+        assert_eq!(eval("(map (fn [[a b]] (+ a b)) (list (list 1 2) (list 3 4)))"), "[3 7]");
+
+        assert!(eval_query("(let [5 1] 1)", &input).is_err());
+        assert!(eval_query("(let [[a :as] (list 1)] a)", &input).is_err());
+        assert!(eval_query("(let [{:keys name} .] name)", &input).is_err());
+        assert!(eval_query("(let [{:keys [a] :or 1} .] a)", &input).is_err());
+        assert!(eval_query("(fn [5] 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_loop_and_recur() {
+        let input = crate::edn::Parser::new("[3 1 4]").parse().unwrap();
+
+        // Deep iteration doesn't overflow the stack
+        assert_eq!(
+            eval_query("(loop [n 10000 acc 0] (if (= n 0) acc (recur (- n 1) (+ acc n))))", &input).unwrap(),
+            EdnValue::Integer(50005000)
+        );
+        // Bindings are sequential, like let, and may destructure
+        assert_eq!(eval_query("(loop [a 1 b (+ a 1)] (list a b))", &input).unwrap().to_string(), "(1 2)");
+        assert_eq!(
+            eval_query("(loop [[x & more] . total 0] (if x (recur more (+ total x)) total))", &input).unwrap(),
+            EdnValue::Integer(8)
+        );
+
+        // recur in a fn calls it again
+        assert_eq!(
+            eval_query("((fn [n acc] (if (= n 0) acc (recur (- n 1) (* acc 2)))) 20 1)", &input).unwrap(),
+            EdnValue::Integer(1 << 20)
+        );
+        // The nearest loop or fn is the target
+        assert_eq!(
+            eval_query("(map (fn [n] (loop [i n acc 1] (if (= i 0) acc (recur (- i 1) (* acc i))))) .)", &input).unwrap().to_string(),
+            "[6 1 24]"
+        );
+        // try doesn't catch recur
+        assert_eq!(
+            eval_query("(loop [x 0] (try (if (< x 3) (recur (+ x 1)) x) (catch e :caught)))", &input).unwrap(),
+            EdnValue::Integer(3)
+        );
+
+        let err = eval_query("(loop [x 1] (recur 1 2))", &input).unwrap_err();
+        assert!(err.to_string().contains("recur expects 1 arguments"));
+        // when, if-let and do pass tail position on to their last form
+        assert_eq!(eval_query("(loop [x 3] (when (> x 0) (recur (- x 1))))", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(loop [x 2] (if-let [y (> x 0)] (do x (recur (- x 1))) :done))", &input).unwrap().to_string(), ":done");
+
+        // recur anywhere but tail position is an error, not a silent restart
+        for query in [
+            "(recur 1)",
+            "(loop [x 3] (if (> x 0) (+ 100 (recur (- x 1))) 0))",
+            "(loop [x 3] (do (recur 1) x))",
+            "(loop [x 3] (let [y (recur 1)] y))",
+            "(loop [x 3] (if (recur 1) x x))",
+            "((fn [n] (if (= n 0) 0 (list (recur (- n 1))))) 3)",
+            "(loop [x 1] (first (lazy-seq (recur 2))))",
+        ] {
+            let err = eval_query(query, &input).unwrap_err();
+            assert!(matches!(err, EqError::QueryError { .. }), "{}: {:?}", query, err);
+            assert_eq!(err.to_string(), "Query error: recur can only be used in tail position of a loop or fn", "{}", query);
+        }
+        assert!(eval_query("(loop [x] x)", &input).is_err());
+        assert!(eval_query("(loop x x)", &input).is_err());
+    }
+
+    #[test]
+    fn test_lazy_sequences() {
+        let input = EdnValue::Nil;
+        let eval = |query| eval_query(query, &input).unwrap().to_string();
+
+        // The body isn't evaluated until an element is needed
+        assert_eq!(eval("(let [s (lazy-seq (+ 1 :a))] 42)"), "42");
+        assert!(eval_query("(first (lazy-seq (+ 1 :a)))", &input).is_err());
+        assert_eq!(eval("(let [x 2] (first (lazy-seq (list x 3))))"), "2");
+        assert_eq!(eval("(rest (lazy-seq [1 2 3]))"), "(2 3)");
+        assert_eq!(eval("(first (lazy-seq nil))"), "nil");
+        assert_eq!(eval("(lazy-seq [1 2])"), "(1 2)");
+
+        // Infinite sequences are fine as long as only part is realized
+        assert_eq!(eval("(take 5 (iterate (fn [x] (* x 2)) 1))"), "[1 2 4 8 16]");
+        assert_eq!(eval("(realize 3 (iterate (fn [x] (+ x 1)) 0))"), "[0 1 2]");
+        assert_eq!(eval("(first (rest (rest (iterate (fn [x] (* x 3)) 1))))"), "9");
+        assert_eq!(eval("(realize 2 [1 2 3])"), "[1 2]");
+
+        // map over a lazy sequence only calls f for realized elements
+        assert_eq!(eval("(take 2 (map (fn [x] (/ 12 x)) (lazy-seq [1 2 0])))"), "[12 6]");
+        assert_eq!(eval("(realize 3 (map (fn [x] (- x)) (iterate (fn [x] (+ x 1)) 1)))"), "[-1 -2 -3]");
+
+        assert!(eval_query("(first (lazy-seq 5))", &input).is_err());
+        assert!(eval_query("(realize :a (lazy-seq []))", &input).is_err());
+    }
+
+    #[test]
+    fn test_lazy_sequence_arguments() {
+        let input = EdnValue::Nil;
+        let eval = |query: &str| eval_query(query, &input).unwrap().to_string();
+
+        // Builtins see a finite lazy sequence as the list it realizes to
+        let lazy = "(lazy-seq (list 3 1 2))";
+        let cases = [
+            ("(count {})", "3"),
+            ("(last {})", "2"),
+            ("(nth {} 1)", "1"),
+            ("(second {})", "1"),
+            ("(drop 1 {})", "[1 2]"),
+            ("(sum {})", "6"),
+            ("(select (fn [x] (> x 1)) {})", "[3 2]"),
+            ("(reverse {})", "[2 1 3]"),
+            ("(sort {})", "[1 2 3]"),
+            ("(for [x {}] (* x 2))", "[6 2 4]"),
+            ("(empty? {})", "false"),
+            ("(= {} [3 1 2])", "true"),
+            ("(= [3 1 2] {})", "true"),
+            ("(= {} (list 3 1 2) {})", "true"),
+            ("(= {} [3 1])", "false"),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(eval(&query.replace("{}", lazy)), expected, "{}", query);
+        }
+        assert_eq!(eval("(empty? (lazy-seq nil))"), "true");
+        assert_eq!(eval("(count (map (fn [x] x) (lazy-seq [1 2])))"), "2");
+
+        // Nested lazy sequences with the same elements are equal as set members and map keys
+        assert_eq!(eval("(distinct (list (lazy-seq (list 1)) (lazy-seq (list 1)) (lazy-seq (list 2))))"), "[(1) (2)]");
+        assert_eq!(eval("(count (frequencies (list (lazy-seq [1]) (lazy-seq (list 1)))))"), "1");
+
+        // Only as much as needed is realized, so these work on infinite sequences
+        assert_eq!(eval("(empty? (iterate (fn [x] x) 1))"), "false");
+        assert_eq!(eval("(coll? (iterate (fn [x] x) 1))"), "true");
+        assert_eq!(eval("(= (iterate (fn [x] x) 1) 1)"), "false");
+        assert_eq!(eval("(= (iterate (fn [x] x) 1) [1 1])"), "false");
+        assert_eq!(eval("(= (iterate (fn [x] (+ x 1)) 0) (iterate (fn [x] (+ x 1)) 0))"), "false");
+
+        // Anything that needs the whole of an infinite sequence fails rather than guessing
+        for query in ["(count (iterate (fn [x] x) 1))", "(last (iterate (fn [x] x) 1))", "(for [x (iterate (fn [x] x) 1)] x)"] {
+            let err = eval_query(query, &input).unwrap_err().to_string();
+            assert!(err.contains("more than 100000 elements"), "{}: {}", query, err);
+        }
+    }
+
+    #[test]
+    fn test_time_and_bench() {
+        let input = EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(2)]);
+        assert_eq!(eval_query("(time (count .))", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(bench 3 (first .))", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(bench (count .) (last .))", &input).unwrap(), EdnValue::Integer(2));
+
+        assert!(eval_query("(time)", &input).is_err());
+        assert!(eval_query("(time (+ 1 :a))", &input).is_err());
+        assert!(eval_query("(bench 0 1)", &input).unwrap_err().to_string().contains("positive run count"));
+        assert!(eval_query("(bench :a 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let input = EdnValue::Nil;
+        let eval = |query| eval_query(query, &input).unwrap().to_string();
+
+        assert_eq!(
+            eval("(diff {:a 1 :b 2 :c 3} {:b 2 :c 4 :d 5})"),
+            "{:only-in-a {:a 1} :only-in-b {:d 5} :in-both {:b 2} :changed {:c {:from 3 :to 4}}}"
+        );
+        assert_eq!(
+            eval("(diff [1 2 3] (list 1 5))"),
+            "{:only-in-a [3] :only-in-b [] :in-both [1] :changed [{:index 1 :from 2 :to 5}]}"
+        );
+        assert_eq!(eval("(diff #{1 2} #{2 3})"), "{:only-in-a #{1} :only-in-b #{3} :in-both #{2}}");
+        assert_eq!(eval("(diff :a :a)"), "{:only-in-a nil :only-in-b nil :in-both :a}");
+        assert_eq!(eval("(diff 1 \"1\")"), "{:only-in-a 1 :only-in-b \"1\" :in-both nil}");
+
+        // Collections that differ are diffed recursively
+        assert_eq!(
+            eval("(get-in (diff {:user {:name \"a\" :age 1}} {:user {:name \"a\" :age 2}}) [:changed :user :diff :changed])"),
+            "{:age {:from 1 :to 2}}"
+        );
+        assert!(eval_query("(diff 1)", &input).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = EdnValue::Map(IndexMap::from([(
+            EdnValue::Keyword("config".to_string()),
+            EdnValue::String("{:port 8080 :hosts [\"a\" \"b\"]}".to_string()),
+        )]));
+        assert_eq!(eval_query("(:port (parse (:config .)))", &input).unwrap(), EdnValue::Integer(8080));
+        assert_eq!(eval_query("(count (:hosts (parse (:config .))))", &input).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(parse (:missing .))", &input).unwrap(), EdnValue::Nil);
+
+        let err = eval_query("(parse \"{:a\")", &input).unwrap_err();
+        assert!(matches!(err, EqError::QueryError { .. }), "{:?}", err);
+        assert!(eval_query("(parse 12)", &input).is_err());
+    }
+
+    #[test]
+    fn test_string_padding() {
+        let input = EdnValue::Nil;
+        let string = |s: &str| EdnValue::String(s.to_string());
+        assert_eq!(eval_query(r#"(str/pad-left "hi" 5 \space)"#, &input).unwrap(), string("   hi"));
+        assert_eq!(eval_query(r#"(str/pad-right "hi" 5 \.)"#, &input).unwrap(), string("hi..."));
+        assert_eq!(eval_query(r#"(str/center "hi" 7 \*)"#, &input).unwrap(), string("**hi***"));
+        assert_eq!(eval_query(r#"(str/center "hi" 6 \*)"#, &input).unwrap(), string("**hi**"));
+
+        // Never truncates; widths count characters
+        assert_eq!(eval_query(r#"(str/pad-left "hello" 3 \space)"#, &input).unwrap(), string("hello"));
+        assert_eq!(eval_query(r#"(str/pad-left "héé" 4 \-)"#, &input).unwrap(), string("-héé"));
+        assert_eq!(eval_query(r#"(str/pad-right "x" -1 \-)"#, &input).unwrap(), string("x"));
+
+        assert!(eval_query(r#"(str/pad-left "hi" 5 " ")"#, &input).is_err());
+        assert!(eval_query(r#"(str/pad-left :hi 5 \space)"#, &input).is_err());
+        assert!(eval_query(r#"(str/pad-left "hi" 5)"#, &input).is_err());
+    }
+
+    #[test]
+    fn test_building_strings() {
+        let input = EdnValue::Nil;
+        let string = |s: &str| EdnValue::String(s.to_string());
+        assert_eq!(eval_query(r#"(str/repeat "abc" 3)"#, &input).unwrap(), string("abcabcabc"));
+        assert_eq!(eval_query(r#"(str/repeat "abc" 0)"#, &input).unwrap(), string(""));
+        assert!(eval_query(r#"(str/repeat "abc" 9223372036854775807)"#, &input).is_err());
+
+        assert_eq!(eval_query(r#"(str/insert "hello world" 5 " beautiful")"#, &input).unwrap(), string("hello beautiful world"));
+        assert_eq!(eval_query(r#"(str/insert "héllo" 2 "-")"#, &input).unwrap(), string("hé-llo"));
+        assert_eq!(eval_query(r#"(str/insert "ab" 2 "c")"#, &input).unwrap(), string("abc"));
+        assert!(eval_query(r#"(str/insert "ab" 3 "c")"#, &input).is_err());
+        assert!(eval_query(r#"(str/insert "ab" -1 "c")"#, &input).is_err());
+
+        assert_eq!(eval_query(r#"(str/remove-prefix "v1.2" "v")"#, &input).unwrap(), string("1.2"));
+        assert_eq!(eval_query(r#"(str/remove-prefix "1.2" "v")"#, &input).unwrap(), string("1.2"));
+        assert_eq!(eval_query(r#"(str/remove-suffix "data.edn" ".edn")"#, &input).unwrap(), string("data"));
+        assert_eq!(eval_query(r#"(str/remove-suffix "data.edn" ".json")"#, &input).unwrap(), string("data.edn"));
+
+        assert_eq!(eval_query(r#"(str/take "héllo" 2)"#, &input).unwrap(), string("hé"));
+        assert_eq!(eval_query(r#"(str/take "héllo" 10)"#, &input).unwrap(), string("héllo"));
+        assert_eq!(eval_query(r#"(str/drop "héllo" 2)"#, &input).unwrap(), string("llo"));
+        assert_eq!(eval_query(r#"(str/drop "héllo" -1)"#, &input).unwrap(), string("héllo"));
+
+        for query in [r#"(str/repeat :abc 3)"#, r#"(str/take nil 1)"#, r#"(str/remove-prefix "abc" 1)"#, r#"(str/insert "ab" 1 \c)"#] {
+            let err = eval_query(query, &input).unwrap_err();
+            assert!(matches!(err, EqError::TypeError { .. }), "{}: {:?}", query, err);
+        }
+    }
+
+    #[test]
+    fn test_radix_conversion() {
+        let input = EdnValue::Nil;
+        let string = |s: &str| EdnValue::String(s.to_string());
+        assert_eq!(eval_query("(number->string 255 16)", &input).unwrap(), string("ff"));
+        assert_eq!(eval_query("(number->string 10 2)", &input).unwrap(), string("1010"));
+        assert_eq!(eval_query("(number->string -35 36)", &input).unwrap(), string("-z"));
+        assert_eq!(eval_query("(number->string 42)", &input).unwrap(), string("42"));
+        assert_eq!(eval_query("(number->string 18446744073709551616N 16)", &input).unwrap(), string("10000000000000000"));
+
+        assert_eq!(eval_query(r#"(string->number "ff" 16)"#, &input).unwrap(), EdnValue::Integer(255));
+        assert_eq!(eval_query(r#"(string->number "FF" 16)"#, &input).unwrap(), EdnValue::Integer(255));
+        assert_eq!(eval_query(r#"(string->number "-101" 2)"#, &input).unwrap(), EdnValue::Integer(-5));
+        assert_eq!(eval_query(r#"(string->number "12")"#, &input).unwrap(), EdnValue::Integer(12));
+        assert_eq!(eval_query(r#"(string->number "ffffffffffffffff" 16)"#, &input).unwrap(), eval_query("18446744073709551615N", &input).unwrap());
+        assert_eq!(eval_query(r#"(string->number "12" 2)"#, &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query(r#"(string->number "" 16)"#, &input).unwrap(), EdnValue::Nil);
+
+        assert_eq!(eval_query(r#"(hex->int "ff")"#, &input).unwrap(), EdnValue::Integer(255));
+        assert_eq!(eval_query("(int->hex 255)", &input).unwrap(), string("ff"));
+        assert_eq!(eval_query(r#"(bin->int "1010")"#, &input).unwrap(), EdnValue::Integer(10));
+        assert_eq!(eval_query("(int->bin 10)", &input).unwrap(), string("1010"));
+        assert_eq!(eval_query(r#"(oct->int "17")"#, &input).unwrap(), EdnValue::Integer(15));
+        assert_eq!(eval_query("(int->oct 15)", &input).unwrap(), string("17"));
+
+        for query in ["(number->string 10 1)", "(number->string 10 37)", r#"(string->number "10" 0)"#, "(number->string 10 2.0)"] {
+            let err = eval_query(query, &input).unwrap_err();
+            assert!(matches!(err, EqError::QueryError { .. }), "{}: {:?}", query, err);
+        }
+        assert!(eval_query("(number->string 2.5 2)", &input).is_err());
+        assert!(eval_query("(hex->int 255)", &input).is_err());
+    }
+
+    #[test]
+    fn test_clamp() {
+        let input = EdnValue::Nil;
+        assert_eq!(eval_query("(clamp 150 0 100)", &input).unwrap(), EdnValue::Integer(100));
+        assert_eq!(eval_query("(clamp -5 0 100)", &input).unwrap(), EdnValue::Integer(0));
+        assert_eq!(eval_query("(clamp 50 0 100)", &input).unwrap(), EdnValue::Integer(50));
+        assert_eq!(eval_query("(clamp 0.5 0.0 0.25)", &input).unwrap(), EdnValue::Float(0.25));
+        assert_eq!(eval_query("(clamp 2.5 0 10)", &input).unwrap(), EdnValue::Float(2.5));
+        assert_eq!(eval_query("(clamp 1/2 1 2)", &input).unwrap(), EdnValue::Integer(1));
+
+        assert_eq!(eval_query("(clamp-or-nil 150 0 100)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(clamp-or-nil 100 0 100)", &input).unwrap(), EdnValue::Integer(100));
+        assert_eq!(eval_query("(in-range? 0 0 100)", &input).unwrap(), EdnValue::Bool(true));
+        assert_eq!(eval_query("(in-range? 100.5 0 100)", &input).unwrap(), EdnValue::Bool(false));
+
+        let err = eval_query("(clamp 5 10 0)", &input).unwrap_err();
+        assert!(matches!(err, EqError::QueryError { .. }), "{:?}", err);
+        assert!(eval_query("(in-range? 5 10 0)", &input).is_err());
+        assert!(eval_query(r#"(clamp "5" 0 10)"#, &input).is_err());
+        assert!(eval_query("(clamp 5 0)", &input).is_err());
+    }
+
+    #[test]
+    fn test_positions() {
+        let input = crate::edn::Parser::new("[{:id 7} {:id 42} {:id 9} {:id 42}]").parse().unwrap();
+        let ints = |ns: &[i64]| EdnValue::Vector(ns.iter().map(|n| EdnValue::Integer(*n)).collect());
+        assert_eq!(eval_query("(positions (fn [x] (= (:id x) 42)) .)", &input).unwrap(), ints(&[1, 3]));
+        assert_eq!(eval_query("(positions (fn [x] (= (:id x) 0)) .)", &input).unwrap(), ints(&[]));
+        assert_eq!(eval_query("(find-index (fn [x] (= (:id x) 42)) .)", &input).unwrap(), EdnValue::Integer(1));
+        assert_eq!(eval_query("(find-index (fn [x] (= (:id x) 0)) .)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(last-index-of-by (fn [x] (> (:id x) 8)) .)", &input).unwrap(), EdnValue::Integer(3));
+        assert_eq!(eval_query("(last-index-of-by (fn [x] (< (:id x) 7)) .)", &input).unwrap(), EdnValue::Nil);
+        assert_eq!(eval_query("(nth . (find-index (fn [x] (= (:id x) 9)) .))", &input).unwrap(), crate::edn::Parser::new("{:id 9}").parse().unwrap());
+        assert!(eval_query("(positions .)", &input).is_err());
+    }
+
+    #[test]
+    fn test_windows() {
+        let input = EdnValue::Nil;
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        assert_eq!(eval_query("(sliding-window 3 [1 2 3 4 5])", &input).unwrap(), parse("[[1 2 3] [2 3 4] [3 4 5]]"));
+        assert_eq!(eval_query("(sliding-window 1 (list :a :b))", &input).unwrap(), parse("[[:a] [:b]]"));
+        assert_eq!(eval_query("(sliding-window 3 [1 2])", &input).unwrap(), parse("[]"));
+        assert_eq!(eval_query("(strides 3 2 [1 2 3 4 5 6])", &input).unwrap(), parse("[[1 2 3] [3 4 5]]"));
+        assert_eq!(eval_query("(strides 2 2 [1 2 3 4])", &input).unwrap(), parse("[[1 2] [3 4]]"));
+        assert_eq!(eval_query("(strides 1 5 [1 2 3 4 5 6])", &input).unwrap(), parse("[[1] [6]]"));
+
+        for query in ["(strides 2 0 [1 2 3])", "(strides 2 -1 [1 2 3])", "(sliding-window 0 [1 2 3])"] {
+            let err = eval_query(query, &input).unwrap_err();
+            assert!(matches!(err, EqError::QueryError { .. }), "{}: {:?}", query, err);
+        }
+        assert!(eval_query("(sliding-window :a [1 2 3])", &input).is_err());
+    }
+
+    #[test]
+    fn test_tree_seq() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        let tree = parse(r#"{:name "root" :children [{:name "a" :children [{:name "a1"}]} {:name "b"}]}"#);
+        assert_eq!(eval_query("(map :name (tree-seq :children :children .))", &tree).unwrap(), parse(r#"["root" "a" "a1" "b"]"#));
+
+        let nested = parse("{:a 1 :b {:c 2 :d {:e 3}}}");
+        assert_eq!(
+            eval_query("(tree-seq map? vals .)", &nested).unwrap(),
+            parse("[{:a 1 :b {:c 2 :d {:e 3}}} 1 {:c 2 :d {:e 3}} 2 {:e 3} 3]")
+        );
+        // Equal subtrees in different places are all kept
+        assert_eq!(eval_query("(tree-seq vector? (fn [x] x) .)", &parse("[[1] [1]]")).unwrap(), parse("[[[1] [1]] [1] 1 [1] 1]"));
+        assert_eq!(eval_query("(tree-seq map? vals .)", &parse("7")).unwrap(), parse("[7]"));
+
+        // A node that is its own descendant, and children that never bottom out
+        assert!(eval_query("(tree-seq vector? (fn [x] (list x)) .)", &parse("[1]")).is_err());
+        assert!(eval_query("(tree-seq number? (fn [x] (list (+ x 1))) .)", &parse("0")).is_err());
+    }
+
+    #[test]
+    fn test_collection_predicates() {
+        let input = crate::edn::Parser::new("[{} [] () #{} 1]").parse().unwrap();
+        let bools = |bs: &[bool]| EdnValue::Vector(bs.iter().map(|b| EdnValue::Bool(*b)).collect());
+        assert_eq!(eval_query("(map map? .)", &input).unwrap(), bools(&[true, false, false, false, false]));
+        assert_eq!(eval_query("(map vector? .)", &input).unwrap(), bools(&[false, true, false, false, false]));
+        assert_eq!(eval_query("(map list? .)", &input).unwrap(), bools(&[false, false, true, false, false]));
+        assert_eq!(eval_query("(map set? .)", &input).unwrap(), bools(&[false, false, false, true, false]));
+        assert_eq!(eval_query("(map coll? .)", &input).unwrap(), bools(&[true, true, true, true, false]));
+        assert!(eval_query("(map? 1 2)", &input).is_err());
+    }
+
+    #[test]
+    fn test_with_default() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        let input = parse(r#"{:name "Alice" :active false :tags nil}"#);
+        let string = |s: &str| EdnValue::String(s.to_string());
+        assert_eq!(eval_query(r#"(with-default "Unknown" (:name .))"#, &input).unwrap(), string("Alice"));
+        assert_eq!(eval_query(r#"(with-default "Unknown" (:email .))"#, &input).unwrap(), string("Unknown"));
+        assert_eq!(eval_query("(?? (:tags .) [])", &input).unwrap(), EdnValue::Vector(vec![]));
+        // Only nil is replaced
+        assert_eq!(eval_query("(?? (:active .) true)", &input).unwrap(), EdnValue::Bool(false));
+
+        // ?? threads first and with-default threads last
+        assert_eq!(eval_query(r#"(-> . (:email) (?? "none"))"#, &input).unwrap(), string("none"));
+        assert_eq!(eval_query("(->> . (:tags) (map count) first (with-default 0))", &input).unwrap(), EdnValue::Integer(0));
+        assert_eq!(eval_query("(let [n 2] (?? (:age .) (* n 10)))", &input).unwrap(), EdnValue::Integer(20));
+        assert_eq!(eval_query("(?? (?? nil (:age .)) (?? nil 3))", &input).unwrap(), EdnValue::Integer(3));
+
+        // The expression is bound once rather than repeated
+        let expr = crate::analyzer::analyze(crate::query::QueryParser::parse("(?? (:name .) 0)").unwrap()).unwrap();
+        assert!(matches!(expr, Expr::Function { ref name, .. } if name == "let"));
+
+        assert!(eval_query("(with-default 1)", &input).is_err());
+        assert!(eval_query("(?? 1 2 3)", &input).is_err());
+    }
+
+    #[test]
+    fn test_keyword_functions() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        let users = parse(r#"[{:name "Alice" :admin true} {:name "Bob"} {:name "Alice"}]"#);
+        let keyword = |k: &str| EdnValue::Keyword(k.to_string());
+
+        // Keywords work wherever a function is expected
+        assert_eq!(eval_query("(map :name .)", &users).unwrap(), parse(r#"["Alice" "Bob" "Alice"]"#));
+        assert_eq!(eval_query("(select :admin .)", &users).unwrap(), parse(r#"[{:name "Alice" :admin true}]"#));
+        assert_eq!(eval_query("(count (distinct-by :name .))", &users).unwrap(), EdnValue::Integer(2));
+        assert_eq!(eval_query("(map (keyword->fn :name) .)", &users).unwrap(), parse(r#"["Alice" "Bob" "Alice"]"#));
+
+        assert_eq!(eval_query("(fn->keyword (fn [m] (:name m)))", &users).unwrap(), keyword("name"));
+        assert_eq!(eval_query("(fn->keyword #(get % :admin))", &users).unwrap(), keyword("admin"));
+        assert_eq!(eval_query("(fn->keyword (keyword->fn :id))", &users).unwrap(), keyword("id"));
+        assert_eq!(eval_query("(fn->keyword :id)", &users).unwrap(), keyword("id"));
+
+        // Lambdas that do anything else have no keyword
+        assert!(eval_query("(fn->keyword (fn [m] (:name .)))", &users).is_err());
+        assert!(eval_query("(fn->keyword (fn [a b] (:name a)))", &users).is_err());
+        assert!(eval_query("(fn->keyword first)", &users).is_err());
+        assert!(eval_query(r#"(keyword->fn "name")"#, &users).is_err());
+    }
+
+    #[test]
+    fn test_keys_by_type() {
+        let parse = |s: &str| crate::edn::Parser::new(s).parse().unwrap();
+        let input = parse(r#"{:a 1 "b" 2 c 3 4 5 :d 6}"#);
+        assert_eq!(eval_query("(keyword-keys .)", &input).unwrap(), parse("[:a :d]"));
+        assert_eq!(eval_query("(string-keys .)", &input).unwrap(), parse(r#"["b"]"#));
+        assert_eq!(eval_query("(symbol-keys .)", &input).unwrap(), parse("[c]"));
+        assert_eq!(eval_query("(keys-of :integer .)", &input).unwrap(), parse("[4]"));
+        assert_eq!(eval_query(r#"(keys-of "keyword" .)"#, &input).unwrap(), parse("[:a :d]"));
+        assert_eq!(eval_query("(keys-where keyword? .)", &input).unwrap(), parse("[:a :d]"));
+        assert_eq!(eval_query("(vals-where keyword? .)", &input).unwrap(), parse("[1 6]"));
+        assert_eq!(eval_query("(vals-where (fn [k] (not (keyword? k))) .)", &input).unwrap(), parse("[2 3 5]"));
+
+        // Like keys, anything but a map has no keys
+        assert_eq!(eval_query("(keyword-keys nil)", &input).unwrap(), parse("[]"));
+        assert!(eval_query("(keys-of 1 .)", &input).is_err());
+        assert!(eval_query("(keys-where keyword?)", &input).is_err());
+    }
+
+    #[test]
+    fn test_pretty_print_builtins() {
+        let input = crate::edn::Parser::new(r#"{:name "Alice" :age 30 :department "Engineering"}"#).parse().unwrap();
+        let string = |s: &str| EdnValue::String(s.to_string());
+        let pretty = "{ :name \"Alice\"\n  :age 30\n  :department \"Engineering\"}";
+
+        assert_eq!(eval_query("(pp .)", &input).unwrap(), input);
+        assert_eq!(eval_query("(pp-str .)", &input).unwrap(), string(pretty));
+        // Lazy sequences are realized first
+        assert_eq!(eval_query("(pp-str (lazy-seq (list 1 2)))", &input).unwrap(), string("(1 2)"));
+
+        assert_eq!(
+            eval_query("(with-format {:compact true :max-string-length 3} .)", &input).unwrap(),
+            string(r#"{:name "Ali..." :age 30 :department "Eng..."}"#)
+        );
+        assert_eq!(
+            eval_query("(with-format {:indent 4 :align-map-values true} .)", &input).unwrap(),
+            string("{ :name       \"Alice\"\n    :age        30\n    :department \"Engineering\"}")
+        );
+        assert_eq!(eval_query("(with-format {} .)", &input).unwrap(), string(pretty));
+        assert!(eval_query("(with-format {:colour true} .)", &input).is_err());
+        assert!(eval_query("(with-format {:indent -1} .)", &input).is_err());
+        assert!(eval_query("(with-format {:compact 1} .)", &input).is_err());
+        assert!(eval_query("(with-format :compact .)", &input).is_err());
+    }
+}
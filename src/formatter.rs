@@ -1,8 +1,9 @@
 use crate::edn::EdnValue;
-use crate::primitives::{escape_string, format_character};
-use crate::output::OutputConfig;
+use crate::primitives::{escape_string, format_character, format_float};
+use crate::output::{ColorMode, OutputConfig};
 use crate::collection_formatter::CollectionFormatter;
 use indexmap::IndexMap;
+use std::borrow::Cow;
 
 /// Trait for formatting EDN values
 pub trait Formatter {
@@ -12,72 +13,181 @@ pub trait Formatter {
     fn format_set(&self, items: &mut Vec<&EdnValue>, prefix: &str, close: char, config: &OutputConfig, depth: usize) -> String;
 }
 
+/// A string cut to `max_string_length` characters, with `...` marking the cut
+fn truncate_string<'a>(s: &'a str, config: &OutputConfig) -> Cow<'a, str> {
+    match config.max_string_length.and_then(|max| s.char_indices().nth(max)) {
+        Some((end, _)) => Cow::Owned(format!("{}...", &s[..end])),
+        None => Cow::Borrowed(s),
+    }
+}
+
+/// Stand-in for a collection nested more than `max_depth` levels deep
+fn depth_placeholder(value: &EdnValue, config: &OutputConfig, depth: usize) -> Option<String> {
+    match config.max_depth {
+        Some(max) if depth >= max => match value {
+            EdnValue::Vector(_) => Some("[...]".to_string()),
+            EdnValue::List(_) => Some("(...)".to_string()),
+            EdnValue::Map(_) => Some("{...}".to_string()),
+            EdnValue::Set(_) => Some("#{...}".to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// How many of `len` elements fit in `max_collection_size`, and the `...`
+/// that ends the collection when some don't
+fn shown(len: usize, config: &OutputConfig) -> (usize, Option<String>) {
+    match config.max_collection_size {
+        Some(max) if len > max => (max, Some("...".to_string())),
+        _ => (len, None),
+    }
+}
+
 /// Compact formatter - no whitespace
 pub struct CompactFormatter;
 
 impl Formatter for CompactFormatter {
-    fn format(&self, value: &EdnValue, config: &OutputConfig, _depth: usize) -> String {
+    fn format(&self, value: &EdnValue, config: &OutputConfig, depth: usize) -> String {
+        if let Some(placeholder) = depth_placeholder(value, config, depth) {
+            return placeholder;
+        }
         match value {
             EdnValue::Nil => "nil".to_string(),
             EdnValue::Bool(b) => b.to_string(),
             EdnValue::String(s) => {
+                let s = truncate_string(s, config);
                 if config.raw_strings {
-                    s.clone()
+                    s.into_owned()
                 } else {
-                    format!("\"{}\"", escape_string(s))
+                    format!("\"{}\"", escape_string(&s))
                 }
             }
             EdnValue::Keyword(k) => format!(":{}", k),
             EdnValue::Symbol(s) => s.clone(),
             EdnValue::Character(c) => format_character(*c),
             EdnValue::Integer(i) => i.to_string(),
-            EdnValue::Float(f) => f.to_string(),
-            EdnValue::Vector(v) => self.format_collection('[', ']', v, config, 0),
-            EdnValue::List(l) => self.format_collection('(', ')', l, config, 0),
-            EdnValue::Map(m) => self.format_map(m, config, 0),
+            EdnValue::BigInt(n) => format!("{}N", n),
+            EdnValue::Ratio { numerator, denominator } => format!("{}/{}", numerator, denominator),
+            EdnValue::Float(f) => format_float(*f),
+            EdnValue::Vector(v) => self.format_collection('[', ']', v, config, depth),
+            EdnValue::List(l) => self.format_collection('(', ')', l, config, depth),
+            EdnValue::Map(m) => self.format_map(m, config, depth),
             EdnValue::Set(s) => {
                 let mut items: Vec<&EdnValue> = s.iter().collect();
-                self.format_set(&mut items, "#{", '}', config, 0)
+                self.format_set(&mut items, "#{", '}', config, depth)
             }
             EdnValue::Tagged { tag, value } => {
-                format!("#{} {}", tag, self.format(value, config, 0))
+                format!("#{} {}", tag, self.format(value, config, depth))
             }
             EdnValue::WithMetadata { metadata, value } => {
-                format!("^{} {}", self.format(metadata, config, 0), self.format(value, config, 0))
+                format!("^{} {}", self.format(metadata, config, depth), self.format(value, config, depth))
             }
+            EdnValue::Lambda(lambda) if lambda.native.is_some() => self.format(&lambda.body, config, depth),
             EdnValue::Lambda(lambda) => {
-                let params = lambda.params.join(" ");
-                format!("(fn [{}] {})", params, self.format(&lambda.body, config, 0))
+                format!("(fn [{}] {})", lambda.param_list(), self.format(&lambda.body, config, depth))
             }
             EdnValue::Instant(s) => format!("#inst \"{}\"", s),
             EdnValue::Uuid(s) => format!("#uuid \"{}\"", s),
+            // format_output realizes these up front; this is only a fallback
+            EdnValue::LazySeq(_) => value.to_string(),
         }
     }
 
     fn format_collection(&self, open: char, close: char, items: &[EdnValue], config: &OutputConfig, depth: usize) -> String {
         let cf = CollectionFormatter::new(self, config);
-        let formatted = items.iter().map(|item| self.format(item, config, depth));
+        let (count, more) = shown(items.len(), config);
+        let formatted = items[..count].iter().map(|item| self.format(item, config, depth + 1)).chain(more);
         cf.format(&open.to_string(), &close.to_string(), formatted, depth, true)
     }
 
     fn format_map(&self, map: &IndexMap<EdnValue, EdnValue>, config: &OutputConfig, depth: usize) -> String {
         let cf = CollectionFormatter::new(self, config);
-        let pairs = map.iter().map(|(k, v)| (self.format(k, config, depth), self.format(v, config, depth)));
-        cf.format_pairs("{", "}", pairs, depth, true)
+        let (count, more) = shown(map.len(), config);
+        let pairs = map.iter().take(count)
+            .map(|(k, v)| (self.format(k, config, depth + 1), self.format(v, config, depth + 1)));
+        cf.format_pairs("{", "}", pairs, more, depth, true)
     }
 
     fn format_set(&self, items: &mut Vec<&EdnValue>, prefix: &str, close: char, config: &OutputConfig, depth: usize) -> String {
         items.sort_by_key(|v| format!("{:?}", v)); // Ensure deterministic output
         let cf = CollectionFormatter::new(self, config);
-        let formatted = items.iter().map(|item| self.format(item, config, depth));
+        let (count, more) = shown(items.len(), config);
+        let formatted = items[..count].iter().map(|item| self.format(item, config, depth + 1)).chain(more);
         cf.format(prefix, &close.to_string(), formatted, depth, true)
     }
 }
 
-/// Pretty formatter - indented, multi-line
+// ANSI SGR color codes used by the pretty formatter
+const KEYWORD_COLOR: &str = "36";
+const STRING_COLOR: &str = "32";
+const NUMBER_COLOR: &str = "33";
+const LITERAL_COLOR: &str = "31";
+const MAP_KEY_COLOR: &str = "34";
+
+/// Pretty formatter - indented, multi-line, optionally colorized
 pub struct PrettyFormatter;
 
 impl PrettyFormatter {
+    fn paint(&self, config: &OutputConfig, color: &str, text: String) -> String {
+        if config.color == ColorMode::Always {
+            format!("\x1b[{}m{}\x1b[0m", color, text)
+        } else {
+            text
+        }
+    }
+
+    /// Map keys are painted as a whole, so nested scalar colors don't apply
+    fn format_key(&self, key: &EdnValue, config: &OutputConfig, depth: usize) -> String {
+        if config.color == ColorMode::Always && !self.is_collection(key) {
+            self.paint(config, MAP_KEY_COLOR, CompactFormatter.format(key, config, depth))
+        } else {
+            self.format(key, config, depth)
+        }
+    }
+
+    /// Single-line rendering for collections that fit; same layout as
+    /// `CompactFormatter`, but keeps the colors
+    fn format_inline(&self, value: &EdnValue, config: &OutputConfig, depth: usize) -> String {
+        if config.color != ColorMode::Always {
+            return CompactFormatter.format(value, config, depth);
+        }
+        if let Some(placeholder) = depth_placeholder(value, config, depth) {
+            return placeholder;
+        }
+        let cf = CollectionFormatter::new(self, config);
+        match value {
+            EdnValue::Vector(items) | EdnValue::List(items) => {
+                let (open, close) = if matches!(value, EdnValue::Vector(_)) { ("[", "]") } else { ("(", ")") };
+                let (count, more) = shown(items.len(), config);
+                let formatted = items[..count].iter().map(|item| self.format_inline(item, config, depth + 1));
+                cf.format(open, close, formatted.chain(more), 0, true)
+            }
+            EdnValue::Set(s) => {
+                let mut items: Vec<&EdnValue> = s.iter().collect();
+                items.sort_by_key(|v| format!("{:?}", v));
+                let (count, more) = shown(items.len(), config);
+                let formatted = items[..count].iter().map(|item| self.format_inline(item, config, depth + 1));
+                cf.format("#{", "}", formatted.chain(more), 0, true)
+            }
+            EdnValue::Map(m) => {
+                let (count, more) = shown(m.len(), config);
+                let pairs = m.iter().take(count)
+                    .map(|(k, v)| (self.format_key(k, config, depth + 1), self.format_inline(v, config, depth + 1)));
+                cf.format_pairs("{", "}", pairs, more, 0, true)
+            }
+            EdnValue::Tagged { tag, value } => format!("#{} {}", tag, self.format_inline(value, config, depth)),
+            EdnValue::WithMetadata { metadata, value } => {
+                format!("^{} {}", self.format_inline(metadata, config, depth), self.format_inline(value, config, depth))
+            }
+            EdnValue::Lambda(lambda) if lambda.native.is_some() => self.format_inline(&lambda.body, config, depth),
+            EdnValue::Lambda(lambda) => {
+                format!("(fn [{}] {})", lambda.param_list(), self.format_inline(&lambda.body, config, depth))
+            }
+            _ => self.format(value, config, depth),
+        }
+    }
+
     fn make_indent(&self, config: &OutputConfig, depth: usize) -> String {
         if config.use_tabs {
             "\t".repeat(depth)
@@ -109,21 +219,29 @@ impl PrettyFormatter {
 
 impl Formatter for PrettyFormatter {
     fn format(&self, value: &EdnValue, config: &OutputConfig, depth: usize) -> String {
+        if let Some(placeholder) = depth_placeholder(value, config, depth) {
+            return placeholder;
+        }
         match value {
-            EdnValue::Nil => "nil".to_string(),
-            EdnValue::Bool(b) => b.to_string(),
+            EdnValue::Nil => self.paint(config, LITERAL_COLOR, "nil".to_string()),
+            EdnValue::Bool(b) => self.paint(config, LITERAL_COLOR, b.to_string()),
             EdnValue::String(s) => {
+                let s = truncate_string(s, config);
                 if config.raw_strings {
-                    s.clone()
+                    s.into_owned()
                 } else {
-                    format!("\"{}\"", escape_string(s))
+                    self.paint(config, STRING_COLOR, format!("\"{}\"", escape_string(&s)))
                 }
             }
-            EdnValue::Keyword(k) => format!(":{}", k),
+            EdnValue::Keyword(k) => self.paint(config, KEYWORD_COLOR, format!(":{}", k)),
             EdnValue::Symbol(s) => s.clone(),
             EdnValue::Character(c) => format_character(*c),
-            EdnValue::Integer(i) => i.to_string(),
-            EdnValue::Float(f) => f.to_string(),
+            EdnValue::Integer(i) => self.paint(config, NUMBER_COLOR, i.to_string()),
+            EdnValue::BigInt(n) => self.paint(config, NUMBER_COLOR, format!("{}N", n)),
+            EdnValue::Ratio { numerator, denominator } => {
+                self.paint(config, NUMBER_COLOR, format!("{}/{}", numerator, denominator))
+            }
+            EdnValue::Float(f) => self.paint(config, NUMBER_COLOR, format_float(*f)),
             EdnValue::Vector(v) => self.format_collection('[', ']', v, config, depth),
             EdnValue::List(l) => self.format_collection('(', ')', l, config, depth),
             EdnValue::Map(m) => self.format_map(m, config, depth),
@@ -137,25 +255,28 @@ impl Formatter for PrettyFormatter {
             EdnValue::WithMetadata { metadata, value } => {
                 format!("^{} {}", self.format(metadata, config, depth), self.format(value, config, depth))
             }
+            EdnValue::Lambda(lambda) if lambda.native.is_some() => self.format(&lambda.body, config, depth),
             EdnValue::Lambda(lambda) => {
-                let params = lambda.params.join(" ");
-                format!("(fn [{}] {})", params, self.format(&lambda.body, config, depth))
+                format!("(fn [{}] {})", lambda.param_list(), self.format(&lambda.body, config, depth))
             }
             EdnValue::Instant(s) => format!("#inst \"{}\"", s),
             EdnValue::Uuid(s) => format!("#uuid \"{}\"", s),
+            // format_output realizes these up front; this is only a fallback
+            EdnValue::LazySeq(_) => value.to_string(),
         }
     }
 
     fn format_collection(&self, open: char, close: char, items: &[EdnValue], config: &OutputConfig, depth: usize) -> String {
         let cf = CollectionFormatter::new(self, config);
+        let (count, more) = shown(items.len(), config);
+        let items = &items[..count];
         let should_inline = cf.should_inline(items);
         
         if should_inline {
-            let compact = CompactFormatter;
-            let formatted = items.iter().map(|item| compact.format(item, config, 0));
+            let formatted = items.iter().map(|item| self.format_inline(item, config, depth + 1)).chain(more);
             cf.format(&open.to_string(), &close.to_string(), formatted, depth, true)
         } else {
-            let formatted = items.iter().map(|item| self.format(item, config, depth + 1));
+            let formatted = items.iter().map(|item| self.format(item, config, depth + 1)).chain(more);
             cf.format(&open.to_string(), &close.to_string(), formatted, depth, false)
         }
     }
@@ -163,23 +284,38 @@ impl Formatter for PrettyFormatter {
     fn format_map(&self, map: &IndexMap<EdnValue, EdnValue>, config: &OutputConfig, depth: usize) -> String {
         let cf = CollectionFormatter::new(self, config);
         let compact = CompactFormatter;
+        let (count, more) = shown(map.len(), config);
         
         // Calculate estimated length
-        let estimated_length: usize = map.iter()
-            .map(|(k, v)| compact.format(k, config, 0).len() + compact.format(v, config, 0).len() + 1)
-            .sum::<usize>() + map.len() * 2;
+        let estimated_length: usize = map.iter().take(count)
+            .map(|(k, v)| compact.format(k, config, depth + 1).len() + compact.format(v, config, depth + 1).len() + 1)
+            .sum::<usize>() + count * 2;
         
-        let should_inline = cf.should_inline_map(map.len(), estimated_length);
+        let should_inline = cf.should_inline_map(count, estimated_length);
         
         if should_inline {
-            let pairs = map.iter().map(|(k, v)| (compact.format(k, config, 0), compact.format(v, config, 0)));
-            cf.format_pairs("{", "}", pairs, depth, true)
+            let pairs = map.iter().take(count)
+                .map(|(k, v)| (self.format_key(k, config, depth + 1), self.format_inline(v, config, depth + 1)));
+            cf.format_pairs("{", "}", pairs, more, depth, true)
         } else {
             // Multi-line with special handling for nested collections
             let mut result = String::new();
             result.push('{');
             
-            for (i, (key, value)) in map.iter().enumerate() {
+            // First pass: key widths, without color codes, for align_map_values
+            let keys: Vec<(String, usize)> = map.iter().take(count)
+                .map(|(key, _)| {
+                    let width = if config.align_map_values {
+                        compact.format(key, config, depth + 1).chars().count()
+                    } else {
+                        0
+                    };
+                    (self.format_key(key, config, depth + 1), width)
+                })
+                .collect();
+            let key_column = keys.iter().map(|(_, width)| *width).max().unwrap_or(0);
+            
+            for (i, ((key, width), value)) in keys.into_iter().zip(map.values()).enumerate() {
                 if i == 0 {
                     result.push(' ');
                 } else {
@@ -187,17 +323,24 @@ impl Formatter for PrettyFormatter {
                     result.push_str(&self.make_indent(config, depth + 1));
                 }
                 
-                result.push_str(&self.format(key, config, depth + 1));
+                result.push_str(&key);
                 result.push(' ');
                 
                 // If value is a collection, put it on the next line
                 if self.is_collection(value) && !self.is_simple_collection(value) {
                     result.push('\n');
                     result.push_str(&self.make_indent(config, depth + 1));
+                } else {
+                    result.push_str(&" ".repeat(key_column - width));
                 }
                 
                 result.push_str(&self.format(value, config, depth + 1));
             }
+            if let Some(more) = more {
+                result.push('\n');
+                result.push_str(&self.make_indent(config, depth + 1));
+                result.push_str(&more);
+            }
             
             result.push('}');
             result
@@ -208,15 +351,16 @@ impl Formatter for PrettyFormatter {
         items.sort_by_key(|v| format!("{:?}", v)); // Deterministic ordering
         
         let cf = CollectionFormatter::new(self, config);
+        let (count, more) = shown(items.len(), config);
+        let items = &items[..count];
         let items_slice: Vec<EdnValue> = items.iter().map(|&v| v.clone()).collect();
         let should_inline = cf.should_inline(&items_slice);
         
         if should_inline {
-            let compact = CompactFormatter;
-            let formatted = items.iter().map(|item| compact.format(item, config, 0));
+            let formatted = items.iter().map(|item| self.format_inline(item, config, depth + 1)).chain(more);
             cf.format(prefix, &close.to_string(), formatted, depth, true)
         } else {
-            let formatted = items.iter().map(|item| self.format(item, config, depth + 1));
+            let formatted = items.iter().map(|item| self.format(item, config, depth + 1)).chain(more);
             cf.format(prefix, &close.to_string(), formatted, depth, false)
         }
     }
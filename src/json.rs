@@ -0,0 +1,353 @@
+//! JSON conversion for EDN values
+
+use crate::edn::EdnValue;
+use crate::error::{EqError, EqResult};
+use crate::output::OutputConfig;
+use indexmap::IndexMap;
+
+/// Format an EDN value as JSON.
+///
+/// Keywords, symbols, characters, instants and uuids become strings; sets and
+/// lists become arrays. Tagged values, metadata and functions have no JSON
+/// equivalent and produce an error.
+pub fn format_json(value: &EdnValue, config: &OutputConfig) -> EqResult<String> {
+    if config.raw_strings {
+        if let EdnValue::String(s) = value {
+            return Ok(s.clone());
+        }
+    }
+
+    let mut out = String::new();
+    write_value(&mut out, value, config, 0)?;
+    Ok(out)
+}
+
+fn write_value(out: &mut String, value: &EdnValue, config: &OutputConfig, depth: usize) -> EqResult<()> {
+    match value {
+        EdnValue::Nil => out.push_str("null"),
+        EdnValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        EdnValue::Integer(i) => out.push_str(&i.to_string()),
+        EdnValue::BigInt(n) => out.push_str(&n.to_string()),
+        // JSON has no exact rationals
+        EdnValue::Ratio { numerator, denominator } => {
+            out.push_str(&(*numerator as f64 / *denominator as f64).to_string());
+        }
+        EdnValue::Float(f) => {
+            if !f.is_finite() {
+                return Err(EqError::query_error(format!("Cannot represent {} in JSON", f)));
+            }
+            out.push_str(&f.to_string());
+        }
+        EdnValue::String(s) | EdnValue::Instant(s) | EdnValue::Uuid(s) => write_string(out, s),
+        EdnValue::Keyword(k) => write_string(out, k),
+        EdnValue::Symbol(s) => write_string(out, s),
+        EdnValue::Character(c) => write_string(out, &c.to_string()),
+        EdnValue::Vector(items) | EdnValue::List(items) => {
+            write_array(out, items.iter(), config, depth)?;
+        }
+        EdnValue::Set(items) => {
+            // Sort for deterministic output, matching the EDN formatters
+            let mut items: Vec<&EdnValue> = items.iter().collect();
+            items.sort_by_key(|item| item.to_string());
+            write_array(out, items.into_iter(), config, depth)?;
+        }
+        EdnValue::Map(map) => {
+            if map.is_empty() {
+                out.push_str("{}");
+                return Ok(());
+            }
+            out.push('{');
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_separator(out, config, depth + 1);
+                write_string(out, &json_key(k)?);
+                out.push(':');
+                if !config.compact {
+                    out.push(' ');
+                }
+                write_value(out, v, config, depth + 1)?;
+            }
+            write_separator(out, config, depth);
+            out.push('}');
+        }
+        EdnValue::Tagged { tag, .. } => {
+            return Err(EqError::query_error(format!("Tagged value #{} has no JSON representation", tag)));
+        }
+        EdnValue::WithMetadata { .. } => {
+            return Err(EqError::query_error("Value with metadata has no JSON representation"));
+        }
+        EdnValue::Lambda(_) => {
+            return Err(EqError::query_error("Functions have no JSON representation"));
+        }
+        EdnValue::LazySeq(seq) => {
+            let items = seq.realize()?;
+            write_array(out, items.iter(), config, depth)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_array<'a>(
+    out: &mut String,
+    items: impl ExactSizeIterator<Item = &'a EdnValue>,
+    config: &OutputConfig,
+    depth: usize,
+) -> EqResult<()> {
+    if items.len() == 0 {
+        out.push_str("[]");
+        return Ok(());
+    }
+    out.push('[');
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_separator(out, config, depth + 1);
+        write_value(out, item, config, depth + 1)?;
+    }
+    write_separator(out, config, depth);
+    out.push(']');
+    Ok(())
+}
+
+/// Newline plus indentation in pretty mode, nothing in compact mode
+fn write_separator(out: &mut String, config: &OutputConfig, depth: usize) {
+    if config.compact {
+        return;
+    }
+    out.push('\n');
+    if config.use_tabs {
+        out.push_str(&"\t".repeat(depth));
+    } else {
+        out.push_str(&" ".repeat(depth * config.indent_size));
+    }
+}
+
+/// JSON object keys must be strings; scalar EDN keys are converted
+pub(crate) fn json_key(key: &EdnValue) -> EqResult<String> {
+    match key {
+        EdnValue::String(s) | EdnValue::Keyword(s) | EdnValue::Symbol(s)
+        | EdnValue::Instant(s) | EdnValue::Uuid(s) => Ok(s.clone()),
+        EdnValue::Character(c) => Ok(c.to_string()),
+        EdnValue::BigInt(n) => Ok(n.to_string()),
+        EdnValue::Nil | EdnValue::Bool(_) | EdnValue::Integer(_) | EdnValue::Float(_)
+        | EdnValue::Ratio { .. } => Ok(key.to_string()),
+        other => Err(EqError::query_error(format!(
+            "Cannot use {} as a JSON object key",
+            other.type_name()
+        ))),
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parse a single JSON document into an EDN value.
+///
+/// Objects become maps with keyword keys, arrays become vectors and null becomes nil.
+pub fn json_to_edn(s: &str) -> EqResult<EdnValue> {
+    let value: serde_json::Value = serde_json::from_str(s).map_err(|e| json_parse_error(e, s, None))?;
+    Ok(convert_json(value))
+}
+
+/// Parse a stream of whitespace-separated JSON documents
+pub fn json_stream_to_edn(s: &str, filename: Option<&str>) -> EqResult<Vec<EdnValue>> {
+    serde_json::Deserializer::from_str(s)
+        .into_iter::<serde_json::Value>()
+        .map(|value| value.map(convert_json).map_err(|e| json_parse_error(e, s, filename)))
+        .collect()
+}
+
+/// Parse newline-delimited JSON, one document per non-empty line.
+/// Errors report the line number within the whole input.
+pub fn ndjson_to_edn(s: &str, filename: Option<&str>) -> EqResult<Vec<EdnValue>> {
+    let mut values = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = json_to_edn(line).map_err(|e| match e {
+            EqError::ParseError { column, message, .. } => {
+                EqError::parse_error_with_file(filename.map(|f| f.to_string()), i + 1, column, message)
+                    .with_source_context(line)
+            }
+            other => other,
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Guess whether input is JSON from its first non-whitespace character
+pub fn looks_like_json(s: &str) -> bool {
+    matches!(s.trim_start().chars().next(), Some(c) if c == '{' || c == '[' || c == '"' || c.is_ascii_digit())
+}
+
+fn convert_json(value: serde_json::Value) -> EdnValue {
+    match value {
+        serde_json::Value::Null => EdnValue::Nil,
+        serde_json::Value::Bool(b) => EdnValue::Bool(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => EdnValue::Integer(i),
+            None => EdnValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => EdnValue::String(s),
+        serde_json::Value::Array(items) => EdnValue::Vector(items.into_iter().map(convert_json).collect()),
+        serde_json::Value::Object(map) => {
+            let map: IndexMap<EdnValue, EdnValue> = map.into_iter()
+                .map(|(k, v)| (EdnValue::Keyword(k), convert_json(v)))
+                .collect();
+            EdnValue::Map(map)
+        }
+    }
+}
+
+/// Objects become maps with keyword keys, as with json_to_edn
+#[cfg(feature = "serde-json")]
+impl From<serde_json::Value> for EdnValue {
+    fn from(value: serde_json::Value) -> Self {
+        convert_json(value)
+    }
+}
+
+/// Fails for values --json-output can't represent either
+#[cfg(feature = "serde-json")]
+impl TryFrom<&EdnValue> for serde_json::Value {
+    type Error = serde_json::Error;
+
+    fn try_from(value: &EdnValue) -> Result<Self, Self::Error> {
+        serde_json::to_value(value)
+    }
+}
+
+fn json_parse_error(err: serde_json::Error, input: &str, filename: Option<&str>) -> EqError {
+    let error = EqError::parse_error_with_file(
+        filename.map(|f| f.to_string()),
+        err.line(),
+        err.column(),
+        format!("Invalid JSON: {}", err),
+    );
+    match input.lines().nth(err.line().saturating_sub(1)) {
+        Some(line) if !line.trim().is_empty() => error.with_source_context(line),
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edn::Parser;
+
+    fn compact() -> OutputConfig {
+        OutputConfig { compact: true, ..OutputConfig::default() }
+    }
+
+    fn to_json(edn: &str) -> EqResult<String> {
+        let value = Parser::new(edn).parse().unwrap();
+        format_json(&value, &compact())
+    }
+
+    #[test]
+    fn test_json_primitives() {
+        assert_eq!(to_json("nil").unwrap(), "null");
+        assert_eq!(to_json("true").unwrap(), "true");
+        assert_eq!(to_json("42").unwrap(), "42");
+        assert_eq!(to_json("2.5").unwrap(), "2.5");
+        assert_eq!(to_json("12345678901234567890N").unwrap(), "12345678901234567890");
+        assert_eq!(to_json("1/4").unwrap(), "0.25");
+        assert_eq!(to_json(":name").unwrap(), "\"name\"");
+        assert_eq!(to_json("sym").unwrap(), "\"sym\"");
+        assert_eq!(to_json("\\a").unwrap(), "\"a\"");
+        assert_eq!(to_json("\"line\\nbreak \\\"q\\\"\"").unwrap(), "\"line\\nbreak \\\"q\\\"\"");
+    }
+
+    #[test]
+    fn test_json_collections() {
+        assert_eq!(to_json("[1 (2 3) #{4}]").unwrap(), "[1,[2,3],[4]]");
+        assert_eq!(to_json("{:a 1 \"b\" [] 3 {}}").unwrap(), "{\"a\":1,\"b\":[],\"3\":{}}");
+    }
+
+    #[test]
+    fn test_json_pretty() {
+        let value = Parser::new("{:a [1 2]}").parse().unwrap();
+        let result = format_json(&value, &OutputConfig::default()).unwrap();
+        assert_eq!(result, "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+    }
+
+    #[test]
+    fn test_json_unrepresentable() {
+        assert!(to_json("#myapp/tag 1").is_err());
+        assert!(to_json("^{:doc \"x\"} [1]").is_err());
+        assert!(to_json("{[1] 2}").is_err());
+    }
+
+    #[test]
+    fn test_json_to_edn() {
+        let value = json_to_edn(r#"{"name": "Alice", "age": 30, "score": 9.5, "tags": ["a", null, true]}"#).unwrap();
+        assert_eq!(value.to_string(), r#"{:name "Alice" :age 30 :score 9.5 :tags ["a" nil true]}"#);
+
+        assert!(json_to_edn("{:a 1}").is_err());
+    }
+
+    #[test]
+    fn test_json_stream() {
+        let values = json_stream_to_edn("{\"a\": 1}\n[2]\n3", None).unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[2], EdnValue::Integer(3));
+
+        let err = json_stream_to_edn("[1,\n 2,", Some("data.json")).unwrap_err();
+        assert!(err.to_string().contains("data.json"));
+    }
+
+    #[test]
+    fn test_looks_like_json() {
+        assert!(looks_like_json("  {\"a\": 1}"));
+        assert!(looks_like_json("[1, 2]"));
+        assert!(looks_like_json("42"));
+        assert!(!looks_like_json(":keyword"));
+        assert!(!looks_like_json("#{1 2}"));
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let values = ndjson_to_edn("{\"a\": 1}\n\n[1, 2]\n", None).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].to_string(), "[1 2]");
+
+        let err = ndjson_to_edn("{\"a\": 1}\n{\"b\": }\n", Some("events.ndjson")).unwrap_err();
+        match err {
+            EqError::ParseError { filename, line, .. } => {
+                assert_eq!(filename.as_deref(), Some("events.ndjson"));
+                assert_eq!(line, 2);
+            }
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serde-json")]
+    #[test]
+    fn test_serde_json_value_conversions() {
+        let json = serde_json::json!({"a": [1, null]});
+        let value = EdnValue::from(json.clone());
+        assert_eq!(value, Parser::new("{:a [1 nil]}").parse().unwrap());
+        assert_eq!(serde_json::Value::try_from(&value).unwrap(), json);
+        assert!(serde_json::Value::try_from(&Parser::new("#point [1]").parse().unwrap()).is_err());
+    }
+}
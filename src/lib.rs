@@ -0,0 +1,42 @@
+//! eq: query and transform EDN data with a jq-like filter language.
+//!
+//! The `eq` binary is a thin command-line wrapper over this library. To use
+//! the engine from another program, parse input with [`parse_edn`], run a
+//! filter with [`eval_query`] and print results with [`format_edn`]:
+//!
+//! ```
+//! let input = eq::parse_edn("{:name \"Alice\" :langs [:clojure :rust]}")?;
+//! let result = eq::eval_query("(count (:langs .))", &input)?;
+//! assert_eq!(eq::format_edn(&result, &eq::OutputConfig::default()), "2");
+//! # Ok::<(), eq::EqError>(())
+//! ```
+
+pub mod edn;
+pub mod error;
+pub mod query;
+pub mod analyzer;
+pub mod evaluator;
+pub mod output;
+pub mod builtins;
+pub mod primitives;
+pub mod json;
+pub mod schema;
+mod formatter;
+mod collection_formatter;
+mod csv;
+mod table;
+
+pub use edn::{EdnValue, Parser as EdnParser};
+pub use error::{EqError, EqResult};
+pub use output::{format_edn, OutputConfig};
+
+/// Parse one EDN value from text
+pub fn parse_edn(s: &str) -> EqResult<EdnValue> {
+    EdnParser::new(s).parse()
+}
+
+/// Run a filter against one input value, as `eq FILTER` does for each input
+pub fn eval_query(query: &str, input: &EdnValue) -> EqResult<EdnValue> {
+    let expr = analyzer::analyze(query::QueryParser::parse(query)?)?;
+    evaluator::evaluate(&expr, input)
+}
@@ -1,32 +1,41 @@
-use clap::Parser;
-use std::io::{self, Read};
+use clap::{CommandFactory, Parser};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
 use std::fs;
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 use glob::Pattern;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 
 mod cli;
-mod edn;
-mod error;
-mod query;
-mod analyzer;
-mod evaluator;
-mod output;
-mod builtins;
-mod primitives;
-mod formatter;
-mod collection_formatter;
-
-use cli::Args;
-use error::EqResult;
-use edn::{EdnValue, Parser as EdnParser};
-use query::QueryParser;
+mod repl;
+
+use cli::{Args, ErrorFormat, InputFormat};
+use eq::{analyzer, builtins, edn, error, evaluator, json, output, primitives, query};
+use error::{EqError, EqResult};
+use edn::{EdnValue, Parser as EdnParser, StreamingParser};
+use query::{ast::Environment, QueryParser};
 use analyzer::analyze;
-use evaluator::evaluate;
-use output::{OutputConfig, format_output};
+use evaluator::evaluate_with_globals;
+use output::{ColorMode, OutputConfig, OutputFormat, format_output};
+use json::{json_stream_to_edn, json_to_edn, ndjson_to_edn, looks_like_json};
+use primitives::format_duration;
 
-fn find_files_recursive(paths: &[PathBuf], pattern: &str, recursive: bool) -> EqResult<Vec<PathBuf>> {
-    let glob_pattern = Pattern::new(pattern)?;
+/// The files under `paths` whose names match one of `includes` and none of
+/// `excludes`. An excluded directory is skipped along with everything in it.
+/// A recursive search only takes files `depth` levels below each directory,
+/// where 1 is its direct children, and only goes through symbolic links if
+/// `follow_links` is set. Files named directly in `paths` are always included.
+fn find_files_recursive(
+    paths: &[PathBuf],
+    includes: &[Pattern],
+    excludes: &[Pattern],
+    recursive: bool,
+    depth: RangeInclusive<usize>,
+    follow_links: bool,
+) -> EqResult<Vec<PathBuf>> {
     let mut files = Vec::new();
     
     for path in paths {
@@ -36,31 +45,28 @@ fn find_files_recursive(paths: &[PathBuf], pattern: &str, recursive: bool) -> Eq
         } else if path.is_dir() {
             if recursive {
                 // Walk the directory tree
-                for entry in WalkDir::new(path).follow_links(true) {
-                    let entry = entry?;
-                    if entry.file_type().is_file() {
-                        if let Some(file_name) = entry.path().file_name() {
-                            if let Some(file_name_str) = file_name.to_str() {
-                                if glob_pattern.matches(file_name_str) {
-                                    files.push(entry.path().to_path_buf());
-                                }
-                            }
-                        }
+                // Not WalkDir's min_depth: entries it skips are never checked against excludes
+                let walker = WalkDir::new(path)
+                    .follow_links(follow_links)
+                    .max_depth(*depth.end())
+                    .into_iter()
+                    .filter_entry(|entry| entry.depth() == 0 || !matches_any(excludes, entry.path()));
+                for entry in walker {
+                    let entry = match entry {
+                        // A link back to a directory being walked: its files are found anyway
+                        Err(e) if e.loop_ancestor().is_some() => continue,
+                        entry => entry?,
+                    };
+                    if entry.file_type().is_file() && depth.contains(&entry.depth()) && matches_any(includes, entry.path()) {
+                        files.push(entry.into_path());
                     }
                 }
             } else {
                 // Just look at immediate children
                 for entry in fs::read_dir(path)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(file_name) = path.file_name() {
-                            if let Some(file_name_str) = file_name.to_str() {
-                                if glob_pattern.matches(file_name_str) {
-                                    files.push(path);
-                                }
-                            }
-                        }
+                    let path = entry?.path();
+                    if path.is_file() && matches_any(includes, &path) && !matches_any(excludes, &path) {
+                        files.push(path);
                     }
                 }
             }
@@ -70,148 +76,723 @@ fn find_files_recursive(paths: &[PathBuf], pattern: &str, recursive: bool) -> Eq
     Ok(files)
 }
 
+fn matches_any(patterns: &[Pattern], path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| patterns.iter().any(|pattern| pattern.matches(name)))
+}
+
+/// Exit code for --exit-status when no output was truthy
+const EXIT_FALSY: i32 = 1;
+/// Exit code for any error, kept distinct from EXIT_FALSY for shell scripts
+const EXIT_ERROR: i32 = 2;
+
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let args = Args::parse();
+    let error_format = args.error_format;
+    // With --from-file the filter is read inside run, and the first
+    // positional argument is an input file
+    let query = (args.from_file.is_none() && !args.filter.is_empty()).then(|| args.filter.clone());
+    match run(args) {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        // The reader went away (e.g. `eq ... | head`); nothing more to say
+        Err(RunError { error: EqError::IoError(e), .. }) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(RunError { error, file }) => {
+            eprintln!("{}", format_error(&error, error_format, query.as_deref(), file.as_deref()));
+            std::process::exit(EXIT_ERROR);
+        }
     }
 }
 
-fn run() -> EqResult<()> {
-    let args = Args::parse();
+/// An error from run, with the input file it came from if it happened while
+/// processing one
+struct RunError {
+    error: EqError,
+    file: Option<String>,
+}
+
+impl RunError {
+    fn in_file(error: EqError, file: &Path) -> Self {
+        Self { error, file: Some(file.display().to_string()) }
+    }
+}
+
+impl From<EqError> for RunError {
+    fn from(error: EqError) -> Self {
+        Self { error, file: None }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(error: io::Error) -> Self {
+        EqError::from(error).into()
+    }
+}
+
+type RunResult<T> = Result<T, RunError>;
+
+/// An error as --error-format asks for it. Structured errors are a single
+/// line and include the filter when it was given on the command line, and
+/// the input file when the error came from processing one.
+fn format_error(error: &EqError, format: ErrorFormat, query: Option<&str>, file: Option<&str>) -> String {
+    let output_format = match format {
+        ErrorFormat::Text => {
+            return match error.source_snippet() {
+                Some(snippet) => format!("Error: {}\n{}", error, snippet),
+                None => format!("Error: {}", error),
+            };
+        }
+        ErrorFormat::Edn => OutputFormat::Edn,
+        ErrorFormat::Json => OutputFormat::Json,
+    };
+    let mut data = error.to_edn_map();
+    if let Some(file) = file {
+        // Parse errors already name the file they were reading
+        data.entry(EdnValue::Keyword("file".to_string())).or_insert_with(|| EdnValue::String(file.to_string()));
+    }
+    if let Some(query) = query {
+        data.insert(EdnValue::Keyword("query".to_string()), EdnValue::String(query.to_string()));
+    }
+    let config = OutputConfig { compact: true, format: output_format, ..OutputConfig::default() };
+    // Every value in the map has a JSON equivalent, so this can't fail
+    format_output(&EdnValue::Map(data), &config).unwrap_or_else(|_| format!("Error: {}", error))
+}
+
+/// Run the filter over all inputs and return the process exit code
+fn run(mut args: Args) -> RunResult<i32> {
+    
+    if let Some(shell) = args.generate_completions {
+        clap_complete::generate(shell, &mut Args::command(), "eq", &mut io::stdout());
+        return Ok(0);
+    }
+    if args.list_builtins {
+        for name in evaluator::function_names() {
+            println!("{}", name);
+        }
+        return Ok(0);
+    }
     
     // Set up output configuration
-    let mut output_config = OutputConfig::default();
-    output_config.compact = args.compact;
-    output_config.raw_strings = args.raw_output;
-    output_config.use_tabs = args.tab;
-    output_config.indent_size = args.indent;
+    let to_terminal = args.output_file.is_none() && args.in_place.is_none() && io::stdout().is_terminal();
+    let color = if args.no_color { ColorMode::Never } else { args.color.resolve(to_terminal) };
+    let output_config = OutputConfig {
+        compact: args.compact,
+        raw_strings: args.raw_output,
+        use_tabs: args.tab,
+        indent_size: args.indent,
+        format: if args.json_output { OutputFormat::Json } else { args.output_format },
+        csv_separator: args.csv_separator,
+        table_format: args.table_format,
+        color,
+        max_string_length: args.max_string_length,
+        max_collection_size: args.max_collection_size,
+        max_depth: args.max_output_depth,
+        align_map_values: args.align_map_values,
+    };
+    
+    if args.allow_imports {
+        let base_dir = match args.from_file.as_deref().and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        builtins::allow_imports(base_dir);
+    }
+    if args.no_env {
+        builtins::disable_env();
+    }
+    
+    if args.repl {
+        repl::run_repl(&args, &output_config)?;
+        return Ok(0);
+    }
+    
+    if let [a, b] = args.diff.as_slice() {
+        let diff = builtins::diff_values(&read_edn_file(a)?, &read_edn_file(b)?);
+        let mut sink = match &args.output_file {
+            Some(path) => Sink::file(path, args.append, &args.diff)?,
+            None => Sink::stdout(),
+        };
+        writeln!(sink.writer, "{}", format_output(&diff, &output_config)?)?;
+        sink.writer.flush()?;
+        return Ok(0);
+    }
     
     // Get the filter expression
-    let filter = if let Some(filter_file) = &args.from_file {
-        fs::read_to_string(filter_file)?
-    } else {
-        args.filter.clone()
-    };
+    let filter = resolve_filter(&mut args)?;
     
     // Parse and analyze the query
     let query_ast = QueryParser::parse(&filter)?;
+    if args.debug {
+        eprintln!(";; Parsed query\n{}", format_output(&query_ast.to_edn(), &OutputConfig::default())?);
+    }
     let analyzed_query = analyze(query_ast)?;
+    if args.debug {
+        eprintln!(";; Analyzed query\n{}", format_output(&analyzed_query.to_edn(), &OutputConfig::default())?);
+    }
     
-    // Process inputs
+    if args.check {
+        return Ok(0);
+    }
+    
+    let globals = global_bindings(&args)?;
+    let files_to_process = input_files(&args)?;
+    
+    if let Some(suffix) = &args.in_place {
+        let any_truthy = process_in_place(&analyzed_query, &globals, &output_config, &args, &files_to_process, suffix)?;
+        return Ok(if args.exit_status && !any_truthy { EXIT_FALSY } else { 0 });
+    }
+    
+    let mut sink = match &args.output_file {
+        Some(path) => Sink::file(path, args.append, &files_to_process)?,
+        None => Sink::stdout(),
+    };
+    
+    let processed = process_inputs(&analyzed_query, &globals, &output_config, &args, &files_to_process, &mut sink)
+        .and_then(|()| sink.finish_stream(&args, &output_config).map_err(RunError::from));
+    // Flush even if processing failed, so the output written so far is kept
+    let flushed = sink.writer.flush();
+    processed?;
+    flushed?;
+    
+    if args.exit_status && !sink.any_truthy {
+        Ok(EXIT_FALSY)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Where results are written, whether any of them was truthy (for --exit-status),
+/// how many non-nil results there were since the last --count report and the
+/// results held back for --stream
+struct Sink<'a> {
+    writer: Box<dyn Write + 'a>,
+    any_truthy: bool,
+    count: usize,
+    stream_results: Vec<EdnValue>,
+}
+
+impl<'a> Sink<'a> {
+    fn new(writer: Box<dyn Write + 'a>) -> Self {
+        Self { writer, any_truthy: false, count: 0, stream_results: Vec::new() }
+    }
+
+    /// Write out a file processed by a --jobs worker, as if it had been processed here
+    fn absorb(&mut self, file: BufferedFile) -> EqResult<()> {
+        self.writer.write_all(&file.output)?;
+        self.any_truthy |= file.any_truthy;
+        self.count += file.count;
+        self.stream_results.extend(file.stream_results);
+        Ok(())
+    }
+
+    /// For --stream, write the collected results as a single vector
+    fn finish_stream(&mut self, args: &Args, output_config: &OutputConfig) -> EqResult<()> {
+        if args.stream {
+            let results = EdnValue::Vector(std::mem::take(&mut self.stream_results));
+            writeln!(self.writer, "{}", format_output(&results, output_config)?)?;
+        }
+        Ok(())
+    }
+
+    fn stdout() -> Self {
+        Self::new(Box::new(io::stdout()))
+    }
+
+    /// Open --output-file, refusing to overwrite one of the inputs
+    fn file(path: &Path, append: bool, inputs: &[PathBuf]) -> EqResult<Self> {
+        if let Ok(output) = path.canonicalize() {
+            if inputs.iter().any(|input| input.canonicalize().is_ok_and(|input| input == output)) {
+                return Err(EqError::query_error(format!("Output file {} is also an input file", path.display())));
+            }
+        }
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+}
+
+/// --in-place: run the filter over each file separately and replace the file
+/// with its output. The output goes to a temporary file in the same directory
+/// that is renamed over the original only once the whole file has been
+/// processed, so a failing filter leaves the original untouched. With a
+/// suffix the original is first copied to its name plus the suffix.
+/// Returns whether any result was truthy.
+fn process_in_place(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    files: &[PathBuf],
+    suffix: &str,
+) -> RunResult<bool> {
+    if files.is_empty() {
+        return Err(EqError::query_error("--in-place needs input files; it can't rewrite stdin").into());
+    }
+
+    let mut any_truthy = false;
+    let mut timings = Vec::new();
+    for file_path in files {
+        let started = Instant::now();
+        any_truthy |= rewrite_in_place(query, globals, output_config, args, file_path, suffix)
+            .map_err(|error| RunError::in_file(error, file_path))?;
+        timings.push((file_path.display().to_string(), started.elapsed()));
+    }
+    if args.profile {
+        print_profile(&timings);
+    }
+    Ok(any_truthy)
+}
+
+/// --in-place for one file. Returns whether any result was truthy.
+fn rewrite_in_place(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    file_path: &Path,
+    suffix: &str,
+) -> EqResult<bool> {
+    let dir = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    let mut sink = Sink::new(Box::new(BufWriter::new(temp.as_file().try_clone()?)));
+
+    let file = fs::File::open(file_path)?;
+    let filename = file_path.to_string_lossy();
+    process_input(query, globals, output_config, args, file, Some(&filename), &mut sink)?;
+    sink.finish_stream(args, output_config)?;
+    sink.writer.flush()?;
+    let any_truthy = sink.any_truthy;
+    drop(sink);
+
+    fs::set_permissions(temp.path(), fs::metadata(file_path)?.permissions())?;
+    if !suffix.is_empty() {
+        let mut backup = file_path.as_os_str().to_owned();
+        backup.push(suffix);
+        fs::copy(file_path, backup)?;
+    }
+    temp.persist(file_path).map_err(|e| e.error)?;
+    Ok(any_truthy)
+}
+
+/// The single EDN value in a file, for --diff
+fn read_edn_file(path: &Path) -> EqResult<EdnValue> {
+    let text = fs::read_to_string(path)?;
+    let mut parser = EdnParser::new_with_filename(&text, Some(path.to_string_lossy().into_owned()));
+    let value = parser.parse()?;
+    if parser.parse_next()?.is_some() {
+        return Err(EqError::query_error(format!("{} holds more than one value; --diff compares one value per file", path.display())));
+    }
+    Ok(value)
+}
+
+/// The files to read, expanding directories and --recursive. Empty when reading stdin.
+fn input_files(args: &Args) -> EqResult<Vec<PathBuf>> {
+    let mut files = find_input_files(args)?;
+    if args.sort_by_name {
+        files.sort();
+    } else if args.sort_by_mtime {
+        let mut by_mtime = files
+            .into_iter()
+            .map(|path| Ok((fs::metadata(&path)?.modified()?, path)))
+            .collect::<EqResult<Vec<_>>>()?;
+        by_mtime.sort();
+        files = by_mtime.into_iter().map(|(_, path)| path).collect();
+    }
+    Ok(files)
+}
+
+fn find_input_files(args: &Args) -> EqResult<Vec<PathBuf>> {
+    if args.null_input || (args.files.is_empty() && !args.recursive) {
+        Ok(Vec::new())
+    } else if args.files.iter().any(|p| p.is_dir()) || args.recursive {
+        // If recursive flag is set but no files specified, search current directory
+        let search_paths = if args.files.is_empty() && args.recursive {
+            vec![PathBuf::from(".")]
+        } else {
+            args.files.clone()
+        };
+        let patterns = |globs: &[String]| globs.iter().map(|glob| Pattern::new(glob)).collect::<Result<Vec<_>, _>>();
+        let (includes, excludes) = (patterns(&args.include_patterns)?, patterns(&args.exclude_patterns)?);
+        let depth = args.min_depth.unwrap_or(0)..=args.max_depth.unwrap_or(usize::MAX);
+        find_files_recursive(&search_paths, &includes, &excludes, args.recursive, depth, args.follow_symlinks)
+    } else {
+        Ok(args.files.clone())
+    }
+}
+
+fn process_inputs(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    files: &[PathBuf],
+    sink: &mut Sink,
+) -> RunResult<()> {
     if args.null_input {
         // No input, just run filter on nil
-        let result = evaluate(&analyzed_query, &EdnValue::Nil)?;
-        print_result(&result, &output_config, &args, None);
+        let result = evaluate_input(query, &EdnValue::Nil, globals, args)?;
+        print_result(&result, output_config, args, None, None, sink)?;
+        Ok(print_count(args, None, sink)?)
     } else if args.files.is_empty() && !args.recursive {
         // Read from stdin
-        process_input(&analyzed_query, &output_config, &args, io::stdin(), None)?;
+        let started = Instant::now();
+        process_input(query, globals, output_config, args, io::stdin(), None, sink)?;
+        if args.profile {
+            print_profile(&[("<stdin>".to_string(), started.elapsed())]);
+        }
+        Ok(print_count(args, None, sink)?)
     } else {
-        // Check if we need to do recursive file finding
-        let files_to_process = if args.files.iter().any(|p| p.is_dir()) || args.recursive {
-            // If recursive flag is set but no files specified, search current directory
-            let search_paths = if args.files.is_empty() && args.recursive {
-                vec![PathBuf::from(".")]
-            } else {
-                args.files.clone()
-            };
-            find_files_recursive(&search_paths, &args.glob_pattern, args.recursive)?
+        let progress = progress_bar(args, files.len());
+        let processed = process_files(query, globals, output_config, args, files, sink, &progress);
+        // Clear the bar on failure too, so it isn't left above the error message
+        progress.finish_and_clear();
+        let timings = processed?;
+        if args.profile {
+            let names = files.iter().map(|file_path| file_path.display().to_string());
+            print_profile(&names.zip(timings).collect::<Vec<_>>());
+        }
+        if args.with_filename {
+            Ok(())
         } else {
-            args.files.clone()
-        };
-        
-        // Process each file
-        for file_path in &files_to_process {
-            let file = fs::File::open(file_path)?;
-            let filename = file_path.to_string_lossy();
-            process_input(&analyzed_query, &output_config, &args, file, Some(&filename))?;
+            Ok(print_count(args, None, sink)?)
         }
     }
-    
+}
+
+fn process_files(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    files: &[PathBuf],
+    sink: &mut Sink,
+    progress: &ProgressBar,
+) -> RunResult<Vec<Duration>> {
+    // Returns how long the file took, for --profile
+    let process_tracked = |file_path: &PathBuf, sink: &mut Sink| {
+        progress.set_message(file_path.display().to_string());
+        let started = Instant::now();
+        let processed = process_file(query, globals, output_config, args, file_path, sink);
+        progress.inc(1);
+        processed.map(|()| started.elapsed())
+    };
+
+    let mut timings = Vec::new();
+
+    if args.jobs != 1 && files.len() > 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs)
+            .build()
+            .map_err(|e| EqError::query_error(format!("Could not start --jobs threads: {}", e)))?;
+        let buffered: Vec<RunResult<(BufferedFile, Duration)>> = pool.install(|| {
+            files.par_iter()
+                .map(|file_path| {
+                    let mut elapsed = Duration::ZERO;
+                    let file = buffer_output(|sink| {
+                        elapsed = process_tracked(file_path, sink)?;
+                        Ok(())
+                    }).map_err(|error| RunError::in_file(error, file_path))?;
+                    Ok((file, elapsed))
+                })
+                .collect()
+        });
+        // Stop at the first failing file, after the output of the files before it
+        for file in buffered {
+            let (file, elapsed) = file?;
+            sink.absorb(file)?;
+            timings.push(elapsed);
+        }
+    } else {
+        for file_path in files {
+            timings.push(process_tracked(file_path, sink).map_err(|error| RunError::in_file(error, file_path))?);
+        }
+    }
+    Ok(timings)
+}
+
+/// --profile: how long each input took, once they have all been processed
+fn print_profile(timings: &[(String, Duration)]) {
+    for (input, elapsed) in timings {
+        eprintln!("{}: {}", input, format_duration(*elapsed));
+    }
+    if timings.len() > 1 {
+        let total: Duration = timings.iter().map(|(_, elapsed)| *elapsed).sum();
+        eprintln!("total: {}", format_duration(total));
+    }
+}
+
+/// The --progress bar on stderr. It's hidden unless stderr is a terminal and
+/// there are at least two files.
+fn progress_bar(args: &Args, total: usize) -> ProgressBar {
+    if !args.progress || total < 2 || !io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template("{pos}/{len} files [{bar:30}] eta {eta} {wide_msg}")
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ");
+    ProgressBar::new(total as u64).with_style(style)
+}
+
+fn process_file(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    file_path: &Path,
+    sink: &mut Sink,
+) -> EqResult<()> {
+    let file = fs::File::open(file_path)?;
+    let filename = file_path.to_string_lossy();
+    process_input(query, globals, output_config, args, file, Some(&filename), sink)?;
+    if args.with_filename {
+        print_count(args, Some(&filename), sink)?;
+    }
     Ok(())
 }
 
-fn print_result(result: &EdnValue, output_config: &OutputConfig, args: &Args, filename: Option<&str>) {
+/// Everything one file wrote under --jobs, with the sink state it left
+/// behind, held until the files before it have been written
+struct BufferedFile {
+    output: Vec<u8>,
+    any_truthy: bool,
+    count: usize,
+    stream_results: Vec<EdnValue>,
+}
+
+/// Run `process` against an in-memory sink
+fn buffer_output(process: impl FnOnce(&mut Sink) -> EqResult<()>) -> EqResult<BufferedFile> {
+    let mut output = Vec::new();
+    let (any_truthy, count, stream_results) = {
+        let mut sink = Sink::new(Box::new(&mut output));
+        process(&mut sink)?;
+        (sink.any_truthy, sink.count, sink.stream_results)
+    };
+    Ok(BufferedFile { output, any_truthy, count, stream_results })
+}
+
+/// For --count, write the number of results counted so far and start again from zero
+fn print_count(args: &Args, filename: Option<&str>, sink: &mut Sink) -> EqResult<()> {
+    if !args.count {
+        return Ok(());
+    }
+    if args.with_filename {
+        write!(sink.writer, "{}:", filename.unwrap_or("(stdin)"))?;
+    }
+    writeln!(sink.writer, "{}", sink.count)?;
+    sink.count = 0;
+    Ok(())
+}
+
+/// Read the filter text. With --from-file, or when the filter argument names
+/// an executable script (a file starting with #!), the positional filter
+/// argument is really the first input file and is moved into `args.files`.
+fn resolve_filter(args: &mut Args) -> EqResult<String> {
+    let script = match &args.from_file {
+        Some(path) => {
+            let script = fs::read_to_string(path)?;
+            if !args.filter.is_empty() {
+                args.files.insert(0, PathBuf::from(std::mem::take(&mut args.filter)));
+            }
+            script
+        }
+        None => {
+            let path = std::path::Path::new(&args.filter);
+            match path.is_file().then(|| fs::read_to_string(path)).transpose()? {
+                Some(script) if script.starts_with("#!") => script,
+                _ => return Ok(args.filter.clone()),
+            }
+        }
+    };
+    Ok(strip_shebang(&script))
+}
+
+/// Names bound by --arg (as strings) and --argjson (parsed as JSON, or EDN if it isn't JSON)
+fn global_bindings(args: &Args) -> EqResult<Environment> {
+    // clap collects both flags as flat NAME VALUE lists
+    let mut env = Environment::new();
+    for pair in args.arg.chunks_exact(2) {
+        env.bind(pair[0].clone(), EdnValue::String(pair[1].clone()));
+    }
+    for pair in args.argjson.chunks_exact(2) {
+        let (name, text) = (&pair[0], &pair[1]);
+        let value = json_to_edn(text)
+            .or_else(|_| EdnParser::new(text).parse())
+            .map_err(|e| EqError::query_error(format!("Invalid --argjson value for {}: {}", name, e)))?;
+        env.bind(name.clone(), value);
+    }
+    Ok(env)
+}
+
+/// Blank out a leading #! line, keeping the newline so error line numbers still match the file
+fn strip_shebang(script: &str) -> String {
+    match script.strip_prefix("#!") {
+        Some(rest) => rest.find('\n').map_or_else(String::new, |i| rest[i..].to_string()),
+        None => script.to_string(),
+    }
+}
+
+/// Write one result. `line` is where its input value started, for
+/// --with-line-numbers, when that is known.
+fn print_result(
+    result: &EdnValue,
+    output_config: &OutputConfig,
+    args: &Args,
+    filename: Option<&str>,
+    line: Option<usize>,
+    sink: &mut Sink,
+) -> EqResult<()> {
+    sink.any_truthy |= result.is_truthy();
+    
+    if args.count {
+        if !matches!(result, EdnValue::Nil) {
+            sink.count += 1;
+        }
+        return Ok(());
+    }
+    
     // Skip output for nil values if suppress_nil flag is set
     if args.suppress_nil && matches!(result, EdnValue::Nil) {
-        return;
+        return Ok(());
+    }
+    
+    if args.stream {
+        sink.stream_results.push(result.clone());
+        return Ok(());
     }
     
-    let output = format_output(result, output_config);
+    let output = format_output(result, output_config)?;
+    let terminator = if args.null_separator {
+        "\0"
+    } else if args.join_output {
+        ""
+    } else {
+        "\n"
+    };
+    // With -0 the filename and line number are their own NUL-terminated fields
+    let separator = if args.null_separator { "\0" } else { ":" };
     if args.with_filename {
-        if let Some(fname) = filename {
-            println!("{}:{}", fname, output);
-        } else {
-            println!("(stdin):{}", output);
+        write!(sink.writer, "{}{}", filename.unwrap_or("(stdin)"), separator)?;
+    }
+    if let (true, Some(line)) = (args.with_line_numbers, line) {
+        write!(sink.writer, "{}{}", line, separator)?;
+    }
+    write!(sink.writer, "{}{}", output, terminator)?;
+    Ok(())
+}
+
+/// Parse input as JSON when the input format calls for it.
+/// Returns None when the input should be read as EDN.
+fn read_json_values(input: &str, format: InputFormat, filename: Option<&str>) -> EqResult<Option<Vec<EdnValue>>> {
+    match format {
+        InputFormat::Edn => Ok(None),
+        InputFormat::Json => json_stream_to_edn(input, filename).map(Some),
+        InputFormat::Ndjson => ndjson_to_edn(input, filename).map(Some),
+        InputFormat::Auto => {
+            let has_json_extension = filename
+                .and_then(|f| std::path::Path::new(f).extension())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+            if has_json_extension {
+                json_stream_to_edn(input, filename).map(Some)
+            } else if looks_like_json(input) {
+                // EDN maps and vectors open the same way, so fall back to EDN if this isn't JSON
+                Ok(json_stream_to_edn(input, filename).ok())
+            } else {
+                Ok(None)
+            }
         }
-    } else {
-        println!("{}", output);
     }
 }
 
 fn process_input<R: Read>(
     query: &query::ast::Expr,
+    globals: &Environment,
     output_config: &OutputConfig,
     args: &Args,
     mut reader: R,
     filename: Option<&str>,
+    sink: &mut Sink,
 ) -> EqResult<()> {
+    if !args.raw_input && !args.slurp && args.input_format == InputFormat::Edn {
+        // Stream top-level values without reading the whole input into memory
+        return process_edn_stream(query, globals, output_config, args, reader, filename, sink);
+    }
+
     let mut input_string = String::new();
     reader.read_to_string(&mut input_string)?;
     
     if args.raw_input {
         // Treat each line as a string
-        for line in input_string.lines() {
+        for (index, line) in input_string.lines().enumerate() {
             let input_value = EdnValue::String(line.to_string());
-            let result = evaluate(query, &input_value)?;
-            print_result(&result, output_config, args, filename);
+            let result = evaluate_input(query, &input_value, globals, args)?;
+            print_result(&result, output_config, args, filename, Some(index + 1), sink)?;
+        }
+    } else if let Some(values) = read_json_values(&input_string, args.input_format, filename)? {
+        // The JSON reader doesn't keep positions, so there are no line numbers
+        if args.slurp {
+            let result = evaluate_input(query, &EdnValue::Vector(values), globals, args)?;
+            print_result(&result, output_config, args, filename, None, sink)?;
+        } else {
+            for value in &values {
+                let result = evaluate_input(query, value, globals, args)?;
+                print_result(&result, output_config, args, filename, None, sink)?;
+            }
         }
     } else if args.slurp {
         // Parse all values and put them in a vector
         let mut values = Vec::new();
         let mut parser = EdnParser::new_with_filename(&input_string, filename.map(|s| s.to_string()));
         
-        // Keep parsing until we reach the end
-        while let Ok(value) = parser.parse() {
-            if matches!(value, EdnValue::Nil) {
-                // Check if we're actually at the end or if nil was parsed
-                break;
-            }
+        // parse_next returns None only at the end of input, so nil values are kept
+        while let Some(value) = parser.parse_next()? {
             values.push(value);
         }
         
         let input_array = EdnValue::Vector(values);
-        let result = evaluate(query, &input_array)?;
-        print_result(&result, output_config, args, filename);
+        let result = evaluate_input(query, &input_array, globals, args)?;
+        print_result(&result, output_config, args, filename, None, sink)?;
     } else {
-        // Parse and process each top-level EDN value
-        let mut parser = EdnParser::new_with_filename(&input_string, filename.map(|s| s.to_string()));
-        
-        loop {
-            let value = parser.parse()?;
-            
-            // Check if we've reached the end of input
-            if matches!(value, EdnValue::Nil) && parser.remaining_input().trim().is_empty() {
-                break;
-            }
-            
-            // Process the parsed value
-            let result = evaluate(query, &value)?;
-            print_result(&result, output_config, args, filename);
-            
-            // Check if there's more to parse
-            if parser.remaining_input().trim().is_empty() {
-                break;
-            }
-        }
+        process_edn_stream(query, globals, output_config, args, input_string.as_bytes(), filename, sink)?;
     }
     
     Ok(())
 }
 
+/// Run the filter on one input value, echoing the value to stderr for --debug-input
+fn evaluate_input(query: &query::ast::Expr, input: &EdnValue, globals: &Environment, args: &Args) -> EqResult<EdnValue> {
+    if args.debug_input {
+        eprintln!(";; Input\n{}", input);
+    }
+    evaluate_with_globals(query, input, globals)
+}
+
+/// Parse and process each top-level EDN value as it is read
+fn process_edn_stream<R: Read>(
+    query: &query::ast::Expr,
+    globals: &Environment,
+    output_config: &OutputConfig,
+    args: &Args,
+    reader: R,
+    filename: Option<&str>,
+    sink: &mut Sink,
+) -> EqResult<()> {
+    let mut values = StreamingParser::new(reader, filename.map(|s| s.to_string()));
+    while let Some(value) = values.next() {
+        let result = evaluate_input(query, &value?, globals, args)?;
+        print_result(&result, output_config, args, filename, Some(values.start_line()), sink)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod integration_tests {
     use super::*;
+    use evaluator::evaluate;
     use std::io::Cursor;
 
     #[test]
@@ -223,7 +804,7 @@ mod integration_tests {
         let input = EdnValue::Integer(42);
         let result = evaluate(&analyzed_query, &input).unwrap();
         
-        assert_eq!(format_output(&result, &config), "42");
+        assert_eq!(format_output(&result, &config).unwrap(), "42");
     }
 
     #[test]
@@ -237,7 +818,7 @@ mod integration_tests {
         let input = EdnValue::Map(map);
         
         let result = evaluate(&analyzed_query, &input).unwrap();
-        assert_eq!(format_output(&result, &config), "\"Alice\"");
+        assert_eq!(format_output(&result, &config).unwrap(), "\"Alice\"");
     }
 
     #[test]
@@ -252,7 +833,7 @@ mod integration_tests {
         ]);
         
         let result = evaluate(&analyzed_query, &input).unwrap();
-        assert_eq!(format_output(&result, &config), "\"first\"");
+        assert_eq!(format_output(&result, &config).unwrap(), "\"first\"");
     }
 
     #[test]
@@ -262,19 +843,58 @@ mod integration_tests {
             files: vec![],
             compact: false,
             raw_output: false,
+            join_output: false,
+            null_separator: false,
+            output_format: OutputFormat::Edn,
+            json_output: false,
+            csv_separator: ',',
+            table_format: output::TableFormat::Ascii,
+            output_file: None,
+            append: false,
+            in_place: None,
             raw_input: true,
+            input_format: InputFormat::Edn,
             slurp: false,
             null_input: false,
+            arg: vec![],
+            argjson: vec![],
+            allow_imports: false,
+            no_env: false,
             exit_status: false,
             from_file: None,
+            jobs: 1,
+            progress: false,
+            profile: false,
+            diff: Vec::new(),
+            max_string_length: None,
+            max_collection_size: None,
+            max_output_depth: None,
             tab: false,
             indent: 2,
+            align_map_values: false,
+            error_format: ErrorFormat::Text,
+            color: output::ColorMode::Auto,
+            no_color: false,
             debug: false,
+            debug_input: false,
             verbose: false,
             with_filename: false,
+            with_line_numbers: false,
             recursive: false,
-            glob_pattern: "*.edn".to_string(),
+            max_depth: None,
+            min_depth: None,
+            follow_symlinks: false,
+            sort_by_name: false,
+            sort_by_mtime: false,
+            include_patterns: vec!["*.edn".to_string()],
+            exclude_patterns: Vec::new(),
             suppress_nil: false,
+            count: false,
+            stream: false,
+            repl: false,
+            generate_completions: None,
+            list_builtins: false,
+            check: false,
         };
         
         let query_ast = QueryParser::parse(".").unwrap();
@@ -286,7 +906,9 @@ mod integration_tests {
         
         // This would normally print, but we can't easily test that
         // In a real implementation, we'd refactor to return results
-        process_input(&analyzed_query, &config, &args, cursor, Some("test_input")).unwrap();
+        let mut sink = Sink::new(Box::new(Vec::new()));
+        process_input(&analyzed_query, &Environment::new(), &config, &args, cursor, Some("test_input"), &mut sink).unwrap();
+        assert!(sink.any_truthy);
     }
 
     #[test]
@@ -307,7 +929,36 @@ mod integration_tests {
         ]);
         
         let result = evaluate(&analyzed_query, &input).unwrap();
-        assert_eq!(format_output(&result, &config), "\"Alice\"");
+        assert_eq!(format_output(&result, &config).unwrap(), "\"Alice\"");
+    }
+    
+    #[test]
+    fn test_format_error() {
+        let error = EqError::parse_error_with_file(Some("data.edn".to_string()), 5, 12, "Unterminated string")
+            .with_source_context("{:a \"b");
+        assert_eq!(
+            format_error(&error, ErrorFormat::Text, Some("."), None),
+            "Error: Parse error in file 'data.edn' at line 5, column 12: Unterminated string\n{:a \"b\n           ^"
+        );
+        assert_eq!(
+            format_error(&error, ErrorFormat::Edn, Some("(:a .)"), Some("other.edn")),
+            r#"{:type :parse-error :message "Unterminated string" :line 5 :column 12 :file "data.edn" :query "(:a .)"}"#
+        );
+        assert_eq!(
+            format_error(&EqError::type_error("number", "keyword"), ErrorFormat::Json, None, Some("data.edn")),
+            r#"{"type":"type-error","message":"expected number, got keyword","expected":"number","actual":"keyword","file":"data.edn"}"#
+        );
+        assert_eq!(
+            format_error(&EqError::query_error("Unknown function: foo"), ErrorFormat::Edn, None, None),
+            r#"{:type :query-error :message "Unknown function: foo"}"#
+        );
+    }
+    
+    #[test]
+    fn test_strip_shebang() {
+        assert_eq!(strip_shebang("#!/usr/bin/env eq\n(first .)\n"), "\n(first .)\n");
+        assert_eq!(strip_shebang("#!/usr/bin/env eq"), "");
+        assert_eq!(strip_shebang("(first .)"), "(first .)");
     }
     
     #[test]
@@ -331,24 +982,71 @@ mod integration_tests {
         fs::write(sub_dir.join("test3.edn"), "nil").unwrap();
         fs::write(sub_dir.join("test4.json"), "{}").unwrap();
         
+        let patterns = |globs: &[&str]| globs.iter().map(|glob| Pattern::new(glob).unwrap()).collect::<Vec<_>>();
+        let (edn, json, none) = (patterns(&["*.edn"]), patterns(&["*.json"]), patterns(&[]));
+        
         // Test non-recursive with *.edn pattern
-        let files = find_files_recursive(&vec![temp_dir.clone()], "*.edn", false).unwrap();
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &none, false, 0..=usize::MAX, false).unwrap();
         assert_eq!(files.len(), 2); // Should find test1.edn and test2.edn
         
         // Test recursive with *.edn pattern
-        let files = find_files_recursive(&vec![temp_dir.clone()], "*.edn", true).unwrap();
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &none, true, 0..=usize::MAX, false).unwrap();
         assert_eq!(files.len(), 3); // Should find test1.edn, test2.edn, and test3.edn
         
         // Test recursive with *.json pattern
-        let files = find_files_recursive(&vec![temp_dir.clone()], "*.json", true).unwrap();
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &json, &none, true, 0..=usize::MAX, false).unwrap();
         assert_eq!(files.len(), 2); // Should find other.json and test4.json
         
+        // Test several includes, and excludes for files and whole directories
+        let both = patterns(&["*.edn", "*.json"]);
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &both, &none, true, 0..=usize::MAX, false).unwrap();
+        assert_eq!(files.len(), 5);
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &both, &patterns(&["test2.*", "other*"]), false, 0..=usize::MAX, false).unwrap();
+        assert_eq!(files, [temp_dir.join("test1.edn")]);
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &patterns(&["subdir"]), true, 0..=usize::MAX, false).unwrap();
+        assert_eq!(files.len(), 2); // test3.edn is in the excluded directory
+        
+        // Test depth limits: 1 is direct children, 0 the directory itself
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &none, true, 0..=1, false).unwrap();
+        assert_eq!(files.len(), 2);
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &none, true, 2..=usize::MAX, false).unwrap();
+        assert_eq!(files, [sub_dir.join("test3.edn")]);
+        let files = find_files_recursive(std::slice::from_ref(&temp_dir), &edn, &none, true, 0..=0, false).unwrap();
+        assert!(files.is_empty());
+        
         // Test with direct file path
         let direct_file = temp_dir.join("test1.edn");
-        let files = find_files_recursive(&vec![direct_file], "*.edn", false).unwrap();
+        let files = find_files_recursive(&[direct_file], &edn, &edn, false, 0..=usize::MAX, false).unwrap();
         assert_eq!(files.len(), 1); // Should return the file itself
         
         // Clean up
         let _ = fs::remove_dir_all(&temp_dir);
     }
-}
\ No newline at end of file
+    #[cfg(unix)]
+    #[test]
+    fn test_find_files_symlinks() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+        
+        let temp_dir = std::env::temp_dir().join("eq_test_symlinks");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let (root, elsewhere) = (temp_dir.join("root"), temp_dir.join("elsewhere"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(root.join("real.edn"), "{}").unwrap();
+        fs::write(elsewhere.join("linked.edn"), "{}").unwrap();
+        symlink(&elsewhere, root.join("link")).unwrap();
+        symlink(&root, root.join("loop")).unwrap();
+        
+        let edn = [Pattern::new("*.edn").unwrap()];
+        let files = find_files_recursive(std::slice::from_ref(&root), &edn, &[], true, 0..=usize::MAX, false).unwrap();
+        assert_eq!(files, [root.join("real.edn")]);
+        
+        // Following links finds linked.edn, and the link back to root is skipped
+        let mut files = find_files_recursive(std::slice::from_ref(&root), &edn, &[], true, 0..=usize::MAX, true).unwrap();
+        files.sort();
+        assert_eq!(files, [root.join("link").join("linked.edn"), root.join("real.edn")]);
+        
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
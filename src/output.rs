@@ -1,5 +1,50 @@
 use crate::edn::EdnValue;
+use crate::error::EqResult;
 use crate::formatter::{Formatter, CompactFormatter, PrettyFormatter};
+use crate::json::format_json;
+use crate::csv::format_csv;
+use crate::table::format_table;
+
+/// Output serialization format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Edn,
+    Json,
+    Csv,
+    /// One compact JSON document per line
+    Ndjson,
+    Table,
+}
+
+/// Style used by table output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum TableFormat {
+    #[default]
+    Ascii,
+    Markdown,
+}
+
+/// When to colorize pretty-printed EDN output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Colorize when stdout is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Resolve `Auto` against whether output goes to a terminal
+    pub fn resolve(self, is_terminal: bool) -> ColorMode {
+        match self {
+            ColorMode::Auto if is_terminal => ColorMode::Always,
+            ColorMode::Auto => ColorMode::Never,
+            mode => mode,
+        }
+    }
+}
 
 /// Configuration for output formatting
 #[derive(Debug, Clone)]
@@ -8,6 +53,19 @@ pub struct OutputConfig {
     pub raw_strings: bool,
     pub use_tabs: bool,
     pub indent_size: usize,
+    pub format: OutputFormat,
+    pub csv_separator: char,
+    pub table_format: TableFormat,
+    /// Only `Always` colorizes; `Auto` should be resolved before formatting
+    pub color: ColorMode,
+    /// EDN output shows at most this many characters of a string, then `...`
+    pub max_string_length: Option<usize>,
+    /// EDN output shows at most this many elements of a collection, then `...`
+    pub max_collection_size: Option<usize>,
+    /// EDN output shows collections nested deeper than this as `[...]`, `{...}` and so on
+    pub max_depth: Option<usize>,
+    /// Pretty-printed multi-line maps line their values up one space past the widest key
+    pub align_map_values: bool,
 }
 
 impl Default for OutputConfig {
@@ -17,18 +75,37 @@ impl Default for OutputConfig {
             raw_strings: false,
             use_tabs: false,
             indent_size: 2,
+            format: OutputFormat::Edn,
+            csv_separator: ',',
+            table_format: TableFormat::Ascii,
+            color: ColorMode::Auto,
+            max_string_length: None,
+            max_collection_size: None,
+            max_depth: None,
+            align_map_values: false,
         }
     }
 }
 
 /// Format an EDN value for output
-pub fn format_output(value: &EdnValue, config: &OutputConfig) -> String {
+pub fn format_output(value: &EdnValue, config: &OutputConfig) -> EqResult<String> {
+    let value = &*value.realize_all()?;
+    match config.format {
+        OutputFormat::Json => format_json(value, config),
+        OutputFormat::Csv => format_csv(value, config),
+        OutputFormat::Table => format_table(value, config),
+        OutputFormat::Ndjson => format_json(value, &OutputConfig { compact: true, ..config.clone() }),
+        OutputFormat::Edn => Ok(format_edn(value, config)),
+    }
+}
+
+/// Format a value as EDN, compact or pretty-printed as the config says,
+/// whatever its output format
+pub fn format_edn(value: &EdnValue, config: &OutputConfig) -> String {
     if config.compact {
-        let formatter = CompactFormatter;
-        formatter.format(value, config, 0)
+        CompactFormatter.format(value, config, 0)
     } else {
-        let formatter = PrettyFormatter;
-        formatter.format(value, config, 0)
+        PrettyFormatter.format(value, config, 0)
     }
 }
 
@@ -42,14 +119,16 @@ mod tests {
     fn test_format_primitives() {
         let config = OutputConfig::default();
         
-        assert_eq!(format_output(&EdnValue::Nil, &config), "nil");
-        assert_eq!(format_output(&EdnValue::Bool(true), &config), "true");
-        assert_eq!(format_output(&EdnValue::Integer(42), &config), "42");
-        assert_eq!(format_output(&EdnValue::Float(3.14), &config), "3.14");
-        assert_eq!(format_output(&EdnValue::String("hello".to_string()), &config), "\"hello\"");
-        assert_eq!(format_output(&EdnValue::Keyword("key".to_string()), &config), ":key");
-        assert_eq!(format_output(&EdnValue::Character('a'), &config), "\\a");
-        assert_eq!(format_output(&EdnValue::Character('\n'), &config), "\\newline");
+        assert_eq!(format_output(&EdnValue::Nil, &config).unwrap(), "nil");
+        assert_eq!(format_output(&EdnValue::Bool(true), &config).unwrap(), "true");
+        assert_eq!(format_output(&EdnValue::Integer(42), &config).unwrap(), "42");
+        assert_eq!(format_output(&EdnValue::Float(2.5), &config).unwrap(), "2.5");
+        assert_eq!(format_output(&EdnValue::String("hello".to_string()), &config).unwrap(), "\"hello\"");
+        assert_eq!(format_output(&EdnValue::Keyword("key".to_string()), &config).unwrap(), ":key");
+        assert_eq!(format_output(&EdnValue::Keyword("db/id".to_string()), &config).unwrap(), ":db/id");
+        assert_eq!(format_output(&EdnValue::Symbol("clojure.core/map".to_string()), &config).unwrap(), "clojure.core/map");
+        assert_eq!(format_output(&EdnValue::Character('a'), &config).unwrap(), "\\a");
+        assert_eq!(format_output(&EdnValue::Character('\n'), &config).unwrap(), "\\newline");
     }
 
     #[test]
@@ -62,19 +141,21 @@ mod tests {
             EdnValue::Integer(2),
             EdnValue::Integer(3),
         ]);
-        assert_eq!(format_output(&vec, &config), "[1 2 3]");
+        assert_eq!(format_output(&vec, &config).unwrap(), "[1 2 3]");
         
         // Simple map
         let mut map = IndexMap::new();
         map.insert(EdnValue::Keyword("name".to_string()), EdnValue::String("Alice".to_string()));
         let map_val = EdnValue::Map(map);
-        assert_eq!(format_output(&map_val, &config), "{:name \"Alice\"}");
+        assert_eq!(format_output(&map_val, &config).unwrap(), "{:name \"Alice\"}");
     }
 
     #[test]
     fn test_compact_format() {
-        let mut config = OutputConfig::default();
-        config.compact = true;
+        let config = OutputConfig {
+            compact: true,
+            ..OutputConfig::default()
+        };
         
         let nested = EdnValue::Vector(vec![
             EdnValue::Map({
@@ -88,7 +169,7 @@ mod tests {
             })
         ]);
         
-        let result = format_output(&nested, &config);
+        let result = format_output(&nested, &config).unwrap();
         assert!(!result.contains('\n')); // Should be all on one line
         assert!(result.contains("{:a 1 :b [2 3]}"));
     }
@@ -106,26 +187,30 @@ mod tests {
             EdnValue::String("item5".to_string()),
         ]);
         
-        let result = format_output(&large_vec, &config);
+        let result = format_output(&large_vec, &config).unwrap();
         assert!(result.contains('\n')); // Should be multi-line
     }
 
     #[test]
     fn test_raw_strings() {
-        let mut config = OutputConfig::default();
-        config.raw_strings = true;
+        let mut config = OutputConfig {
+            raw_strings: true,
+            ..OutputConfig::default()
+        };
         
         let string_val = EdnValue::String("hello world".to_string());
-        assert_eq!(format_output(&string_val, &config), "hello world");
+        assert_eq!(format_output(&string_val, &config).unwrap(), "hello world");
         
         config.raw_strings = false;
-        assert_eq!(format_output(&string_val, &config), "\"hello world\"");
+        assert_eq!(format_output(&string_val, &config).unwrap(), "\"hello world\"");
     }
 
     #[test]
     fn test_indentation_config() {
-        let mut config = OutputConfig::default();
-        config.indent_size = 4;
+        let config = OutputConfig {
+            indent_size: 4,
+            ..OutputConfig::default()
+        };
         
         let nested = EdnValue::Vector(vec![
             EdnValue::Vector(vec![EdnValue::Integer(1)]),
@@ -135,7 +220,7 @@ mod tests {
             EdnValue::Vector(vec![EdnValue::Integer(5)]),
         ]);
         
-        let result = format_output(&nested, &config);
+        let result = format_output(&nested, &config).unwrap();
         // Should contain 4-space indentation
         assert!(result.lines().any(|line| line.starts_with("    ")));
     }
@@ -149,7 +234,7 @@ mod tests {
         
         let set_val = EdnValue::Set(set);
         let config = OutputConfig::default();
-        let result = format_output(&set_val, &config);
+        let result = format_output(&set_val, &config).unwrap();
         
         // Should be deterministically ordered
         assert!(result.starts_with("#{"));
@@ -167,6 +252,118 @@ mod tests {
             value: Box::new(EdnValue::String("2023-01-01".to_string())),
         };
         
-        assert_eq!(format_output(&tagged, &config), "#inst \"2023-01-01\"");
+        assert_eq!(format_output(&tagged, &config).unwrap(), "#inst \"2023-01-01\"");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_json_output_format() {
+        let config = OutputConfig {
+            compact: true,
+            format: OutputFormat::Json,
+            ..OutputConfig::default()
+        };
+
+        let mut map = IndexMap::new();
+        map.insert(EdnValue::Keyword("name".to_string()), EdnValue::String("Alice".to_string()));
+        map.insert(EdnValue::Keyword("tags".to_string()), EdnValue::Vector(vec![EdnValue::Nil]));
+        assert_eq!(format_output(&EdnValue::Map(map), &config).unwrap(), "{\"name\":\"Alice\",\"tags\":[null]}");
+    }
+
+    #[test]
+    fn test_ndjson_output_format() {
+        // NDJSON is always compact, even without --compact
+        let config = OutputConfig {
+            format: OutputFormat::Ndjson,
+            ..OutputConfig::default()
+        };
+
+        let value = EdnValue::Vector(vec![EdnValue::Integer(1), EdnValue::Integer(2)]);
+        assert_eq!(format_output(&value, &config).unwrap(), "[1,2]");
+    }
+
+    #[test]
+    fn test_color_output() {
+        let config = OutputConfig { color: ColorMode::Always, ..OutputConfig::default() };
+
+        let mut map = IndexMap::new();
+        map.insert(EdnValue::Keyword("name".to_string()), EdnValue::String("Alice".to_string()));
+        map.insert(EdnValue::Keyword("tags".to_string()), EdnValue::Vector(vec![
+            EdnValue::Keyword("admin".to_string()),
+            EdnValue::Nil,
+            EdnValue::Integer(3),
+        ]));
+        assert_eq!(
+            format_output(&EdnValue::Map(map.clone()), &config).unwrap(),
+            "{\x1b[34m:name\x1b[0m \x1b[32m\"Alice\"\x1b[0m \x1b[34m:tags\x1b[0m [\x1b[36m:admin\x1b[0m \x1b[31mnil\x1b[0m \x1b[33m3\x1b[0m]}"
+        );
+
+        // Compact output and unresolved Auto never colorize
+        let compact = OutputConfig { compact: true, ..config.clone() };
+        assert_eq!(format_output(&EdnValue::Map(map.clone()), &compact).unwrap(), "{:name \"Alice\" :tags [:admin nil 3]}");
+        let auto = OutputConfig::default();
+        assert!(!format_output(&EdnValue::Map(map), &auto).unwrap().contains('\x1b'));
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let value = crate::edn::Parser::new(
+            r#"{:name "abcdefgh" :tags [1 2 3 4] :nested {:a {:b 1}} :set #{1 2 3}}"#
+        ).parse().unwrap();
+        let compact = OutputConfig { compact: true, ..OutputConfig::default() };
+
+        let config = OutputConfig { max_string_length: Some(3), ..compact.clone() };
+        assert_eq!(format_output(&EdnValue::String("abcdefgh".to_string()), &config).unwrap(), "\"abc...\"");
+        assert_eq!(format_output(&EdnValue::String("abc".to_string()), &config).unwrap(), "\"abc\"");
+        let raw = OutputConfig { raw_strings: true, ..config.clone() };
+        assert_eq!(format_output(&EdnValue::String("héllo".to_string()), &raw).unwrap(), "hél...");
+
+        let config = OutputConfig { max_collection_size: Some(2), ..compact.clone() };
+        assert_eq!(format_output(&value, &config).unwrap(), "{:name \"abcdefgh\" :tags [1 2 ...] ...}");
+
+        let config = OutputConfig { max_depth: Some(1), ..compact.clone() };
+        assert_eq!(
+            format_output(&value, &config).unwrap(),
+            "{:name \"abcdefgh\" :tags [...] :nested {...} :set #{...}}"
+        );
+        let config = OutputConfig { max_depth: Some(0), ..compact.clone() };
+        assert_eq!(format_output(&value, &config).unwrap(), "{...}");
+
+        // The pretty formatter applies the same limits
+        let config = OutputConfig {
+            max_collection_size: Some(3),
+            max_depth: Some(2),
+            ..OutputConfig::default()
+        };
+        assert_eq!(
+            format_output(&value, &config).unwrap(),
+            "{ :name \"abcdefgh\"\n  :tags [1 2 3 ...]\n  :nested \n  {:a {...}}\n  ...}"
+        );
+    }
+
+    #[test]
+    fn test_align_map_values() {
+        let value = crate::edn::Parser::new(r#"{:name "Alice" :age 30 :department "Eng" :nested {:a {:b 1}}}"#)
+            .parse().unwrap();
+        let config = OutputConfig { align_map_values: true, ..OutputConfig::default() };
+        assert_eq!(
+            format_output(&value, &config).unwrap(),
+            "{ :name       \"Alice\"\n  :age        30\n  :department \"Eng\"\n  :nested \n  {:a {:b 1}}}"
+        );
+
+        // Color codes don't count towards the key width
+        let colored = OutputConfig { color: ColorMode::Always, ..config.clone() };
+        assert!(format_output(&value, &colored).unwrap().contains(":age\x1b[0m        \x1b[33m30"));
+
+        // Inline maps are left alone
+        let small = crate::edn::Parser::new("{:a 1 :bbb 2}").parse().unwrap();
+        assert_eq!(format_output(&small, &config).unwrap(), "{:a 1 :bbb 2}");
+    }
+
+    #[test]
+    fn test_color_mode_resolve() {
+        assert_eq!(ColorMode::Auto.resolve(true), ColorMode::Always);
+        assert_eq!(ColorMode::Auto.resolve(false), ColorMode::Never);
+        assert_eq!(ColorMode::Never.resolve(true), ColorMode::Never);
+        assert_eq!(ColorMode::Always.resolve(false), ColorMode::Always);
+    }
+}
@@ -1,4 +1,6 @@
-/// Primitive formatting utilities for EDN values
+//! Primitive formatting utilities for EDN values
+
+use std::time::Duration;
 
 /// Escape special characters in strings
 pub fn escape_string(s: &str) -> String {
@@ -21,10 +23,29 @@ pub fn format_character(c: char) -> String {
         '\t' => "\\tab".to_string(),
         '\r' => "\\return".to_string(),
         ' ' => "\\space".to_string(),
+        '\x0C' => "\\formfeed".to_string(),
+        '\x08' => "\\backspace".to_string(),
+        // Other control characters would be invisible, or swallowed as whitespace
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
         c => format!("\\{}", c),
     }
 }
 
+/// Format a float so it reads back as a float: whole numbers keep a `.0`
+pub fn format_float(f: f64) -> String {
+    let s = f.to_string();
+    if f.is_finite() && !s.contains(['.', 'e', 'E']) {
+        format!("{}.0", s)
+    } else {
+        s
+    }
+}
+
+/// Format a timing in milliseconds, as printed by time, bench and --profile
+pub fn format_duration(elapsed: Duration) -> String {
+    format!("{:.3}ms", elapsed.as_secs_f64() * 1000.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,5 +63,18 @@ mod tests {
         assert_eq!(format_character('\n'), "\\newline");
         assert_eq!(format_character('\t'), "\\tab");
         assert_eq!(format_character(' '), "\\space");
+        assert_eq!(format_character('\x0C'), "\\formfeed");
+        assert_eq!(format_character('\x08'), "\\backspace");
+        assert_eq!(format_character('\x01'), "\\u0001");
+        assert_eq!(format_character('('), "\\(");
+    }
+
+    #[test]
+    fn test_float_formatting() {
+        assert_eq!(format_float(2.5), "2.5");
+        assert_eq!(format_float(3.0), "3.0");
+        assert_eq!(format_float(-0.0), "-0.0");
+        assert_eq!(format_float(1e20), "100000000000000000000.0");
+        assert_eq!(format_float(f64::INFINITY), "inf");
     }
 }
\ No newline at end of file
@@ -20,16 +20,14 @@ pub enum FunctionType {
 }
 
 /// Environment for symbol bindings during evaluation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Environment {
     bindings: HashMap<String, EdnValue>,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        Self {
-            bindings: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn with_context(context: EdnValue) -> Self {
@@ -45,10 +43,14 @@ impl Environment {
     pub fn lookup(&self, name: &str) -> Option<&EdnValue> {
         self.bindings.get(name)
     }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &EdnValue)> {
+        self.bindings.iter()
+    }
 }
 
 /// Registry for builtin functions and special forms
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct FunctionRegistry {
     functions: HashMap<String, FunctionType>,
 }
@@ -63,12 +65,32 @@ impl std::fmt::Debug for FunctionRegistry {
 
 impl FunctionRegistry {
     pub fn new() -> Self {
-        Self {
-            functions: HashMap::new(),
-        }
+        Self::default()
     }
 
+    /// Register a function that sees its arguments realized: a lazy sequence
+    /// argument is passed as a list, or fails if it is too long to realize
     pub fn register<F>(&mut self, name: String, func: F)
+    where
+        F: Fn(&[EdnValue]) -> crate::error::EqResult<EdnValue> + Send + Sync + 'static,
+    {
+        self.register_lazy(name, move |args: &[EdnValue]| {
+            if !args.iter().any(|arg| matches!(arg, EdnValue::LazySeq(_))) {
+                return func(args);
+            }
+            let realized = args.iter()
+                .map(|arg| match arg {
+                    EdnValue::LazySeq(seq) => seq.realize().map(EdnValue::List),
+                    other => Ok(other.clone()),
+                })
+                .collect::<crate::error::EqResult<Vec<_>>>()?;
+            func(&realized)
+        });
+    }
+
+    /// Register a function that handles lazy sequences itself, forcing only
+    /// as much of them as it needs
+    pub fn register_lazy<F>(&mut self, name: String, func: F)
     where
         F: Fn(&[EdnValue]) -> crate::error::EqResult<EdnValue> + Send + Sync + 'static,
     {
@@ -92,6 +114,13 @@ impl FunctionRegistry {
     pub fn get(&self, name: &str) -> Option<&FunctionType> {
         self.functions.get(name)
     }
+
+    /// Registered names in sorted order
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -126,6 +155,67 @@ pub enum Expr {
     
 }
 
+impl Expr {
+    /// The expression as EDN for --debug: a list headed by the variant name,
+    /// e.g. (Function map (KeywordAccess :name) (Symbol .))
+    pub fn to_edn(&self) -> EdnValue {
+        let node = |variant: &str, fields: Vec<EdnValue>| {
+            let mut items = vec![EdnValue::Symbol(variant.to_string())];
+            items.extend(fields);
+            EdnValue::List(items)
+        };
+        match self {
+            Expr::Symbol(name) => node("Symbol", vec![EdnValue::Symbol(name.clone())]),
+            Expr::KeywordAccess(name) => node("KeywordAccess", vec![EdnValue::Keyword(name.clone())]),
+            Expr::KeywordGet(name, expr) => node("KeywordGet", vec![EdnValue::Keyword(name.clone()), expr.to_edn()]),
+            Expr::KeywordGetWithDefault(name, expr, default) => node(
+                "KeywordGetWithDefault",
+                vec![EdnValue::Keyword(name.clone()), expr.to_edn(), default.to_edn()],
+            ),
+            Expr::Function { name, args } => {
+                let mut fields = vec![EdnValue::Symbol(name.clone())];
+                fields.extend(args.iter().map(Expr::to_edn));
+                node("Function", fields)
+            }
+            Expr::LambdaCall { func, args } => {
+                let mut fields = vec![func.to_edn()];
+                fields.extend(args.iter().map(Expr::to_edn));
+                node("LambdaCall", fields)
+            }
+            Expr::Comp(exprs) => node("Comp", exprs.iter().map(Expr::to_edn).collect()),
+            Expr::List(elements) => node("List", vec![EdnValue::List(elements.clone())]),
+            Expr::Literal(value) => node("Literal", vec![value.clone()]),
+        }
+    }
+
+    /// The expression as a form that analyzes back to it, for macros that
+    /// expand into special forms such as let, which take their bindings as EDN.
+    /// Literals come back as written, so a threaded (map (fn ...)) keeps its
+    /// fn form. None for comp, which has no such form.
+    pub fn to_form(&self) -> Option<EdnValue> {
+        let call = |head: EdnValue, args: &[Expr]| -> Option<EdnValue> {
+            let mut items = vec![head];
+            for arg in args {
+                items.push(arg.to_form()?);
+            }
+            Some(EdnValue::List(items))
+        };
+        match self {
+            Expr::Symbol(name) => Some(EdnValue::Symbol(name.clone())),
+            Expr::KeywordAccess(name) => call(EdnValue::Keyword(name.clone()), &[Expr::Symbol(".".to_string())]),
+            Expr::KeywordGet(name, expr) => call(EdnValue::Keyword(name.clone()), std::slice::from_ref(expr)),
+            Expr::KeywordGetWithDefault(name, expr, default) => {
+                call(EdnValue::Keyword(name.clone()), &[(**expr).clone(), (**default).clone()])
+            }
+            Expr::Function { name, args } => call(EdnValue::Symbol(name.clone()), args),
+            Expr::LambdaCall { func, args } => call(func.to_form()?, args),
+            Expr::Comp(_) => None,
+            Expr::List(elements) => Some(EdnValue::List(elements.clone())),
+            Expr::Literal(value) => Some(value.clone()),
+        }
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -159,6 +249,25 @@ mod tests {
         assert_eq!(keyword_expr, Expr::KeywordAccess("name".to_string()));
     }
 
+    #[test]
+    fn test_to_edn() {
+        let expr = Expr::Function {
+            name: "map".to_string(),
+            args: vec![
+                Expr::KeywordGetWithDefault(
+                    "a".to_string(),
+                    Box::new(Expr::Symbol(".".to_string())),
+                    Box::new(Expr::Literal(EdnValue::Integer(0))),
+                ),
+                Expr::List(vec![EdnValue::Symbol("f".to_string())]),
+            ],
+        };
+        assert_eq!(
+            expr.to_edn().to_string(),
+            "(Function map (KeywordGetWithDefault :a (Symbol .) (Literal 0)) (List (f)))"
+        );
+    }
+
     #[test]
     fn test_composition_expressions() {
         let comp_expr = Expr::Comp(vec![
@@ -0,0 +1,200 @@
+//! Interactive query REPL
+//!
+//! Each line is a query evaluated against the current context `.`. A non-nil
+//! result becomes the new context, so queries can drill into data step by step.
+
+use crate::cli::Args;
+use eq::analyzer::analyze;
+use eq::edn::{EdnValue, StreamingParser};
+use eq::error::{EqError, EqResult};
+use eq::evaluator::evaluate_with_globals;
+use eq::output::{format_output, OutputConfig};
+use eq::query::{ast::Environment, QueryParser};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "\
+Enter a query to evaluate it against the current data (.).
+A non-nil result becomes the new current data.
+
+Commands:
+  :help          Show this help
+  :reset         Restore the originally loaded data
+  :load <file>   Load a file as the new data
+  :q             Quit (also Ctrl-D)";
+
+/// What the REPL should do after handling a line
+#[derive(Debug, PartialEq)]
+enum Step {
+    Print(String),
+    Nothing,
+    Quit,
+}
+
+struct Repl<'a> {
+    args: &'a Args,
+    output_config: &'a OutputConfig,
+    original: EdnValue,
+    current: EdnValue,
+    // Bindings from --arg and --argjson
+    globals: Environment,
+}
+
+impl<'a> Repl<'a> {
+    fn new(args: &'a Args, output_config: &'a OutputConfig, data: EdnValue) -> Self {
+        Self {
+            args,
+            output_config,
+            original: data.clone(),
+            current: data,
+            globals: Environment::new(),
+        }
+    }
+
+    fn handle_line(&mut self, line: &str) -> EqResult<Step> {
+        let line = line.trim();
+        match line {
+            "" => return Ok(Step::Nothing),
+            ":q" | ":quit" => return Ok(Step::Quit),
+            ":help" => return Ok(Step::Print(HELP.to_string())),
+            ":reset" => {
+                self.current = self.original.clone();
+                return Ok(Step::Nothing);
+            }
+            _ => {}
+        }
+
+        let load_arg = line.strip_prefix(":load")
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+        if let Some(file) = load_arg {
+            let file = file.trim();
+            if file.is_empty() {
+                return Err(EqError::query_error(":load expects a file name"));
+            }
+            self.original = load_files(&[PathBuf::from(file)], self.args)?;
+            self.current = self.original.clone();
+            return Ok(Step::Nothing);
+        }
+
+        let query = analyze(QueryParser::parse(line)?)?;
+        let result = evaluate_with_globals(&query, &self.current, &self.globals)?;
+        let output = format_output(&result, self.output_config)?;
+        if !matches!(result, EdnValue::Nil) {
+            self.current = result;
+        }
+        Ok(Step::Print(output))
+    }
+}
+
+/// Run the REPL. In REPL mode every positional argument names an input file.
+pub fn run_repl(args: &Args, output_config: &OutputConfig) -> EqResult<()> {
+    let files: Vec<PathBuf> = Some(&args.filter)
+        .filter(|f| !f.is_empty())
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(args.files.iter().cloned())
+        .collect();
+    let data = if files.is_empty() {
+        EdnValue::Nil
+    } else {
+        load_files(&files, args)?
+    };
+
+    let mut repl = Repl::new(args, output_config, data);
+    repl.globals = crate::global_bindings(args)?;
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // No history yet is fine
+        let _ = editor.load_history(path);
+    }
+
+    println!("eq {} - type :help for help, :q to quit", env!("CARGO_PKG_VERSION"));
+    loop {
+        match editor.readline("eq> ") {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    editor.add_history_entry(line.as_str())?;
+                }
+                match repl.handle_line(&line) {
+                    Ok(Step::Print(output)) => println!("{}", output),
+                    Ok(Step::Nothing) => {}
+                    Ok(Step::Quit) => break,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        if let Some(snippet) = e.source_snippet() {
+                            eprintln!("{}", snippet);
+                        }
+                    }
+                }
+            }
+            // Ctrl-C abandons the current line
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+/// Read every top-level value from the files into one vector, as with --slurp
+fn load_files(files: &[PathBuf], args: &Args) -> EqResult<EdnValue> {
+    let mut values = Vec::new();
+    for path in files {
+        values.extend(load_file(path, args)?);
+    }
+    Ok(EdnValue::Vector(values))
+}
+
+fn load_file(path: &Path, args: &Args) -> EqResult<Vec<EdnValue>> {
+    let input = fs::read_to_string(path)?;
+    let filename = path.to_string_lossy();
+    match crate::read_json_values(&input, args.input_format, Some(&filename))? {
+        Some(values) => Ok(values),
+        None => StreamingParser::new(input.as_bytes(), Some(filename.to_string())).collect(),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".eq_history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn repl_step(repl: &mut Repl, line: &str) -> Step {
+        repl.handle_line(line).unwrap()
+    }
+
+    #[test]
+    fn test_repl_context() {
+        let args = Args::try_parse_from(["eq", "--repl"]).unwrap();
+        let config = OutputConfig { compact: true, ..OutputConfig::default() };
+        let data = crate::edn::Parser::new("{:users [{:name \"Alice\"}]}").parse().unwrap();
+        let mut repl = Repl::new(&args, &config, data);
+
+        assert_eq!(repl_step(&mut repl, "(:users .)"), Step::Print("[{:name \"Alice\"}]".to_string()));
+        // The result is the new context
+        assert_eq!(repl_step(&mut repl, "(first .)"), Step::Print("{:name \"Alice\"}".to_string()));
+
+        // Nil results leave the context alone
+        assert_eq!(repl_step(&mut repl, "(:missing .)"), Step::Print("nil".to_string()));
+        assert_eq!(repl_step(&mut repl, "(:name .)"), Step::Print("\"Alice\"".to_string()));
+
+        assert_eq!(repl_step(&mut repl, ":reset"), Step::Nothing);
+        assert_eq!(repl_step(&mut repl, "(count .)"), Step::Print("1".to_string()));
+
+        assert!(repl.handle_line("(no-such-function .)").is_err());
+        assert!(repl.handle_line(":load").is_err());
+        assert_eq!(repl_step(&mut repl, "  "), Step::Nothing);
+        assert_eq!(repl_step(&mut repl, ":q"), Step::Quit);
+    }
+}
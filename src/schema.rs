@@ -0,0 +1,257 @@
+//! Validation of EDN data against schemas that are themselves EDN.
+//!
+//! A schema is a map with a `:type` and constraints for that type:
+//!
+//! ```edn
+//! {:type :map
+//!  :keys {:name {:type :string :required true}
+//!         :age {:type :integer :min 0 :max 150}
+//!         :tags {:type :vector :element-type :keyword :max-count 5}}}
+//! ```
+//!
+//! A bare type keyword such as `:string` is short for `{:type :string}`.
+
+use crate::edn::EdnValue;
+use crate::error::{EqError, EqResult};
+use indexmap::IndexMap;
+use num_bigint::BigInt;
+use std::cmp::Ordering;
+
+const TYPES: [&str; 10] = ["string", "integer", "float", "boolean", "keyword", "nil", "map", "vector", "set", "any"];
+
+/// Check `data` against `schema`. Returns one `{:path [...] :error "..."}`
+/// map per problem found, so an empty result means the data is valid.
+/// A malformed schema is an error rather than a validation failure.
+pub fn validate(schema: &EdnValue, data: &EdnValue) -> EqResult<Vec<EdnValue>> {
+    let mut errors = Vec::new();
+    check(schema, data, &mut Vec::new(), &mut errors)?;
+    Ok(errors)
+}
+
+fn check(schema: &EdnValue, data: &EdnValue, path: &mut Vec<EdnValue>, errors: &mut Vec<EdnValue>) -> EqResult<()> {
+    let schema = schema_map(schema)?;
+    let type_name = match schema.get(&keyword("type")) {
+        None => "any",
+        Some(EdnValue::Keyword(t)) if TYPES.contains(&t.as_str()) => t.as_str(),
+        Some(other) => return Err(EqError::query_error(format!("schema/validate: unknown type {}", other))),
+    };
+
+    if !has_type(type_name, data) {
+        report(errors, path, format!("expected {}, got {}", type_name, data.type_name()));
+        return Ok(());
+    }
+
+    if let Some(min) = schema.get(&keyword("min")) {
+        if compare_number(data, min)? == Ordering::Less {
+            report(errors, path, format!("must be at least {}", min));
+        }
+    }
+    if let Some(max) = schema.get(&keyword("max")) {
+        if compare_number(data, max)? == Ordering::Greater {
+            report(errors, path, format!("must be at most {}", max));
+        }
+    }
+
+    match data {
+        EdnValue::Map(map) => check_keys(&schema, map, path, errors),
+        EdnValue::Vector(items) => check_elements(&schema, items.iter(), path, errors, true),
+        EdnValue::Set(items) => check_elements(&schema, items.iter(), path, errors, false),
+        _ => Ok(()),
+    }
+}
+
+/// `:keys` of a map schema. A field is required if its schema says
+/// `:required true` or it is listed in the map schema's own `:required`
+/// vector; `:optional true` makes it optional either way.
+fn check_keys(
+    schema: &IndexMap<EdnValue, EdnValue>,
+    map: &IndexMap<EdnValue, EdnValue>,
+    path: &mut Vec<EdnValue>,
+    errors: &mut Vec<EdnValue>,
+) -> EqResult<()> {
+    let required_keys: &[EdnValue] = match schema.get(&keyword("required")) {
+        None => &[],
+        Some(EdnValue::Vector(keys)) => keys,
+        Some(other) => return Err(EqError::query_error(format!("schema/validate: :required must be a vector of keys, got {}", other))),
+    };
+    let fields = match schema.get(&keyword("keys")) {
+        None => &IndexMap::new(),
+        Some(EdnValue::Map(fields)) => fields,
+        Some(other) => return Err(EqError::query_error(format!("schema/validate: :keys must be a map, got {}", other))),
+    };
+
+    for (key, field_schema) in fields {
+        let field = schema_map(field_schema)?;
+        let optional = field.get(&keyword("optional")).is_some_and(EdnValue::is_truthy);
+        let required = !optional
+            && (field.get(&keyword("required")).is_some_and(EdnValue::is_truthy) || required_keys.contains(key));
+        path.push(key.clone());
+        match map.get(key) {
+            Some(value) => check(field_schema, value, path, errors)?,
+            None if required => report(errors, path, "required field missing".to_string()),
+            None => {}
+        }
+        path.pop();
+    }
+    for key in required_keys.iter().filter(|key| !fields.contains_key(*key) && !map.contains_key(*key)) {
+        path.push(key.clone());
+        report(errors, path, "required field missing".to_string());
+        path.pop();
+    }
+    Ok(())
+}
+
+/// `:element-type`, `:min-count` and `:max-count` of a vector or set schema.
+/// Vector elements are reported by index; set elements by value.
+fn check_elements<'a>(
+    schema: &IndexMap<EdnValue, EdnValue>,
+    items: impl ExactSizeIterator<Item = &'a EdnValue>,
+    path: &mut Vec<EdnValue>,
+    errors: &mut Vec<EdnValue>,
+    indexed: bool,
+) -> EqResult<()> {
+    let count = items.len();
+    if let Some(min) = count_limit(schema, "min-count")? {
+        if count < min {
+            report(errors, path, format!("must have at least {} elements, has {}", min, count));
+        }
+    }
+    if let Some(max) = count_limit(schema, "max-count")? {
+        if count > max {
+            report(errors, path, format!("must have at most {} elements, has {}", max, count));
+        }
+    }
+
+    if let Some(element_schema) = schema.get(&keyword("element-type")) {
+        for (i, item) in items.enumerate() {
+            path.push(if indexed { EdnValue::Integer(i as i64) } else { item.clone() });
+            check(element_schema, item, path, errors)?;
+            path.pop();
+        }
+    }
+    Ok(())
+}
+
+fn schema_map(schema: &EdnValue) -> EqResult<IndexMap<EdnValue, EdnValue>> {
+    match schema {
+        EdnValue::Map(map) => Ok(map.clone()),
+        EdnValue::Keyword(_) => Ok(IndexMap::from([(keyword("type"), schema.clone())])),
+        other => Err(EqError::query_error(format!("schema/validate: a schema must be a map or a type keyword, got {}", other))),
+    }
+}
+
+fn has_type(type_name: &str, value: &EdnValue) -> bool {
+    matches!(
+        (type_name, value),
+        ("any", _)
+            | ("string", EdnValue::String(_))
+            | ("integer", EdnValue::Integer(_) | EdnValue::BigInt(_))
+            | ("float", EdnValue::Float(_))
+            | ("boolean", EdnValue::Bool(_))
+            | ("keyword", EdnValue::Keyword(_))
+            | ("nil", EdnValue::Nil)
+            | ("map", EdnValue::Map(_))
+            | ("vector", EdnValue::Vector(_))
+            | ("set", EdnValue::Set(_))
+    )
+}
+
+/// Order a value against a :min or :max bound. Non-numeric values are never
+/// out of bounds, so the bounds can sit on an `:any` schema.
+fn compare_number(value: &EdnValue, bound: &EdnValue) -> EqResult<Ordering> {
+    let as_f64 = |v: &EdnValue| match v {
+        EdnValue::Integer(i) => Some(*i as f64),
+        EdnValue::BigInt(n) => num_traits::ToPrimitive::to_f64(n),
+        EdnValue::Float(f) => Some(*f),
+        _ => None,
+    };
+    let Some(limit) = as_f64(bound) else {
+        return Err(EqError::query_error(format!("schema/validate: bounds must be numbers, got {}", bound)));
+    };
+    Ok(match (value, bound) {
+        (EdnValue::BigInt(n), EdnValue::Integer(i)) => n.cmp(&BigInt::from(*i)),
+        _ => as_f64(value).and_then(|v| v.partial_cmp(&limit)).unwrap_or(Ordering::Equal),
+    })
+}
+
+fn count_limit(schema: &IndexMap<EdnValue, EdnValue>, name: &str) -> EqResult<Option<usize>> {
+    match schema.get(&keyword(name)) {
+        None => Ok(None),
+        Some(EdnValue::Integer(n)) if *n >= 0 => Ok(Some(*n as usize)),
+        Some(other) => Err(EqError::query_error(format!("schema/validate: :{} must be a non-negative integer, got {}", name, other))),
+    }
+}
+
+fn report(errors: &mut Vec<EdnValue>, path: &[EdnValue], message: String) {
+    errors.push(EdnValue::Map(IndexMap::from([
+        (keyword("path"), EdnValue::Vector(path.to_vec())),
+        (keyword("error"), EdnValue::String(message)),
+    ])));
+}
+
+fn keyword(name: &str) -> EdnValue {
+    EdnValue::Keyword(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edn::Parser;
+
+    fn parse(s: &str) -> EdnValue {
+        Parser::new(s).parse().unwrap()
+    }
+
+    fn errors(schema: &str, data: &str) -> String {
+        EdnValue::Vector(validate(&parse(schema), &parse(data)).unwrap()).to_string()
+    }
+
+    const PERSON: &str = "{:type :map :keys {:name {:type :string :required true} :age {:type :integer :min 0 :max 150}}}";
+
+    #[test]
+    fn test_map_schemas() {
+        assert_eq!(errors(PERSON, r#"{:name "Alice" :age 30}"#), "[]");
+        assert_eq!(errors(PERSON, r#"{:name "Alice" :extra 1}"#), "[]");
+        assert_eq!(errors(PERSON, "{:age 30}"), r#"[{:path [:name] :error "required field missing"}]"#);
+        assert_eq!(
+            errors(PERSON, r#"{:name :alice :age 200}"#),
+            r#"[{:path [:name] :error "expected string, got keyword"} {:path [:age] :error "must be at most 150"}]"#
+        );
+        assert_eq!(errors(PERSON, "[]"), r#"[{:path [] :error "expected map, got vector"}]"#);
+
+        // Required keys can also be listed on the map schema; :optional wins
+        assert_eq!(errors("{:type :map :required [:id]}", "{}"), r#"[{:path [:id] :error "required field missing"}]"#);
+        assert_eq!(errors("{:type :map :required [:id] :keys {:id {:optional true}}}", "{}"), "[]");
+    }
+
+    #[test]
+    fn test_collection_schemas() {
+        let schema = "{:type :vector :element-type :integer :min-count 1 :max-count 3}";
+        assert_eq!(errors(schema, "[1 2]"), "[]");
+        assert_eq!(errors(schema, r#"[1 "2"]"#), r#"[{:path [1] :error "expected integer, got string"}]"#);
+        assert_eq!(errors(schema, "[]"), r#"[{:path [] :error "must have at least 1 elements, has 0"}]"#);
+        assert_eq!(errors("{:type :set :element-type :keyword}", "#{:a 1}"), r#"[{:path [1] :error "expected keyword, got integer"}]"#);
+
+        // Nested paths
+        let schema = "{:type :map :keys {:users {:type :vector :element-type {:type :map :keys {:id {:type :integer :required true}}}}}}";
+        assert_eq!(errors(schema, "{:users [{:id 1} {}]}"), r#"[{:path [:users 1 :id] :error "required field missing"}]"#);
+    }
+
+    #[test]
+    fn test_scalar_types() {
+        for (schema, data) in [(":string", "\"s\""), (":integer", "10000000000000000000N"), (":float", "1.5"),
+                               (":boolean", "false"), (":keyword", ":k"), (":nil", "nil"), (":set", "#{}"), (":any", "{}")] {
+            assert_eq!(errors(schema, data), "[]", "{} should accept {}", schema, data);
+        }
+        assert_eq!(errors(":float", "1"), r#"[{:path [] :error "expected float, got integer"}]"#);
+        assert_eq!(errors("{:min 1.5}", "1"), r#"[{:path [] :error "must be at least 1.5"}]"#);
+    }
+
+    #[test]
+    fn test_malformed_schemas() {
+        assert!(validate(&parse("{:type :text}"), &parse("1")).is_err());
+        assert!(validate(&parse("\"string\""), &parse("1")).is_err());
+        assert!(validate(&parse("{:type :vector :min-count -1}"), &parse("[]")).is_err());
+        assert!(validate(&parse("{:type :integer :min :zero}"), &parse("1")).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Table output for collections of maps
+
+use crate::csv::header_name;
+use crate::edn::EdnValue;
+use crate::error::{EqError, EqResult};
+use crate::formatter::{CompactFormatter, Formatter};
+use crate::output::{OutputConfig, TableFormat};
+
+/// Format a vector of maps as a table.
+///
+/// Columns are the union of keys across all maps, in first-seen order.
+/// Cells hold compact EDN; missing keys are left empty.
+pub fn format_table(value: &EdnValue, config: &OutputConfig) -> EqResult<String> {
+    let rows = match value {
+        EdnValue::Vector(rows) | EdnValue::List(rows) => rows,
+        other => return Err(EqError::query_error(format!(
+            "Table output requires a vector of maps, got {}",
+            other.type_name()
+        ))),
+    };
+
+    let mut columns: Vec<&EdnValue> = Vec::new();
+    let mut maps = Vec::with_capacity(rows.len());
+    for row in rows {
+        match row {
+            EdnValue::Map(map) => {
+                for key in map.keys() {
+                    if !columns.contains(&key) {
+                        columns.push(key);
+                    }
+                }
+                maps.push(map);
+            }
+            other => return Err(EqError::query_error(format!(
+                "Table output requires a vector of maps, found {} element",
+                other.type_name()
+            ))),
+        }
+    }
+
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let cell_config = OutputConfig { compact: true, ..OutputConfig::default() };
+    let header: Vec<String> = columns.iter().map(|key| header_name(key)).collect();
+    let body: Vec<Vec<String>> = maps.iter()
+        .map(|map| {
+            columns.iter()
+                .map(|key| map.get(*key).map(|v| CompactFormatter.format(v, &cell_config, 0)).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    // Markdown needs at least three dashes per column
+    let min_width = if config.table_format == TableFormat::Markdown { 3 } else { 0 };
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            body.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].chars().count(), min_width])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut lines = Vec::new();
+    match config.table_format {
+        TableFormat::Ascii => {
+            let border = format!(
+                "+{}+",
+                widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("+")
+            );
+            lines.push(border.clone());
+            lines.push(format_row(&header, &widths));
+            lines.push(border.clone());
+            lines.extend(body.iter().map(|row| format_row(row, &widths)));
+            lines.push(border);
+        }
+        TableFormat::Markdown => {
+            lines.push(format_row(&header, &widths));
+            lines.push(format!(
+                "|{}|",
+                widths.iter().map(|w| format!(" {} ", "-".repeat(*w))).collect::<Vec<_>>().join("|")
+            ));
+            lines.extend(body.iter().map(|row| format_row(row, &widths)));
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn format_row(cells: &[String], widths: &[usize]) -> String {
+    let padded: Vec<String> = cells.iter()
+        .zip(widths)
+        .map(|(cell, width)| format!(" {}{} ", cell, " ".repeat(width - cell.chars().count())))
+        .collect();
+    format!("|{}|", padded.join("|"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edn::Parser;
+
+    fn to_table(edn: &str, table_format: TableFormat) -> EqResult<String> {
+        let value = Parser::new(edn).parse().unwrap();
+        let config = OutputConfig { table_format, ..OutputConfig::default() };
+        format_table(&value, &config)
+    }
+
+    #[test]
+    fn test_ascii_table() {
+        let result = to_table(r#"[{:name "Alice" :age 30} {:name "Bob" :role :admin}]"#, TableFormat::Ascii).unwrap();
+        let expected = "\
++---------+-----+--------+
+| name    | age | role   |
++---------+-----+--------+
+| \"Alice\" | 30  |        |
+| \"Bob\"   |     | :admin |
++---------+-----+--------+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_markdown_table() {
+        let result = to_table("[{:a 1 :b [1 2]}]", TableFormat::Markdown).unwrap();
+        assert_eq!(result, "| a   | b     |\n| --- | ----- |\n| 1   | [1 2] |");
+    }
+
+    #[test]
+    fn test_table_errors() {
+        assert!(to_table("{:a 1}", TableFormat::Ascii).is_err());
+        assert!(to_table("[{:a 1} :x]", TableFormat::Ascii).is_err());
+    }
+}
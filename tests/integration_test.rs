@@ -1,4 +1,4 @@
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::fs;
 use std::env;
 
@@ -29,8 +29,8 @@ fn test_basic_operations() {
     fs::write("test_basic.edn", r#"{:name "Alice" :age 30}"#).unwrap();
     
     // Test identity
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_basic.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_basic.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -40,8 +40,8 @@ fn test_basic_operations() {
     assert!(stdout.contains("30"));
     
     // Test keyword access
-    let output = Command::new(&get_binary_path())
-        .args(&["(:name .)", "test_basic.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(:name .)", "test_basic.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -59,8 +59,8 @@ fn test_collection_operations() {
     fs::write("test_array.edn", r#"[1 2 3 4 5]"#).unwrap();
     
     // Test first
-    let output = Command::new(&get_binary_path())
-        .args(&["(first .)", "test_array.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(first .)", "test_array.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -69,8 +69,8 @@ fn test_collection_operations() {
     assert_eq!(stdout.trim(), "1");
     
     // Test count
-    let output = Command::new(&get_binary_path())
-        .args(&["(count .)", "test_array.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(count .)", "test_array.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -88,8 +88,8 @@ fn test_compact_output() {
     fs::write("test_compact.edn", r#"{:a {:b {:c 42}}}"#).unwrap();
     
     // Test compact output
-    let output = Command::new(&get_binary_path())
-        .args(&["-c", ".", "test_compact.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["-c", ".", "test_compact.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -108,8 +108,8 @@ fn test_raw_output() {
     fs::write("test_raw.edn", r#"{:message "Hello World"}"#).unwrap();
     
     // Test raw string output
-    let output = Command::new(&get_binary_path())
-        .args(&["--raw-output", "(:message .)", "test_raw.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["--raw-output", "(:message .)", "test_raw.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -121,24 +121,265 @@ fn test_raw_output() {
     fs::remove_file("test_raw.edn").unwrap();
 }
 
+#[test]
+fn test_join_output() {
+    fs::write("test_join.edn", r#"{:a "x"} {:a "y"} {:a 1}"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--raw-output", "--join-output", "(:a .)", "test_join.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "xy1");
+
+    fs::remove_file("test_join.edn").unwrap();
+}
+
+#[test]
+fn test_null_separator() {
+    fs::write("test_null_sep.edn", "{:path \"a b\"} {:path \"c\\nd\"}").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(args)
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&["-0", "--raw-output", "(:path .)", "test_null_sep.edn"]), "a b\0c\nd\0");
+    assert_eq!(run(&["-0", "(:path .)", "test_null_sep.edn"]), "\"a b\"\0\"c\\nd\"\0");
+    assert_eq!(
+        run(&["-0", "-H", "--raw-output", "(:path .)", "test_null_sep.edn"]),
+        "test_null_sep.edn\0a b\0test_null_sep.edn\0c\nd\0"
+    );
+
+    fs::remove_file("test_null_sep.edn").unwrap();
+}
+
+#[test]
+fn test_in_place() {
+    fs::write("test_in_place_a.edn", "{:a 1 :b 2}").unwrap();
+    fs::write("test_in_place_b.edn", "{:a 3}").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-c", "--in-place=.bak", "(update-vals #(* 10 %) .)", "test_in_place_a.edn", "test_in_place_b.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(fs::read_to_string("test_in_place_a.edn").unwrap(), "{:a 10 :b 20}\n");
+    assert_eq!(fs::read_to_string("test_in_place_b.edn").unwrap(), "{:a 30}\n");
+    assert_eq!(fs::read_to_string("test_in_place_a.edn.bak").unwrap(), "{:a 1 :b 2}");
+
+    // A failing filter leaves the file alone
+    let output = Command::new(get_binary_path())
+        .args(["-i", "(+ :a 1)", "test_in_place_b.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(fs::read_to_string("test_in_place_b.edn").unwrap(), "{:a 30}\n");
+
+    // There is nothing to rewrite when reading stdin
+    let output = Command::new(get_binary_path())
+        .args(["-i", "."])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+
+    for file in ["test_in_place_a.edn", "test_in_place_b.edn", "test_in_place_a.edn.bak", "test_in_place_b.edn.bak"] {
+        fs::remove_file(file).unwrap();
+    }
+    let leftovers = fs::read_dir(".").unwrap().filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(".tmp")).count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn test_with_line_numbers() {
+    fs::write("test_line_numbers.edn", "{:a 1}\n\n; comment\n{:a\n 2} {:a 3}\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(args)
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&["--with-line-numbers", "(:a .)", "test_line_numbers.edn"]), "1:1\n4:2\n5:3\n");
+    assert_eq!(
+        run(&["-H", "--with-line-numbers", "(:a .)", "test_line_numbers.edn"]),
+        "test_line_numbers.edn:1:1\ntest_line_numbers.edn:4:2\ntest_line_numbers.edn:5:3\n"
+    );
+    assert_eq!(run(&["-R", "--with-line-numbers", "(count .)", "test_line_numbers.edn"]), "1:6\n2:0\n3:9\n4:3\n5:10\n");
+
+    fs::remove_file("test_line_numbers.edn").unwrap();
+}
+
+#[test]
+fn test_count() {
+    fs::write("test_count_a.edn", "{:age 20} {:age 40} {:age 50}").unwrap();
+    fs::write("test_count_b.edn", "{:age 45} {:name \"x\"}").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(args)
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let query = "(when (> (:age . 0) 30) .)";
+    assert_eq!(run(&["--count", query, "test_count_a.edn", "test_count_b.edn"]), "3\n");
+    assert_eq!(
+        run(&["--count", "-H", query, "test_count_a.edn", "test_count_b.edn"]),
+        "test_count_a.edn:2\ntest_count_b.edn:1\n"
+    );
+    assert_eq!(run(&["--count", "-n", "nil"]), "0\n");
+
+    fs::remove_file("test_count_a.edn").unwrap();
+    fs::remove_file("test_count_b.edn").unwrap();
+}
+
+#[test]
+fn test_stream() {
+    fs::write("test_stream.edn", "{:a 1} {:a nil} {:a \"x\"}").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(args)
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&["--stream", "-c", "(:a .)", "test_stream.edn"]), "[1 nil \"x\"]\n");
+    assert_eq!(run(&["--stream", "-c", "--suppress-nil", "(:a .)", "test_stream.edn"]), "[1 \"x\"]\n");
+    assert_eq!(run(&["--stream", "-c", "-j", "(:a .)", "test_stream.edn"]), "[1,null,\"x\"]\n");
+    assert_eq!(run(&["--stream", "-c", "(:b .)", "-n"]), "[nil]\n");
+
+    // Nothing is printed when processing fails partway through
+    let output = Command::new(get_binary_path())
+        .args(["--stream", "(+ (:a .) 1)", "test_stream.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_file("test_stream.edn").unwrap();
+}
+
+#[test]
+fn test_debug_output() {
+    let output = Command::new(get_binary_path())
+        .args(["--debug", "-n", "(:a .)"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "nil\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(";; Parsed query\n(List (:a .))"));
+    assert!(stderr.contains(";; Analyzed query\n(KeywordGet :a (Symbol .))"));
+
+    fs::write("test_debug_input.edn", "{:a 1}\n[2]").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["--debug-input", "-c", "(count .)", "test_debug_input.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n1\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), ";; Input\n{:a 1}\n;; Input\n[2]\n");
+    fs::remove_file("test_debug_input.edn").unwrap();
+}
+
+#[test]
+fn test_print_writes_to_stderr() {
+    let output = Command::new(get_binary_path())
+        .args(["-n", "-c", r#"(map (fn [x] (do (print x) (eprintln "!") (* x 10))) (list 1 2))"#])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[10 20]\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "1\"!\"\n2\"!\"\n");
+}
+
+#[test]
+fn test_timing_goes_to_stderr() {
+    let output = Command::new(get_binary_path())
+        .args(["-n", "-c", "(bench 3 (time (+ 1 2)))"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[..3].iter().all(|line| line.starts_with("Elapsed: ") && line.ends_with("ms")));
+    assert!(lines[3].starts_with("Bench: 3 runs, min "));
+
+    let output = Command::new(get_binary_path())
+        .args(["--profile", "-c", ":name", "test_data.edn", "test_data.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("test_data.edn: ") && lines[0].ends_with("ms"));
+    assert!(lines[2].starts_with("total: "));
+}
+
 #[test]
 fn test_error_handling() {
     // Test invalid query
-    let output = Command::new(&get_binary_path())
-        .args(&["(invalid-function)", "-n"]) // null input to avoid file issues
+    let output = Command::new(get_binary_path())
+        .args(["(invalid-function)", "-n"]) // null input to avoid file issues
         .output()
         .expect("Failed to execute eq");
     
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
     let stderr = String::from_utf8(output.stderr).unwrap();
     assert!(stderr.contains("Unknown function"));
 }
 
+#[test]
+fn test_exit_status() {
+    let exit_code = |args: &[&str]| {
+        Command::new(get_binary_path())
+            .args(args)
+            .output()
+            .expect("Failed to execute eq")
+            .status
+            .code()
+    };
+
+    // Without -e the output value doesn't matter
+    assert_eq!(exit_code(&["-n", "(:a .)"]), Some(0));
+
+    assert_eq!(exit_code(&["-e", "-n", "(nil? .)"]), Some(0));
+    assert_eq!(exit_code(&["-e", "-n", "(:a .)"]), Some(1));
+    assert_eq!(exit_code(&["-e", "-n", "(no-such-function .)"]), Some(2));
+
+    // Any truthy output across multiple inputs is enough
+    fs::write("test_exit_status.edn", "nil false 0").unwrap();
+    assert_eq!(exit_code(&["-e", ".", "test_exit_status.edn"]), Some(0));
+    assert_eq!(exit_code(&["-e", "(nil? .)", "test_exit_status.edn"]), Some(0));
+    assert_eq!(exit_code(&["-e", "(number? .)", "test_exit_status.edn"]), Some(0));
+    assert_eq!(exit_code(&["-e", "(string? .)", "test_exit_status.edn"]), Some(1));
+    fs::remove_file("test_exit_status.edn").unwrap();
+}
+
 #[test]
 fn test_null_input() {
     // Test null input mode - just test that nil input works
-    let output = Command::new(&get_binary_path())
-        .args(&["-n", "(nil? .)"])
+    let output = Command::new(get_binary_path())
+        .args(["-n", "(nil? .)"])
         .output()
         .expect("Failed to execute eq");
     
@@ -147,12 +388,103 @@ fn test_null_input() {
     assert_eq!(stdout.trim(), "true");
 }
 
+#[test]
+fn test_arg_bindings() {
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path()).args(args).output().expect("Failed to execute eq");
+        (output.status.code(), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (code, stdout, _) = run(&["-n", "--arg", "greeting", "hello", r#"(str "Hello " greeting)"#]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.trim(), r#""Hello hello""#);
+
+    // --argjson accepts JSON or EDN, and bindings are visible alongside the input
+    fs::write("test_arg_bindings.edn", "{:score 7}").unwrap();
+    let (_, stdout, _) = run(&[
+        "-c", "--argjson", "limits", r#"{"max": 2}"#, "--argjson", "field", ":score", "--arg", "n", "1",
+        "(list (:max limits) (get . field) n)", "test_arg_bindings.edn",
+    ]);
+    fs::remove_file("test_arg_bindings.edn").unwrap();
+    assert_eq!(stdout.trim(), r#"(2 7 "1")"#);
+
+    let (code, _, stderr) = run(&["-n", "--argjson", "bad", "{:a", "bad"]);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("--argjson"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_output_file() {
+    fs::write("test_output_file_in.edn", "1 2").unwrap();
+    let run = |args: &[&str]| Command::new(get_binary_path()).args(args).output().expect("Failed to execute eq");
+
+    let output = run(&["-o", "test_output_file_out.edn", "(* . 10)", "test_output_file_in.edn"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(fs::read_to_string("test_output_file_out.edn").unwrap(), "10\n20\n");
+
+    let output = run(&["-o", "test_output_file_out.edn", "--append", "-H", ".", "test_output_file_in.edn"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string("test_output_file_out.edn").unwrap(),
+        "10\n20\ntest_output_file_in.edn:1\ntest_output_file_in.edn:2\n",
+    );
+
+    // Writing over an input is refused before the file is touched
+    let output = run(&["-o", "./test_output_file_in.edn", ".", "test_output_file_in.edn"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("also an input file"));
+    assert_eq!(fs::read_to_string("test_output_file_in.edn").unwrap(), "1 2");
+
+    // Output produced before an error is still written
+    let output = run(&["-o", "test_output_file_out.edn", "(/ 10 .)", "test_output_file_in.edn", "test_output_file_missing.edn"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(fs::read_to_string("test_output_file_out.edn").unwrap(), "10\n5\n");
+
+    fs::remove_file("test_output_file_in.edn").unwrap();
+    fs::remove_file("test_output_file_out.edn").unwrap();
+}
+
+#[test]
+fn test_slurp_keeps_nil_values() {
+    fs::write("test_slurp_nil.edn", "nil true false 42").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["-c", "-s", ".", "test_slurp_nil.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    fs::remove_file("test_slurp_nil.edn").unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "[nil true false 42]");
+
+    // A parse error part way through is reported rather than ending the input early
+    fs::write("test_slurp_broken.edn", "1 2 {:a").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["-s", "(count .)", "test_slurp_broken.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    fs::remove_file("test_slurp_broken.edn").unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_spy_prints_to_stderr() {
+    let output = Command::new(get_binary_path())
+        .args(["-n", "(count (spy [1 2]))"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "2");
+    assert_eq!(String::from_utf8(output.stderr).unwrap().trim(), "[1 2]");
+}
+
 #[test]
 fn test_broken_edn_files() {
     // Test unterminated string
     fs::write("test_broken1.edn", r#"{"unterminated string}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_broken1.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_broken1.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -163,8 +495,8 @@ fn test_broken_edn_files() {
     
     // Test unterminated vector
     fs::write("test_broken2.edn", r#"[1 2 3"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_broken2.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_broken2.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -175,8 +507,8 @@ fn test_broken_edn_files() {
     
     // Test invalid map (odd number of elements)
     fs::write("test_broken3.edn", r#"{:key}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_broken3.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_broken3.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -187,8 +519,8 @@ fn test_broken_edn_files() {
     
     // Test duplicate set elements  
     fs::write("test_broken4.edn", r#"#{1 1}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_broken4.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_broken4.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -198,11 +530,83 @@ fn test_broken_edn_files() {
     fs::remove_file("test_broken4.edn").unwrap();
 }
 
+#[test]
+fn test_parse_error_source_context() {
+    fs::write("test_broken_context.edn", "{:a 1}\n[1 2 ~]\n").unwrap();
+    let output = Command::new(get_binary_path())
+        .args([".", "test_broken_context.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    fs::remove_file("test_broken_context.edn").unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("in file 'test_broken_context.edn' at line 2, column 6"));
+    assert!(stderr.ends_with("[1 2 ~]\n     ^\n"), "unexpected stderr: {}", stderr);
+}
+
+#[test]
+fn test_structured_errors() {
+    fs::write("test_structured_error.edn", "{:a 1}\n[1 2 ~]\n").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["--error-format", "json", ".", "test_structured_error.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    fs::remove_file("test_structured_error.edn").unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let error: serde_json::Value = serde_json::from_str(stderr.trim_end()).unwrap();
+    assert_eq!(error["type"], "parse-error");
+    assert_eq!(error["line"], 2);
+    assert_eq!(error["column"], 6);
+    assert_eq!(error["file"], "test_structured_error.edn");
+    assert_eq!(error["query"], ".");
+
+    let output = Command::new(get_binary_path())
+        .args(["--error-format", "edn", "-n", "(no-such-fn)"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{:type :query-error :message \"Unknown function: no-such-fn\" :query \"(no-such-fn)\"}\n"
+    );
+
+    // Errors while processing a file name it, whatever their kind
+    fs::write("test_structured_query_error.edn", "{:a 1}\n").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["--error-format", "json", "(+ :a 1)", "test_structured_query_error.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    fs::remove_file("test_structured_query_error.edn").unwrap();
+    let error: serde_json::Value = serde_json::from_str(String::from_utf8(output.stderr).unwrap().trim_end()).unwrap();
+    assert_eq!(error["type"], "type-error");
+    assert_eq!(error["file"], "test_structured_query_error.edn");
+
+    let output = Command::new(get_binary_path())
+        .args(["--error-format", "edn", ".", "test_structured_missing.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("{:type :io-error "), "{}", stderr);
+    assert!(stderr.contains(r#":file "test_structured_missing.edn""#), "{}", stderr);
+
+    // A stray recur is reported as an ordinary query error
+    let output = Command::new(get_binary_path())
+        .args(["--error-format", "edn", "-n", "(recur 1)"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{:type :query-error :message \"recur can only be used in tail position of a loop or fn\" :query \"(recur 1)\"}\n"
+    );
+}
+
 #[test]
 fn test_broken_queries() {
     // Test empty parentheses
-    let output = Command::new(&get_binary_path())
-        .args(&["()", "-n"])
+    let output = Command::new(get_binary_path())
+        .args(["()", "-n"])
         .output()
         .expect("Failed to execute eq");
     
@@ -211,8 +615,8 @@ fn test_broken_queries() {
     assert!(stderr.contains("QueryError") || stderr.contains("Empty"));
     
     // Test unterminated parentheses
-    let output = Command::new(&get_binary_path())
-        .args(&["(first", "-n"])
+    let output = Command::new(get_binary_path())
+        .args(["(first", "-n"])
         .output()
         .expect("Failed to execute eq");
     
@@ -221,8 +625,8 @@ fn test_broken_queries() {
     assert!(stderr.contains("Parse error") || stderr.contains("ParseError") || stderr.contains("Unterminated"));
     
     // Test invalid function arguments
-    let output = Command::new(&get_binary_path())
-        .args(&["(get)", "-n"])
+    let output = Command::new(get_binary_path())
+        .args(["(get)", "-n"])
         .output()
         .expect("Failed to execute eq");
     
@@ -231,8 +635,8 @@ fn test_broken_queries() {
     assert!(stderr.contains("QueryError") || stderr.contains("expects"));
     
     // Test too many arguments
-    let output = Command::new(&get_binary_path())
-        .args(&["(get :a :b :c)", "-n"])
+    let output = Command::new(get_binary_path())
+        .args(["(get :a :b :c)", "-n"])
         .output()
         .expect("Failed to execute eq");
     
@@ -245,8 +649,8 @@ fn test_broken_queries() {
 fn test_metadata_parsing() {
     // Test simple keyword metadata
     fs::write("test_metadata1.edn", r#"^:tag {:key "value"}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_metadata1.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_metadata1.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -258,8 +662,8 @@ fn test_metadata_parsing() {
     
     // Test map metadata with the user's example
     fs::write("test_metadata2.edn", r#"{:features ^{:replace true} #{:datomic :datomic-init}}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_metadata2.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_metadata2.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -271,8 +675,8 @@ fn test_metadata_parsing() {
     
     // Test accessing the value through metadata
     fs::write("test_metadata3.edn", r#"^{:doc "A set"} #{:a :b :c}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&["(count .)", "test_metadata3.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(count .)", "test_metadata3.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -286,8 +690,8 @@ fn test_metadata_parsing() {
 fn test_discard_macro() {
     // Test discard in vector
     fs::write("test_discard1.edn", r#"[1 2 #_ 3 4]"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_discard1.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_discard1.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -298,8 +702,8 @@ fn test_discard_macro() {
     
     // Test discard in map
     fs::write("test_discard2.edn", r#"{:a 1 #_ :b #_ 2 :c 3}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_discard2.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_discard2.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -312,8 +716,8 @@ fn test_discard_macro() {
     
     // Test discard in set
     fs::write("test_discard3.edn", r#"#{1 #_ 2 3}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&["(count .)", "test_discard3.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(count .)", "test_discard3.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -327,8 +731,8 @@ fn test_discard_macro() {
 fn test_builtin_tagged_literals() {
     // Test #inst
     fs::write("test_inst.edn", r#"#inst "2023-01-01T12:30:45Z""#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_inst.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_inst.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -339,8 +743,8 @@ fn test_builtin_tagged_literals() {
     
     // Test #uuid
     fs::write("test_uuid.edn", r#"#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6""#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_uuid.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_uuid.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -351,8 +755,8 @@ fn test_builtin_tagged_literals() {
     
     // Test invalid formats
     fs::write("test_bad_inst.edn", r#"#inst "not-a-date""#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_bad_inst.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_bad_inst.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -366,8 +770,8 @@ fn test_builtin_tagged_literals() {
 fn test_unicode_escapes() {
     // Test unicode character literal
     fs::write("test_unicode_char.edn", r#"\u03A9"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_unicode_char.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_unicode_char.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -378,8 +782,8 @@ fn test_unicode_escapes() {
     
     // Test unicode in string
     fs::write("test_unicode_string.edn", r#""Hello \u03A9 World""#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_unicode_string.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_unicode_string.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -393,8 +797,8 @@ fn test_unicode_escapes() {
 fn test_new_character_literals() {
     // Test formfeed character
     fs::write("test_formfeed.edn", r#"\formfeed"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_formfeed.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_formfeed.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -403,8 +807,8 @@ fn test_new_character_literals() {
     
     // Test backspace character
     fs::write("test_backspace.edn", r#"\backspace"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_backspace.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_backspace.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -416,8 +820,8 @@ fn test_new_character_literals() {
 fn test_comma_as_whitespace() {
     // Test commas in various collections
     fs::write("test_commas.edn", r#"[1, 2, 3]"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_commas.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_commas.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -428,8 +832,8 @@ fn test_comma_as_whitespace() {
     
     // Test commas in map
     fs::write("test_commas_map.edn", r#"{:a 1, :b 2, :c 3}"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_commas_map.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_commas_map.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -442,8 +846,8 @@ fn test_comma_as_whitespace() {
     
     // Test trailing commas
     fs::write("test_trailing_commas.edn", r#"[1, 2, 3,]"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&["(count .)", "test_trailing_commas.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(count .)", "test_trailing_commas.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -454,8 +858,8 @@ fn test_comma_as_whitespace() {
     
     // Test multiple consecutive commas
     fs::write("test_multiple_commas.edn", r#"[1,, 2,,, 3]"#).unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&["(count .)", "test_multiple_commas.edn"])
+    let output = Command::new(get_binary_path())
+        .args(["(count .)", "test_multiple_commas.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -468,8 +872,8 @@ fn test_comma_as_whitespace() {
 #[test]
 fn test_file_errors() {
     // Test non-existent file
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "nonexistent.edn"])
+    let output = Command::new(get_binary_path())
+        .args([".", "nonexistent.edn"])
         .output()
         .expect("Failed to execute eq");
     
@@ -480,8 +884,8 @@ fn test_file_errors() {
     // Test directory instead of file - should now succeed but produce no output
     let _ = fs::remove_dir("test_dir"); // Clean up if exists from previous run
     fs::create_dir("test_dir").unwrap();
-    let output = Command::new(&get_binary_path())
-        .args(&[".", "test_dir"])
+    let output = Command::new(get_binary_path())
+        .args([".", "test_dir"])
         .output()
         .expect("Failed to execute eq");
     
@@ -489,4 +893,408 @@ fn test_file_errors() {
     assert!(output.status.success());
     assert!(output.stdout.is_empty());
     fs::remove_dir("test_dir").unwrap();
-}
\ No newline at end of file
+}
+#[test]
+fn test_json_output() {
+    fs::write("test_json_output.edn", r#"{:name "Alice" :tags #{:admin} :manager nil}"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-c", "--output-format", "json", ".", "test_json_output.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), r#"{"name":"Alice","tags":["admin"],"manager":null}"#);
+
+    // -j is shorthand for --output-format json
+    let output = Command::new(get_binary_path())
+        .args(["-j", "(:name .)", "test_json_output.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "\"Alice\"");
+
+    // Tagged values have no JSON equivalent
+    let output = Command::new(get_binary_path())
+        .args(["-j", "-n", "#myapp/thing 1"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(!output.status.success());
+
+    fs::remove_file("test_json_output.edn").unwrap();
+}
+
+#[test]
+fn test_json_input() {
+    fs::write("test_json_input.json", r#"{"users": [{"name": "Alice", "age": 30}, {"name": "Bob", "age": null}]}"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-c", "--input-format", "json", "(map :name (:users .))", "test_json_input.json"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), r#"["Alice" "Bob"]"#);
+
+    // Auto detection uses the .json extension
+    let output = Command::new(get_binary_path())
+        .args(["-I", "auto", "(:age (first (:users .)))", "test_json_input.json"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "30");
+
+    fs::remove_file("test_json_input.json").unwrap();
+
+    // Auto detection falls back to EDN for EDN maps
+    fs::write("test_json_input_auto.edn", r#"{:name "Carol"}"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-I", "auto", "(:name .)", "test_json_input_auto.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "\"Carol\"");
+
+    fs::remove_file("test_json_input_auto.edn").unwrap();
+}
+
+#[test]
+fn test_csv_output() {
+    fs::write("test_csv_output.edn", r#"[{:name "Alice" :role :admin} {:name "Bob"}]"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--output-format", "csv", ".", "test_csv_output.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "name,role\nAlice,\":admin\"\nBob,\n");
+
+    // Anything other than a vector of maps is an error
+    let output = Command::new(get_binary_path())
+        .args(["-F", "csv", "(first .)", "test_csv_output.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(!output.status.success());
+
+    fs::remove_file("test_csv_output.edn").unwrap();
+}
+
+#[test]
+fn test_ndjson_input_and_output() {
+    fs::write("test_ndjson.ndjson", "{\"id\": 1, \"tags\": [\"a\"]}\n\n{\"id\": 2, \"tags\": []}\n").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-I", "ndjson", "-F", "ndjson", ".", "test_ndjson.ndjson"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "{\"id\":1,\"tags\":[\"a\"]}\n{\"id\":2,\"tags\":[]}\n");
+
+    // Parse errors report the offending line
+    fs::write("test_ndjson.ndjson", "{\"id\": 1}\n{\"id\": }\n").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-I", "ndjson", ".", "test_ndjson.ndjson"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("line 2"));
+
+    fs::remove_file("test_ndjson.ndjson").unwrap();
+}
+
+#[test]
+fn test_table_output() {
+    fs::write("test_table_output.edn", r#"[{:name "Alice" :age 30} {:name "Bob"}]"#).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--output-format", "table", "--table-format", "markdown", ".", "test_table_output.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "| name    | age |\n| ------- | --- |\n| \"Alice\" | 30  |\n| \"Bob\"   |     |\n");
+
+    fs::remove_file("test_table_output.edn").unwrap();
+}
+
+#[test]
+fn test_multiple_top_level_values() {
+    fs::write("test_multiple_values.edn", "1 nil\n{:a [1 2\n 3]}\n#_ :skipped \"last\"\n").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-c", ".", "test_multiple_values.edn"])
+        .output()
+        .expect("Failed to execute eq");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "1\nnil\n{:a [1 2 3]}\n\"last\"\n");
+
+    fs::remove_file("test_multiple_values.edn").unwrap();
+}
+
+#[test]
+fn test_completions_and_builtin_list() {
+    let output = Command::new(get_binary_path())
+        .args(["--list-builtins"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout.lines().collect();
+    assert!(names.contains(&"map"));
+    assert!(names.contains(&"if"));
+    assert!(names.windows(2).all(|w| w[0] < w[1]));
+
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(get_binary_path())
+            .args(["--generate-completions", shell])
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("compact"), "{} completions missing flags", shell);
+    }
+}
+
+#[test]
+fn test_filter_scripts_and_check() {
+    fs::write("test_script.eq", "#!/usr/bin/env eq\n;; names of all users\n(map :name ; one per user\n  .)\n").unwrap();
+    fs::write("test_script_input.edn", "[{:name \"Alice\"} {:name \"Bob\"}]").unwrap();
+
+    // With -f the first positional argument is an input file
+    let output = Command::new(get_binary_path())
+        .args(["-c", "-f", "test_script.eq", "test_script_input.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), r#"["Alice" "Bob"]"#);
+
+    // Executing the script passes its path as the filter argument
+    let output = Command::new(get_binary_path())
+        .args(["-c", "test_script.eq", "test_script_input.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), r#"["Alice" "Bob"]"#);
+
+    // --check validates without reading input
+    let output = Command::new(get_binary_path())
+        .args(["--check", "-f", "test_script.eq"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    fs::remove_file("test_script.eq").unwrap();
+    fs::remove_file("test_script_input.edn").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-C", "(map :name"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unterminated list"));
+}
+
+#[test]
+fn test_diff_files() {
+    fs::write("test_diff_a.edn", "{:name \"eq\" :version 1 :tags #{:cli}}").unwrap();
+    fs::write("test_diff_b.edn", "{:name \"eq\" :version 2 :license :mit}").unwrap();
+    fs::write("test_diff_c.edn", "1 2").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["-c", "--diff", "test_diff_a.edn", "test_diff_b.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{:only-in-a {:tags #{:cli}} :only-in-b {:license :mit} :in-both {:name \"eq\"} :changed {:version {:from 1 :to 2}}}\n"
+    );
+
+    let output = Command::new(get_binary_path())
+        .args(["--diff", "test_diff_a.edn", "test_diff_c.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("more than one value"));
+
+    for file in ["test_diff_a.edn", "test_diff_b.edn", "test_diff_c.edn"] {
+        fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn test_import_edn() {
+    fs::create_dir_all("test_imports").unwrap();
+    fs::write("test_imports/roles.edn", "{1 :admin 2 :user}").unwrap();
+    fs::write("test_imports/roles.eq", "(map (fn [u] (get (import-edn \"roles.edn\") (:role u))) .)").unwrap();
+    fs::write("test_imports_input.edn", "[{:role 2} {:role 1}]").unwrap();
+
+    // Relative filenames are resolved against the directory of the --from-file script
+    let output = Command::new(get_binary_path())
+        .args(["-c", "--allow-imports", "-f", "test_imports/roles.eq", "test_imports_input.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[:user :admin]\n");
+
+    // Without a script they are resolved against the working directory
+    let output = Command::new(get_binary_path())
+        .args(["-n", "-c", "--allow-imports", r#"(import-edn "test_imports/roles.edn")"#])
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{1 :admin 2 :user}\n");
+
+    let output = Command::new(get_binary_path())
+        .args(["-n", "--allow-imports", r#"(import-edn "test_imports/missing.edn")"#])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("import-edn could not read"));
+
+    // Nothing outside the base directory can be read, by path or through a link
+    fs::write("test_imports/escape.eq", r#"(import-edn "../test_imports_input.edn")"#).unwrap();
+    fs::write("test_imports/link.eq", r#"(import-edn "link.edn")"#).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("../test_imports_input.edn", "test_imports/link.edn").unwrap();
+    let absolute = fs::canonicalize("test_imports/roles.edn").unwrap().display().to_string();
+    let mut escapes = vec![
+        vec!["-n".to_string(), "-f".to_string(), "test_imports/escape.eq".to_string()],
+        vec!["-n".to_string(), format!("(import-edn {:?})", absolute)],
+    ];
+    if cfg!(unix) {
+        escapes.push(vec!["-n".to_string(), "-f".to_string(), "test_imports/link.eq".to_string()]);
+    }
+    for args in escapes {
+        let output = Command::new(get_binary_path())
+            .arg("--allow-imports")
+            .args(&args)
+            .output()
+            .expect("Failed to execute eq");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8(output.stderr).unwrap().contains("import-edn can only read files under"), "{:?}", args);
+    }
+
+    // Imports are disabled by default
+    let output = Command::new(get_binary_path())
+        .args(["-f", "test_imports/roles.eq", "test_imports_input.edn"])
+        .output()
+        .expect("Failed to execute eq");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--allow-imports"));
+
+    fs::remove_dir_all("test_imports").unwrap();
+    fs::remove_file("test_imports_input.edn").unwrap();
+}
+
+#[test]
+fn test_env_builtins() {
+    let output = Command::new(get_binary_path())
+        .args(["-n", "-c", r#"(list (env "EQ_TEST_BASE_URL") (env "EQ_TEST_UNSET" "d") (:eq-test-base-url (env-all)))"#])
+        .env("EQ_TEST_BASE_URL", "http://example.com")
+        .env_remove("EQ_TEST_UNSET")
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "(\"http://example.com\" \"d\" \"http://example.com\")\n"
+    );
+
+    // --no-env treats every variable as unset
+    let output = Command::new(get_binary_path())
+        .args(["--no-env", "-n", "-c", r#"(list (env "EQ_TEST_BASE_URL") (env "EQ_TEST_BASE_URL" "d") (env-all))"#])
+        .env("EQ_TEST_BASE_URL", "http://example.com")
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "(nil \"d\" {})\n");
+}
+
+#[test]
+fn test_parallel_jobs_and_progress() {
+    fs::create_dir_all("test_jobs").unwrap();
+    let files: Vec<String> = (1..=12).map(|i| format!("test_jobs/f{}.edn", i)).collect();
+    for (i, file) in files.iter().enumerate() {
+        fs::write(file, format!("{{:n {}}} {{:n {}}}", i * 2, i * 2 + 1)).unwrap();
+    }
+
+    let run = |extra: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(extra)
+            .args(["-c", "(:n .)"])
+            .args(&files)
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let sequential = run(&[]);
+    assert_eq!(sequential.lines().count(), 24);
+    assert_eq!(run(&["--jobs", "4"]), sequential);
+    assert_eq!(run(&["--jobs", "0"]), sequential);
+    assert_eq!(run(&["--jobs", "4", "--count"]), "24\n");
+    assert_eq!(run(&["--jobs", "4", "--stream"]), run(&["--stream"]));
+
+    // The progress bar is never drawn when stderr isn't a terminal
+    let output = Command::new(get_binary_path())
+        .args(["--progress", "--jobs", "2", "-c", "(:n .)"])
+        .args(&files)
+        .output()
+        .expect("Failed to execute eq");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), sequential);
+    assert!(output.stderr.is_empty());
+
+    fs::remove_dir_all("test_jobs").unwrap();
+}
+
+#[test]
+fn test_sorted_directory_input() {
+    use std::time::{Duration, SystemTime};
+
+    fs::create_dir_all("test_sorted").unwrap();
+    let now = SystemTime::now();
+    for (name, age) in [("b", 1), ("a", 2), ("c", 3)] {
+        let path = format!("test_sorted/{}.edn", name);
+        fs::write(&path, format!(":{}", name)).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(now - Duration::from_secs(age * 60)).unwrap();
+    }
+
+    let run = |flag: &str| {
+        let output = Command::new(get_binary_path())
+            .args([flag, "-c", ".", "test_sorted"])
+            .output()
+            .expect("Failed to execute eq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run("--sort-by-name"), ":a\n:b\n:c\n");
+    assert_eq!(run("--sort-by-mtime"), ":c\n:a\n:b\n");
+
+    fs::remove_dir_all("test_sorted").unwrap();
+}